@@ -0,0 +1,58 @@
+#![allow(clippy::needless_return)]
+//! Compares bulk payload unpacking against the per-word decoding functions.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rustvdif::data_encoding::{decode_1bit_real, decode_2bit_real, decode_4bit_real};
+use rustvdif::unpack::{unpack_u8_with, Backend};
+
+fn payload() -> Vec<u32> {
+    let mut state = 1u32;
+    return (0..2000)
+        .map(|_| {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            state
+        })
+        .collect();
+}
+
+fn per_word(words: &[u32], bits: u32, out: &mut [u8]) {
+    let per_word = 32 / bits as usize;
+    for (word, samples) in words.iter().zip(out.chunks_exact_mut(per_word)) {
+        match bits {
+            1 => samples.copy_from_slice(&decode_1bit_real(word)),
+            2 => samples.copy_from_slice(&decode_2bit_real(word)),
+            _ => samples.copy_from_slice(&decode_4bit_real(word)),
+        }
+    }
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let words = payload();
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Bytes(words.len() as u64 * 4));
+    for bits in [1, 2, 4] {
+        let mut out = vec![0u8; words.len() * 32 / bits as usize];
+        group.bench_with_input(BenchmarkId::new("per_word", bits), &bits, |b, bits| {
+            b.iter(|| per_word(&words, *bits, &mut out))
+        });
+        for backend in [
+            Backend::Scalar,
+            Backend::Lut,
+            Backend::Sse2,
+            Backend::Avx2,
+            Backend::Neon,
+        ] {
+            if !backend.is_supported() {
+                continue;
+            }
+            let name = format!("{:?}", backend);
+            group.bench_with_input(BenchmarkId::new(name, bits), &bits, |b, bits| {
+                b.iter(|| unpack_u8_with(backend, &words, *bits, &mut out))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode);
+criterion_main!(benches);
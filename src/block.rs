@@ -0,0 +1,172 @@
+//! Implements [`FrameBlock`], a batch of same-sized frames held contiguously in a single allocation.
+//!
+//! Processing frames one [`VDIFFrame`] at a time means one allocation per frame, scattered around the heap. A block
+//! keeps every frame of a batch next to each other, which is far kinder to caches, and can be handed in one piece to
+//! anything that wants a flat buffer, such as a GPU upload.
+
+use alloc::boxed::Box;
+use alloc::vec;
+
+use crate::consts::HEADER_SIZE;
+use crate::frame::VDIFFrame;
+use crate::header::VDIFHeader;
+use crate::header_encoding::{decode_header, header_words};
+
+/// A batch of frames of the same size, stored back to back in one allocation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameBlock {
+    data: Box<[u32]>,
+    frame_words: usize,
+    len: usize,
+}
+
+impl FrameBlock {
+    /// Construct a block of `len` zeroed frames of `frame_size` bytes.
+    pub fn new(frame_size: usize, len: usize) -> Self {
+        assert!(
+            frame_size % 8 == 0 && frame_size >= 32,
+            "VDIF frames must be a multiple of 8 bytes in size, and at least 32 bytes"
+        );
+        return Self {
+            data: vec![0; len * frame_size / 4].into_boxed_slice(),
+            frame_words: frame_size / 4,
+            len: len,
+        };
+    }
+
+    /// Construct a block by copying `frames`, which must all be the same size.
+    pub fn from_frames(frames: &[VDIFFrame]) -> Self {
+        let frame_size = frames.first().map(|f| f.bytesize()).unwrap_or(HEADER_SIZE);
+        let mut block = Self::new(frame_size, frames.len());
+        for (i, frame) in frames.iter().enumerate() {
+            assert!(
+                frame.bytesize() == frame_size,
+                "Every frame in a block must be the same size"
+            );
+            block.frame_mut(i).copy_from_slice(frame.as_slice());
+        }
+        return block;
+    }
+
+    /// Get the number of frames in the block.
+    pub fn len(&self) -> usize {
+        return self.len;
+    }
+
+    /// Returns `true` if the block holds no frames.
+    pub fn is_empty(&self) -> bool {
+        return self.len == 0;
+    }
+
+    /// Get the size in bytes of each frame.
+    pub fn frame_size(&self) -> usize {
+        return self.frame_words * 4;
+    }
+
+    /// Get frame `i` as a `u32` slice.
+    pub fn frame(&self, i: usize) -> &[u32] {
+        return &self.data[i * self.frame_words..(i + 1) * self.frame_words];
+    }
+
+    /// Get frame `i` as a mutable `u32` slice.
+    pub fn frame_mut(&mut self, i: usize) -> &mut [u32] {
+        return &mut self.data[i * self.frame_words..(i + 1) * self.frame_words];
+    }
+
+    /// Get frame `i` as a mutable byte slice.
+    pub fn frame_bytes_mut(&mut self, i: usize) -> &mut [u8] {
+        let frame_size = self.frame_size();
+        return &mut self.as_mut_bytes()[i * frame_size..(i + 1) * frame_size];
+    }
+
+    /// Decode the header of frame `i`.
+    pub fn header(&self, i: usize) -> VDIFHeader {
+        return decode_header(self.frame(i)[0..8].try_into().unwrap());
+    }
+
+    /// Get the payload of frame `i`.
+    pub fn payload(&self, i: usize) -> &[u32] {
+        let frame = self.frame(i);
+        return &frame[header_words(frame)..];
+    }
+
+    /// Get the payload of frame `i` mutably.
+    pub fn payload_mut(&mut self, i: usize) -> &mut [u32] {
+        let frame = self.frame_mut(i);
+        let start = header_words(frame);
+        return &mut frame[start..];
+    }
+
+    /// Copy frame `i` out into its own [`VDIFFrame`].
+    pub fn to_frame(&self, i: usize) -> VDIFFrame {
+        return VDIFFrame::from_slice(self.frame(i));
+    }
+
+    /// Iterate over the frames of the block as `u32` slices.
+    pub fn iter(&self) -> core::slice::ChunksExact<'_, u32> {
+        return self.data[0..self.len * self.frame_words].chunks_exact(self.frame_words);
+    }
+
+    /// Iterate over the decoded headers of the block.
+    pub fn headers(&self) -> impl Iterator<Item = VDIFHeader> + '_ {
+        return (0..self.len).map(|i| self.header(i));
+    }
+
+    /// Shorten the block to its first `len` frames, keeping the allocation.
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    /// Get every frame of the block as one `u32` slice.
+    pub fn as_slice(&self) -> &[u32] {
+        return &self.data[0..self.len * self.frame_words];
+    }
+
+    /// Get every frame of the block as one byte slice, for example to export in one piece.
+    pub fn as_bytes(&self) -> &[u8] {
+        let words = self.as_slice();
+        return unsafe {
+            core::slice::from_raw_parts(words.as_ptr() as *const u8, words.len() * 4)
+        };
+    }
+
+    /// Get every frame of the block as one mutable byte slice, for example to read into in one piece.
+    pub fn as_mut_bytes(&mut self) -> &mut [u8] {
+        let len = self.len * self.frame_words;
+        let words = &mut self.data[0..len];
+        return unsafe {
+            core::slice::from_raw_parts_mut(words.as_mut_ptr() as *mut u8, words.len() * 4)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_block() {
+        let frames: Vec<VDIFFrame> = (0..3)
+            .map(|frameno| {
+                VDIFFrame::from_header(VDIFHeader {
+                    frameno: frameno,
+                    size: 6,
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let mut block = FrameBlock::from_frames(&frames);
+        assert_eq!((block.len(), block.frame_size()), (3, 48));
+        assert_eq!(block.header(2).frameno, 2);
+        assert_eq!(block.as_bytes().len(), 144);
+
+        block.payload_mut(1)[0] = 7;
+        assert_eq!(block.to_frame(1).get_data_word(0), 7);
+        block.truncate(2);
+        assert_eq!(block.iter().count(), 2);
+        assert_eq!(
+            block.headers().map(|h| h.frameno).collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Implements [`VDIFRead`] and [`VDIFWrite`] for channel types, so frames can be passed between threads and
+//! application stages using whichever channels the host application already uses.
+//!
+//! Receivers read frames and senders write them. A receiver whose senders have all been dropped returns an error of
+//! kind [`UnexpectedEof`](ErrorKind::UnexpectedEof), like any other source reaching its end, and a sender whose
+//! receiver has been dropped returns an error of kind [`BrokenPipe`](ErrorKind::BrokenPipe).
+//!
+//! [`std::sync::mpsc`] channels are always supported. `crossbeam-channel`, `flume` and `tokio` channels are
+//! supported behind the `crossbeam`, `flume` and `tokio` features respectively. The `tokio` implementations block
+//! the calling thread, so on any thread belonging to a Tokio runtime they never wait: reads return an error of kind
+//! [`WouldBlock`](ErrorKind::WouldBlock) if no frame is waiting, and writes if a bounded channel is full. Async code
+//! should use the channel's own async methods, or hand it to a thread outside the runtime.
+
+use std::io::{Error, ErrorKind, Result};
+use std::sync::mpsc;
+
+use crate::frame::VDIFFrame;
+use crate::io::{VDIFRead, VDIFWrite};
+
+fn disconnected() -> Error {
+    return Error::new(ErrorKind::UnexpectedEof, "All senders have disconnected");
+}
+
+fn closed() -> Error {
+    return Error::new(ErrorKind::BrokenPipe, "The receiver has disconnected");
+}
+
+// Whether the current thread belongs to a Tokio runtime, where blocking on a channel would panic or stall it.
+#[cfg(feature = "tokio")]
+fn in_runtime() -> bool {
+    return tokio::runtime::Handle::try_current().is_ok();
+}
+
+#[cfg(feature = "tokio")]
+fn would_block() -> Error {
+    return Error::new(
+        ErrorKind::WouldBlock,
+        "Can't wait on a channel from within a Tokio runtime",
+    );
+}
+
+impl VDIFRead for mpsc::Receiver<VDIFFrame> {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        return self.recv().map_err(|_| disconnected());
+    }
+}
+
+impl VDIFWrite for mpsc::Sender<VDIFFrame> {
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        return self.send(frame).map_err(|_| closed());
+    }
+}
+
+impl VDIFWrite for mpsc::SyncSender<VDIFFrame> {
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        return self.send(frame).map_err(|_| closed());
+    }
+}
+
+#[cfg(feature = "crossbeam")]
+impl VDIFRead for crossbeam_channel::Receiver<VDIFFrame> {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        return self.recv().map_err(|_| disconnected());
+    }
+}
+
+#[cfg(feature = "crossbeam")]
+impl VDIFWrite for crossbeam_channel::Sender<VDIFFrame> {
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        return self.send(frame).map_err(|_| closed());
+    }
+}
+
+#[cfg(feature = "flume")]
+impl VDIFRead for flume::Receiver<VDIFFrame> {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        return self.recv().map_err(|_| disconnected());
+    }
+}
+
+#[cfg(feature = "flume")]
+impl VDIFWrite for flume::Sender<VDIFFrame> {
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        return self.send(frame).map_err(|_| closed());
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl VDIFRead for tokio::sync::mpsc::Receiver<VDIFFrame> {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        if in_runtime() {
+            return self.try_recv().map_err(|e| match e {
+                tokio::sync::mpsc::error::TryRecvError::Empty => would_block(),
+                tokio::sync::mpsc::error::TryRecvError::Disconnected => disconnected(),
+            });
+        }
+        return self.blocking_recv().ok_or_else(disconnected);
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl VDIFRead for tokio::sync::mpsc::UnboundedReceiver<VDIFFrame> {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        if in_runtime() {
+            return self.try_recv().map_err(|e| match e {
+                tokio::sync::mpsc::error::TryRecvError::Empty => would_block(),
+                tokio::sync::mpsc::error::TryRecvError::Disconnected => disconnected(),
+            });
+        }
+        return self.blocking_recv().ok_or_else(disconnected);
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl VDIFWrite for tokio::sync::mpsc::Sender<VDIFFrame> {
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        if in_runtime() {
+            return self.try_send(frame).map_err(|e| match e {
+                tokio::sync::mpsc::error::TrySendError::Full(_) => would_block(),
+                tokio::sync::mpsc::error::TrySendError::Closed(_) => closed(),
+            });
+        }
+        return self.blocking_send(frame).map_err(|_| closed());
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl VDIFWrite for tokio::sync::mpsc::UnboundedSender<VDIFFrame> {
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        return self.send(frame).map_err(|_| closed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mpsc_channel() {
+        let (mut tx, mut rx) = mpsc::channel();
+        let writer = std::thread::spawn(move || {
+            for _ in 0..3 {
+                tx.write_frame(VDIFFrame::empty(64)).unwrap();
+            }
+        });
+        for _ in 0..3 {
+            assert_eq!(rx.read_frame().unwrap().bytesize(), 64);
+        }
+        writer.join().unwrap();
+        assert_eq!(
+            rx.read_frame().unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+
+        let (mut tx, rx) = mpsc::sync_channel::<VDIFFrame>(1);
+        drop(rx);
+        assert_eq!(
+            tx.write_frame(VDIFFrame::empty(64)).unwrap_err().kind(),
+            ErrorKind::BrokenPipe
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "tokio")]
+    fn test_tokio_channel_in_runtime() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let (mut tx, mut rx) = tokio::sync::mpsc::channel(1);
+            assert_eq!(rx.read_frame().unwrap_err().kind(), ErrorKind::WouldBlock);
+            tx.write_frame(VDIFFrame::empty(64)).unwrap();
+            assert_eq!(
+                tx.write_frame(VDIFFrame::empty(64)).unwrap_err().kind(),
+                ErrorKind::WouldBlock
+            );
+            assert_eq!(rx.read_frame().unwrap().bytesize(), 64);
+            drop(tx);
+            assert_eq!(
+                rx.read_frame().unwrap_err().kind(),
+                ErrorKind::UnexpectedEof
+            );
+        });
+
+        let (mut tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let writer = std::thread::spawn(move || tx.write_frame(VDIFFrame::empty(64)));
+        assert_eq!(rx.read_frame().unwrap().bytesize(), 64);
+        writer.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,123 @@
+//! Constant tables shared by the crate's converters, for applications that need the same values.
+
+/// The size in bytes of a VDIF header.
+pub const HEADER_SIZE: usize = 32;
+
+/// The size in bytes of a legacy VDIF header, which lacks the four extended data words.
+pub const LEGACY_HEADER_SIZE: usize = 16;
+
+/// A frame size of 8032 bytes, an 8000 byte payload. This is the most common size, and needs a network with jumbo
+/// frames to send as single datagrams.
+pub const FRAME_SIZE_8032: usize = 8032;
+
+/// A frame size of 5032 bytes, a 5000 byte payload. This needs a network with jumbo frames to send as single
+/// datagrams.
+pub const FRAME_SIZE_5032: usize = 5032;
+
+/// A frame size of 1032 bytes, a 1000 byte payload, which fits within a standard 1500 byte Ethernet MTU.
+pub const FRAME_SIZE_1032: usize = 1032;
+
+/// The frame size to reach for when you don't know better, [`FRAME_SIZE_8032`]. Nothing in the crate assumes it, so
+/// pass it explicitly where a frame size is needed.
+pub const DEFAULT_FRAME_SIZE: usize = FRAME_SIZE_8032;
+
+/// The standard Ethernet MTU in bytes.
+pub const MTU_STANDARD: usize = 1500;
+
+/// The usual jumbo frame Ethernet MTU in bytes.
+pub const MTU_JUMBO: usize = 9000;
+
+/// The largest UDP payload that can be sent over IPv4, and so the largest VDIF frame that fits in one datagram.
+pub const MAX_UDP_PAYLOAD: usize = 65507;
+
+/// Get the largest VDIF frame size that fits in a single UDP datagram over IPv4 on a link with the given `mtu`, after
+/// the 20 byte IPv4 and 8 byte UDP headers. Subtract a further 8 bytes for VTP.
+pub const fn max_frame_size_for_mtu(mtu: usize) -> usize {
+    return (mtu.saturating_sub(28) / 8) * 8;
+}
+
+/// The `(year, month)` each VDIF reference epoch starts on. Epochs are half years, starting on January 1st and July
+/// 1st, from January 2000.
+pub const EPOCH_START_DATES: [(i32, u32); 64] = epoch_start_dates();
+
+/// The length in seconds of each VDIF reference epoch, i.e. the time from its start until the start of the next.
+pub const EPOCH_SECONDS: [u32; 64] = epoch_seconds();
+
+/// The 2-bit quantisation threshold, in units of the input RMS, that maximises the quantisation efficiency of
+/// Gaussian noise.
+pub const OPTIMAL_2BIT_THRESHOLD: f32 = 0.9816;
+
+/// The standard decode levels of 1-bit data, indexed by the raw sample value.
+pub const LEVELS_1BIT: [f32; 2] = [-1.0, 1.0];
+
+/// The standard decode levels of 2-bit data, indexed by the raw sample value. These are the optimal levels for
+/// samples quantised at [`OPTIMAL_2BIT_THRESHOLD`], in units of the threshold.
+pub const LEVELS_2BIT: [f32; 4] = [-3.3359, -1.0, 1.0, 3.3359];
+
+/// The standard decode levels of 4-bit data, indexed by the raw sample value. Raw value `k` decodes to `k - 7.5`.
+pub const LEVELS_4BIT: [f32; 16] = levels_4bit();
+
+const fn epoch_start_dates() -> [(i32, u32); 64] {
+    let mut out = [(0, 0); 64];
+    let mut epoch = 0;
+    while epoch < 64 {
+        out[epoch] = (2000 + epoch as i32 / 2, if epoch % 2 > 0 { 7 } else { 1 });
+        epoch += 1;
+    }
+    return out;
+}
+
+const fn epoch_seconds() -> [u32; 64] {
+    let mut out = [0; 64];
+    let mut epoch = 0;
+    while epoch < 64 {
+        let year = 2000 + epoch as i32 / 2;
+        let days = if epoch % 2 > 0 {
+            // July to December
+            184
+        } else if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) {
+            182
+        } else {
+            181
+        };
+        out[epoch] = days * 86400;
+        epoch += 1;
+    }
+    return out;
+}
+
+const fn levels_4bit() -> [f32; 16] {
+    let mut out = [0.0; 16];
+    let mut k = 0;
+    while k < 16 {
+        out[k] = k as f32 - 7.5;
+        k += 1;
+    }
+    return out;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_epoch_tables() {
+        for epoch in 0..64 {
+            let (year, month) = EPOCH_START_DATES[epoch];
+            let start = NaiveDate::from_ymd_opt(year, month, 1).unwrap();
+            let (year, month) = if month == 7 { (year + 1, 1) } else { (year, 7) };
+            let end = NaiveDate::from_ymd_opt(year, month, 1).unwrap();
+            assert_eq!((end - start).num_seconds(), EPOCH_SECONDS[epoch] as i64);
+        }
+        assert_eq!(LEVELS_4BIT[0], -7.5);
+        assert_eq!(LEVELS_4BIT[15], 7.5);
+    }
+
+    #[test]
+    fn test_mtu_frame_sizes() {
+        assert_eq!(max_frame_size_for_mtu(MTU_STANDARD), 1472);
+        assert!(max_frame_size_for_mtu(MTU_STANDARD) >= FRAME_SIZE_1032);
+        assert!(max_frame_size_for_mtu(MTU_JUMBO) >= FRAME_SIZE_8032);
+    }
+}
@@ -22,7 +22,9 @@
 // where the number of real samples is not exactly 2x the number of complex samples.
 // For example, in 6-bit encoding, you can fit 5 real samples, but only 2 complex samples
 // (otherwise a real component would not have an attached complex component).
-// In these cases I take the safer approach and maintain the extra real sample.
+// The per-word functions here follow the spec and truncate to whole pairs, leaving the extra
+// component unused. Whole-payload encoding and decoding can instead keep it, see
+// `layout::ComplexPacking`.
 
 const DC_MASK_1BIT: u32 = u32::MAX >> 31;
 const DC_MASK_2BIT: u32 = u32::MAX >> 30;
@@ -827,6 +829,217 @@ pub fn encode_16bit_complex(real: u16, imag: u16) -> [u8; 4] {
     return word.to_le_bytes();
 }
 
+/// Marks a bit depth supported by [`decode_bits`] and the other generic functions. Only `Bits<B>` for the bit depths
+/// with a per-bit-depth function in this module implement [`BitDepth`].
+pub struct Bits<const B: u8>;
+
+/// Relates a supported bit depth to its per-bit-depth encoding and decoding functions.
+pub trait BitDepth {
+    /// The real samples held in one word.
+    type Real;
+    /// The real or imaginary components of the complex samples held in one word.
+    type ComplexPart;
+
+    /// Decode one word of real samples.
+    fn decode_real(input: &u32) -> Self::Real;
+    /// Decode one word of complex samples.
+    fn decode_complex(input: &u32) -> (Self::ComplexPart, Self::ComplexPart);
+    /// Encode one word of real samples.
+    fn encode_real(input: Self::Real) -> [u8; 4];
+    /// Encode one word of complex samples.
+    fn encode_complex(real: Self::ComplexPart, imag: Self::ComplexPart) -> [u8; 4];
+}
+
+macro_rules! impl_bit_depth {
+    ($bits:literal, $real:ty, $part:ty, $dr:ident, $dc:ident, $er:ident, $ec:ident) => {
+        impl BitDepth for Bits<$bits> {
+            type Real = $real;
+            type ComplexPart = $part;
+
+            #[inline]
+            fn decode_real(input: &u32) -> Self::Real {
+                return $dr(input);
+            }
+            #[inline]
+            fn decode_complex(input: &u32) -> (Self::ComplexPart, Self::ComplexPart) {
+                return $dc(input);
+            }
+            #[inline]
+            fn encode_real(input: Self::Real) -> [u8; 4] {
+                return $er(input);
+            }
+            #[inline]
+            fn encode_complex(real: Self::ComplexPart, imag: Self::ComplexPart) -> [u8; 4] {
+                return $ec(real, imag);
+            }
+        }
+    };
+}
+
+impl_bit_depth!(
+    1,
+    [u8; 32],
+    [u8; 16],
+    decode_1bit_real,
+    decode_1bit_complex,
+    encode_1bit_real,
+    encode_1bit_complex
+);
+impl_bit_depth!(
+    2,
+    [u8; 16],
+    [u8; 8],
+    decode_2bit_real,
+    decode_2bit_complex,
+    encode_2bit_real,
+    encode_2bit_complex
+);
+impl_bit_depth!(
+    3,
+    [u8; 10],
+    [u8; 5],
+    decode_3bit_real,
+    decode_3bit_complex,
+    encode_3bit_real,
+    encode_3bit_complex
+);
+impl_bit_depth!(
+    4,
+    [u8; 8],
+    [u8; 4],
+    decode_4bit_real,
+    decode_4bit_complex,
+    encode_4bit_real,
+    encode_4bit_complex
+);
+impl_bit_depth!(
+    6,
+    [u8; 5],
+    [u8; 2],
+    decode_6bit_real,
+    decode_6bit_complex,
+    encode_6bit_real,
+    encode_6bit_complex
+);
+impl_bit_depth!(
+    7,
+    [u8; 4],
+    [u8; 2],
+    decode_7bit_real,
+    decode_7bit_complex,
+    encode_7bit_real,
+    encode_7bit_complex
+);
+impl_bit_depth!(
+    8,
+    [u8; 4],
+    [u8; 2],
+    decode_8bit_real,
+    decode_8bit_complex,
+    encode_8bit_real,
+    encode_8bit_complex
+);
+impl_bit_depth!(
+    11,
+    [u16; 2],
+    u16,
+    decode_11bit_real,
+    decode_11bit_complex,
+    encode_11bit_real,
+    encode_11bit_complex
+);
+impl_bit_depth!(
+    12,
+    [u16; 2],
+    u16,
+    decode_12bit_real,
+    decode_12bit_complex,
+    encode_12bit_real,
+    encode_12bit_complex
+);
+impl_bit_depth!(
+    13,
+    [u16; 2],
+    u16,
+    decode_13bit_real,
+    decode_13bit_complex,
+    encode_13bit_real,
+    encode_13bit_complex
+);
+impl_bit_depth!(
+    14,
+    [u16; 2],
+    u16,
+    decode_14bit_real,
+    decode_14bit_complex,
+    encode_14bit_real,
+    encode_14bit_complex
+);
+impl_bit_depth!(
+    15,
+    [u16; 2],
+    u16,
+    decode_15bit_real,
+    decode_15bit_complex,
+    encode_15bit_real,
+    encode_15bit_complex
+);
+impl_bit_depth!(
+    16,
+    [u16; 2],
+    u16,
+    decode_16bit_real,
+    decode_16bit_complex,
+    encode_16bit_real,
+    encode_16bit_complex
+);
+
+/// Decode one word of real `B` bit samples, e.g. `decode_bits::<2>(&word)`. Equivalent to calling the matching
+/// `decode_Nbit_real` function, but usable in code that is generic over bit depth.
+#[inline]
+pub fn decode_bits<const B: u8>(input: &u32) -> <Bits<B> as BitDepth>::Real
+where
+    Bits<B>: BitDepth,
+{
+    return <Bits<B>>::decode_real(input);
+}
+
+/// Decode one word of complex `B` bit samples. The mirror of [`decode_bits`] for complex data.
+#[inline]
+pub fn decode_complex_bits<const B: u8>(
+    input: &u32,
+) -> (
+    <Bits<B> as BitDepth>::ComplexPart,
+    <Bits<B> as BitDepth>::ComplexPart,
+)
+where
+    Bits<B>: BitDepth,
+{
+    return <Bits<B>>::decode_complex(input);
+}
+
+/// Encode one word of real `B` bit samples, e.g. `encode_bits::<2>(samples)`. Equivalent to calling the matching
+/// `encode_Nbit_real` function.
+#[inline]
+pub fn encode_bits<const B: u8>(input: <Bits<B> as BitDepth>::Real) -> [u8; 4]
+where
+    Bits<B>: BitDepth,
+{
+    return <Bits<B>>::encode_real(input);
+}
+
+/// Encode one word of complex `B` bit samples. The mirror of [`encode_bits`] for complex data.
+#[inline]
+pub fn encode_complex_bits<const B: u8>(
+    real: <Bits<B> as BitDepth>::ComplexPart,
+    imag: <Bits<B> as BitDepth>::ComplexPart,
+) -> [u8; 4]
+where
+    Bits<B>: BitDepth,
+{
+    return <Bits<B>>::encode_complex(real, imag);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1206,4 +1419,21 @@ mod tests {
         let test_in: (u16, u16) = (0b0101010101010101, 0b0101010101010101);
         assert_eq!(encode_16bit_complex(test_in.0, test_in.1), result)
     }
+
+    #[test]
+    fn test_generic_bits() {
+        let word: u32 = 0x9C3A_F071;
+        assert_eq!(decode_bits::<2>(&word), decode_2bit_real(&word));
+        assert_eq!(decode_bits::<16>(&word), decode_16bit_real(&word));
+        assert_eq!(decode_complex_bits::<4>(&word), decode_4bit_complex(&word));
+        assert_eq!(
+            encode_bits::<8>(decode_bits::<8>(&word)),
+            word.to_le_bytes()
+        );
+        let (real, imag) = decode_complex_bits::<12>(&word);
+        assert_eq!(
+            encode_complex_bits::<12>(real, imag),
+            encode_12bit_complex(real, imag)
+        );
+    }
 }
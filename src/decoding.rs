@@ -0,0 +1,457 @@
+//! Implements whole-payload decoding of VDIF frames, dispatched at runtime using the header of each frame.
+//!
+//! Where the functions in [`data_encoding`](crate::data_encoding) decode a single `u32` word at a known bit depth, the
+//! functions here decode an entire payload into a [`Samples`] value, so you don't need a match arm for every
+//! possible bit depth.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use num_complex::Complex;
+
+use crate::consts::{LEVELS_1BIT, LEVELS_2BIT, LEVELS_4BIT};
+use crate::frame::VDIFFrame;
+use crate::layout::{ComplexPacking, FrameLayout};
+use crate::unpack::{unpack_u8, unpack_u8_with, Backend};
+
+/// A buffer of decoded samples.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Samples {
+    /// Unsigned samples of up to 8 bits.
+    U8(Vec<u8>),
+    /// Unsigned samples of up to 16 bits.
+    U16(Vec<u16>),
+    /// Signed samples of up to 8 bits.
+    I8(Vec<i8>),
+    /// Floating point samples.
+    F32(Vec<f32>),
+    /// Complex floating point samples.
+    ComplexF32(Vec<Complex<f32>>),
+}
+
+impl Samples {
+    /// Get the number of samples. Each complex sample counts as one sample.
+    pub fn len(&self) -> usize {
+        return match self {
+            Self::U8(s) => s.len(),
+            Self::U16(s) => s.len(),
+            Self::I8(s) => s.len(),
+            Self::F32(s) => s.len(),
+            Self::ComplexF32(s) => s.len(),
+        };
+    }
+
+    /// Returns `true` if there are no samples.
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    /// Convert the samples to `f32`s. Complex samples are returned as interleaved real and imaginary parts.
+    pub fn to_f32(&self) -> Vec<f32> {
+        return match self {
+            Self::U8(s) => s.iter().map(|v| *v as f32).collect(),
+            Self::U16(s) => s.iter().map(|v| *v as f32).collect(),
+            Self::I8(s) => s.iter().map(|v| *v as f32).collect(),
+            Self::F32(s) => s.clone(),
+            Self::ComplexF32(s) => s.iter().flat_map(|v| [v.re, v.im]).collect(),
+        };
+    }
+
+    /// Split channel-interleaved samples into `n` buffers, one per channel. Sample `i` is assigned to channel
+    /// `i % n`.
+    pub fn split_channels(&self, n: usize) -> Vec<Samples> {
+        return match self {
+            Self::U8(s) => split(s, n).into_iter().map(Self::U8).collect(),
+            Self::U16(s) => split(s, n).into_iter().map(Self::U16).collect(),
+            Self::I8(s) => split(s, n).into_iter().map(Self::I8).collect(),
+            Self::F32(s) => split(s, n).into_iter().map(Self::F32).collect(),
+            Self::ComplexF32(s) => split(s, n).into_iter().map(Self::ComplexF32).collect(),
+        };
+    }
+}
+
+fn split<T: Copy>(samples: &[T], n: usize) -> Vec<Vec<T>> {
+    let mut out: Vec<Vec<T>> = (0..n)
+        .map(|_| Vec::with_capacity(samples.len() / n))
+        .collect();
+    for (i, sample) in samples.iter().enumerate() {
+        out[i % n].push(*sample);
+    }
+    return out;
+}
+
+/// Decode the entire payload of `frame`, using its header to determine the bits/sample and whether the data is real
+/// or complex.
+///
+/// Real data of up to 8 bits/sample is returned as [`Samples::U8`], and up to 16 bits/sample as [`Samples::U16`].
+/// Complex data is returned as [`Samples::ComplexF32`]. In all cases the samples hold the raw encoded values.
+///
+/// Samples are unpacked following the packing rules described in [`FrameLayout`], so unused bits at the top of each
+/// word are ignored, as is any leftover component in each word of complex data. To keep that component instead, use
+/// [`decode_payload_with_layout`] with [`ComplexPacking::KeepExtraReal`].
+///
+/// Returns [`None`] if the header doesn't describe a usable layout (see [`FrameLayout::from_header`]), or the samples
+/// are wider than 16 bits.
+pub fn decode_payload(frame: &VDIFFrame) -> Option<Samples> {
+    return decode_payload_with_layout(frame, &FrameLayout::from_header(&frame.get_header())?);
+}
+
+/// Decode the entire payload of `frame` using a precomputed [`FrameLayout`], rather than the frame's header.
+///
+/// See [`decode_payload`] for the form of the returned samples. Returns [`None`] if the samples are wider than 16
+/// bits, or the layout describes more payload than `frame` holds.
+pub fn decode_payload_with_layout(frame: &VDIFFrame, layout: &FrameLayout) -> Option<Samples> {
+    let bits = layout.bits_per_sample;
+    if !(1..=16).contains(&bits) || layout.used_words() > frame.get_payload().len() {
+        return None;
+    }
+
+    let components = if layout.is_real { 1 } else { 2 };
+    let used_words = layout.used_words();
+    if layout.is_real
+        && matches!(bits, 1 | 2 | 4 | 8)
+        && layout.samples_per_word as u32 * bits == 32
+    {
+        // Samples fill every word, so the whole payload can be unpacked in bulk
+        let mut out = vec![0u8; layout.samples_per_frame];
+        unpack_u8(&frame.get_payload()[0..used_words], bits, &mut out);
+        return Some(Samples::U8(out));
+    }
+    let mut raw: Vec<u16> = Vec::with_capacity(layout.samples_per_frame * components);
+    if !layout.is_real && layout.complex_packing == ComplexPacking::KeepExtraReal {
+        for word in &frame.get_payload()[0..used_words] {
+            unpack_word(*word, bits, 32 / bits as usize, &mut raw);
+        }
+        raw.truncate(layout.samples_per_frame * 2);
+    } else {
+        for (i, word) in frame.get_payload()[0..used_words].iter().enumerate() {
+            unpack_word(
+                *word,
+                bits,
+                layout.samples_in_word(i) * components,
+                &mut raw,
+            );
+        }
+    }
+
+    if !layout.is_real {
+        return Some(Samples::ComplexF32(
+            raw.chunks_exact(2)
+                .map(|pair| Complex::new(pair[0] as f32, pair[1] as f32))
+                .collect(),
+        ));
+    } else if bits <= 8 {
+        return Some(Samples::U8(raw.into_iter().map(|v| v as u8).collect()));
+    } else {
+        return Some(Samples::U16(raw));
+    }
+}
+
+/// Decode the entire payload of `frame` into normalised floating point samples, using its header to determine the
+/// bits/sample and whether the data is real or complex.
+///
+/// Samples are interleaved across channels as in the payload, and complex samples are returned as interleaved real
+/// and imaginary parts. 1, 2 and 4-bit values are mapped to [`LEVELS_1BIT`], [`LEVELS_2BIT`] and [`LEVELS_4BIT`]
+/// respectively, and other bit depths from offset binary to values centred on zero, so raw value `k` of `b` bits
+/// decodes to `k - (2^b - 1) / 2`.
+///
+/// Returns [`None`] if the payload can't be decoded, as for [`decode_payload`].
+pub fn decode_payload_f32(frame: &VDIFFrame) -> Option<Vec<f32>> {
+    let bits = frame.get_header().bits();
+    let mut samples = decode_payload(frame)?.to_f32();
+    let offset = ((1u32 << bits) - 1) as f32 / 2.0;
+    for sample in samples.iter_mut() {
+        let raw = *sample as usize;
+        *sample = match bits {
+            1 => LEVELS_1BIT[raw],
+            2 => LEVELS_2BIT[raw],
+            4 => LEVELS_4BIT[raw],
+            _ => *sample - offset,
+        };
+    }
+    return Some(samples);
+}
+
+/// Decode the entire complex payload of `frame` into normalised complex samples, with the same levels as
+/// [`decode_payload_f32`]. Samples are interleaved across channels as in the payload, ready to pass to an FFT library
+/// once split by channel.
+///
+/// Returns [`None`] if the frame holds real data, or can't be decoded as for [`decode_payload`].
+pub fn decode_complex_f32(frame: &VDIFFrame) -> Option<Vec<Complex<f32>>> {
+    if frame.get_header().is_real {
+        return None;
+    }
+    return Some(
+        decode_payload_f32(frame)?
+            .chunks_exact(2)
+            .map(|pair| Complex::new(pair[0], pair[1]))
+            .collect(),
+    );
+}
+
+/// Decode the entire complex payload of `frame`, of up to 8 bits/sample, into signed complex samples. Each component
+/// is converted from VDIF's offset binary, so raw value `k` of `b` bits decodes to `k - 2^(b - 1)`. This is the
+/// inverse of [`encode_complex_payload_from_channels`](crate::encoding::encode_complex_payload_from_channels).
+///
+/// Returns [`None`] if the frame holds real data or samples wider than 8 bits, or can't be decoded as for
+/// [`decode_payload`].
+pub fn decode_complex_i8(frame: &VDIFFrame) -> Option<Vec<Complex<i8>>> {
+    let header = frame.get_header();
+    if header.is_real || header.bits() > 8 {
+        return None;
+    }
+    let offset = 1i16 << (header.bits() - 1);
+    let Samples::ComplexF32(samples) = decode_payload(frame)? else {
+        return None;
+    };
+    return Some(
+        samples
+            .iter()
+            .map(|s| Complex::new((s.re as i16 - offset) as i8, (s.im as i16 - offset) as i8))
+            .collect(),
+    );
+}
+
+/// Decode the entire payload of `frame` as [`decode_payload`] does, split into one buffer per channel using the channel
+/// count in its header. Returns [`None`] if the payload can't be decoded.
+pub fn decode_channels(frame: &VDIFFrame) -> Option<Vec<Samples>> {
+    return Some(decode_payload(frame)?.split_channels(frame.get_header().channelno()));
+}
+
+/// Decode the entire payload of `frame` into normalised floating point samples as [`decode_payload_f32`] does, split
+/// into one buffer per channel using the channel count in its header. Complex samples are interleaved real and
+/// imaginary parts within each channel. Returns [`None`] if the payload can't be decoded.
+pub fn decode_channels_f32(frame: &VDIFFrame) -> Option<Vec<Vec<f32>>> {
+    let header = frame.get_header();
+    let samples = decode_payload_f32(frame)?;
+    if header.is_real {
+        return Some(split(&samples, header.channelno()));
+    }
+    let pairs: Vec<[f32; 2]> = samples.chunks_exact(2).map(|p| [p[0], p[1]]).collect();
+    return Some(
+        split(&pairs, header.channelno())
+            .into_iter()
+            .map(|c| c.into_iter().flatten().collect())
+            .collect(),
+    );
+}
+
+/// Decode the entire payload of `frame` into a single channel-major buffer of normalised floating point samples, so
+/// the samples of channel `c` follow those of channel `c - 1`. See [`decode_channels_f32`].
+pub fn decode_channel_major_f32(frame: &VDIFFrame) -> Option<Vec<f32>> {
+    return Some(decode_channels_f32(frame)?.concat());
+}
+
+/// Decode the raw samples of `frame`, which must hold real data of 1, 2, 4 or 8 bits/sample in a power of two
+/// channels, into the start of `out` using `backend`. Returns the number of samples written.
+///
+/// This avoids allocating for every frame, unlike [`decode_payload`]. Returns [`None`] if the frame's header doesn't
+/// describe such data, and panics if `out` is too small.
+pub fn decode_payload_into(frame: &VDIFFrame, backend: Backend, out: &mut [u8]) -> Option<usize> {
+    let layout = FrameLayout::from_header(&frame.get_header())?;
+    if !layout.is_real
+        || !matches!(layout.bits_per_sample, 1 | 2 | 4 | 8)
+        || layout.samples_per_word as u32 * layout.bits_per_sample != 32
+    {
+        return None;
+    }
+    let samples = layout.samples_per_frame;
+    unpack_u8_with(
+        backend,
+        &frame.get_payload()[0..layout.used_words()],
+        layout.bits_per_sample,
+        &mut out[0..samples],
+    );
+    return Some(samples);
+}
+
+/// Get the payload word index and bit shift of sample `n` of `channel` within `frame`. See
+/// [`FrameLayout::sample_index`]. Returns [`None`] if the header doesn't describe a usable layout.
+pub fn sample_index(frame: &VDIFFrame, channel: usize, n: usize) -> Option<(usize, u32)> {
+    return Some(FrameLayout::from_header(&frame.get_header())?.sample_index(channel, n));
+}
+
+/// Get the raw value of sample `n` of `channel` within `frame` holding real data, without decoding the rest of the
+/// payload. Returns [`None`] if the frame holds complex data, or its header doesn't describe a usable layout.
+pub fn get_sample(frame: &VDIFFrame, channel: usize, n: usize) -> Option<u16> {
+    let layout = FrameLayout::from_header(&frame.get_header())?;
+    if !layout.is_real {
+        return None;
+    }
+    let (word, shift) = layout.sample_index(channel, n);
+    return Some(extract(
+        frame.get_data_word(word),
+        shift,
+        layout.bits_per_sample,
+    ));
+}
+
+/// Get the raw components of sample `n` of `channel` within `frame` holding complex data, without decoding the rest
+/// of the payload. Returns [`None`] if the frame holds real data, or its header doesn't describe a usable layout.
+pub fn get_complex_sample(frame: &VDIFFrame, channel: usize, n: usize) -> Option<Complex<u16>> {
+    let layout = FrameLayout::from_header(&frame.get_header())?;
+    if layout.is_real {
+        return None;
+    }
+    return Some(get_complex_sample_with_layout(frame, &layout, channel, n));
+}
+
+/// Get the raw components of sample `n` of `channel` within `frame` using a precomputed [`FrameLayout`], for example
+/// one with a non-standard [`ComplexPacking`].
+pub fn get_complex_sample_with_layout(
+    frame: &VDIFFrame,
+    layout: &FrameLayout,
+    channel: usize,
+    n: usize,
+) -> Complex<u16> {
+    assert!(!layout.is_real, "Use get_sample for real data");
+    let bits = layout.bits_per_sample;
+    let (re_word, re_shift) = layout.component_index(channel, n, false);
+    let (im_word, im_shift) = layout.component_index(channel, n, true);
+    return Complex::new(
+        extract(frame.get_data_word(re_word), re_shift, bits),
+        extract(frame.get_data_word(im_word), im_shift, bits),
+    );
+}
+
+fn extract(word: u32, shift: u32, bits: u32) -> u16 {
+    return ((word >> shift) & mask(bits)) as u16;
+}
+
+// A mask of the low `bits` bits, for `bits` from 1 to 32.
+pub(crate) fn mask(bits: u32) -> u32 {
+    return u32::MAX.checked_shr(32 - bits).unwrap_or(0);
+}
+
+// Unpack the first `count` values of `bits` bits in `word`, oldest first.
+fn unpack_word(word: u32, bits: u32, count: usize, out: &mut Vec<u16>) {
+    let mask = mask(bits);
+    for i in 0..count as u32 {
+        out.push(((word >> (i * bits)) & mask) as u16);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::VDIFHeader;
+
+    fn test_frame(bits_per_sample: u8, is_real: bool, payload: &[u32]) -> VDIFFrame {
+        let mut frame = VDIFFrame::empty(32 + payload.len() * 4);
+        frame.set_header(VDIFHeader {
+            size: (4 + payload.len() / 2) as u32,
+            bits_per_sample: bits_per_sample - 1,
+            is_real: is_real,
+            ..Default::default()
+        });
+        frame.get_mut_payload().copy_from_slice(payload);
+        return frame;
+    }
+
+    #[test]
+    fn test_decode_payload() {
+        let frame = test_frame(2, true, &[0b11100100_11100100_11100100_11100100, 0]);
+        let samples = decode_payload(&frame).unwrap();
+        assert_eq!(samples.len(), 32);
+        assert_eq!(
+            samples.split_channels(4)[3],
+            Samples::U8(vec![3, 3, 3, 3, 0, 0, 0, 0])
+        );
+
+        let frame = test_frame(6, false, &[0b00_000100_000011_000010_000001, 0]);
+        assert_eq!(
+            decode_payload(&frame).unwrap().to_f32()[0..4],
+            [1.0, 2.0, 3.0, 4.0]
+        );
+    }
+
+    #[test]
+    fn test_decode_payload_f32() {
+        let frame = test_frame(2, true, &[0b11100100, 0]);
+        assert_eq!(
+            decode_payload_f32(&frame).unwrap()[0..5],
+            [-3.3359, -1.0, 1.0, 3.3359, -3.3359]
+        );
+
+        let frame = test_frame(8, false, &[0x00FF8180, 0]);
+        assert_eq!(
+            decode_payload_f32(&frame).unwrap()[0..4],
+            [0.5, 1.5, 127.5, -127.5]
+        );
+    }
+
+    #[test]
+    fn test_decode_channels() {
+        // 2-bit real, 2 channels: raw values alternate 0, 1, 2, 3 so channel 0 holds 0, 2 and channel 1 holds 1, 3
+        let mut frame = test_frame(2, true, &[0b11100100_11100100_11100100_11100100, 0]);
+        let mut header = frame.get_header();
+        header.channels = 1;
+        frame.set_header(header);
+        let channels = decode_channels(&frame).unwrap();
+        assert_eq!(channels.len(), 2);
+        assert_eq!(
+            channels[1],
+            Samples::U8([1, 3].repeat(4).into_iter().chain([0; 8]).collect())
+        );
+        let major = decode_channel_major_f32(&frame).unwrap();
+        assert_eq!((major[0], major[1], major[16]), (-3.3359, 1.0, -1.0));
+
+        // 4-bit complex, 2 channels
+        let mut frame = test_frame(4, false, &[0x87654321, 0]);
+        let mut header = frame.get_header();
+        header.channels = 1;
+        frame.set_header(header);
+        let channels = decode_channels_f32(&frame).unwrap();
+        assert_eq!(channels[1][0..4], [-4.5, -3.5, -0.5, 0.5]);
+    }
+
+    #[test]
+    fn test_decode_payload_into() {
+        let frame = test_frame(4, true, &[0x76543210, 0xFEDCBA98]);
+        let mut out = [0u8; 20];
+        assert_eq!(
+            decode_payload_into(&frame, Backend::Lut, &mut out),
+            Some(16)
+        );
+        assert_eq!(out[0..16], (0..16).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_decode_complex() {
+        let frame = test_frame(4, false, &[0x87654321, 0]);
+        let samples = decode_complex_f32(&frame).unwrap();
+        assert_eq!(samples[0], Complex::new(-6.5, -5.5));
+        assert_eq!(samples.len(), 8);
+
+        let mut frame = test_frame(2, false, &[0, 0]);
+        let channel = [Complex::new(-2i8, 1i8), Complex::new(0, -1)].repeat(8);
+        crate::encoding::encode_complex_payload_from_channels(
+            &[&channel],
+            2,
+            ComplexPacking::TruncateToPairs,
+            &mut frame,
+        );
+        assert_eq!(decode_complex_i8(&frame).unwrap(), channel);
+
+        // A 32-bit complex header describes no usable layout
+        let frame = test_frame(32, false, &[0, 0]);
+        assert_eq!(decode_payload(&frame), None);
+        assert_eq!(decode_complex_i8(&frame), None);
+        assert_eq!(decode_complex_f32(&test_frame(2, true, &[0, 0])), None);
+    }
+
+    #[test]
+    fn test_get_sample() {
+        let mut frame = test_frame(2, true, &[0b11100100_11100100_11100100_11100100, 0]);
+        let mut header = frame.get_header();
+        header.channels = 1;
+        frame.set_header(header);
+        assert_eq!(sample_index(&frame, 1, 3), Some((0, 14)));
+        assert_eq!(get_sample(&frame, 1, 3), Some(3));
+        assert_eq!(get_sample(&frame, 0, 1), Some(2));
+
+        let frame = test_frame(6, false, &[0b00_000100_000011_000010_000001, 0]);
+        assert_eq!(get_complex_sample(&frame, 0, 1), Some(Complex::new(3, 4)));
+        assert_eq!(get_sample(&frame, 0, 1), None);
+    }
+}
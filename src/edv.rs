@@ -0,0 +1,305 @@
+//! Implements [`ExtendedData`], typed access to the registered Extended Data Versions (EDVs) carried in the last four
+//! words of a VDIF header.
+//!
+//! The EDV number sits in the top 8 bits of the first extended data word. The layouts here follow the registered EDV
+//! descriptions at [vlbi.org](https://vlbi.org/vlbi-standards/vdif/).
+
+/// The sync word carried by EDVs 1, 3 and 4.
+pub const EDV_SYNC_WORD: u32 = 0xACABFEED;
+
+/// The 20-bit sync word carried by EDV 2 (ALMA).
+pub const ALMA_SYNC_WORD: u32 = 0xA5EA5;
+
+/// A sample rate as carried in EDVs 1 and 3: a 23-bit value in units of either kHz or MHz.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SampleRate {
+    /// The sample rate, in the units given by [`in_mhz`](SampleRate::in_mhz).
+    pub value: u32,
+    /// Whether [`value`](SampleRate::value) is in MHz rather than kHz.
+    pub in_mhz: bool,
+}
+
+impl SampleRate {
+    /// Get the sample rate in samples per second.
+    pub fn hz(&self) -> u64 {
+        return self.value as u64 * if self.in_mhz { 1_000_000 } else { 1_000 };
+    }
+
+    fn decode(word: u32) -> Self {
+        return Self {
+            value: word & 0x7FFFFF,
+            in_mhz: word & (1 << 23) != 0,
+        };
+    }
+
+    fn encode(&self) -> u32 {
+        return (self.value & 0x7FFFFF) | ((self.in_mhz as u32) << 23);
+    }
+}
+
+/// The extended data of a VDIF header, decoded according to its EDV.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtendedData {
+    /// EDV 0: no extended data.
+    None,
+    /// EDV 1, used by NICT.
+    Nict {
+        /// The sample rate.
+        sample_rate: SampleRate,
+        /// The sync word, which should be [`EDV_SYNC_WORD`].
+        sync: u32,
+        /// The name of the data acquisition system, as 8 ASCII characters.
+        das_name: [u8; 8],
+    },
+    /// EDV 2, used by ALMA.
+    Alma {
+        /// The 20-bit sync word, which should be [`ALMA_SYNC_WORD`].
+        sync: u32,
+        /// The polarization, 0 or 1.
+        polarization: u8,
+        /// The baseband down-converter sideband.
+        bdc_sideband: bool,
+        /// The quadrant, 1 to 4.
+        quadrant: u8,
+        /// The PIC status word.
+        pic_status: u32,
+        /// The packet serial number.
+        packet_serial: u64,
+    },
+    /// EDV 3, used by the VLBA.
+    Vlba {
+        /// The sample rate.
+        sample_rate: SampleRate,
+        /// The sync word, which should be [`EDV_SYNC_WORD`].
+        sync: u32,
+        /// The raw DBE tuning word.
+        tuning_word: u32,
+        /// The DBE personality type.
+        personality: u8,
+        /// The minor revision of the DBE personality.
+        minor_rev: u8,
+        /// The major revision of the DBE personality.
+        major_rev: u8,
+        /// Whether the data is upper sideband.
+        upper_sideband: bool,
+        /// The sub-band number.
+        subband: u8,
+        /// The IF number.
+        if_number: u8,
+        /// The DBE unit number.
+        dbe_unit: u8,
+    },
+    /// EDV 4, a validity mask for multiplexed or partially valid data.
+    Validity {
+        /// The number of valid bits in [`mask`](ExtendedData::Validity::mask).
+        mask_length: u8,
+        /// The sync word, which should be [`EDV_SYNC_WORD`].
+        sync: u32,
+        /// The validity mask, one bit per channel or payload segment.
+        mask: u64,
+    },
+    /// An EDV this crate doesn't know, holding the raw extended data words.
+    Unknown {
+        /// The EDV number.
+        version: u8,
+        /// The raw extended data words.
+        words: [u32; 4],
+    },
+}
+
+impl ExtendedData {
+    /// Decode the four extended data words of a header.
+    pub fn decode(words: [u32; 4]) -> Self {
+        return match (words[0] >> 24) as u8 {
+            0 => Self::None,
+            1 => Self::Nict {
+                sample_rate: SampleRate::decode(words[0]),
+                sync: words[1],
+                das_name: [words[2].to_le_bytes(), words[3].to_le_bytes()]
+                    .concat()
+                    .try_into()
+                    .unwrap(),
+            },
+            2 => Self::Alma {
+                sync: words[0] & 0xFFFFF,
+                polarization: ((words[0] >> 20) & 1) as u8,
+                bdc_sideband: (words[0] >> 21) & 1 != 0,
+                quadrant: ((words[0] >> 22) & 0b11) as u8 + 1,
+                pic_status: words[1],
+                packet_serial: words[2] as u64 | ((words[3] as u64) << 32),
+            },
+            3 => Self::Vlba {
+                sample_rate: SampleRate::decode(words[0]),
+                sync: words[1],
+                tuning_word: words[2],
+                personality: words[3] as u8,
+                minor_rev: ((words[3] >> 8) & 0xF) as u8,
+                major_rev: ((words[3] >> 12) & 0xF) as u8,
+                upper_sideband: (words[3] >> 16) & 1 != 0,
+                subband: ((words[3] >> 17) & 0b111) as u8,
+                if_number: ((words[3] >> 20) & 0xF) as u8,
+                dbe_unit: ((words[3] >> 24) & 0xF) as u8,
+            },
+            4 => Self::Validity {
+                mask_length: (words[0] >> 16) as u8,
+                sync: words[1],
+                mask: words[2] as u64 | ((words[3] as u64) << 32),
+            },
+            version => Self::Unknown {
+                version: version,
+                words: words,
+            },
+        };
+    }
+
+    /// Encode this extended data into the four extended data words of a header.
+    pub fn encode(&self) -> [u32; 4] {
+        return match *self {
+            Self::None => [0; 4],
+            Self::Nict {
+                sample_rate,
+                sync,
+                das_name,
+            } => [
+                (1 << 24) | sample_rate.encode(),
+                sync,
+                u32::from_le_bytes(das_name[0..4].try_into().unwrap()),
+                u32::from_le_bytes(das_name[4..8].try_into().unwrap()),
+            ],
+            Self::Alma {
+                sync,
+                polarization,
+                bdc_sideband,
+                quadrant,
+                pic_status,
+                packet_serial,
+            } => [
+                (2 << 24)
+                    | (((quadrant.saturating_sub(1) & 0b11) as u32) << 22)
+                    | ((bdc_sideband as u32) << 21)
+                    | (((polarization & 1) as u32) << 20)
+                    | (sync & 0xFFFFF),
+                pic_status,
+                packet_serial as u32,
+                (packet_serial >> 32) as u32,
+            ],
+            Self::Vlba {
+                sample_rate,
+                sync,
+                tuning_word,
+                personality,
+                minor_rev,
+                major_rev,
+                upper_sideband,
+                subband,
+                if_number,
+                dbe_unit,
+            } => [
+                (3 << 24) | sample_rate.encode(),
+                sync,
+                tuning_word,
+                personality as u32
+                    | (((minor_rev & 0xF) as u32) << 8)
+                    | (((major_rev & 0xF) as u32) << 12)
+                    | ((upper_sideband as u32) << 16)
+                    | (((subband & 0b111) as u32) << 17)
+                    | (((if_number & 0xF) as u32) << 20)
+                    | (((dbe_unit & 0xF) as u32) << 24),
+            ],
+            Self::Validity {
+                mask_length,
+                sync,
+                mask,
+            } => [
+                (4 << 24) | ((mask_length as u32) << 16),
+                sync,
+                mask as u32,
+                (mask >> 32) as u32,
+            ],
+            Self::Unknown { version, words } => [
+                ((version as u32) << 24) | (words[0] & 0xFFFFFF),
+                words[1],
+                words[2],
+                words[3],
+            ],
+        };
+    }
+
+    /// Get the EDV number.
+    pub fn version(&self) -> u8 {
+        return match self {
+            Self::None => 0,
+            Self::Nict { .. } => 1,
+            Self::Alma { .. } => 2,
+            Self::Vlba { .. } => 3,
+            Self::Validity { .. } => 4,
+            Self::Unknown { version, .. } => *version,
+        };
+    }
+
+    /// Returns `true` if this EDV carries a sync word and it holds the expected value. EDVs without a sync word
+    /// always return `true`.
+    pub fn is_synced(&self) -> bool {
+        return match self {
+            Self::Nict { sync, .. } | Self::Vlba { sync, .. } | Self::Validity { sync, .. } => {
+                *sync == EDV_SYNC_WORD
+            }
+            Self::Alma { sync, .. } => *sync == ALMA_SYNC_WORD,
+            Self::None | Self::Unknown { .. } => true,
+        };
+    }
+
+    /// Get the sample rate in samples per second, for the EDVs that carry one.
+    pub fn sample_rate(&self) -> Option<u64> {
+        return match self {
+            Self::Nict { sample_rate, .. } | Self::Vlba { sample_rate, .. } => {
+                Some(sample_rate.hz())
+            }
+            _ => None,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extended_data_roundtrip() {
+        let vlba = ExtendedData::Vlba {
+            sample_rate: SampleRate {
+                value: 64,
+                in_mhz: true,
+            },
+            sync: EDV_SYNC_WORD,
+            tuning_word: 0x12345678,
+            personality: 0xE0,
+            minor_rev: 2,
+            major_rev: 1,
+            upper_sideband: true,
+            subband: 3,
+            if_number: 1,
+            dbe_unit: 0,
+        };
+        let words = vlba.encode();
+        assert_eq!(words[0], 0x03800040);
+        assert_eq!(ExtendedData::decode(words), vlba);
+        assert_eq!(vlba.sample_rate(), Some(64_000_000));
+        assert!(vlba.is_synced());
+
+        let alma = ExtendedData::Alma {
+            sync: ALMA_SYNC_WORD,
+            polarization: 1,
+            bdc_sideband: false,
+            quadrant: 4,
+            pic_status: 7,
+            packet_serial: 1 << 40,
+        };
+        assert_eq!(ExtendedData::decode(alma.encode()), alma);
+
+        let nict = ExtendedData::decode([(1 << 24) | 512, 0, 0, 0]);
+        assert_eq!(nict.sample_rate(), Some(512_000));
+        assert!(!nict.is_synced());
+        assert_eq!(ExtendedData::decode([0; 4]), ExtendedData::None);
+    }
+}
@@ -0,0 +1,471 @@
+//! Implements whole-payload encoding of VDIF frames, the counterpart to [`decoding`](crate::decoding).
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use num_complex::Complex;
+use num_traits::Float;
+
+use crate::frame::VDIFFrame;
+use crate::layout::{ComplexPacking, FrameLayout};
+
+pub use crate::consts::OPTIMAL_2BIT_THRESHOLD;
+
+/// Encode channel-major real samples into the payload of `frame` in a single pass.
+///
+/// `channels[c][n]` is the `n`th sample of channel `c`. Samples are signed and converted to VDIF's offset binary
+/// representation, so valid values lie in `-2^(bits - 1)..2^(bits - 1)`; values outside this range are clipped.
+/// Samples are interleaved across channels and packed into words as the VDIF specification requires (see
+/// [`FrameLayout`]), and the header of `frame` is left untouched.
+///
+/// The number of channels must be a power of two, and each channel must provide exactly enough samples to fill the
+/// payload.
+pub fn encode_payload_from_channels(channels: &[&[i8]], bits: u8, frame: &mut VDIFFrame) {
+    assert!(
+        (1..=8).contains(&bits),
+        "Only 1 to 8 bits/sample can be encoded from i8 samples"
+    );
+    assert!(
+        channels.len().is_power_of_two(),
+        "The number of channels must be a power of two"
+    );
+    let layout = FrameLayout::with_payload_words(
+        bits as u32,
+        true,
+        channels.len(),
+        frame.get_payload().len(),
+    )
+    .expect("The layout was checked above");
+    assert!(
+        channels
+            .iter()
+            .all(|c| c.len() == layout.samples_per_channel()),
+        "Each channel must contain exactly {} samples",
+        layout.samples_per_channel()
+    );
+
+    let offset = 1i16 << (bits - 1);
+    let max = (1i16 << bits) - 1;
+    let nchan = channels.len();
+    let used_words = layout.used_words();
+    let mut sample = 0;
+    for (i, word) in frame.get_mut_payload().iter_mut().enumerate() {
+        let mut out: u32 = 0;
+        if i < used_words {
+            for j in 0..layout.samples_in_word(i) {
+                let value = channels[sample % nchan][sample / nchan] as i16;
+                let code = (value + offset).clamp(0, max) as u32;
+                out |= code << (j as u32 * bits as u32);
+                sample += 1;
+            }
+        }
+        *word = out;
+    }
+}
+
+/// Encode channel-major complex samples into the payload of `frame`, packed according to `packing`.
+///
+/// This is the complex counterpart to [`encode_payload_from_channels`], with the same conversion of each component to
+/// offset binary and the same requirements on the channels.
+pub fn encode_complex_payload_from_channels(
+    channels: &[&[Complex<i8>]],
+    bits: u8,
+    packing: ComplexPacking,
+    frame: &mut VDIFFrame,
+) {
+    assert!(
+        (1..=8).contains(&bits),
+        "Only 1 to 8 bits/sample can be encoded from i8 samples"
+    );
+    assert!(
+        channels.len().is_power_of_two(),
+        "The number of channels must be a power of two"
+    );
+    let layout = FrameLayout::with_payload_words(
+        bits as u32,
+        false,
+        channels.len(),
+        frame.get_payload().len(),
+    )
+    .expect("The layout was checked above")
+    .with_complex_packing(packing);
+    assert!(
+        channels
+            .iter()
+            .all(|c| c.len() == layout.samples_per_channel()),
+        "Each channel must contain exactly {} samples",
+        layout.samples_per_channel()
+    );
+
+    let offset = 1i16 << (bits - 1);
+    let max = (1i16 << bits) - 1;
+    let payload = frame.get_mut_payload();
+    payload.fill(0);
+    for (c, channel) in channels.iter().enumerate() {
+        for (n, sample) in channel.iter().enumerate() {
+            for (imag, value) in [(false, sample.re), (true, sample.im)] {
+                let (word, shift) = layout.component_index(c, n, imag);
+                let code = (value as i16 + offset).clamp(0, max) as u32;
+                payload[word] |= code << shift;
+            }
+        }
+    }
+}
+
+/// Configures how a [`Quantiser`] converts floating point samples to low bit depths.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuantiseOptions {
+    /// The bits/sample to quantise to, from 1 to 8.
+    pub bits: u8,
+    /// The input magnitude at which samples are clipped to the outermost quantisation levels.
+    pub clip_level: f32,
+    /// Whether to add triangular (TPDF) dither of ±1 quantisation step before quantising.
+    pub dither: bool,
+}
+
+/// Statistics accumulated by a [`Quantiser`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct QuantiseStats {
+    /// The number of samples quantised.
+    pub samples: u64,
+    /// The number of samples that were clipped.
+    pub clipped: u64,
+}
+
+impl QuantiseStats {
+    /// Get the fraction of quantised samples that were clipped.
+    pub fn clip_fraction(&self) -> f64 {
+        if self.samples == 0 {
+            return 0.0;
+        }
+        return self.clipped as f64 / self.samples as f64;
+    }
+}
+
+/// Quantises floating point samples into signed low bit depth samples, suitable for
+/// [`encode_payload_from_channels`].
+///
+/// The input range `-clip_level..clip_level` is divided into `2^bits` equal steps, and a sample is clipped if it falls
+/// outside this range.
+pub struct Quantiser {
+    options: QuantiseOptions,
+    stats: QuantiseStats,
+    rng_state: u64,
+}
+
+impl Quantiser {
+    /// Construct a new [`Quantiser`].
+    pub fn new(options: QuantiseOptions) -> Self {
+        return Self::with_seed(options, 0x2545F4914F6CDD1D);
+    }
+
+    /// Construct a new [`Quantiser`] whose dither is generated from `seed`, for reproducible output.
+    pub fn with_seed(options: QuantiseOptions, seed: u64) -> Self {
+        assert!(
+            (1..=8).contains(&options.bits),
+            "Only 1 to 8 bits/sample can be quantised to"
+        );
+        return Self {
+            options: options,
+            stats: QuantiseStats::default(),
+            // xorshift can't recover from a zero state
+            rng_state: seed.max(1),
+        };
+    }
+
+    /// Quantise `input` into `output`, which must be the same length.
+    pub fn quantise(&mut self, input: &[f32], output: &mut [i8]) {
+        assert_eq!(
+            input.len(),
+            output.len(),
+            "Input and output must be the same length"
+        );
+        let half_levels = 1i32 << (self.options.bits - 1);
+        let step = self.options.clip_level / half_levels as f32;
+
+        for (x, out) in input.iter().zip(output.iter_mut()) {
+            let mut value = *x;
+            if self.options.dither {
+                value += (self.next_uniform() + self.next_uniform() - 1.0) * step;
+            }
+            let level = Float::floor(value / step) as i32;
+            if level < -half_levels || level >= half_levels {
+                self.stats.clipped += 1;
+            }
+            *out = level.clamp(-half_levels, half_levels - 1) as i8;
+        }
+        self.stats.samples += input.len() as u64;
+    }
+
+    /// Get the statistics accumulated since construction or the last call to [`reset_stats`](Quantiser::reset_stats).
+    pub fn stats(&self) -> QuantiseStats {
+        return self.stats;
+    }
+
+    /// Reset the accumulated statistics.
+    pub fn reset_stats(&mut self) {
+        self.stats = QuantiseStats::default();
+    }
+
+    // A uniform value in [0, 1) from a xorshift64* generator.
+    fn next_uniform(&mut self) -> f32 {
+        self.rng_state ^= self.rng_state >> 12;
+        self.rng_state ^= self.rng_state << 25;
+        self.rng_state ^= self.rng_state >> 27;
+        let value = self.rng_state.wrapping_mul(0x2545F4914F6CDD1D);
+        return (value >> 40) as f32 / (1u64 << 24) as f32;
+    }
+}
+
+/// Quantise channel-major floating point samples with `quantiser` and encode them into the payload of `frame`.
+///
+/// This is equivalent to quantising each channel and passing the results to [`encode_payload_from_channels`].
+pub fn encode_payload_from_f32_channels(
+    channels: &[&[f32]],
+    quantiser: &mut Quantiser,
+    frame: &mut VDIFFrame,
+) {
+    let quantised: Vec<Vec<i8>> = channels
+        .iter()
+        .map(|c| {
+            let mut out = vec![0i8; c.len()];
+            quantiser.quantise(c, &mut out);
+            out
+        })
+        .collect();
+    let refs: Vec<&[i8]> = quantised.iter().map(|c| c.as_slice()).collect();
+    encode_payload_from_channels(&refs, quantiser.options.bits, frame);
+}
+
+/// Configures an [`Agc`] stage.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AgcOptions {
+    /// The RMS the output is scaled to.
+    pub target_rms: f32,
+    /// The time constant in samples used while the input power is rising.
+    pub attack: f32,
+    /// The time constant in samples used while the input power is falling.
+    pub decay: f32,
+}
+
+impl AgcOptions {
+    /// Construct [`AgcOptions`] targeting the optimal 2-bit thresholds of a [`Quantiser`] with the given `clip_level`.
+    pub fn two_bit(clip_level: f32, attack: f32, decay: f32) -> Self {
+        // A 2-bit quantiser places its inner thresholds at half the clip level
+        return Self {
+            target_rms: clip_level / 2.0 / OPTIMAL_2BIT_THRESHOLD,
+            attack: attack,
+            decay: decay,
+        };
+    }
+}
+
+/// An automatic gain control stage, which tracks the RMS of its input and scales it towards a target RMS before
+/// requantisation.
+///
+/// The input power is tracked with an exponential moving average, using separate time constants for rising and falling
+/// power.
+pub struct Agc {
+    options: AgcOptions,
+    power: Option<f32>,
+}
+
+impl Agc {
+    /// Construct a new [`Agc`].
+    pub fn new(options: AgcOptions) -> Self {
+        assert!(
+            options.attack >= 1.0 && options.decay >= 1.0,
+            "AGC time constants must be at least one sample"
+        );
+        return Self {
+            options: options,
+            power: None,
+        };
+    }
+
+    /// Scale `samples` in place towards the target RMS, updating the tracked input power.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples.iter_mut() {
+            let power_in = *sample * *sample;
+            let power = match self.power {
+                // Seed the estimate with the first sample rather than ramping up from zero
+                None => power_in,
+                Some(power) => {
+                    let tau = if power_in > power {
+                        self.options.attack
+                    } else {
+                        self.options.decay
+                    };
+                    power + (power_in - power) / tau
+                }
+            };
+            self.power = Some(power);
+            *sample *= self.gain();
+        }
+    }
+
+    /// Get the gain currently being applied.
+    pub fn gain(&self) -> f32 {
+        return match self.power {
+            Some(power) if power > 0.0 => self.options.target_rms / Float::sqrt(power),
+            _ => 1.0,
+        };
+    }
+
+    /// Get the currently tracked input RMS, if any samples have been processed.
+    pub fn input_rms(&self) -> Option<f32> {
+        return self.power.map(Float::sqrt);
+    }
+
+    /// Forget the tracked input power.
+    pub fn reset(&mut self) {
+        self.power = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoding::{decode_payload, decode_payload_with_layout, Samples};
+    use crate::header::VDIFHeader;
+
+    #[test]
+    fn test_encode_payload_from_channels() {
+        let mut frame = VDIFFrame::empty(40);
+        frame.set_header(VDIFHeader {
+            size: 5,
+            channels: 1,
+            bits_per_sample: 1,
+            is_real: true,
+            ..Default::default()
+        });
+
+        let ch0 = [-2i8; 16];
+        let ch1 = [1i8; 16];
+        encode_payload_from_channels(&[&ch0, &ch1], 2, &mut frame);
+        assert_eq!(frame.get_payload(), [0xCCCCCCCC, 0xCCCCCCCC]);
+
+        let decoded = decode_payload(&frame).unwrap().split_channels(2);
+        assert_eq!(decoded[0], Samples::U8(vec![0; 16]));
+        assert_eq!(decoded[1], Samples::U8(vec![3; 16]));
+
+        // A legacy header leaves room for four more words of samples
+        frame.set_header(VDIFHeader {
+            is_legacy: true,
+            size: 5,
+            channels: 1,
+            bits_per_sample: 1,
+            is_real: true,
+            ..Default::default()
+        });
+        let ch0 = [-2i8; 48];
+        let ch1 = [1i8; 48];
+        encode_payload_from_channels(&[&ch0, &ch1], 2, &mut frame);
+        assert_eq!(frame.get_payload(), [0xCCCCCCCC; 6]);
+    }
+
+    #[test]
+    fn test_encode_spanning_complete_samples() {
+        // 3-bit samples from 16 channels span two words per complete sample
+        let mut frame = VDIFFrame::empty(48);
+        frame.set_header(VDIFHeader {
+            size: 6,
+            channels: 4,
+            bits_per_sample: 2,
+            is_real: true,
+            ..Default::default()
+        });
+
+        let data: Vec<[i8; 2]> = (0..16)
+            .map(|c| [(c % 8) as i8 - 4, 3 - (c % 8) as i8])
+            .collect();
+        let channels: Vec<&[i8]> = data.iter().map(|c| c.as_slice()).collect();
+        encode_payload_from_channels(&channels, 3, &mut frame);
+        // The top two bits of full words and the top 14 bits of the second word of each sample are unused
+        assert_eq!(frame.get_payload()[0] >> 30, 0);
+        assert_eq!(frame.get_payload()[1] >> 18, 0);
+
+        let decoded = decode_payload(&frame).unwrap().split_channels(16);
+        for (c, samples) in decoded.iter().enumerate() {
+            let expected: Vec<u8> = data[c].iter().map(|v| (v + 4) as u8).collect();
+            assert_eq!(*samples, Samples::U8(expected));
+        }
+    }
+
+    #[test]
+    fn test_complex_packing_roundtrip() {
+        let mut frame = VDIFFrame::empty(40);
+        frame.set_header(VDIFHeader {
+            size: 5,
+            bits_per_sample: 5,
+            is_real: false,
+            ..Default::default()
+        });
+
+        for packing in [
+            ComplexPacking::TruncateToPairs,
+            ComplexPacking::KeepExtraReal,
+        ] {
+            let layout = FrameLayout::from_header(&frame.get_header())
+                .unwrap()
+                .with_complex_packing(packing);
+            let samples: Vec<Complex<i8>> = (0..layout.samples_per_channel() as i8)
+                .map(|n| Complex::new(n, -n))
+                .collect();
+            encode_complex_payload_from_channels(&[&samples], 6, packing, &mut frame);
+
+            let decoded = decode_payload_with_layout(&frame, &layout);
+            let expected: Vec<Complex<f32>> = samples
+                .iter()
+                .map(|s| Complex::new(s.re as f32 + 32.0, s.im as f32 + 32.0))
+                .collect();
+            assert_eq!(decoded, Some(Samples::ComplexF32(expected)));
+        }
+    }
+
+    #[test]
+    fn test_quantiser() {
+        let options = QuantiseOptions {
+            bits: 2,
+            clip_level: 2.0,
+            dither: false,
+        };
+        let mut quantiser = Quantiser::new(options);
+        let input = [-3.0, -1.5, -0.5, 0.5, 1.5, 2.5, 0.0, 1.0];
+        let mut output = [0i8; 8];
+        quantiser.quantise(&input, &mut output);
+        assert_eq!(output, [-2, -2, -1, 0, 1, 1, 0, 1]);
+        assert_eq!(quantiser.stats().clip_fraction(), 0.25);
+
+        let mut dithered = Quantiser::with_seed(
+            QuantiseOptions {
+                dither: true,
+                ..options
+            },
+            7,
+        );
+        let input = [0.5; 1000];
+        let mut output = [0i8; 1000];
+        dithered.quantise(&input, &mut output);
+        assert!(output.iter().all(|v| (-1..=1).contains(v)));
+        assert!(output.iter().any(|v| *v != 0));
+    }
+
+    #[test]
+    fn test_agc() {
+        let mut agc = Agc::new(AgcOptions {
+            target_rms: 1.0,
+            attack: 10.0,
+            decay: 100.0,
+        });
+        let mut samples: Vec<f32> = (0..2000)
+            .map(|i| if i % 2 == 0 { 8.0 } else { -8.0 })
+            .collect();
+        agc.process(&mut samples);
+        assert!((agc.input_rms().unwrap() - 8.0).abs() < 1e-3);
+        assert!((samples[1999].abs() - 1.0).abs() < 1e-3);
+
+        let options = AgcOptions::two_bit(2.0, 10.0, 10.0);
+        assert!((options.target_rms * OPTIMAL_2BIT_THRESHOLD - 1.0).abs() < 1e-6);
+    }
+}
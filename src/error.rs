@@ -0,0 +1,159 @@
+//! Implements [`VDIFError`], describing the ways VDIF data can be malformed.
+//!
+//! Readers and receivers return [`std::io::Error`]s, since most of what can go wrong with them is I/O. When the data
+//! itself is at fault, the [`io::Error`](std::io::Error) has kind [`InvalidData`](std::io::ErrorKind::InvalidData)
+//! and carries a [`VDIFError`] as its inner error, which [`VDIFError::from_io`] recovers:
+//!
+//! ```rust,ignore
+//! match reader.read_frame() {
+//!     Ok(frame) => process(frame),
+//!     Err(e) => match VDIFError::from_io(&e) {
+//!         Some(VDIFError::ShortFrame { .. }) => println!("Recording ends partway through a frame"),
+//!         Some(other) => println!("Malformed frame: {}", other),
+//!         None => return Err(e),
+//!     },
+//! }
+//! ```
+//!
+//! Constructors that would otherwise panic on malformed input, such as [`VDIFFrame::from_byte_slice`], return a
+//! [`VDIFError`] directly.
+//!
+//! [`VDIFFrame::from_byte_slice`]: crate::frame::VDIFFrame::from_byte_slice
+
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io::{Error, ErrorKind};
+
+use crate::validation::ValidationError;
+
+/// A problem with VDIF data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VDIFError {
+    /// A frame ended before all of its bytes arrived, such as at the end of a file or in a short datagram.
+    ShortFrame {
+        /// The expected size of the frame in bytes.
+        expected: usize,
+        /// The number of bytes received, where known.
+        received: Option<usize>,
+    },
+    /// A frame size is not a multiple of 8 bytes, is smaller than a VDIF header, or doesn't match the stream it belongs
+    /// to.
+    BadFrameSize(usize),
+    /// The header size field differs from the size of the frame carrying it.
+    HeaderSizeMismatch {
+        /// The frame size according to the header.
+        header: usize,
+        /// The size of the frame carrying the header.
+        frame: usize,
+    },
+    /// The header size field is smaller than a VDIF header, or larger than the largest frame accepted.
+    HeaderSizeOutOfRange {
+        /// The frame size according to the header.
+        header: usize,
+        /// The largest frame size accepted.
+        max: usize,
+    },
+    /// A frame's VDIF version differs from that of the rest of the stream.
+    VersionMismatch {
+        /// The version of the rest of the stream.
+        expected: u8,
+        /// The version of the frame.
+        found: u8,
+    },
+    /// A frame's legacy header flag differs from that of the rest of the stream.
+    LegacyMismatch {
+        /// Whether the rest of the stream uses legacy headers.
+        expected: bool,
+    },
+    /// A frame failed [validation](crate::validation::ValidationLevel).
+    Validation(ValidationError),
+}
+
+#[cfg(feature = "std")]
+impl VDIFError {
+    /// Get the [`VDIFError`] carried by `error`, if any.
+    pub fn from_io(error: &Error) -> Option<&VDIFError> {
+        return error.get_ref()?.downcast_ref::<VDIFError>();
+    }
+}
+
+impl fmt::Display for VDIFError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Self::ShortFrame {
+                expected,
+                received: Some(received),
+            } => write!(
+                f,
+                "Received {} bytes, expected a {} byte frame",
+                received, expected
+            ),
+            Self::ShortFrame { expected, .. } => {
+                write!(f, "Did not read a complete {} byte VDIF frame", expected)
+            }
+            Self::BadFrameSize(size) => write!(f, "{} bytes is not a valid VDIF frame size", size),
+            Self::HeaderSizeMismatch { header, frame } => write!(
+                f,
+                "Header frame size of {} bytes does not match the {} byte frame",
+                header, frame
+            ),
+            Self::HeaderSizeOutOfRange { header, max } => write!(
+                f,
+                "Header frame size of {} bytes is outside the accepted range of {} to {} bytes",
+                header,
+                crate::consts::HEADER_SIZE,
+                max
+            ),
+            Self::VersionMismatch { expected, found } => write!(
+                f,
+                "Frame VDIF version {} differs from version {} of the rest of the stream",
+                found, expected
+            ),
+            Self::LegacyMismatch { expected: true } => {
+                write!(f, "Frame has a full header in a stream of legacy headers")
+            }
+            Self::LegacyMismatch { expected: false } => {
+                write!(f, "Frame has a legacy header in a stream of full headers")
+            }
+            Self::Validation(error) => write!(f, "{}", error),
+        };
+    }
+}
+
+impl core::error::Error for VDIFError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        return match self {
+            Self::Validation(error) => Some(error),
+            _ => None,
+        };
+    }
+}
+
+impl From<ValidationError> for VDIFError {
+    fn from(error: ValidationError) -> Self {
+        return Self::Validation(error);
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<VDIFError> for Error {
+    fn from(error: VDIFError) -> Self {
+        return Error::new(ErrorKind::InvalidData, error);
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_io() {
+        let error: Error = VDIFError::BadFrameSize(12).into();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            VDIFError::from_io(&error),
+            Some(&VDIFError::BadFrameSize(12))
+        );
+        assert_eq!(VDIFError::from_io(&ErrorKind::NotFound.into()), None);
+    }
+}
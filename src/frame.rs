@@ -1,77 +1,184 @@
 //! Implements [`VDIFFrame`].
 
-use crate::header::VDIFHeader;
-use crate::header_encoding::decode_frame_header;
+use alloc::alloc::{alloc_zeroed, dealloc, handle_alloc_error, Layout};
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ptr::NonNull;
+#[cfg(feature = "std")]
+use std::io::Result;
 
-/// A VDIF frame.
+use chrono::{DateTime, Utc};
+
+use crate::consts::{HEADER_SIZE, LEGACY_HEADER_SIZE};
+use crate::error::VDIFError;
+#[cfg(feature = "std")]
+use crate::header::check_buffer;
+use crate::header::{FrameMeta, VDIFHeader};
+use crate::header_encoding::{decode_frame_header, encode_header, header_words};
+use crate::validation::{ValidationError, ValidationProfile};
+
+/// Storage for the words of a [`VDIFFrame`].
 ///
-/// Each [`VDIFFrame`] simply contains a heap allocated slice of `u32`s. The header is decoded when you call
-/// [`get_header`](VDIFFrame::get_header), so you don't pay a cost for simply creating this type.
+/// This is implemented for anything that derefs to a `u32` slice, so frames can live in memory other than the default
+/// heap allocation, such as a hugepage pool, a shared memory segment or pinned memory, without being copied. Use
+/// [`VDIFFrame::with_storage`] to wrap such storage.
+pub trait FrameStorage: AsRef<[u32]> + AsMut<[u32]> {}
+
+impl<S: AsRef<[u32]> + AsMut<[u32]>> FrameStorage for S {}
+
+/// Zeroed heap storage for a [`VDIFFrame`], aligned to a chosen boundary, such as a cache line for SIMD kernels or a
+/// page for DMA engines and GPUs. Construct frames with it using [`VDIFFrame::new_aligned`].
 #[derive(Debug)]
-pub struct VDIFFrame {
-    data: Box<[u32]>,
+pub struct AlignedStorage {
+    ptr: NonNull<u32>,
+    len: usize,
+    layout: Layout,
 }
 
-impl VDIFFrame {
-    /// Construct a [`VDIFFrame`] from a raw `u32` slice.
-    pub fn new(data: Box<[u32]>) -> Self {
-        assert!(
-            data.len() % 2 == 0,
-            "VDIF frames must be a multiple of 8 bytes in size."
-        );
-        return Self { data: data };
-    }
-
-    /// Construct a [`VDIFFrame`] by copying the contents of `data`.
-    pub fn from_slice(data: &[u32]) -> Self {
-        assert!(
-            data.len() % 2 == 0,
-            "VDIF frames must be a multiple of 8 bytes in size."
-        );
+impl AlignedStorage {
+    /// Allocate `words` zeroed `u32` words, starting on a multiple of `align` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `words` is zero or too large to allocate, or `align` is not a power of two.
+    pub fn new(words: usize, align: usize) -> Self {
+        assert!(words > 0, "Aligned frame storage can't be empty");
+        let layout = Layout::array::<u32>(words)
+            .expect("Aligned frame storage is too large")
+            .align_to(align.max(4))
+            .expect("Frame alignment must be a power of two");
+        // Safety: the layout has a non-zero size
+        let ptr = unsafe { alloc_zeroed(layout) } as *mut u32;
+        let Some(ptr) = NonNull::new(ptr) else {
+            handle_alloc_error(layout);
+        };
         return Self {
-            data: Box::from(data),
+            ptr: ptr,
+            len: words,
+            layout: layout,
         };
     }
 
-    /// Construct a completely empty [`VDIFFrame`].
-    pub fn empty(frame_size: usize) -> Self {
+    /// Get the alignment of the storage in bytes.
+    pub fn align(&self) -> usize {
+        return self.layout.align();
+    }
+}
+
+impl AsRef<[u32]> for AlignedStorage {
+    fn as_ref(&self) -> &[u32] {
+        // Safety: the allocation holds `len` initialised words, owned by us
+        return unsafe { core::slice::from_raw_parts(self.ptr.as_ptr(), self.len) };
+    }
+}
+
+impl AsMut<[u32]> for AlignedStorage {
+    fn as_mut(&mut self) -> &mut [u32] {
+        // Safety: as above, and `&mut self` guarantees the access is unique
+        return unsafe { core::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) };
+    }
+}
+
+impl Drop for AlignedStorage {
+    fn drop(&mut self) {
+        // Safety: allocated in `new` with this layout
+        unsafe { dealloc(self.ptr.as_ptr() as *mut u8, self.layout) };
+    }
+}
+
+// Safety: the storage uniquely owns its allocation, like a `Box<[u32]>`
+unsafe impl Send for AlignedStorage {}
+unsafe impl Sync for AlignedStorage {}
+
+/// A VDIF frame.
+///
+/// By default each [`VDIFFrame`] simply contains a heap allocated slice of `u32`s, but any [`FrameStorage`] can be used
+/// instead. The header is decoded when you call [`get_header`](VDIFFrame::get_header), so you don't pay a cost for
+/// simply creating this type.
+#[derive(Debug)]
+pub struct VDIFFrame<S: FrameStorage = Box<[u32]>> {
+    data: S,
+}
+
+impl<S: FrameStorage> VDIFFrame<S> {
+    /// Construct a [`VDIFFrame`] around `storage`, without copying it.
+    pub fn with_storage(storage: S) -> Self {
         assert!(
-            frame_size % 8 == 0,
+            storage.as_ref().len() % 2 == 0,
             "VDIF frames must be a multiple of 8 bytes in size."
         );
-        return Self {
-            data: vec![0; frame_size / 4].into_boxed_slice(),
-        };
+        return Self { data: storage };
+    }
+
+    /// Consume this frame, returning its storage.
+    pub fn into_storage(self) -> S {
+        return self.data;
     }
 
     /// Get a single `u32` word from this frame.
     pub fn get_word(&self, ind: usize) -> u32 {
-        return self.data[ind];
+        return self.as_slice()[ind];
     }
 
-    /// Get a single `u32` word from the payload. Equivalent to `get_word(8 + ind)`.
+    /// Get a single `u32` word from the payload. Equivalent to `get_word(8 + ind)`, or `get_word(4 + ind)` for a
+    /// legacy frame.
     pub fn get_data_word(&self, ind: usize) -> u32 {
-        return self.data[8 + ind];
+        return self.as_slice()[self.header_words() + ind];
     }
 
     /// Construct a [`VDIFHeader`] from this frame.
     pub fn get_header(&self) -> VDIFHeader {
-        return decode_frame_header(&self);
+        return decode_frame_header(self);
+    }
+
+    /// Summarise the header of this frame as a [`FrameMeta`].
+    pub fn meta(&self) -> FrameMeta {
+        return self.get_header().meta();
     }
 
-    /// Get a reference to the payload portion of this frame.
+    /// Get every problem with this frame under `profile`, or an empty list if it is fine. Unlike
+    /// [`ValidationLevel::check`](crate::validation::ValidationLevel::check), this doesn't stop at the first problem.
+    pub fn validate(&self, profile: ValidationProfile) -> Vec<ValidationError> {
+        return profile.problems(&self.get_header(), self.bytesize());
+    }
+
+    /// Get the UTC time of the start of this frame, given `frame_rate` frames per second per thread. See
+    /// [`time`](crate::time). Returns [`None`] if `frame_rate` is zero.
+    pub fn timestamp(&self, frame_rate: u32) -> Option<DateTime<Utc>> {
+        return self.get_header().timestamp(frame_rate);
+    }
+
+    /// Encode `header` into the first eight words of this frame, or the first four for a legacy header, overwriting
+    /// the existing header.
+    pub fn set_header(&mut self, header: VDIFHeader) {
+        let words = header.header_bytesize() as usize / 4;
+        self.as_mut_slice()[0..words].copy_from_slice(&encode_header(header)[0..words]);
+    }
+
+    /// Get a reference to the payload portion of this frame, which starts after a 16 byte header for legacy frames.
     pub fn get_payload(&self) -> &[u32] {
-        return &self.data[8..];
+        return &self.as_slice()[self.header_words()..];
     }
 
     /// Get a mutable reference to the payload portion of this frame.
     pub fn get_mut_payload(&mut self) -> &mut [u32] {
-        return &mut self.data[8..];
+        let start = self.header_words();
+        return &mut self.as_mut_slice()[start..];
+    }
+
+    /// Check whether this frame has a legacy header.
+    pub fn is_legacy(&self) -> bool {
+        return self.header_words() * 4 == LEGACY_HEADER_SIZE;
+    }
+
+    fn header_words(&self) -> usize {
+        return header_words(self.as_slice());
     }
 
     /// Get the length in `u32` words of this frame.
     pub fn len(&self) -> usize {
-        return self.data.len();
+        return self.as_slice().len();
     }
 
     /// Get the size in bytes of this frame.
@@ -81,25 +188,234 @@ impl VDIFFrame {
 
     /// Return a reference to the underlying `u32` slice, including the header.
     pub fn as_slice(&self) -> &[u32] {
-        return &self.data;
+        return self.data.as_ref();
     }
 
     /// Return a mutable reference to the underlying `u32` slice, including the header.
     pub fn as_mut_slice(&mut self) -> &mut [u32] {
-        return &mut self.data;
+        return self.data.as_mut();
     }
 
     /// Return a reference to the underlying bytes, including the header.
     pub fn as_bytes(&self) -> &[u8] {
-        return unsafe {
-            std::slice::from_raw_parts(self.data.as_ptr() as *const u8, self.data.len() * 4)
-        };
+        let data = self.as_slice();
+        return unsafe { core::slice::from_raw_parts(data.as_ptr() as *const u8, data.len() * 4) };
     }
 
     /// Return a mutable reference to the underlying bytes, including the header.
     pub fn as_mut_bytes(&mut self) -> &mut [u8] {
+        let data = self.as_mut_slice();
         return unsafe {
-            std::slice::from_raw_parts_mut(self.data.as_mut_ptr() as *mut u8, self.data.len() * 4)
+            core::slice::from_raw_parts_mut(data.as_mut_ptr() as *mut u8, data.len() * 4)
+        };
+    }
+
+    /// Copy this frame, header and payload, into the start of `buf` as little-endian bytes. Returns the number of bytes
+    /// written, or an error of kind [`InvalidInput`](std::io::ErrorKind::InvalidInput) if `buf` is too small.
+    #[cfg(feature = "std")]
+    pub fn write_to(&self, buf: &mut [u8]) -> Result<usize> {
+        let bytes = self.as_bytes();
+        check_buffer(buf, bytes.len())?;
+        buf[0..bytes.len()].copy_from_slice(bytes);
+        return Ok(bytes.len());
+    }
+
+    /// Copy this frame into a [`VDIFFrame`] with the default heap storage.
+    pub fn to_boxed(&self) -> VDIFFrame {
+        return VDIFFrame::from_slice(self.as_slice());
+    }
+}
+
+impl VDIFFrame {
+    /// Construct a [`VDIFFrame`] from a raw `u32` slice.
+    pub fn new(data: Box<[u32]>) -> Self {
+        assert!(
+            data.len() % 2 == 0,
+            "VDIF frames must be a multiple of 8 bytes in size."
+        );
+        return Self { data: data };
+    }
+
+    /// Construct a [`VDIFFrame`] from a raw `u32` slice, returning an error rather than panicking if it is not a valid
+    /// frame size.
+    pub fn try_new(data: Box<[u32]>) -> core::result::Result<Self, VDIFError> {
+        check_frame_size(data.len() * 4)?;
+        return Ok(Self { data: data });
+    }
+
+    /// Construct a [`VDIFFrame`] by copying raw little-endian bytes, such as a received datagram, returning an error if
+    /// they are not a valid frame size.
+    pub fn from_byte_slice(bytes: &[u8]) -> core::result::Result<Self, VDIFError> {
+        check_frame_size(bytes.len())?;
+        let mut frame = Self::empty(bytes.len());
+        frame.as_mut_bytes().copy_from_slice(bytes);
+        return Ok(frame);
+    }
+
+    /// Construct a [`VDIFFrame`] by copying the contents of `data`.
+    pub fn from_slice(data: &[u32]) -> Self {
+        assert!(
+            data.len() % 2 == 0,
+            "VDIF frames must be a multiple of 8 bytes in size."
+        );
+        return Self {
+            data: Box::from(data),
         };
     }
+
+    /// Construct a completely empty [`VDIFFrame`].
+    pub fn empty(frame_size: usize) -> Self {
+        assert!(
+            frame_size % 8 == 0,
+            "VDIF frames must be a multiple of 8 bytes in size."
+        );
+        return Self {
+            data: vec![0; frame_size / 4].into_boxed_slice(),
+        };
+    }
+
+    /// Construct a [`VDIFFrame`] with an empty payload, sized according to `header` and starting with it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size field of `header` is too small to hold the header itself, such as a size of zero. Use
+    /// [`try_from_header`](VDIFFrame::try_from_header) for headers that haven't been checked.
+    pub fn from_header(header: VDIFHeader) -> Self {
+        return Self::try_from_header(header)
+            .expect("VDIF frames must be large enough to hold their header");
+    }
+
+    /// Construct a [`VDIFFrame`] with an empty payload, sized according to `header` and starting with it, returning an
+    /// error rather than panicking if the size field of `header` is too small to hold the header itself.
+    pub fn try_from_header(header: VDIFHeader) -> core::result::Result<Self, VDIFError> {
+        if header.bytesize() < header.header_bytesize() {
+            return Err(VDIFError::BadFrameSize(header.bytesize() as usize));
+        }
+        let mut frame = Self::empty(header.bytesize() as usize);
+        frame.set_header(header);
+        return Ok(frame);
+    }
+}
+
+impl VDIFFrame<AlignedStorage> {
+    /// Construct a completely empty [`VDIFFrame`] whose words start on a multiple of `align` bytes, so its payload can
+    /// be handed to code with alignment requirements without being copied.
+    ///
+    /// Note that the payload itself starts 32 bytes into the frame, or 16 for a legacy frame, so is only aligned to
+    /// `align` if `align` divides the header size.
+    ///
+    /// Returns an error if `frame_size` is not a valid frame size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is not a power of two.
+    pub fn new_aligned(frame_size: usize, align: usize) -> core::result::Result<Self, VDIFError> {
+        check_frame_size(frame_size)?;
+        return Ok(Self {
+            data: AlignedStorage::new(frame_size / 4, align),
+        });
+    }
+}
+
+fn check_frame_size(size: usize) -> core::result::Result<(), VDIFError> {
+    if size < HEADER_SIZE || size % 8 != 0 {
+        return Err(VDIFError::BadFrameSize(size));
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_storage() {
+        let header = VDIFHeader {
+            size: 5,
+            frameno: 7,
+            ..Default::default()
+        };
+
+        // A frame borrowing part of a larger buffer, as it would from a memory pool
+        let mut pool = [0u32; 30];
+        let mut frame = VDIFFrame::with_storage(&mut pool[10..20]);
+        frame.set_header(header);
+        frame.get_mut_payload()[0] = 42;
+        assert_eq!(frame.to_boxed().get_header(), header);
+        assert_eq!((pool[11], pool[18]), (7, 42));
+
+        let frame = VDIFFrame::with_storage(vec![0u32; 10]);
+        assert_eq!(frame.into_storage().len(), 10);
+    }
+
+    #[test]
+    fn test_new_aligned() {
+        let mut frame = VDIFFrame::new_aligned(8032, 4096).unwrap();
+        assert_eq!(frame.as_slice().as_ptr() as usize % 4096, 0);
+        assert!(frame.as_slice().iter().all(|word| *word == 0));
+        frame.set_header(VDIFHeader {
+            size: 1004,
+            thread: 3,
+            ..Default::default()
+        });
+        assert_eq!(frame.to_boxed().get_header().thread, 3);
+        assert_eq!(frame.into_storage().align(), 4096);
+        assert_eq!(
+            VDIFFrame::new_aligned(16, 64).unwrap_err(),
+            VDIFError::BadFrameSize(16)
+        );
+    }
+
+    #[test]
+    fn test_legacy() {
+        let mut frame = VDIFFrame::from_header(VDIFHeader {
+            is_legacy: true,
+            size: 6,
+            edv0: 7,
+            ..Default::default()
+        });
+        assert!(frame.is_legacy());
+        assert_eq!(frame.get_payload().len(), 8);
+        frame.get_mut_payload()[0] = 5;
+        assert_eq!((frame.get_word(4), frame.get_data_word(0)), (5, 5));
+        assert_eq!(frame.get_header().edv0, 0);
+
+        let bytes = frame.as_bytes();
+        let (header, payload, rest) = crate::header_encoding::parse_frame(bytes).unwrap();
+        assert_eq!(
+            (header.data_bytesize(), payload.len(), rest.len()),
+            (32, 32, 0)
+        );
+        assert_eq!(payload[0], 5);
+    }
+
+    #[test]
+    fn test_from_byte_slice() {
+        let frame = VDIFFrame::from_byte_slice(&[1; 40]).unwrap();
+        assert_eq!(frame.get_word(9), 0x01010101);
+        assert_eq!(
+            VDIFFrame::from_byte_slice(&[0; 44]).unwrap_err(),
+            VDIFError::BadFrameSize(44)
+        );
+        assert!(VDIFFrame::try_new(vec![0; 4].into_boxed_slice()).is_err());
+        assert_eq!(
+            VDIFFrame::try_from_header(VDIFHeader::default()).unwrap_err(),
+            VDIFError::BadFrameSize(0)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_write_to() {
+        let frame = VDIFFrame::from_header(VDIFHeader {
+            size: 5,
+            frameno: 7,
+            ..Default::default()
+        });
+        let mut datagram = [0u8; 48];
+        datagram[0..8].copy_from_slice(b"MYHEADER");
+        assert_eq!(frame.write_to(&mut datagram[8..]).unwrap(), 40);
+        assert_eq!(&datagram[8..48], frame.as_bytes());
+        assert!(frame.write_to(&mut datagram[9..]).is_err());
+    }
 }
@@ -1,10 +1,21 @@
 //! Provides functionality for interacting with VDIF headers and header information.
 
+use alloc::string::{String, ToString};
+#[cfg(feature = "std")]
+use std::io::{Error, ErrorKind, Result};
+
 use chrono::{
     naive::{NaiveDate, NaiveDateTime},
-    Datelike, NaiveTime, TimeDelta,
+    DateTime, Datelike, NaiveTime, TimeDelta, Utc,
 };
 
+use crate::consts::{EPOCH_SECONDS, EPOCH_START_DATES, HEADER_SIZE, LEGACY_HEADER_SIZE};
+use crate::edv::ExtendedData;
+#[cfg(feature = "std")]
+use crate::header_encoding::encode_header_bytes;
+use crate::station::station_code;
+use crate::time::{from_datetime, to_datetime, MJD_UNIX_EPOCH};
+
 /// Station identifiers can be either a two character ASCII string, or a numeric ID.
 pub enum StationID {
     /// The station ID as a two character ASCII string
@@ -31,6 +42,7 @@ impl StationID {
 ///
 /// The header information is accessed through public fields and methods.
 #[derive(Debug, Default, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VDIFHeader {
     /// Whether the frame is valid.
     pub is_valid: bool,
@@ -50,7 +62,8 @@ pub struct VDIFHeader {
     pub size: u32,
     /// Whether the encoded data is real or complex.
     pub is_real: bool,
-    /// The bits/sample of the encoded data.
+    /// The raw bits/sample field, which holds one less than the number of bits in each sample (or each component of
+    /// a complex sample). Use [`bits`](VDIFHeader::bits) for the bit depth itself.
     pub bits_per_sample: u8,
     /// The thread ID of the frame.
     pub thread: u16,
@@ -68,31 +81,87 @@ pub struct VDIFHeader {
 }
 
 impl VDIFHeader {
+    /// A header with every field zeroed or `false`, equal to [`VDIFHeader::default`] but usable in const contexts, for
+    /// example:
+    ///
+    /// ```rust,ignore
+    /// const HEADER: VDIFHeader = VDIFHeader { size: 1004, bits_per_sample: 2, ..VDIFHeader::EMPTY };
+    /// ```
+    pub const EMPTY: VDIFHeader = VDIFHeader {
+        is_valid: false,
+        is_legacy: false,
+        time: 0,
+        epoch: 0,
+        frameno: 0,
+        version: 0,
+        channels: 0,
+        size: 0,
+        is_real: false,
+        bits_per_sample: 0,
+        thread: 0,
+        station: 0,
+        edv0: 0,
+        edv1: 0,
+        edv2: 0,
+        edv3: 0,
+    };
+
     /// Get the total size in bytes of the associated VDIF frame.
-    pub fn bytesize(&self) -> u32 {
+    pub const fn bytesize(&self) -> u32 {
         return self.size * 8;
     }
 
     /// Get the total size in 32-bit words of the associated VDIF frame.
-    pub fn wordsize(&self) -> u32 {
+    pub const fn wordsize(&self) -> u32 {
         return self.bytesize() / 4;
     }
 
-    /// Get the total size in bytes of the associated VDIF payload.
-    pub fn data_bytesize(&self) -> u32 {
-        return self.bytesize() - 32;
+    /// Get the size in bytes of this header: 16 for a legacy header, else 32.
+    pub const fn header_bytesize(&self) -> u32 {
+        if self.is_legacy {
+            return LEGACY_HEADER_SIZE as u32;
+        }
+        return HEADER_SIZE as u32;
+    }
+
+    /// Get the total size in bytes of the associated VDIF payload, or 0 if the frame size is too small to hold even
+    /// the header.
+    pub const fn data_bytesize(&self) -> u32 {
+        return self.bytesize().saturating_sub(self.header_bytesize());
     }
 
     /// Get the total size in 32-bit words of the associated VDIF payload.
-    pub fn data_wordsize(&self) -> u32 {
-        return (self.bytesize() - 32) / 4;
+    pub const fn data_wordsize(&self) -> u32 {
+        return self.data_bytesize() / 4;
+    }
+
+    /// Get the number of bits in each sample, or each component of a complex sample, decoded from the raw
+    /// [`bits_per_sample`](VDIFHeader::bits_per_sample) field.
+    pub const fn bits(&self) -> u32 {
+        return self.bits_per_sample as u32 + 1;
+    }
+
+    /// Set the [`bits_per_sample`](VDIFHeader::bits_per_sample) field to describe samples of `bits` bits, from 1 to 32.
+    pub fn set_bits(&mut self, bits: u32) {
+        assert!((1..=32).contains(&bits), "Samples must be 1 to 32 bits");
+        self.bits_per_sample = (bits - 1) as u8;
     }
 
     /// Get the number of channels contained within the associated VDIF payload.
-    pub fn channelno(&self) -> usize {
+    pub const fn channelno(&self) -> usize {
         return 1usize << self.channels;
     }
 
+    /// Decode the extended data words according to their EDV.
+    pub fn extended_data(&self) -> ExtendedData {
+        return ExtendedData::decode([self.edv0, self.edv1, self.edv2, self.edv3]);
+    }
+
+    /// Encode `data` into the extended data words, overwriting the existing values.
+    pub fn set_extended_data(&mut self, data: ExtendedData) {
+        [self.edv0, self.edv1, self.edv2, self.edv3] = data.encode();
+    }
+
     /// Get a [`NaiveDateTime`] representing the `epoch` and `time` of the associated VDIF frame.
     pub fn date(&self) -> NaiveDateTime {
         return vdiftime_to_date(self.epoch, self.time);
@@ -111,33 +180,210 @@ impl VDIFHeader {
             Err(_) => StationID::NumericID(self.station),
         }
     }
+
+    /// Move the timestamp of the associated VDIF frame into the reference epoch it actually falls in.
+    ///
+    /// Some recorders keep counting seconds past the end of the reference epoch they started in, rather than
+    /// incrementing `epoch` and resetting `time` at the boundary (January 1st / July 1st). Returns whether the header
+    /// was changed.
+    pub fn normalise_epoch(&mut self) -> bool {
+        let (epoch, time) = normalise_vdiftime(self.epoch, self.time);
+        let changed = epoch != self.epoch;
+        self.epoch = epoch;
+        self.time = time;
+        return changed;
+    }
+
+    /// Get the number of seconds from the timestamp of `earlier` to the timestamp of this header, correctly handling
+    /// headers in different reference epochs.
+    pub fn seconds_since(&self, earlier: &VDIFHeader) -> i64 {
+        return (self.date() - earlier.date()).num_seconds();
+    }
+
+    /// Get the header of the frame following the associated VDIF frame in its thread, given the number of frames per
+    /// second. Only the timestamp and frame number change, rolling over into the next reference epoch if needed.
+    pub fn next(&self, frame_rate: u32) -> VDIFHeader {
+        let mut next = *self;
+        if self.frameno + 1 < frame_rate {
+            next.frameno += 1;
+        } else {
+            next.frameno = 0;
+            next.time = next.time.saturating_add(1);
+            next.normalise_epoch();
+        }
+        return next;
+    }
+
+    /// Get the timestamp of the associated VDIF frame in seconds since the UNIX epoch, ignoring the frame number.
+    pub fn epoch_seconds_to_unix(&self) -> i64 {
+        return self.date().and_utc().timestamp();
+    }
+
+    /// Get the timestamp of the associated VDIF frame as a Modified Julian Date and the seconds since the start of that
+    /// day, ignoring the frame number. Use [`to_mjd`](crate::time::to_mjd) with [`timestamp`](VDIFHeader::timestamp)
+    /// for sub-second precision.
+    pub fn mjd(&self) -> (i64, u32) {
+        let seconds = self.epoch_seconds_to_unix();
+        return (
+            seconds.div_euclid(86400) + MJD_UNIX_EPOCH,
+            seconds.rem_euclid(86400) as u32,
+        );
+    }
+
+    /// Get the UTC time of the start of the associated VDIF frame, given `frame_rate` frames per second per thread.
+    /// Returns [`None`] if `frame_rate` is zero.
+    pub fn timestamp(&self, frame_rate: u32) -> Option<DateTime<Utc>> {
+        return to_datetime(self.instant(), frame_rate);
+    }
+
+    /// Set the reference epoch, seconds and frame number of this header to those of the frame covering `datetime`,
+    /// given `frame_rate` frames per second per thread. Returns [`None`], leaving the header unchanged, if `frame_rate`
+    /// is zero.
+    pub fn set_timestamp(&mut self, datetime: DateTime<Utc>, frame_rate: u32) -> Option<()> {
+        let instant = from_datetime(datetime, frame_rate)?;
+        self.epoch = instant.epoch;
+        self.time = instant.time;
+        self.frameno = instant.frameno;
+        return Some(());
+    }
+
+    /// Encode this header as the little-endian bytes that start a raw VDIF frame, 16 for a legacy header or else 32,
+    /// writing them to the start of `buf`. Returns the number of bytes written, or an error of kind
+    /// [`InvalidInput`](ErrorKind::InvalidInput) if `buf` is too small.
+    #[cfg(feature = "std")]
+    pub fn write_to(&self, buf: &mut [u8]) -> Result<usize> {
+        let size = self.header_bytesize() as usize;
+        check_buffer(buf, size)?;
+        buf[0..size].copy_from_slice(&encode_header_bytes(*self)[0..size]);
+        return Ok(size);
+    }
+
+    /// Summarise this header as a [`FrameMeta`].
+    pub fn meta(&self) -> FrameMeta {
+        return FrameMeta {
+            thread: self.thread,
+            station: station_code(self.station),
+            unix_seconds: self.epoch_seconds_to_unix(),
+            frameno: self.frameno,
+            frame_size: self.bytesize() as usize,
+            channels: self.channelno(),
+            bits_per_sample: self.bits() as u8,
+            is_real: self.is_real,
+            is_valid: self.is_valid,
+            is_legacy: self.is_legacy,
+            edv: self.extended_data().version(),
+        };
+    }
+
+    /// Get the [`FrameInstant`] of the associated VDIF frame.
+    pub const fn instant(&self) -> FrameInstant {
+        return FrameInstant {
+            epoch: self.epoch,
+            time: self.time,
+            frameno: self.frameno,
+        };
+    }
+}
+
+/// A human-readable summary of a VDIF header, for monitoring dashboards, logs and configuration files.
+///
+/// Unlike a [`VDIFHeader`], fields hold decoded values: the channel count rather than its log2, the frame size in
+/// bytes, the station as a string and the time in seconds since the UNIX epoch. With the `serde` feature both can be
+/// serialised to JSON, YAML or any other format `serde` supports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameMeta {
+    /// The thread ID of the frame.
+    pub thread: u16,
+    /// The source station of the frame, as its two character code where it has one. See
+    /// [`station_code`](crate::station::station_code).
+    pub station: String,
+    /// The second the frame falls in, in seconds since the UNIX epoch.
+    pub unix_seconds: i64,
+    /// The frame number within the second.
+    pub frameno: u32,
+    /// The size in bytes of the frame, header and payload.
+    pub frame_size: usize,
+    /// The number of channels within the frame.
+    pub channels: usize,
+    /// The bits/sample of the encoded data.
+    pub bits_per_sample: u8,
+    /// Whether the encoded data is real or complex.
+    pub is_real: bool,
+    /// Whether the frame is valid.
+    pub is_valid: bool,
+    /// Whether the frame is a legacy VDIF data frame.
+    pub is_legacy: bool,
+    /// The extended data version.
+    pub edv: u8,
+}
+
+/// The position in time of a VDIF frame within its thread.
+///
+/// Instants are ordered by epoch, then seconds, then frame number, so frames of different threads taken at the same
+/// time compare equal.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameInstant {
+    /// The raw reference epoch.
+    pub epoch: u8,
+    /// The raw seconds since the reference epoch.
+    pub time: u32,
+    /// The frame number within the second.
+    pub frameno: u32,
 }
 
-impl std::fmt::Display for VDIFHeader {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for VDIFHeader {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let mut station: String = "  ".to_string();
         if let StationID::StringID(str) = self.station() {
             station = str
         }
 
         write!(f, "(Frame: {}, Thread: {}, Time: {}, Size: {}, Channels: {}, Bits/sample: {}, Real: {}, Valid: {}, Station: {} ({}))",
-        self.frameno, self.thread, self.time, self.size*8, 1 << self.channels, self.bits_per_sample, self.is_real, self.is_valid, station, self.station)
+        self.frameno, self.thread, self.time, self.size*8, 1 << self.channels, self.bits(), self.is_real,
+        self.is_valid, station, self.station)
     }
 }
 
 /// Convert a VDIF `epoch` and `time` value to a [`NaiveDateTime`] from the [`chrono`] library.
 pub fn vdiftime_to_date(epoch: u8, time: u32) -> NaiveDateTime {
-    let years = epoch / 2;
-    let months = if epoch % 2 > 0 { 7 } else { 1 };
+    let (year, month) = EPOCH_START_DATES
+        .get(epoch as usize)
+        .copied()
+        .unwrap_or((2000 + epoch as i32 / 2, if epoch % 2 > 0 { 7 } else { 1 }));
     let delta = TimeDelta::new(time as i64, 0).expect("Incorrect time supplied to chrono");
 
     return NaiveDateTime::new(
-        NaiveDate::from_ymd_opt(2000 + years as i32, months as u32, 1)
-            .expect("Incorrect epoch supplied to chrono"),
+        NaiveDate::from_ymd_opt(year, month, 1).expect("Incorrect epoch supplied to chrono"),
         NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
     ) + delta;
 }
 
+/// Get the length in seconds of the reference epoch `epoch`, i.e. the time from its start until the start of the next.
+pub fn epoch_seconds(epoch: u8) -> u32 {
+    assert!(epoch < 64, "VDIF reference epochs are 6 bits");
+    return EPOCH_SECONDS[epoch as usize];
+}
+
+/// Carry any `time` beyond the end of reference epoch `epoch` into the following epochs, returning the equivalent
+/// `(epoch, time)` pair.
+///
+/// There is no epoch after the last, 63, so any time beyond its end is left in it.
+pub fn normalise_vdiftime(mut epoch: u8, mut time: u32) -> (u8, u32) {
+    loop {
+        if epoch >= 63 {
+            return (epoch, time);
+        }
+        let length = epoch_seconds(epoch);
+        if time < length {
+            return (epoch, time);
+        }
+        time -= length;
+        epoch += 1;
+    }
+}
+
 /// Convert a [`NaiveDateTime`] from the [`chrono`] library to a VDIF `epoch` and `time`.
 pub fn vdiftime_from_date(datetime: NaiveDateTime) -> (u8, u32) {
     let epoch_month = if datetime.month() > 6 { 7 } else { 1 };
@@ -152,9 +398,25 @@ pub fn vdiftime_from_date(datetime: NaiveDateTime) -> (u8, u32) {
     return (epoch as u8, time.num_seconds() as u32);
 }
 
+// Check `buf` can hold `needed` bytes.
+#[cfg(feature = "std")]
+pub(crate) fn check_buffer(buf: &[u8], needed: usize) -> Result<()> {
+    if buf.len() < needed {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Buffer of {} bytes is too small to hold {} bytes",
+                buf.len(),
+                needed
+            ),
+        ));
+    }
+    return Ok(());
+}
+
 #[cfg(test)]
 mod tests {
-    use super::StationID;
+    use super::*;
 
     #[test]
     fn test_stationid_encode() {
@@ -164,4 +426,70 @@ mod tests {
         let teststr = StationID::StringID("JB".to_owned());
         assert_eq!(teststr.encode(), 0b0100101001000010)
     }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_write_to() {
+        let header = VDIFHeader {
+            size: 4,
+            frameno: 3,
+            ..Default::default()
+        };
+        let mut buf = [0u8; 40];
+        assert_eq!(header.write_to(&mut buf[8..]).unwrap(), 32);
+        assert_eq!(buf[12], 3);
+        assert!(header.write_to(&mut buf[16..]).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let header = VDIFHeader {
+            size: 1004,
+            station: u16::from_be_bytes(*b"Jb"),
+            bits_per_sample: 2,
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&header).unwrap();
+        assert_eq!(serde_json::from_str::<VDIFHeader>(&json).unwrap(), header);
+
+        let meta = serde_json::to_value(header.meta()).unwrap();
+        assert_eq!(meta["station"], "Jb");
+        assert_eq!(meta["frame_size"], 8032);
+        assert_eq!(meta["unix_seconds"], 946684800);
+    }
+
+    #[test]
+    fn test_epoch_rollover() {
+        // 2000-01-01 to 2000-07-01, a leap year
+        assert_eq!(epoch_seconds(0), 182 * 86400);
+        assert_eq!(epoch_seconds(1), 184 * 86400);
+        assert_eq!(normalise_vdiftime(0, 182 * 86400 + 5), (1, 5));
+        assert_eq!(normalise_vdiftime(0, 5), (0, 5));
+        assert_eq!(
+            normalise_vdiftime(62, u32::MAX),
+            (63, u32::MAX - epoch_seconds(62))
+        );
+
+        let before = VDIFHeader {
+            epoch: 0,
+            time: 182 * 86400 - 1,
+            ..Default::default()
+        };
+        let mut after = VDIFHeader {
+            epoch: 0,
+            time: 182 * 86400,
+            ..Default::default()
+        };
+        assert!(after.normalise_epoch());
+        assert_eq!((after.epoch, after.time), (1, 0));
+        assert_eq!(after.seconds_since(&before), 1);
+
+        let last = VDIFHeader {
+            epoch: 63,
+            time: u32::MAX,
+            ..Default::default()
+        };
+        assert_eq!((last.next(1).epoch, last.next(1).time), (63, u32::MAX));
+    }
 }
@@ -1,6 +1,7 @@
 //! Provides functionality for encoding/decoding VDIF headers.
 
-use crate::frame::VDIFFrame;
+use crate::consts::{HEADER_SIZE, LEGACY_HEADER_SIZE};
+use crate::frame::{FrameStorage, VDIFFrame};
 use crate::header::VDIFHeader;
 
 pub(crate) const MASK_IS_VALID: u32 = 0b10000000000000000000000000000000;
@@ -17,20 +18,31 @@ pub(crate) const MASK_THREAD_ID: u32 = 0b00000011111111110000000000000000;
 pub(crate) const MASK_STATION_ID: u32 = 0b00000000000000001111111111111111;
 
 /// Construct a [`VDIFHeader`] from a [`VDIFFrame`].
-pub fn decode_frame_header(frame: &VDIFFrame) -> VDIFHeader {
-    return decode_header(frame.as_slice()[0..8].try_into().unwrap());
+pub fn decode_frame_header<S: FrameStorage>(frame: &VDIFFrame<S>) -> VDIFHeader {
+    let data = frame.as_slice();
+    let mut words = [0u32; 8];
+    let n = header_words(data).min(data.len());
+    words[0..n].copy_from_slice(&data[0..n]);
+    return decode_header(words);
 }
 
 /// Construct a [`VDIFHeader`] from a series of eight `u32`s.
-pub fn decode_header(words: [u32; 8]) -> VDIFHeader {
+///
+/// If the legacy bit is set only the first four words are header, so the extended data words are left zeroed.
+pub const fn decode_header(words: [u32; 8]) -> VDIFHeader {
     let (is_valid, is_legacy, time) = decode_w0(words[0]);
+    let edv = if is_legacy {
+        [0; 4]
+    } else {
+        [words[4], words[5], words[6], words[7]]
+    };
     let (epoch, frameno) = decode_w1(words[1]);
     let (version, channels, size) = decode_w2(words[2]);
     let (is_real, bits_per_sample, thread, station) = decode_w3(words[3]);
-    let edv0 = words[4];
-    let edv1 = words[5];
-    let edv2 = words[6];
-    let edv3 = words[7];
+    let edv0 = edv[0];
+    let edv1 = edv[1];
+    let edv2 = edv[2];
+    let edv3 = edv[3];
 
     return VDIFHeader {
         is_valid: is_valid,
@@ -52,8 +64,49 @@ pub fn decode_header(words: [u32; 8]) -> VDIFHeader {
     };
 }
 
+/// Construct a [`VDIFHeader`] from the first 32 bytes of a raw, little-endian VDIF frame.
+pub const fn decode_header_bytes(bytes: &[u8; 32]) -> VDIFHeader {
+    let mut words = [0u32; 8];
+    let mut i = 0;
+    while i < 8 {
+        let b = 4 * i;
+        words[i] = u32::from_le_bytes([bytes[b], bytes[b + 1], bytes[b + 2], bytes[b + 3]]);
+        i += 1;
+    }
+    return decode_header(words);
+}
+
+/// Split the VDIF frame at the start of `input` into its decoded header and its payload, also returning the
+/// remaining input.
+///
+/// The frame size is taken from the header. Returns [`None`] if `input` is shorter than the header, or than the frame
+/// size it specifies, or if the specified size is smaller than a header. Nothing is allocated or copied other than the
+/// decoded header.
+pub fn parse_frame(input: &[u8]) -> Option<(VDIFHeader, &[u8], &[u8])> {
+    let mut bytes = [0u8; HEADER_SIZE];
+    let available = input.len().min(HEADER_SIZE);
+    bytes[0..available].copy_from_slice(&input[0..available]);
+    let header = decode_header_bytes(&bytes);
+    let (header_size, size) = (
+        header.header_bytesize() as usize,
+        header.bytesize() as usize,
+    );
+    if input.len() < header_size || size < header_size || input.len() < size {
+        return None;
+    }
+    return Some((header, &input[header_size..size], &input[size..]));
+}
+
+// The number of words in the header starting `words`, which must not be empty: four for a legacy header, else eight.
+pub(crate) fn header_words(words: &[u32]) -> usize {
+    if words[0] & MASK_IS_LEGACY != 0 {
+        return LEGACY_HEADER_SIZE / 4;
+    }
+    return HEADER_SIZE / 4;
+}
+
 /// Decode the zeroth word of a VDIFHeader
-pub(crate) fn decode_w0(word: u32) -> (bool, bool, u32) {
+pub(crate) const fn decode_w0(word: u32) -> (bool, bool, u32) {
     let is_valid = (word & MASK_IS_VALID) == 0;
     let is_legacy = (word & MASK_IS_LEGACY) != 0;
     let time = word & MASK_TIME;
@@ -61,14 +114,14 @@ pub(crate) fn decode_w0(word: u32) -> (bool, bool, u32) {
 }
 
 /// Decode the first word of a VDIFHeader
-pub(crate) fn decode_w1(word: u32) -> (u8, u32) {
+pub(crate) const fn decode_w1(word: u32) -> (u8, u32) {
     let epoch = ((word & MASK_REF_EPOCH) >> 24) as u8;
     let frameno = word & MASK_FRAME_NO;
     return (epoch, frameno);
 }
 
 /// Decode the second word of a VDIFHeader
-pub(crate) fn decode_w2(word: u32) -> (u8, u8, u32) {
+pub(crate) const fn decode_w2(word: u32) -> (u8, u8, u32) {
     let version = ((word & MASK_VERSION_NO) >> 29) as u8;
     let channels = ((word & MASK_LOG2_CHANNELS) >> 24) as u8;
     let size = word & MASK_BYTE_SIZE;
@@ -76,7 +129,7 @@ pub(crate) fn decode_w2(word: u32) -> (u8, u8, u32) {
 }
 
 /// Decode the third word of a VDIFHeader
-pub(crate) fn decode_w3(word: u32) -> (bool, u8, u16, u16) {
+pub(crate) const fn decode_w3(word: u32) -> (bool, u8, u16, u16) {
     let is_real = (word & MASK_IS_REAL) == 0;
     let bits_per_sample = ((word & MASK_BITS_PER_SAMPLE) >> 26) as u8;
     let thread = ((word & MASK_THREAD_ID) >> 16) as u16;
@@ -84,8 +137,9 @@ pub(crate) fn decode_w3(word: u32) -> (bool, u8, u16, u16) {
     return (is_real, bits_per_sample, thread, station);
 }
 
-/// Encode a [`VDIFHeader`] into an array of eight `u32`s.
-pub fn encode_header(header: VDIFHeader) -> [u32; 8] {
+/// Encode a [`VDIFHeader`] into an array of eight `u32`s. Only the first four belong to a legacy header, and the rest
+/// are zeroed.
+pub const fn encode_header(header: VDIFHeader) -> [u32; 8] {
     let mut w0 = header.time;
     if header.is_legacy {
         w0 = w0 | MASK_IS_LEGACY
@@ -109,14 +163,56 @@ pub fn encode_header(header: VDIFHeader) -> [u32; 8] {
         w3 = w3 | MASK_IS_REAL
     }
 
-    let w4 = header.edv0;
-    let w5 = header.edv1;
-    let w6 = header.edv2;
-    let w7 = header.edv3;
+    // Legacy headers have no extended data words
+    let (w4, w5, w6, w7) = if header.is_legacy {
+        (0, 0, 0, 0)
+    } else {
+        (header.edv0, header.edv1, header.edv2, header.edv3)
+    };
 
     return [w0, w1, w2, w3, w4, w5, w6, w7];
 }
 
+/// Encode a [`VDIFHeader`] into the 32 little-endian bytes that start a raw VDIF frame.
+pub const fn encode_header_bytes(header: VDIFHeader) -> [u8; 32] {
+    let words = encode_header(header);
+    let mut bytes = [0u8; 32];
+    let mut i = 0;
+    while i < 32 {
+        bytes[i] = words[i / 4].to_le_bytes()[i % 4];
+        i += 1;
+    }
+    return bytes;
+}
+
+// Conversions between the decoded and raw representations of a header. These are lossless, except that the two
+// unassigned bits at the top of word 1 are always zero in the raw representation, as are the extended data words of a
+// legacy header.
+
+impl From<[u32; 8]> for VDIFHeader {
+    fn from(words: [u32; 8]) -> Self {
+        return decode_header(words);
+    }
+}
+
+impl From<VDIFHeader> for [u32; 8] {
+    fn from(header: VDIFHeader) -> Self {
+        return encode_header(header);
+    }
+}
+
+impl From<[u8; 32]> for VDIFHeader {
+    fn from(bytes: [u8; 32]) -> Self {
+        return decode_header_bytes(&bytes);
+    }
+}
+
+impl From<VDIFHeader> for [u8; 32] {
+    fn from(header: VDIFHeader) -> Self {
+        return encode_header_bytes(header);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -147,4 +243,54 @@ mod tests {
         let cpy = test_header;
         assert_eq!(cpy, decode_header(encode_header(test_header)))
     }
+
+    #[test]
+    fn test_const_header() {
+        const HEADER: VDIFHeader = VDIFHeader {
+            time: 100,
+            size: 1004,
+            bits_per_sample: 2,
+            ..VDIFHeader::EMPTY
+        };
+        const BYTES: [u8; 32] = encode_header_bytes(HEADER);
+        const DECODED: VDIFHeader = decode_header_bytes(&BYTES);
+        assert_eq!(DECODED, HEADER);
+        assert_eq!(VDIFHeader::EMPTY, VDIFHeader::default());
+    }
+
+    #[test]
+    fn test_header_conversions() {
+        let words: [u32; 8] = [
+            0x8000_1234,
+            0x0300_0010,
+            0x6300_03EC,
+            0x8C05_4A42,
+            1,
+            2,
+            3,
+            4,
+        ];
+        let header = VDIFHeader::from(words);
+        assert_eq!(<[u32; 8]>::from(header), words);
+        let bytes: [u8; 32] = header.into();
+        assert_eq!(VDIFHeader::from(bytes), header);
+    }
+
+    #[test]
+    fn test_parse_frame() {
+        let mut frame = VDIFFrame::empty(64);
+        frame.set_header(VDIFHeader {
+            size: 8,
+            frameno: 5,
+            ..Default::default()
+        });
+        let mut bytes = frame.as_bytes().to_vec();
+        bytes.extend_from_slice(&[1, 2, 3]);
+
+        let (header, payload, rest) = parse_frame(&bytes).unwrap();
+        assert_eq!(header, frame.get_header());
+        assert_eq!(payload.len(), 32);
+        assert_eq!(rest, &[1, 2, 3]);
+        assert!(parse_frame(&bytes[0..63]).is_none());
+    }
 }
@@ -0,0 +1,361 @@
+//! Implements [`FrameIndex`], a compact index of the frames of a VDIF file for fast random access and time-based
+//! seeking.
+//!
+//! Building an index reads the whole file once. The index can then be saved next to the file, so later sessions can
+//! find the byte offset of any time without scanning again. The index for `path/to/my.vdif` lives at
+//! `path/to/my.vdif.idx`.
+//!
+//! Indexes are stored in a simple little-endian binary format: the magic bytes `VDIFIDX2`, the frame size (`u64`, zero
+//! if frames vary in size), the length in bytes of the indexed file (`u64`), the granularity (`u8`), the number of
+//! entries (`u64`), then 20 bytes per entry holding the byte offset (`u64`), the seconds since the reference epoch
+//! (`u32`), the reference epoch and frame number packed as in word 1 of a VDIF header (`u32`), the thread (`u16`), and
+//! flags (`u16`, with bit 0 set for valid frames).
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Result, Write};
+use std::path::{Path, PathBuf};
+
+use crate::header::{FrameInstant, VDIFHeader};
+use crate::io::{VDIFRead, VDIFReader};
+
+const MAGIC: &[u8; 8] = b"VDIFIDX2";
+
+/// Which frames a [`FrameIndex`] records.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum IndexGranularity {
+    /// Every frame.
+    #[default]
+    Frame,
+    /// The first frame of each thread in each second, for an index a few thousand times smaller.
+    Second,
+}
+
+/// The location and timestamp of one frame recorded in a [`FrameIndex`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// The byte offset of the frame within the file.
+    pub offset: u64,
+    /// The timestamp of the frame.
+    pub instant: FrameInstant,
+    /// The thread of the frame.
+    pub thread: u16,
+    /// Whether the frame is marked valid.
+    pub is_valid: bool,
+}
+
+/// An index of the frames of a VDIF file, in file order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameIndex {
+    frame_size: usize,
+    file_len: u64,
+    granularity: IndexGranularity,
+    entries: Vec<IndexEntry>,
+    // Whether the entries are in time order, so can be binary searched
+    sorted: bool,
+}
+
+impl FrameIndex {
+    /// Build an index of the frames read from `reader` until the end of the stream, assuming they lie back to back
+    /// from offset zero.
+    pub fn build<R: VDIFRead>(reader: &mut R, granularity: IndexGranularity) -> Result<Self> {
+        let mut entries = Vec::new();
+        let mut seconds: HashMap<u16, (u8, u32)> = HashMap::new();
+        let mut frame_size = None;
+        let mut offset = 0;
+        loop {
+            let frame = match reader.read_frame() {
+                Ok(frame) => frame,
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            };
+            let header = frame.get_header();
+            let size = frame.bytesize();
+            if *frame_size.get_or_insert(size) != size {
+                frame_size = Some(0);
+            }
+            let second = (header.epoch, header.time);
+            if granularity == IndexGranularity::Frame
+                || seconds.insert(header.thread, second) != Some(second)
+            {
+                entries.push(entry(offset, &header));
+            }
+            offset += size as u64;
+        }
+
+        return Ok(Self {
+            frame_size: frame_size.unwrap_or(0),
+            file_len: offset,
+            granularity: granularity,
+            sorted: is_sorted(&entries),
+            entries: entries,
+        });
+    }
+
+    /// Build an index of the VDIF file at `path`, holding frames of `frame_size` bytes.
+    pub fn build_file<P: AsRef<Path>>(
+        path: P,
+        frame_size: usize,
+        granularity: IndexGranularity,
+    ) -> Result<Self> {
+        let file_len = std::fs::metadata(&path)?.len();
+        let mut index = Self::build(&mut VDIFReader::open(path, frame_size)?, granularity)?;
+        // Every frame was read at `frame_size`, even if there were none, and any partial frame at the end still counts
+        // towards the length of the file
+        index.frame_size = frame_size;
+        index.file_len = file_len;
+        return Ok(index);
+    }
+
+    /// Get the size in bytes of every frame in the file, or zero if they vary.
+    pub fn frame_size(&self) -> usize {
+        return self.frame_size;
+    }
+
+    /// Get the length in bytes of the file when it was indexed.
+    pub fn file_len(&self) -> u64 {
+        return self.file_len;
+    }
+
+    /// Get which frames this index records.
+    pub fn granularity(&self) -> IndexGranularity {
+        return self.granularity;
+    }
+
+    /// Get the entries of this index, in file order.
+    pub fn entries(&self) -> &[IndexEntry] {
+        return &self.entries;
+    }
+
+    /// Find the earliest entry of `thread`, or of any thread if `None`, at or after `instant`, taking the first in
+    /// file order if several share that time. This is a binary search if the frames are in time order within the
+    /// file, and a scan of every entry otherwise.
+    pub fn find(&self, instant: FrameInstant, thread: Option<u16>) -> Option<&IndexEntry> {
+        let matches = |e: &&IndexEntry| thread.is_none_or(|t| e.thread == t);
+        if !self.sorted {
+            return self
+                .entries
+                .iter()
+                .filter(|e| e.instant >= instant)
+                .filter(matches)
+                .min_by_key(|e| e.instant);
+        }
+        let start = self.entries.partition_point(|e| e.instant < instant);
+        return self.entries[start..].iter().find(matches);
+    }
+
+    /// Write this index to `writer`.
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<()> {
+        let mut writer = BufWriter::new(writer);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(self.frame_size as u64).to_le_bytes())?;
+        writer.write_all(&self.file_len.to_le_bytes())?;
+        writer.write_all(&[self.granularity as u8])?;
+        writer.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+        for e in &self.entries {
+            writer.write_all(&e.offset.to_le_bytes())?;
+            writer.write_all(&e.instant.time.to_le_bytes())?;
+            let w1 = ((e.instant.epoch as u32) << 24) | e.instant.frameno;
+            writer.write_all(&w1.to_le_bytes())?;
+            writer.write_all(&e.thread.to_le_bytes())?;
+            writer.write_all(&(e.is_valid as u16).to_le_bytes())?;
+        }
+        return writer.flush();
+    }
+
+    /// Read an index from `reader`. Returns an error of kind [`InvalidData`](ErrorKind::InvalidData) if it isn't an
+    /// index, or was written by an older version of the format.
+    pub fn read_from<R: Read>(reader: R) -> Result<Self> {
+        let mut reader = BufReader::new(reader);
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("Not a VDIF frame index"));
+        }
+        let frame_size = read_u64(&mut reader)? as usize;
+        let file_len = read_u64(&mut reader)?;
+        let mut granularity = [0u8];
+        reader.read_exact(&mut granularity)?;
+        let granularity = match granularity[0] {
+            0 => IndexGranularity::Frame,
+            1 => IndexGranularity::Second,
+            _ => return Err(invalid("Unknown index granularity")),
+        };
+
+        let count = read_u64(&mut reader)?;
+        let mut entries = Vec::new();
+        let mut bytes = [0u8; 20];
+        for _ in 0..count {
+            reader.read_exact(&mut bytes)?;
+            let w1 = u32::from_le_bytes(bytes[12..16].try_into().unwrap());
+            entries.push(IndexEntry {
+                offset: u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+                instant: FrameInstant {
+                    epoch: (w1 >> 24) as u8,
+                    time: u32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+                    frameno: w1 & 0xFFFFFF,
+                },
+                thread: u16::from_le_bytes(bytes[16..18].try_into().unwrap()),
+                is_valid: bytes[18] & 1 != 0,
+            });
+        }
+
+        return Ok(Self {
+            frame_size: frame_size,
+            file_len: file_len,
+            granularity: granularity,
+            sorted: is_sorted(&entries),
+            entries: entries,
+        });
+    }
+
+    /// Write this index to the file at `path`, overwriting it if it exists.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        return self.write_to(File::create(path)?);
+    }
+
+    /// Read an index from the file at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        return Self::read_from(File::open(path)?);
+    }
+
+    /// Read the index saved next to the VDIF file at `vdif_path`, or build and save one if there is none.
+    ///
+    /// A saved index is rebuilt if it can't be read, or if its frame size, length or granularity no longer match, such
+    /// as after the file has been appended to.
+    pub fn load_or_build<P: AsRef<Path>>(
+        vdif_path: P,
+        frame_size: usize,
+        granularity: IndexGranularity,
+    ) -> Result<Self> {
+        let path = index_path(&vdif_path);
+        if path.is_file() {
+            let file_len = std::fs::metadata(&vdif_path)?.len();
+            match Self::load(&path) {
+                Ok(index)
+                    if index.frame_size == frame_size
+                        && index.file_len == file_len
+                        && index.granularity == granularity =>
+                {
+                    return Ok(index);
+                }
+                Ok(_) => {}
+                Err(e)
+                    if e.kind() == ErrorKind::InvalidData
+                        || e.kind() == ErrorKind::UnexpectedEof => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let index = Self::build_file(vdif_path, frame_size, granularity)?;
+        index.save(path)?;
+        return Ok(index);
+    }
+}
+
+/// Get the path of the index file associated with the VDIF file at `vdif_path`.
+pub fn index_path<P: AsRef<Path>>(vdif_path: P) -> PathBuf {
+    let mut path = vdif_path.as_ref().as_os_str().to_owned();
+    path.push(".idx");
+    return PathBuf::from(path);
+}
+
+fn entry(offset: u64, header: &VDIFHeader) -> IndexEntry {
+    return IndexEntry {
+        offset: offset,
+        instant: header.instant(),
+        thread: header.thread,
+        is_valid: header.is_valid,
+    };
+}
+
+fn is_sorted(entries: &[IndexEntry]) -> bool {
+    return entries.windows(2).all(|w| w[0].instant <= w[1].instant);
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    return Ok(u64::from_le_bytes(bytes));
+}
+
+fn invalid(msg: &str) -> Error {
+    return Error::new(ErrorKind::InvalidData, msg.to_owned());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::VDIFFrame;
+    use crate::testing::temp_path;
+
+    #[test]
+    fn test_index() {
+        // Two threads at 4 frames/s for 3 seconds
+        let mut file = Vec::new();
+        for n in 0..12 {
+            for thread in 0..2 {
+                let frame = VDIFFrame::from_header(VDIFHeader {
+                    time: 100 + n / 4,
+                    frameno: n % 4,
+                    thread: thread,
+                    size: 8,
+                    ..Default::default()
+                });
+                file.extend_from_slice(frame.as_bytes());
+            }
+        }
+
+        let mut reader = VDIFReader::new(&file[..], 64);
+        let index = FrameIndex::build(&mut reader, IndexGranularity::Frame).unwrap();
+        assert_eq!((index.entries().len(), index.frame_size()), (24, 64));
+        let start = index.entries()[0].instant;
+        let target = FrameInstant {
+            time: start.time + 1,
+            frameno: 2,
+            ..start
+        };
+        let found = index.find(target, Some(1)).unwrap();
+        assert_eq!((found.offset, found.thread), (13 * 64, 1));
+        assert_eq!(index.find(target, None).unwrap().offset, 12 * 64);
+
+        let mut reader = VDIFReader::new(&file[..], 64);
+        let index = FrameIndex::build(&mut reader, IndexGranularity::Second).unwrap();
+        assert_eq!(index.entries().len(), 6);
+        let mut bytes = Vec::new();
+        index.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 33 + 6 * 20);
+        assert_eq!(FrameIndex::read_from(&bytes[..]).unwrap(), index);
+
+        // Swap the first and last second, so the index can't be binary searched
+        let mut shuffled = file[16 * 64..].to_vec();
+        shuffled.extend_from_slice(&file[8 * 64..16 * 64]);
+        shuffled.extend_from_slice(&file[..8 * 64]);
+        let mut reader = VDIFReader::new(&shuffled[..], 64);
+        let index = FrameIndex::build(&mut reader, IndexGranularity::Frame).unwrap();
+        assert_eq!(index.find(start, Some(1)).unwrap().offset, 17 * 64);
+        assert_eq!(index.find(target, None).unwrap().offset, 12 * 64);
+    }
+
+    #[test]
+    fn test_load_or_build() {
+        let path = temp_path("load_or_build.vdif");
+        let frame = VDIFFrame::from_header(VDIFHeader {
+            size: 8,
+            ..Default::default()
+        });
+        std::fs::write(&path, frame.as_bytes()).unwrap();
+        let index = FrameIndex::load_or_build(&path, 64, IndexGranularity::Frame).unwrap();
+        assert_eq!((index.entries().len(), index.file_len()), (1, 64));
+
+        // Appending to the file makes the saved index stale
+        std::fs::write(&path, [frame.as_bytes(), frame.as_bytes()].concat()).unwrap();
+        let index = FrameIndex::load_or_build(&path, 64, IndexGranularity::Frame).unwrap();
+        assert_eq!((index.entries().len(), index.file_len()), (2, 128));
+        assert_eq!(FrameIndex::load(index_path(&path)).unwrap(), index);
+        let index = FrameIndex::load_or_build(&path, 32, IndexGranularity::Frame).unwrap();
+        assert_eq!((index.entries().len(), index.frame_size()), (4, 32));
+
+        std::fs::remove_file(index_path(&path)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+}
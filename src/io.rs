@@ -1,21 +1,73 @@
 //! Implements the main [`VDIFReader`] and [`VDIFWriter`] types, as well as the [`VDIFRead`] and [`VDIFWrite`] traits.
 
-use std::fs::File;
-use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Result, Write};
+use std::fs::{File, OpenOptions};
+use std::io::{
+    BufRead, BufReader, BufWriter, Error, ErrorKind, IoSlice, Read, Result, Seek, SeekFrom, Write,
+};
 use std::path::Path;
 
-use crate::VDIFFrame;
+use chrono::{DateTime, Utc};
+
+use crate::block::FrameBlock;
+use crate::consts::{HEADER_SIZE, LEGACY_HEADER_SIZE};
+use crate::error::VDIFError;
+use crate::frame::{AlignedStorage, FrameStorage, VDIFFrame};
+use crate::header::{FrameInstant, VDIFHeader};
+use crate::header_encoding::{decode_header_bytes, MASK_BYTE_SIZE, MASK_IS_LEGACY};
+use crate::index::{FrameIndex, IndexGranularity};
+use crate::provenance::Tagged;
+use crate::spec::StreamSpec;
+use crate::time::from_datetime;
+use crate::validation::ValidationLevel;
 
 /// A trait indicating a type that can read VDIF frames.
 pub trait VDIFRead {
     /// Read a [`VDIFFrame`]
     fn read_frame(&mut self) -> Result<VDIFFrame>;
+
+    /// Read a [`VDIFFrame`] along with its [`Provenance`](crate::provenance::Provenance). By default this only
+    /// records the time the frame was read, but sources that know more, such as network receivers, fill in more.
+    fn read_tagged(&mut self) -> Result<Tagged> {
+        return self.read_frame().map(Tagged::now);
+    }
+}
+
+impl<R: VDIFRead + ?Sized> VDIFRead for &mut R {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        return (**self).read_frame();
+    }
+
+    fn read_tagged(&mut self) -> Result<Tagged> {
+        return (**self).read_tagged();
+    }
 }
 
 /// A trait indicating a type that can write VDIF frames.
 pub trait VDIFWrite {
     /// Write a [`VDIFFrame`].
     fn write_frame(&mut self, frame: VDIFFrame) -> Result<()>;
+
+    /// Flush any frames buffered by this writer. Does nothing by default.
+    fn flush(&mut self) -> Result<()> {
+        return Ok(());
+    }
+}
+
+impl<W: VDIFWrite + ?Sized> VDIFWrite for &mut W {
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        return (**self).write_frame(frame);
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        return (**self).flush();
+    }
+}
+
+impl VDIFWrite for Vec<VDIFFrame> {
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        self.push(frame);
+        return Ok(());
+    }
 }
 
 /// A type capable of reading VDIF frames from any source implementing [`Read`].
@@ -55,8 +107,32 @@ pub trait VDIFWrite {
 /// [`VDIFReader`]s implement buffered IO by default since VDIF streams are often quite data heavy, so you don't
 /// need to worry about using the normal [`BufReader`].
 pub struct VDIFReader<T: Read> {
-    inner: BufReader<T>,
+    inner: BufReader<Rewind<T>>,
     frame_size: usize,
+    options: ReaderOptions,
+    // The VDIF version and legacy flag of the first frame verified
+    format: Option<(u8, bool)>,
+}
+
+/// Options controlling how a [`VDIFReader`] reads frames.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ReaderOptions {
+    /// Take the size of each frame from its own header, rather than assuming every frame is the size the reader was
+    /// constructed with. This allows reading streams that mix threads with different frame sizes. In this mode the
+    /// reader's frame size is treated as the largest acceptable frame size.
+    pub trust_header_size: bool,
+    /// Check every header is consistent with the rest of the stream, rejecting frames whose VDIF version or legacy flag
+    /// differs from that of the first frame read. Checks on each frame alone, such as whether its size field matches
+    /// the number of bytes read, are made by [`validation`](ReaderOptions::validation).
+    pub verify_headers: bool,
+    /// Reject frames that don't match this [`StreamSpec`] in frame size, channels, bits/sample, data type, station
+    /// or thread (if [`threads`](StreamSpec::threads) isn't empty).
+    pub expected_spec: Option<StreamSpec>,
+    /// How thoroughly to check each frame read. [`ValidationLevel::None`] by default.
+    pub validation: ValidationLevel,
+    /// Treat a partial frame at the end of the stream as the end of the stream, returning an
+    /// [`UnexpectedEof`](ErrorKind::UnexpectedEof) error rather than [`InvalidData`](ErrorKind::InvalidData).
+    pub ignore_partial_tail: bool,
 }
 
 impl<T: Read> VDIFReader<T> {
@@ -64,8 +140,10 @@ impl<T: Read> VDIFReader<T> {
     pub fn new(inner: T, frame_size: usize) -> Self {
         // Default to a buffer of 10 frames
         return Self {
-            inner: BufReader::with_capacity(10 * frame_size, inner),
+            inner: BufReader::with_capacity(10 * frame_size, Rewind::new(inner)),
             frame_size: frame_size,
+            options: ReaderOptions::default(),
+            format: None,
         };
     }
 
@@ -73,29 +151,389 @@ impl<T: Read> VDIFReader<T> {
     /// buffer size is 10 frames.
     pub fn with_capacity(inner: T, frame_size: usize, frame_capacity: usize) -> Self {
         return Self {
-            inner: BufReader::with_capacity(frame_capacity * frame_size, inner),
+            inner: BufReader::with_capacity(frame_capacity * frame_size, Rewind::new(inner)),
             frame_size: frame_size,
+            options: ReaderOptions::default(),
+            format: None,
         };
     }
-}
 
-impl<T: Read> VDIFRead for VDIFReader<T> {
-    fn read_frame(&mut self) -> Result<VDIFFrame> {
-        // Allocate a frame and read bytes into it
-        let mut outframe = VDIFFrame::empty(self.frame_size);
-        let bytes_read = self.inner.read(outframe.as_mut_bytes())?;
+    /// Set the [`ReaderOptions`] used by this reader.
+    pub fn set_options(&mut self, options: ReaderOptions) {
+        self.options = options;
+    }
+
+    /// Get the [`ReaderOptions`] used by this reader.
+    pub fn options(&self) -> &ReaderOptions {
+        return &self.options;
+    }
+
+    /// Read a [`VDIFFrame`] whose size is taken from the size field of its own header.
+    ///
+    /// The header size is validated against the size of a VDIF header and the reader's (maximum) frame size. This
+    /// is called by [`read_frame`](VDIFRead::read_frame) when
+    /// [`trust_header_size`](ReaderOptions::trust_header_size) is set.
+    pub fn read_sized_frame(&mut self) -> Result<VDIFFrame> {
+        // Peek the size straight out of the internal buffer, so the whole frame can then be copied once into its
+        // final allocation. Only if the size word straddles the end of the buffer do we copy it out separately.
+        let buffered = self.inner.fill_buf()?;
+        let mut size_bytes = [0u8; 12];
+        let prefix = if buffered.len() >= 12 {
+            size_bytes.copy_from_slice(&buffered[0..12]);
+            0
+        } else {
+            if !self.read_whole(&mut size_bytes)? {
+                return Err(Error::new(ErrorKind::UnexpectedEof, "Reached EOF"));
+            }
+            12
+        };
 
-        if bytes_read == 0 {
+        let size = u32::from_le_bytes(size_bytes[8..12].try_into().unwrap());
+        let size = (size & MASK_BYTE_SIZE) as usize * 8;
+        let legacy = u32::from_le_bytes(size_bytes[0..4].try_into().unwrap()) & MASK_IS_LEGACY != 0;
+        let min_size = if legacy {
+            LEGACY_HEADER_SIZE
+        } else {
+            HEADER_SIZE
+        };
+        if size < min_size || size > self.frame_size {
+            return Err(VDIFError::HeaderSizeOutOfRange {
+                header: size,
+                max: self.frame_size,
+            }
+            .into());
+        }
+
+        let mut outframe = VDIFFrame::empty(size);
+        let bytes = outframe.as_mut_bytes();
+        bytes[0..prefix].copy_from_slice(&size_bytes[0..prefix]);
+        self.fill_frame(&mut bytes[prefix..])?;
+        return Ok(outframe);
+    }
+
+    /// Read the next frame into `frame`, reusing its storage rather than allocating a new frame, and checking it as
+    /// [`read_frame`](VDIFRead::read_frame) would.
+    ///
+    /// `frame` must be the reader's frame size, so this is not available when
+    /// [`trust_header_size`](ReaderOptions::trust_header_size) is set. If an error is returned, the contents of
+    /// `frame` are unspecified.
+    pub fn read_frame_into<S: FrameStorage>(&mut self, frame: &mut VDIFFrame<S>) -> Result<()> {
+        if self.options.trust_header_size {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Frames can't be read in place when trusting header sizes",
+            ));
+        }
+        check_frame_buffer(frame, self.frame_size)?;
+        if !self.read_whole(frame.as_mut_bytes())? {
             return Err(Error::new(ErrorKind::UnexpectedEof, "Reached EOF"));
-        } else if bytes_read != self.frame_size {
+        }
+
+        if self.options.verify_headers || self.options.expected_spec.is_some() {
+            self.verify(&frame.get_header())?;
+        }
+        self.options.validation.check(frame)?;
+        return Ok(());
+    }
+
+    /// Read up to `n` frames into a single [`FrameBlock`], stopping early at EOF. Returns an
+    /// [`UnexpectedEof`](ErrorKind::UnexpectedEof) error if no frames are left.
+    ///
+    /// Every frame is the reader's frame size, so this is not available when
+    /// [`trust_header_size`](ReaderOptions::trust_header_size) is set.
+    pub fn read_block(&mut self, n: usize) -> Result<FrameBlock> {
+        if self.options.trust_header_size {
             return Err(Error::new(
-                ErrorKind::InvalidData,
-                "Did not read a complete VDIF frame",
+                ErrorKind::InvalidInput,
+                "Blocks can't be read when trusting header sizes",
             ));
         }
+        let mut block = FrameBlock::new(self.frame_size, n);
+        let mut read = 0;
+        while read < n && self.read_whole(block.frame_bytes_mut(read))? {
+            read += 1;
+        }
+        if read == 0 && n > 0 {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "Reached EOF"));
+        }
+        block.truncate(read);
+        if self.options.verify_headers || self.options.expected_spec.is_some() {
+            for i in 0..read {
+                self.verify(&block.header(i))?;
+            }
+        }
+        if self.options.validation != ValidationLevel::None {
+            for i in 0..read {
+                self.options.validation.check(&block.to_frame(i))?;
+            }
+        }
+        return Ok(block);
+    }
+
+    /// Skip forward to the next frame boundary, for when the stream is corrupted or started partway through a frame.
+    /// Returns the number of bytes skipped.
+    ///
+    /// A boundary is accepted where `confirm + 1` consecutive plausible headers follow it: each of the expected size
+    /// (or, when [`trust_header_size`](ReaderOptions::trust_header_size) is set, no larger than the reader's frame
+    /// size), sharing the reference epoch, station and VDIF version of the first, and with timestamps and
+    /// frame numbers that never decrease. Returns an [`UnexpectedEof`](ErrorKind::UnexpectedEof) error if no
+    /// boundary is found before the end of the stream.
+    pub fn resync(&mut self, confirm: usize) -> Result<u64> {
+        let window_len = (confirm + 1) * self.frame_size;
+        // Take everything already buffered, so any bytes read past the boundary can be put back beneath the buffer
+        let mut window = self.inner.buffer().to_vec();
+        self.inner.consume(window.len());
+        let rewind = self.inner.get_mut();
+        window.extend_from_slice(&rewind.prefix[rewind.pos..]);
+        rewind.prefix.clear();
+        rewind.pos = 0;
+
+        let mut chunk = vec![0u8; self.frame_size.max(HEADER_SIZE)];
+        let mut skipped = 0;
+        loop {
+            let mut eof = false;
+            while window.len() < window_len {
+                let read = rewind.inner.read(&mut chunk)?;
+                if read == 0 {
+                    eof = true;
+                    break;
+                }
+                window.extend_from_slice(&chunk[0..read]);
+            }
+
+            let candidates = (window.len() + 1).saturating_sub(window_len);
+            let trust = self.options.trust_header_size;
+            if let Some(offset) = (0..candidates)
+                .find(|off| plausible_frames(&window[*off..], self.frame_size, confirm, trust))
+            {
+                rewind.prefix = window.split_off(offset);
+                return Ok(skipped + offset as u64);
+            } else if eof {
+                return Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "Reached EOF before finding a frame boundary",
+                ));
+            }
+            window.drain(0..candidates);
+            skipped += candidates as u64;
+        }
+    }
 
+    fn read_fixed_frame(&mut self) -> Result<VDIFFrame> {
+        // Read directly into the final frame allocation
+        let mut outframe = VDIFFrame::empty(self.frame_size);
+        if !self.read_whole(outframe.as_mut_bytes())? {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "Reached EOF"));
+        }
         return Ok(outframe);
     }
+
+    // Fill `bytes` from the stream, returning `false` if it had already ended. Checks for the end with a read rather
+    // than by peeking the buffer, which is always empty when the reader has no capacity.
+    fn read_whole(&mut self, bytes: &mut [u8]) -> Result<bool> {
+        let read = loop {
+            match self.inner.read(bytes) {
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                other => break other?,
+            }
+        };
+        if read == 0 && !bytes.is_empty() {
+            return Ok(false);
+        }
+        self.fill_frame(&mut bytes[read..])?;
+        return Ok(true);
+    }
+
+    // Fill `bytes` from the stream, which is known to not be at EOF.
+    fn fill_frame(&mut self, bytes: &mut [u8]) -> Result<()> {
+        let ignore_partial_tail = self.options.ignore_partial_tail;
+        let expected = bytes.len();
+        return self.inner.read_exact(bytes).map_err(|e| {
+            if e.kind() != ErrorKind::UnexpectedEof {
+                e
+            } else if ignore_partial_tail {
+                Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "Reached a partial VDIF frame at EOF",
+                )
+            } else {
+                VDIFError::ShortFrame {
+                    expected: expected,
+                    received: None,
+                }
+                .into()
+            }
+        });
+    }
+
+    fn verify(&mut self, header: &VDIFHeader) -> Result<()> {
+        if self.options.verify_headers {
+            let (version, legacy) = *self
+                .format
+                .get_or_insert((header.version, header.is_legacy));
+            if version != header.version {
+                return Err(VDIFError::VersionMismatch {
+                    expected: version,
+                    found: header.version,
+                }
+                .into());
+            } else if legacy != header.is_legacy {
+                return Err(VDIFError::LegacyMismatch { expected: legacy }.into());
+            }
+        }
+        if let Some(spec) = &self.options.expected_spec {
+            check_spec(header, spec)?;
+        }
+        return Ok(());
+    }
+}
+
+impl<T: Read> VDIFRead for VDIFReader<T> {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        let frame = if self.options.trust_header_size {
+            self.read_sized_frame()?
+        } else {
+            self.read_fixed_frame()?
+        };
+
+        if self.options.verify_headers || self.options.expected_spec.is_some() {
+            self.verify(&frame.get_header())?;
+        }
+        self.options.validation.check(&frame)?;
+        return Ok(frame);
+    }
+}
+
+/// Read exactly one frame from `reader` into `frame`, reusing its storage rather than allocating a new frame. The size
+/// of `frame` sets how many bytes are read.
+///
+/// Returns an error of kind [`UnexpectedEof`](ErrorKind::UnexpectedEof) if `reader` ends before the frame is filled,
+/// in which case the contents of `frame` are unspecified.
+///
+/// ```rust,ignore
+/// let mut frame = VDIFFrame::empty(8032);
+/// while read_frame_into(&mut stream, &mut frame).is_ok() {
+///     process(&frame);
+/// }
+/// ```
+pub fn read_frame_into<T: Read, S: FrameStorage>(
+    reader: &mut T,
+    frame: &mut VDIFFrame<S>,
+) -> Result<()> {
+    return reader.read_exact(frame.as_mut_bytes());
+}
+
+fn check_frame_buffer<S: FrameStorage>(frame: &VDIFFrame<S>, frame_size: usize) -> Result<()> {
+    if frame.bytesize() != frame_size {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Can't read {} byte frames into a {} byte frame",
+                frame_size,
+                frame.bytesize()
+            ),
+        ));
+    }
+    return Ok(());
+}
+
+// Check `bytes` starts with `confirm + 1` consecutive plausible frames.
+fn plausible_frames(bytes: &[u8], frame_size: usize, confirm: usize, trust_size: bool) -> bool {
+    let mut pos = 0;
+    let mut previous: Option<VDIFHeader> = None;
+    for _ in 0..=confirm {
+        let Some(header_bytes) = bytes.get(pos..pos + HEADER_SIZE) else {
+            return false;
+        };
+        let header = decode_header_bytes(header_bytes.try_into().unwrap());
+        let size = header.bytesize() as usize;
+        let size_ok = if trust_size {
+            (header.header_bytesize() as usize..=frame_size).contains(&size) && size % 8 == 0
+        } else {
+            size == frame_size
+        };
+        if !size_ok {
+            return false;
+        }
+        if let Some(previous) = previous {
+            if header.epoch != previous.epoch
+                || header.station != previous.station
+                || header.version != previous.version
+                || (header.time, header.frameno) < (previous.time, previous.frameno)
+                || header.time > previous.time + 1
+            {
+                return false;
+            }
+        }
+        previous = Some(header);
+        pos += size;
+    }
+    return true;
+}
+
+// A reader that yields `prefix` before reading from `inner`, so a VDIFReader can put back bytes it read ahead.
+struct Rewind<T> {
+    prefix: Vec<u8>,
+    pos: usize,
+    inner: T,
+}
+
+impl<T> Rewind<T> {
+    fn new(inner: T) -> Self {
+        return Self {
+            prefix: Vec::new(),
+            pos: 0,
+            inner: inner,
+        };
+    }
+}
+
+impl<T: Read> Read for Rewind<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.pos < self.prefix.len() {
+            let n = buf.len().min(self.prefix.len() - self.pos);
+            buf[0..n].copy_from_slice(&self.prefix[self.pos..self.pos + n]);
+            self.pos += n;
+            return Ok(n);
+        }
+        return self.inner.read(buf);
+    }
+}
+
+// The bytes of `prefix` were read from just before the current position of `inner`, so seeking drops them and moves
+// `inner` back over them.
+impl<T: Seek> Seek for Rewind<T> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let pos = match pos {
+            SeekFrom::Current(n) => SeekFrom::Current(n - (self.prefix.len() - self.pos) as i64),
+            other => other,
+        };
+        self.prefix.clear();
+        self.pos = 0;
+        return self.inner.seek(pos);
+    }
+}
+
+// Check `header` belongs to the stream described by `spec`.
+fn check_spec(header: &VDIFHeader, spec: &StreamSpec) -> Result<()> {
+    if header.bytesize() as usize != spec.frame_size {
+        return Err(invalid("Frame size does not match the expected stream"));
+    } else if header.channelno() != spec.channels {
+        return Err(invalid("Channel count does not match the expected stream"));
+    } else if header.bits() != spec.bits_per_sample as u32 {
+        return Err(invalid("Bits/sample does not match the expected stream"));
+    } else if header.is_real != spec.is_real {
+        return Err(invalid("Data type does not match the expected stream"));
+    } else if header.station != spec.station {
+        return Err(invalid("Station does not match the expected stream"));
+    } else if !spec.threads.is_empty() && !spec.threads.contains(&header.thread) {
+        return Err(invalid("Thread is not part of the expected stream"));
+    }
+    return Ok(());
+}
+
+fn invalid(msg: &str) -> Error {
+    return Error::new(ErrorKind::InvalidData, msg.to_owned());
 }
 
 impl VDIFReader<File> {
@@ -104,11 +542,43 @@ impl VDIFReader<File> {
         let file = File::open(path)?;
         // Default to a buffer of 10 frames
         return Ok(Self {
-            inner: BufReader::with_capacity(10 * frame_size, file),
+            inner: BufReader::with_capacity(10 * frame_size, Rewind::new(file)),
             frame_size: frame_size,
+            options: ReaderOptions::default(),
+            format: None,
         });
     }
 
+    /// Open a VDIF file on disk that may hold non-VDIF data around the frames, such as a proprietary header written by
+    /// the capture tool, or a partial frame at the end. Returns the reader and the number of bytes skipped at the
+    /// start of the file.
+    ///
+    /// The first frame is located with [`resync`](VDIFReader::resync), preferring a boundary followed by three
+    /// consistent headers, and falling back to fewer for very short files.
+    /// [`ignore_partial_tail`](ReaderOptions::ignore_partial_tail) is set, so reading stops cleanly at a trailing
+    /// partial frame.
+    pub fn open_skipping_junk<P: AsRef<Path>>(path: P, frame_size: usize) -> Result<(Self, u64)> {
+        let path = path.as_ref();
+        let mut skipped = None;
+        for confirm in (0..=2).rev() {
+            match Self::open(path, frame_size)?.resync(confirm) {
+                Ok(offset) => {
+                    skipped = Some(offset);
+                    break;
+                }
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        let skipped = skipped.ok_or_else(|| invalid("No VDIF frames found in the file"))?;
+
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(skipped))?;
+        let mut reader = Self::new(file, frame_size);
+        reader.options.ignore_partial_tail = true;
+        return Ok((reader, skipped));
+    }
+
     /// Open a VDIF file on disk with the specified buffer capacity.
     pub fn open_withcapacity<P: AsRef<Path>>(
         path: P,
@@ -117,10 +587,124 @@ impl VDIFReader<File> {
     ) -> Result<Self> {
         let file = File::open(path)?;
         return Ok(Self {
-            inner: BufReader::with_capacity(frame_capacity * frame_size, file),
+            inner: BufReader::with_capacity(frame_capacity * frame_size, Rewind::new(file)),
             frame_size: frame_size,
+            options: ReaderOptions::default(),
+            format: None,
         });
     }
+
+    /// Seek to the first frame at or after `instant`, returning its byte offset within the file.
+    ///
+    /// The frames are assumed to all be of the reader's frame size and in time order, as in a normal recording, so
+    /// the frame is found by a binary search reading only a few dozen headers. Frames are taken to be aligned with the
+    /// current position, so this also works after [`open_skipping_junk`](VDIFReader::open_skipping_junk). Returns an
+    /// error of kind [`UnexpectedEof`](ErrorKind::UnexpectedEof), leaving the reader at the end of the file, if every
+    /// frame is earlier than `instant`.
+    pub fn seek_to_instant(&mut self, instant: FrameInstant) -> Result<u64> {
+        let size = self.frame_size as u64;
+        let base = self.inner.stream_position()? % size;
+        let count = self
+            .inner
+            .get_ref()
+            .inner
+            .metadata()?
+            .len()
+            .saturating_sub(base)
+            / size;
+
+        let (mut lo, mut hi) = (0, count);
+        let mut bytes = [0u8; HEADER_SIZE];
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            self.inner.seek(SeekFrom::Start(base + mid * size))?;
+            self.inner.read_exact(&mut bytes)?;
+            if decode_header_bytes(&bytes).instant() < instant {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+
+        let offset = base + lo * size;
+        self.inner.seek(SeekFrom::Start(offset))?;
+        if lo == count {
+            return Err(no_frame_after());
+        }
+        return Ok(offset);
+    }
+
+    /// Seek to the first frame at or after the given reference epoch, seconds since that epoch, and frame number,
+    /// returning its byte offset within the file. See [`seek_to_instant`](VDIFReader::seek_to_instant).
+    pub fn seek_to_time(&mut self, epoch: u8, seconds: u32, frameno: u32) -> Result<u64> {
+        return self.seek_to_instant(FrameInstant {
+            epoch: epoch,
+            time: seconds,
+            frameno: frameno,
+        });
+    }
+
+    /// Seek to the frame covering `datetime`, given `frame_rate` frames per second per thread, returning its byte
+    /// offset within the file. See [`seek_to_instant`](VDIFReader::seek_to_instant).
+    ///
+    /// Returns an error of kind [`InvalidInput`](ErrorKind::InvalidInput) if `frame_rate` is zero.
+    pub fn seek_to_utc(&mut self, datetime: DateTime<Utc>, frame_rate: u32) -> Result<u64> {
+        let instant = from_datetime(datetime, frame_rate).ok_or(Error::new(
+            ErrorKind::InvalidInput,
+            "Frame rate must be positive",
+        ))?;
+        return self.seek_to_instant(instant);
+    }
+
+    /// Seek to the first frame at or after `instant` using a [`FrameIndex`] of the file, returning its byte offset.
+    ///
+    /// Unlike [`seek_to_instant`](VDIFReader::seek_to_instant) this allows frames of varying size. With an index of
+    /// [`Second`](IndexGranularity::Second) granularity, the frames of the target second are scanned from its first
+    /// indexed frame.
+    pub fn seek_with_index(&mut self, index: &FrameIndex, instant: FrameInstant) -> Result<u64> {
+        let start = match index.granularity() {
+            IndexGranularity::Frame => instant,
+            IndexGranularity::Second => FrameInstant {
+                frameno: 0,
+                ..instant
+            },
+        };
+        let Some(entry) = index.find(start, None) else {
+            self.inner.seek(SeekFrom::End(0))?;
+            return Err(no_frame_after());
+        };
+
+        let mut offset = entry.offset;
+        let mut bytes = [0u8; HEADER_SIZE];
+        loop {
+            self.inner.seek(SeekFrom::Start(offset))?;
+            match self.inner.read_exact(&mut bytes) {
+                Ok(()) => {}
+                // Every frame of the indexed second is earlier than `instant`
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                    self.inner.seek(SeekFrom::End(0))?;
+                    return Err(no_frame_after());
+                }
+                Err(e) => return Err(e),
+            }
+            let header = decode_header_bytes(&bytes);
+            if header.instant() >= instant {
+                break;
+            } else if header.bytesize() == 0 {
+                return Err(invalid("Frame with a zero size field"));
+            }
+            offset += header.bytesize() as u64;
+        }
+        self.inner.seek(SeekFrom::Start(offset))?;
+        return Ok(offset);
+    }
+}
+
+fn no_frame_after() -> Error {
+    return Error::new(
+        ErrorKind::UnexpectedEof,
+        "No frame at or after the requested time",
+    );
 }
 
 /// A type capable of writing VDIF frames to any destination implementing [`Write`].
@@ -129,6 +713,7 @@ impl VDIFReader<File> {
 pub struct VDIFWriter<T: Write> {
     inner: BufWriter<T>,
     frame_size: usize,
+    validation: ValidationLevel,
 }
 
 impl<T: Write> VDIFWriter<T> {
@@ -138,6 +723,7 @@ impl<T: Write> VDIFWriter<T> {
         return Self {
             inner: BufWriter::with_capacity(10 * frame_size, inner),
             frame_size: frame_size,
+            validation: ValidationLevel::None,
         };
     }
 
@@ -147,9 +733,15 @@ impl<T: Write> VDIFWriter<T> {
         return Self {
             inner: BufWriter::with_capacity(frame_capacity * frame_size, inner),
             frame_size: frame_size,
+            validation: ValidationLevel::None,
         };
     }
 
+    /// Set how thoroughly to check each frame before it is written. [`ValidationLevel::None`] by default.
+    pub fn set_validation(&mut self, validation: ValidationLevel) {
+        self.validation = validation;
+    }
+
     /// Flush the contents of the buffer.
     pub fn flush(&mut self) -> Result<()> {
         return self.inner.flush();
@@ -157,15 +749,21 @@ impl<T: Write> VDIFWriter<T> {
 }
 
 impl<T: Write> VDIFWrite for VDIFWriter<T> {
+    /// Write `frame`, returning an error of kind [`InvalidInput`](ErrorKind::InvalidInput) carrying a
+    /// [`VDIFError::BadFrameSize`] if it isn't the writer's frame size.
     fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
-        assert_eq!(
-            self.frame_size,
-            frame.bytesize(),
-            "VDIF frames must be {} bytes in size for this VDIFWriter",
-            self.frame_size
-        );
-        let _ = self.inner.write(frame.as_bytes())?;
-        return Ok(());
+        if frame.bytesize() != self.frame_size {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                VDIFError::BadFrameSize(frame.bytesize()),
+            ));
+        }
+        self.validation.check(&frame)?;
+        return self.inner.write_all(frame.as_bytes());
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        return self.inner.flush();
     }
 }
 
@@ -177,6 +775,7 @@ impl VDIFWriter<File> {
         return Ok(Self {
             inner: BufWriter::with_capacity(10 * frame_size, newfile),
             frame_size: frame_size,
+            validation: ValidationLevel::None,
         });
     }
 
@@ -191,6 +790,688 @@ impl VDIFWriter<File> {
         return Ok(Self {
             inner: BufWriter::with_capacity(frame_capacity * frame_size, newfile),
             frame_size: frame_size,
+            validation: ValidationLevel::None,
+        });
+    }
+}
+
+/// A buffered writer of VDIF frames to a file on disk, counting the frames and bytes written.
+///
+/// This is the file counterpart of [`VDIFReader::open`], so recording code doesn't have to manage the underlying
+/// [`File`] and buffer itself.
+pub struct VDIFFileWriter {
+    inner: VDIFWriter<File>,
+    frames: u64,
+    bytes: u64,
+}
+
+impl VDIFFileWriter {
+    /// Create a new VDIF file at `path`, truncating any existing file, with a buffer of 10 frames.
+    pub fn create<P: AsRef<Path>>(path: P, frame_size: usize) -> Result<Self> {
+        return Ok(Self::wrap(VDIFWriter::create(path, frame_size)?));
+    }
+
+    /// Create a new VDIF file at `path`, truncating any existing file, with a buffer of `frame_capacity` frames.
+    pub fn create_withcapacity<P: AsRef<Path>>(
+        path: P,
+        frame_size: usize,
+        frame_capacity: usize,
+    ) -> Result<Self> {
+        return Ok(Self::wrap(VDIFWriter::create_withcapacity(
+            path,
+            frame_size,
+            frame_capacity,
+        )?));
+    }
+
+    /// Open the VDIF file at `path` to append frames to it, creating it if it doesn't exist. Fails with
+    /// [`InvalidData`](ErrorKind::InvalidData) if the existing file ends in a partial frame, since appended frames
+    /// would then be misaligned, or [`InvalidInput`](ErrorKind::InvalidInput) if `frame_size` is zero or not a
+    /// multiple of 8 bytes.
+    pub fn append<P: AsRef<Path>>(path: P, frame_size: usize) -> Result<Self> {
+        if frame_size == 0 || frame_size % 8 != 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                VDIFError::BadFrameSize(frame_size),
+            ));
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let existing = file.metadata()?.len();
+        if existing % frame_size as u64 != 0 {
+            return Err(invalid("Existing file ends in a partial VDIF frame"));
+        }
+        return Ok(Self::wrap(VDIFWriter::new(file, frame_size)));
+    }
+
+    fn wrap(inner: VDIFWriter<File>) -> Self {
+        return Self {
+            inner: inner,
+            frames: 0,
+            bytes: 0,
+        };
+    }
+
+    /// Set how thoroughly to check each frame before it is written. [`ValidationLevel::None`] by default.
+    pub fn set_validation(&mut self, validation: ValidationLevel) {
+        self.inner.set_validation(validation);
+    }
+
+    /// Get the number of frames written by this writer.
+    pub fn frames(&self) -> u64 {
+        return self.frames;
+    }
+
+    /// Get the number of bytes written by this writer.
+    pub fn bytes(&self) -> u64 {
+        return self.bytes;
+    }
+
+    /// Flush the buffer and ask the operating system to write the file's contents to disk.
+    pub fn sync(&mut self) -> Result<()> {
+        self.inner.flush()?;
+        return self.inner.inner.get_ref().sync_data();
+    }
+}
+
+impl VDIFWrite for VDIFFileWriter {
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        let size = frame.bytesize() as u64;
+        self.inner.write_frame(frame)?;
+        self.frames += 1;
+        self.bytes += size;
+        return Ok(());
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        return self.inner.flush();
+    }
+}
+
+// The most frames handed to a single vectored write, within the `IOV_MAX` of common platforms.
+const MAX_WRITE_FRAMES: usize = 1024;
+
+/// Write every frame of `frames` to `writer` with [`write_vectored`](Write::write_vectored), so many frames go out in
+/// each system call rather than one call per frame, and without first copying them into one buffer.
+pub fn write_frames<T: Write, S: FrameStorage>(
+    writer: &mut T,
+    frames: &[VDIFFrame<S>],
+) -> Result<()> {
+    for chunk in frames.chunks(MAX_WRITE_FRAMES) {
+        let mut slices: Vec<IoSlice> = chunk.iter().map(|f| IoSlice::new(f.as_bytes())).collect();
+        let mut remaining = &mut slices[..];
+        while !remaining.is_empty() {
+            match writer.write_vectored(remaining) {
+                Ok(0) => {
+                    return Err(Error::new(
+                        ErrorKind::WriteZero,
+                        "Failed to write every frame",
+                    ))
+                }
+                Ok(written) => IoSlice::advance_slices(&mut remaining, written),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    return Ok(());
+}
+
+/// A [`Write`] adaptor that coalesces everything written to it into blocks of a fixed size, each written to the inner
+/// writer from an aligned buffer in a single call.
+///
+/// This suits recorders writing to files opened with `O_DIRECT`, which need the buffer, length and file offset of
+/// every write to be aligned, and fast disks generally, which prefer a few large writes to many frame-sized ones.
+/// Frames are written with [`write_frame`](VDIFWrite::write_frame) or any [`Write`] method.
+///
+/// [`flush`](Write::flush) writes out a final partial block, which may not be aligned, so only flush at the end of a
+/// recording. Dropping the writer flushes it too, but any error is lost, such as a file opened with `O_DIRECT` refusing
+/// the unaligned final block, so call [`into_inner`](CoalescingWriter::into_inner) to see it.
+///
+/// ```rust,ignore
+/// let file = OpenOptions::new().write(true).create(true).custom_flags(libc::O_DIRECT).open("out.vdif").unwrap();
+/// let mut writer = CoalescingWriter::new(file, 4 << 20, 4096);
+/// writer.write_frame(frame).unwrap();
+/// ```
+pub struct CoalescingWriter<T: Write> {
+    // Only taken by `into_inner`
+    inner: Option<T>,
+    block: AlignedStorage,
+    filled: usize,
+}
+
+impl<T: Write> CoalescingWriter<T> {
+    /// Construct a new [`CoalescingWriter`] writing blocks of `block_size` bytes to `inner`, from a buffer aligned to
+    /// `align` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block_size` is zero or not a multiple of 4 bytes, or `align` is not a power of two.
+    pub fn new(inner: T, block_size: usize, align: usize) -> Self {
+        assert!(
+            block_size % 4 == 0,
+            "Coalesced blocks must be a multiple of 4 bytes in size."
+        );
+        return Self {
+            inner: Some(inner),
+            block: AlignedStorage::new(block_size / 4, align),
+            filled: 0,
+        };
+    }
+
+    /// Get the size in bytes of the blocks written.
+    pub fn block_size(&self) -> usize {
+        return self.block.as_ref().len() * 4;
+    }
+
+    /// Get the number of bytes waiting in the current, partial block.
+    pub fn buffered(&self) -> usize {
+        return self.filled;
+    }
+
+    /// Get a reference to the inner writer.
+    pub fn get_ref(&self) -> &T {
+        return self.inner.as_ref().unwrap();
+    }
+
+    /// Flush the writer, including any partial block, and return the inner writer.
+    pub fn into_inner(mut self) -> Result<T> {
+        Write::flush(&mut self)?;
+        return Ok(self.inner.take().unwrap());
+    }
+
+    fn block_bytes(&mut self) -> &mut [u8] {
+        let words = self.block.as_mut();
+        // Safety: any bytes are a valid u32, and u8 has no alignment requirement
+        return unsafe {
+            core::slice::from_raw_parts_mut(words.as_mut_ptr() as *mut u8, words.len() * 4)
+        };
+    }
+
+    // Write out the first `filled` bytes of the block.
+    fn write_block(&mut self) -> Result<()> {
+        let filled = self.filled;
+        let words = self.block.as_ref();
+        // Safety: as in `block_bytes`
+        let bytes = unsafe { core::slice::from_raw_parts(words.as_ptr() as *const u8, filled) };
+        self.inner.as_mut().unwrap().write_all(bytes)?;
+        self.filled = 0;
+        return Ok(());
+    }
+}
+
+impl<T: Write> Write for CoalescingWriter<T> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        // A full block is only written out once more data arrives, so a failure never loses input already accepted
+        if self.filled == self.block_size() && !buf.is_empty() {
+            self.write_block()?;
+        }
+        let filled = self.filled;
+        let n = (self.block_size() - filled).min(buf.len());
+        self.block_bytes()[filled..filled + n].copy_from_slice(&buf[0..n]);
+        self.filled += n;
+        return Ok(n);
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.filled > 0 {
+            self.write_block()?;
+        }
+        return self.inner.as_mut().unwrap().flush();
+    }
+}
+
+impl<T: Write> VDIFWrite for CoalescingWriter<T> {
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        return self.write_all(frame.as_bytes());
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        return Write::flush(self);
+    }
+}
+
+impl<T: Write> Drop for CoalescingWriter<T> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = Write::flush(self);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::VDIFHeader;
+    use crate::testing::temp_path;
+
+    #[test]
+    fn test_file_writer() {
+        let path = temp_path("file_writer.vdif");
+        let frame = || {
+            VDIFFrame::from_header(VDIFHeader {
+                size: 8,
+                ..Default::default()
+            })
+        };
+        let mut writer = VDIFFileWriter::create(&path, 64).unwrap();
+        writer.write_frame(frame()).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        let mut writer = VDIFFileWriter::append(&path, 64).unwrap();
+        writer.write_frame(frame()).unwrap();
+        writer.write_frame(frame()).unwrap();
+        writer.sync().unwrap();
+        assert_eq!((writer.frames(), writer.bytes()), (2, 128));
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 192);
+        assert!(VDIFFileWriter::append(&path, 80).is_err());
+        assert_eq!(
+            VDIFFileWriter::append(&path, 0).err().unwrap().kind(),
+            ErrorKind::InvalidInput
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_frame_into() {
+        let mut stream = Vec::new();
+        for frameno in 0..3 {
+            let frame = VDIFFrame::from_header(VDIFHeader {
+                size: 8,
+                frameno: frameno,
+                ..Default::default()
+            });
+            stream.extend_from_slice(frame.as_bytes());
+        }
+
+        let mut frame = VDIFFrame::empty(64);
+        let mut raw = &stream[..];
+        read_frame_into(&mut raw, &mut frame).unwrap();
+        assert_eq!(frame.get_header().frameno, 0);
+
+        let mut reader = VDIFReader::new(&stream[..], 64);
+        for frameno in 0..3 {
+            reader.read_frame_into(&mut frame).unwrap();
+            assert_eq!(frame.get_header().frameno, frameno);
+        }
+        assert_eq!(
+            reader.read_frame_into(&mut frame).unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+        assert_eq!(
+            reader
+                .read_frame_into(&mut VDIFFrame::empty(32))
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn test_write_frames() {
+        // Records the size of each write, taking at most 100 bytes from each vectored write
+        struct Recorder(Vec<u8>, Vec<usize>);
+
+        impl Write for Recorder {
+            fn write(&mut self, buf: &[u8]) -> Result<usize> {
+                self.0.extend_from_slice(buf);
+                self.1.push(buf.len());
+                return Ok(buf.len());
+            }
+
+            fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> Result<usize> {
+                let bytes: Vec<u8> = bufs
+                    .iter()
+                    .flat_map(|b| b.iter().copied())
+                    .take(100)
+                    .collect();
+                return self.write(&bytes);
+            }
+
+            fn flush(&mut self) -> Result<()> {
+                return Ok(());
+            }
+        }
+
+        let frames: Vec<VDIFFrame> = (0..5)
+            .map(|frameno| {
+                VDIFFrame::from_header(VDIFHeader {
+                    size: 8,
+                    frameno: frameno,
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let mut recorder = Recorder(Vec::new(), Vec::new());
+        write_frames(&mut recorder, &frames).unwrap();
+        assert_eq!(recorder.1, [100, 100, 100, 20]);
+        let mut reader = VDIFReader::new(&recorder.0[..], 64);
+        for frameno in 0..5 {
+            assert_eq!(reader.read_frame().unwrap().get_header().frameno, frameno);
+        }
+
+        let mut writer = CoalescingWriter::new(Recorder(Vec::new(), Vec::new()), 128, 4096);
+        for frame in frames {
+            writer.write_frame(frame).unwrap();
+        }
+        assert_eq!(writer.buffered(), 64);
+        let recorder = writer.into_inner().unwrap();
+        assert_eq!(recorder.1, [128, 128, 64]);
+        assert_eq!(recorder.0.len(), 320);
+
+        // A failed block write leaves the block in place to be retried, rather than losing bytes already accepted
+        struct Failing(bool, Vec<u8>);
+
+        impl Write for Failing {
+            fn write(&mut self, buf: &[u8]) -> Result<usize> {
+                if std::mem::take(&mut self.0) {
+                    return Err(ErrorKind::StorageFull.into());
+                }
+                self.1.extend_from_slice(buf);
+                return Ok(buf.len());
+            }
+
+            fn flush(&mut self) -> Result<()> {
+                return Ok(());
+            }
+        }
+
+        let mut writer = CoalescingWriter::new(Failing(true, Vec::new()), 8, 8);
+        assert_eq!(writer.write(&[1; 8]).unwrap(), 8);
+        assert!(writer.write(&[2; 4]).is_err());
+        assert_eq!(writer.write(&[2; 4]).unwrap(), 4);
+        assert_eq!(
+            writer.into_inner().unwrap().1,
+            [vec![1; 8], vec![2; 4]].concat()
+        );
+    }
+
+    #[test]
+    fn test_open_skipping_junk() {
+        let path = temp_path("junk.vdif");
+        let mut contents = b"CAPTURE TOOL HEADER\n".to_vec();
+        for frameno in 0..4 {
+            let frame = VDIFFrame::from_header(VDIFHeader {
+                size: 8,
+                frameno: frameno,
+                ..Default::default()
+            });
+            contents.extend_from_slice(frame.as_bytes());
+        }
+        contents.extend_from_slice(&[0; 40]);
+        std::fs::write(&path, &contents).unwrap();
+
+        let (mut reader, skipped) = VDIFReader::open_skipping_junk(&path, 64).unwrap();
+        assert_eq!(skipped, 20);
+        for frameno in 0..4 {
+            assert_eq!(reader.read_frame().unwrap().get_header().frameno, frameno);
+        }
+        assert_eq!(
+            reader.read_frame().unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_seek_to_time() {
+        let path = temp_path("seek.vdif");
+        // Two threads at 4 frames/s for 3 seconds
+        let mut contents = Vec::new();
+        for n in 0..12 {
+            for thread in 0..2 {
+                let frame = VDIFFrame::from_header(VDIFHeader {
+                    time: n / 4,
+                    frameno: n % 4,
+                    thread: thread,
+                    size: 8,
+                    ..Default::default()
+                });
+                contents.extend_from_slice(frame.as_bytes());
+            }
+        }
+        std::fs::write(&path, &contents).unwrap();
+
+        let mut reader = VDIFReader::open(&path, 64).unwrap();
+        reader.read_frame().unwrap();
+        assert_eq!(reader.seek_to_time(0, 1, 2).unwrap(), 12 * 64);
+        let header = reader.read_frame().unwrap().get_header();
+        assert_eq!((header.time, header.frameno, header.thread), (1, 2, 0));
+        let target = header.instant();
+        let datetime = crate::time::to_datetime(target, 4).unwrap();
+        assert_eq!(reader.seek_to_utc(datetime, 4).unwrap(), 12 * 64);
+        assert_eq!(
+            reader.seek_to_time(0, 3, 0).unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+
+        let mut index_reader = VDIFReader::open(&path, 64).unwrap();
+        let index = FrameIndex::build(&mut index_reader, IndexGranularity::Second).unwrap();
+        assert_eq!(reader.seek_with_index(&index, target).unwrap(), 12 * 64);
+        assert_eq!(reader.read_frame().unwrap().get_header().instant(), target);
+        let late = reader.seek_with_index(
+            &index,
+            FrameInstant {
+                time: 2,
+                frameno: 9,
+                ..target
+            },
+        );
+        assert_eq!(
+            late.unwrap_err().to_string(),
+            "No frame at or after the requested time"
+        );
+        assert_eq!(
+            reader.read_frame().unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_resync() {
+        let mut stream: Vec<u8> = vec![0xFF; 13];
+        for frameno in 0..5 {
+            let frame = VDIFFrame::from_header(VDIFHeader {
+                is_valid: true,
+                size: 8,
+                frameno: frameno,
+                station: 7,
+                ..Default::default()
+            });
+            stream.extend_from_slice(frame.as_bytes());
+        }
+
+        let mut reader = VDIFReader::with_capacity(stream.as_slice(), 64, 1);
+        // The first read straddles the junk and the first frame, so resync skips to the second frame
+        reader.read_frame().unwrap();
+        assert_eq!(reader.resync(2).unwrap(), 13);
+        for frameno in 1..5 {
+            assert_eq!(reader.read_frame().unwrap().get_header().frameno, frameno);
+        }
+        assert_eq!(
+            reader.resync(0).unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn test_read_mixed_frame_sizes() {
+        let mut stream: Vec<u8> = Vec::new();
+        for (thread, size) in [(0u16, 64usize), (1, 96), (0, 64), (1, 96)] {
+            let mut frame = VDIFFrame::empty(size);
+            frame.set_header(VDIFHeader {
+                is_valid: true,
+                size: (size / 8) as u32,
+                thread: thread,
+                ..Default::default()
+            });
+            stream.extend_from_slice(frame.as_bytes());
+        }
+
+        let mut reader = VDIFReader::new(stream.as_slice(), 96);
+        reader.set_options(ReaderOptions {
+            trust_header_size: true,
+            ..Default::default()
         });
+        for size in [64, 96, 64, 96] {
+            assert_eq!(reader.read_frame().unwrap().bytesize(), size);
+        }
+        assert_eq!(
+            reader.read_frame().unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn test_read_across_buffer_boundary() {
+        let mut stream: Vec<u8> = Vec::new();
+        for frameno in 0..4 {
+            let mut frame = VDIFFrame::empty(64);
+            frame.set_header(VDIFHeader {
+                size: 8,
+                frameno: frameno,
+                ..Default::default()
+            });
+            stream.extend_from_slice(frame.as_bytes());
+        }
+        stream.extend_from_slice(&[0u8; 16]);
+
+        // A buffer that doesn't hold a whole number of frames forces the size word to straddle its end
+        let mut reader = VDIFReader::with_capacity(stream.as_slice(), 70, 1);
+        reader.set_options(ReaderOptions {
+            trust_header_size: true,
+            ..Default::default()
+        });
+        for frameno in 0..4 {
+            assert_eq!(reader.read_frame().unwrap().get_header().frameno, frameno);
+        }
+        assert_eq!(
+            reader.read_frame().unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+
+        let mut reader = VDIFReader::new(&stream[64..], 64);
+        for _ in 0..3 {
+            assert!(reader.read_frame().is_ok());
+        }
+        assert_eq!(
+            reader.read_frame().unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_read_without_capacity() {
+        let mut stream: Vec<u8> = Vec::new();
+        for frameno in 0..3 {
+            let frame = VDIFFrame::from_header(VDIFHeader {
+                size: 8,
+                frameno: frameno,
+                ..Default::default()
+            });
+            stream.extend_from_slice(frame.as_bytes());
+        }
+
+        // With no buffer to peek, the end of the stream is still told apart from a frame
+        let mut reader = VDIFReader::with_capacity(stream.as_slice(), 64, 0);
+        assert_eq!(reader.read_frame().unwrap().get_header().frameno, 0);
+        let mut frame = VDIFFrame::empty(64);
+        reader.read_frame_into(&mut frame).unwrap();
+        assert_eq!(frame.get_header().frameno, 1);
+        assert_eq!(reader.read_block(4).unwrap().len(), 1);
+        assert_eq!(
+            reader.read_frame().unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+
+        let mut reader = VDIFReader::with_capacity(stream.as_slice(), 64, 0);
+        reader.set_options(ReaderOptions {
+            trust_header_size: true,
+            ..Default::default()
+        });
+        for frameno in 0..3 {
+            assert_eq!(reader.read_frame().unwrap().get_header().frameno, frameno);
+        }
+        assert_eq!(
+            reader.read_frame().unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn test_read_block() {
+        let mut stream: Vec<u8> = Vec::new();
+        for frameno in 0..5 {
+            let frame = VDIFFrame::from_header(VDIFHeader {
+                size: 8,
+                frameno: frameno,
+                ..Default::default()
+            });
+            stream.extend_from_slice(frame.as_bytes());
+        }
+
+        let mut reader = VDIFReader::new(stream.as_slice(), 64);
+        let block = reader.read_block(3).unwrap();
+        assert_eq!(block.len(), 3);
+        assert_eq!(block.header(2).frameno, 2);
+        let block = reader.read_block(3).unwrap();
+        assert_eq!(
+            block.headers().map(|h| h.frameno).collect::<Vec<_>>(),
+            vec![3, 4]
+        );
+        assert_eq!(
+            reader.read_block(3).unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn test_verify_headers() {
+        let header = VDIFHeader {
+            is_valid: true,
+            size: 8,
+            bits_per_sample: 2,
+            is_real: true,
+            station: 134,
+            ..Default::default()
+        };
+        let mut stream: Vec<u8> = Vec::new();
+        for (version, station) in [(0, 134), (0, 135), (1, 134)] {
+            let mut frame = VDIFFrame::empty(64);
+            frame.set_header(VDIFHeader {
+                version: version,
+                station: station,
+                ..header
+            });
+            stream.extend_from_slice(frame.as_bytes());
+        }
+
+        let mut reader = VDIFReader::new(stream.as_slice(), 64);
+        reader.set_options(ReaderOptions {
+            verify_headers: true,
+            expected_spec: Some(StreamSpec::from_header(&header, 1)),
+            ..Default::default()
+        });
+        assert!(reader.read_frame().is_ok());
+        assert_eq!(
+            reader.read_frame().unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        assert_eq!(
+            VDIFError::from_io(&reader.read_frame().unwrap_err()),
+            Some(&VDIFError::VersionMismatch {
+                expected: 0,
+                found: 1
+            })
+        );
+
+        let mut writer = VDIFWriter::new(Vec::new(), 32);
+        let error = writer.write_frame(VDIFFrame::empty(64)).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert_eq!(
+            VDIFError::from_io(&error),
+            Some(&VDIFError::BadFrameSize(64))
+        );
     }
 }
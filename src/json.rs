@@ -0,0 +1,35 @@
+//! Helpers for the small amounts of JSON (and TOML) this crate writes by hand.
+
+use alloc::format;
+use alloc::string::String;
+
+// Quote and escape a string, escaping quotes, backslashes and control characters. The escapes are the same in JSON
+// strings and TOML basic strings, so the result is valid in both.
+pub(crate) fn quote(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    return out;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("a \"b\" \\c"), "\"a \\\"b\\\" \\\\c\"");
+        assert_eq!(quote("line\nbreak\u{1}"), "\"line\\nbreak\\u0001\"");
+    }
+}
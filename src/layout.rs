@@ -0,0 +1,280 @@
+//! Implements [`FrameLayout`], describing where samples sit within the payload of a VDIF frame.
+
+use crate::consts::HEADER_SIZE;
+use crate::header::VDIFHeader;
+use crate::spec::StreamSpec;
+
+/// How complex samples are packed when a word can hold an odd number of components, such as five 6-bit components.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ComplexPacking {
+    /// Each word holds only whole complex samples, leaving the extra component's bits unused. This follows the VDIF
+    /// specification, and is the default.
+    #[default]
+    TruncateToPairs,
+    /// The payload is a continuous stream of components packed as tightly as possible, so the extra component at the
+    /// top of a word holds the real part of a sample whose imaginary part starts the next word.
+    KeepExtraReal,
+}
+
+/// The layout of samples within the payload of a VDIF frame.
+///
+/// Samples follow the packing rules of the VDIF specification. A *complete sample* is one sample from every channel,
+/// and no sample is ever split across two words:
+///
+/// - If a complete sample fits within 32 bits, each word holds as many whole complete samples as fit, and any bits
+///   left over at the top of the word are unused.
+/// - Otherwise each complete sample starts on a word boundary and spans as many words as needed, each holding as many
+///   whole channel samples as fit. Any bits left over at the top of each word are unused.
+///
+/// This matters for bit depths and channel counts whose product doesn't divide 32, such as 3 or 6 bits with multiple
+/// channels. Complex data may instead use a non-standard packing, see [`ComplexPacking`].
+///
+/// A layout is derived from header values that are constant across a stream, so it can be computed once and reused
+/// for every frame (see [`decode_payload_with_layout`](crate::decoding::decode_payload_with_layout)), rather than
+/// being recomputed from the header bits of each frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameLayout {
+    /// The bits/sample of the encoded data. For complex data this is the size of each component.
+    pub bits_per_sample: u32,
+    /// Whether the encoded data is real or complex.
+    pub is_real: bool,
+    /// The number of channels within the frame.
+    pub channels: usize,
+    /// The number of samples within each full payload word. Complex samples count as one sample.
+    pub samples_per_word: usize,
+    /// The number of payload words each complete sample (one sample from every channel) spans. This is one when
+    /// complete samples fit within a word.
+    pub words_per_sample: usize,
+    /// The number of `u32` words in the payload.
+    pub payload_words: usize,
+    /// The total number of samples within the payload, across all channels.
+    pub samples_per_frame: usize,
+    /// The distance in samples between consecutive samples of the same channel.
+    pub channel_stride: usize,
+    /// How complex samples are packed. Ignored for real data.
+    pub complex_packing: ComplexPacking,
+}
+
+impl FrameLayout {
+    /// Construct a [`FrameLayout`] for samples of `bits_per_sample` bits (the bit depth, not the raw header field) in
+    /// `channels` channels, in frames of `frame_size` bytes with a standard 32 byte header.
+    ///
+    /// Returns [`None`] if the values don't describe a usable layout: a bit depth outside 1 to 32 (or 1 to 16 for
+    /// complex data), no channels, or a frame too small to hold a header.
+    pub fn new(
+        bits_per_sample: u32,
+        is_real: bool,
+        channels: usize,
+        frame_size: usize,
+    ) -> Option<Self> {
+        return Self::with_payload_words(
+            bits_per_sample,
+            is_real,
+            channels,
+            frame_size.checked_sub(HEADER_SIZE)? / 4,
+        );
+    }
+
+    // Construct a layout for a payload of `payload_words` words, whatever the size of the header before it.
+    pub(crate) fn with_payload_words(
+        bits_per_sample: u32,
+        is_real: bool,
+        channels: usize,
+        payload_words: usize,
+    ) -> Option<Self> {
+        let components = if is_real { 1 } else { 2 };
+        let sample_bits = bits_per_sample as usize * components;
+        if bits_per_sample == 0 || sample_bits > 32 || channels == 0 {
+            return None;
+        }
+
+        let (samples_per_word, words_per_sample, samples_per_channel) =
+            if sample_bits * channels <= 32 {
+                let complete_per_word = 32 / (sample_bits * channels);
+                (
+                    complete_per_word * channels,
+                    1,
+                    payload_words * complete_per_word,
+                )
+            } else {
+                let samples_per_word = 32 / sample_bits;
+                let words_per_sample = channels.div_ceil(samples_per_word);
+                (
+                    samples_per_word,
+                    words_per_sample,
+                    payload_words / words_per_sample,
+                )
+            };
+
+        return Some(Self {
+            bits_per_sample: bits_per_sample,
+            is_real: is_real,
+            channels: channels,
+            samples_per_word: samples_per_word,
+            words_per_sample: words_per_sample,
+            payload_words: payload_words,
+            samples_per_frame: samples_per_channel * channels,
+            channel_stride: channels,
+            complex_packing: ComplexPacking::TruncateToPairs,
+        });
+    }
+
+    /// Use `packing` for complex data, rather than the default [`ComplexPacking::TruncateToPairs`].
+    pub fn with_complex_packing(mut self, packing: ComplexPacking) -> Self {
+        self.complex_packing = packing;
+        if !self.is_real && packing == ComplexPacking::KeepExtraReal {
+            let components = self.payload_words * (32 / self.bits_per_sample as usize);
+            self.samples_per_word = 32 / self.bits_per_sample as usize / 2;
+            self.words_per_sample = 1;
+            self.samples_per_frame = (components / 2 / self.channels) * self.channels;
+        }
+        return self;
+    }
+
+    /// Construct the [`FrameLayout`] of the frame `header` belongs to, which may be a legacy frame. Returns [`None`]
+    /// if the header doesn't describe a usable layout, as for [`new`](FrameLayout::new).
+    pub fn from_header(header: &VDIFHeader) -> Option<Self> {
+        let payload_bytes = header.bytesize().checked_sub(header.header_bytesize())?;
+        return Self::with_payload_words(
+            header.bits(),
+            header.is_real,
+            header.channelno(),
+            payload_bytes as usize / 4,
+        );
+    }
+
+    /// Construct the [`FrameLayout`] of the frames in the stream described by `spec`. Returns [`None`] if the spec
+    /// doesn't describe a usable layout, as for [`new`](FrameLayout::new).
+    pub fn from_spec(spec: &StreamSpec) -> Option<Self> {
+        return Self::new(
+            spec.bits_per_sample as u32,
+            spec.is_real,
+            spec.channels,
+            spec.frame_size,
+        );
+    }
+
+    /// Get the number of samples of each channel within the payload.
+    pub fn samples_per_channel(&self) -> usize {
+        return self.samples_per_frame / self.channels;
+    }
+
+    /// Get the payload word index and bit shift of sample `n` of `channel`.
+    ///
+    /// Samples of all channels are interleaved, so sample `n` of `channel` is sample `n * channels + channel` of the
+    /// payload, with the oldest samples in the least significant bits of each word. For complex data this is the
+    /// position of the real component. With [`ComplexPacking::TruncateToPairs`] the imaginary component follows it in
+    /// the next `bits_per_sample` bits, but in general use [`component_index`](FrameLayout::component_index).
+    pub fn sample_index(&self, channel: usize, n: usize) -> (usize, u32) {
+        if !self.is_real && self.complex_packing == ComplexPacking::KeepExtraReal {
+            return self.component_index(channel, n, false);
+        }
+        assert!(channel < self.channels, "Channel is out of range");
+        assert!(n < self.samples_per_channel(), "Sample is out of range");
+        let components = if self.is_real { 1 } else { 2 };
+        let (word, slot) = if self.words_per_sample == 1 {
+            let complete_per_word = self.samples_per_word / self.channels;
+            (
+                n / complete_per_word,
+                (n % complete_per_word) * self.channels + channel,
+            )
+        } else {
+            (
+                n * self.words_per_sample + channel / self.samples_per_word,
+                channel % self.samples_per_word,
+            )
+        };
+        return (word, slot as u32 * self.bits_per_sample * components);
+    }
+
+    /// Get the payload word index and bit shift of the real (`imag == false`) or imaginary component of sample `n` of
+    /// `channel`. For real data, use [`sample_index`](FrameLayout::sample_index).
+    pub fn component_index(&self, channel: usize, n: usize, imag: bool) -> (usize, u32) {
+        if self.complex_packing == ComplexPacking::TruncateToPairs {
+            let (word, shift) = self.sample_index(channel, n);
+            return (word, shift + imag as u32 * self.bits_per_sample);
+        }
+        assert!(channel < self.channels, "Channel is out of range");
+        assert!(n < self.samples_per_channel(), "Sample is out of range");
+        let per_word = 32 / self.bits_per_sample as usize;
+        let k = (n * self.channel_stride + channel) * 2 + imag as usize;
+        return (k / per_word, (k % per_word) as u32 * self.bits_per_sample);
+    }
+
+    /// Get the number of payload words holding samples. Any words after these, left over when a complete sample spans
+    /// several words, are unused.
+    pub fn used_words(&self) -> usize {
+        if self.words_per_sample == 1 {
+            return self.payload_words;
+        }
+        return self.samples_per_channel() * self.words_per_sample;
+    }
+
+    /// Get the number of samples held in payload word `word`. This is
+    /// [`samples_per_word`](FrameLayout::samples_per_word) except in the last word of a complete sample spanning
+    /// several words, which may be partly filled.
+    pub fn samples_in_word(&self, word: usize) -> usize {
+        if self.words_per_sample == 1 {
+            return self.samples_per_word;
+        }
+        let first = (word % self.words_per_sample) * self.samples_per_word;
+        return self.samples_per_word.min(self.channels - first);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_packing() {
+        // 2-bit, 4 channels: 4 complete samples fill each word exactly
+        let layout = FrameLayout::new(2, true, 4, 40).unwrap();
+        assert_eq!((layout.samples_per_word, layout.words_per_sample), (16, 1));
+        assert_eq!(layout.samples_per_channel(), 8);
+
+        // 6-bit, 2 channels: two 12-bit complete samples per word, leaving the top 8 bits unused
+        let layout = FrameLayout::new(6, true, 2, 40).unwrap();
+        assert_eq!((layout.samples_per_word, layout.words_per_sample), (4, 1));
+        assert_eq!(layout.sample_index(1, 1), (0, 18));
+        assert_eq!(layout.sample_index(0, 2), (1, 0));
+
+        // 3-bit, 16 channels: each 48-bit complete sample spans two words, of 10 and 6 samples
+        let layout = FrameLayout::new(3, true, 16, 48).unwrap();
+        assert_eq!((layout.samples_per_word, layout.words_per_sample), (10, 2));
+        assert_eq!(layout.samples_per_channel(), 2);
+        assert_eq!(layout.sample_index(9, 0), (0, 27));
+        assert_eq!(layout.sample_index(10, 0), (1, 0));
+        assert_eq!(layout.sample_index(15, 1), (3, 15));
+        assert_eq!(layout.samples_in_word(1), 6);
+
+        // 4-bit complex, 2 channels: two complete samples per word
+        let layout = FrameLayout::new(4, false, 2, 40).unwrap();
+        assert_eq!(layout.sample_index(1, 1), (0, 24));
+
+        // Values straight from a corrupt header are rejected rather than panicking
+        assert_eq!(FrameLayout::new(0, true, 1, 40), None);
+        assert_eq!(FrameLayout::new(32, false, 1, 40), None);
+        assert_eq!(FrameLayout::new(2, true, 1, 8), None);
+        let header = VDIFHeader {
+            size: 1,
+            bits_per_sample: 31,
+            ..Default::default()
+        };
+        assert_eq!(FrameLayout::from_header(&header), None);
+    }
+
+    #[test]
+    fn test_complex_packing() {
+        // 6-bit complex: five components fit in a word
+        let layout = FrameLayout::new(6, false, 1, 40).unwrap();
+        assert_eq!(layout.samples_per_channel(), 4);
+        assert_eq!(layout.component_index(0, 1, true), (0, 18));
+
+        let layout = layout.with_complex_packing(ComplexPacking::KeepExtraReal);
+        assert_eq!(layout.samples_per_channel(), 5);
+        assert_eq!(layout.component_index(0, 2, false), (0, 24));
+        assert_eq!(layout.component_index(0, 2, true), (1, 0));
+        assert_eq!(layout.component_index(0, 4, true), (1, 24));
+    }
+}
@@ -1,4 +1,13 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![warn(missing_docs)]
+// The crate deliberately favours explicit returns and field names, so silence the lints that disagree.
+#![allow(
+    clippy::needless_return,
+    clippy::redundant_field_names,
+    clippy::assign_op_pattern,
+    clippy::manual_is_multiple_of,
+    clippy::len_without_is_empty
+)]
 
 //! A rust crate for interacting with data encoded in the VLBI Data Interchange Format (VDIF), commonly used in
 //! radio astronomy experiments. The VDIF data format is defined in the VDIF specification,
@@ -16,8 +25,10 @@
 //!
 //! # Usage
 //!
-//! Reading from VDIF files is quite simple:
+//! The core types and traits are available through the [`prelude`]. Reading from VDIF files is quite simple:
 //! ```rust,ignore
+//! use rustvdif::prelude::*;
+//!
 //! fn main() {
 //!     // A file of 8032 byte VDIF frames
 //!     let mut file = VDIFReader::open("path/to/my/vdif", 8032).unwrap();
@@ -35,6 +46,7 @@
 //! instead of a [`File`](std::fs::File):
 //! ```rust,ignore
 //! use std::net::TcpStream;
+//! use rustvdif::prelude::*;
 //!
 //! fn main() {
 //!     // Connect to a TCP stream of VDIF frames
@@ -54,19 +66,134 @@
 //! [`VDIFReader`](crate::io::VDIFReader)s, to minimise expensive system calls.
 //!
 //! In general, this library uses byte sizes for the frame size (header *and* payload), and assumes you know the size
-//! of the incoming/outgoing VDIF frames in advance.
+//! of the incoming/outgoing VDIF frames in advance. Common frame sizes, and the largest frame size for a given network
+//! MTU, are available in [`consts`].
+//!
+//! # Features
+//!
+//! The frame, header and payload encoding types make up the core of the crate and are always available. Everything
+//! else sits in a layer behind a feature flag, so embedded users can depend only on the core:
+//!
+//! - `std` (default): the standard library. Without it the core is `no_std`, needing only `alloc`, so the same
+//!   framing code can run on embedded packetisers. Every other layer implies `std`, and a few `std`-only items of the
+//!   core, such as [`VDIFFrame::write_to`](crate::frame::VDIFFrame::write_to), are left out without it.
+//! - `io` (default): the [`VDIFRead`](crate::io::VDIFRead)/[`VDIFWrite`](crate::io::VDIFWrite) traits, readers,
+//!   writers and `std` channel adapters.
+//! - `net` (default): sending and receiving frames over UDP, including VTP. Implies `io`.
+//! - `utils` (default): simulation, redaction, filtering and file manipulation tools. Implies `io`.
+//! - `async`: `tokio-util` codecs and async `tokio` readers, writers and UDP sockets in `net`. Implies `net`.
+//! - `compress`: experimental per-frame payload compression, with LZ4 and bitshuffle, for UDP in `net`. Implies `net`.
+//! - `control`: a minimal HTTP/JSON control plane in `net`. Implies `net`.
+//! - `ptp`: reading PTP hardware clocks through [`Clock`](crate::utils::clock::Clock) on Linux. Implies `utils`.
+//! - `shm`: a frame ring in shared memory for exchanging frames between processes on Linux. Implies `utils`.
+//! - `busy-poll`: batched, non-blocking receives with `recvmmsg` and `SO_BUSY_POLL` for UDP on Linux. Implies `net`.
+//! - `io-uring`: a UDP receiver built on `io_uring` with pre-registered buffers, in `utils`, on Linux. Implies `utils`.
+//! - `mio`: [`mio`](https://docs.rs/mio) event sources for the UDP and VTP receivers in `net`, on Unix. Implies `net`.
+//! - `crc32c`: sampled payload checksums for checking the integrity of network links, in `net`. Implies `net`.
+//! - `crossbeam`, `flume`, `tokio`: [`VDIFRead`](crate::io::VDIFRead)/[`VDIFWrite`](crate::io::VDIFWrite) for the
+//!   channels of these crates. Each implies `io`.
+//! - `mmap`: a memory-mapped file reader giving borrowed views of frames, in [`mmap`](crate::mmap). Implies `io`.
+//! - `serde`: `Serialize`/`Deserialize` for [`VDIFHeader`](crate::header::VDIFHeader) and
+//!   [`FrameMeta`](crate::header::FrameMeta).
+//! - `nom`: [`nom`](https://docs.rs/nom) parsers for headers and frames. The core's hand-rolled parsers cover the same
+//!   ground without the dependency.
 
+extern crate alloc;
+
+pub mod block;
+#[cfg(feature = "io")]
+pub mod channel;
+pub mod consts;
 pub mod data_encoding;
+pub mod decoding;
+pub mod edv;
+pub mod encoding;
+pub mod error;
 pub mod frame;
 pub mod header;
 pub mod header_encoding;
+#[cfg(feature = "io")]
+pub mod index;
+#[cfg(feature = "io")]
 pub mod io;
-pub mod sim;
-pub mod udp;
-pub mod vtp;
+#[cfg(any(feature = "control", feature = "utils"))]
+mod json;
+pub mod layout;
+pub mod mark5b;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "net")]
+pub mod net;
+#[cfg(feature = "nom")]
+pub mod parsers;
+pub mod prelude;
+#[cfg(feature = "io")]
+pub mod provenance;
+pub mod regions;
+pub mod spec;
+pub mod station;
+pub mod stats;
+#[cfg(all(test, feature = "io"))]
+mod testing;
+pub mod time;
+pub mod unpack;
+#[cfg(feature = "utils")]
+pub mod utils;
+pub mod validation;
+
+// Kept for compatibility with older versions of this crate. New code should use the prelude, and the modules
+// under `net` and `utils`, instead. Deprecating a re-export has no effect, so the types are aliased instead, leaving
+// only the traits hidden.
+/// Moved to [`VDIFFrame`](prelude::VDIFFrame).
+#[deprecated(note = "use `prelude::VDIFFrame` instead")]
+pub type VDIFFrame<S = alloc::boxed::Box<[u32]>> = frame::VDIFFrame<S>;
+#[cfg(feature = "io")]
+#[doc(hidden)]
+pub use io::{VDIFRead, VDIFWrite};
+/// Moved to [`VDIFReader`](prelude::VDIFReader).
+#[cfg(feature = "io")]
+#[deprecated(note = "use `prelude::VDIFReader` instead")]
+pub type VDIFReader<T> = io::VDIFReader<T>;
+/// Moved to [`VDIFWriter`](prelude::VDIFWriter).
+#[cfg(feature = "io")]
+#[deprecated(note = "use `prelude::VDIFWriter` instead")]
+pub type VDIFWriter<T> = io::VDIFWriter<T>;
+
+/// Moved to [`net::udp`].
+#[cfg(feature = "net")]
+#[deprecated(note = "use `net::udp` instead")]
+pub mod udp {
+    /// Moved to [`net::udp::RecvMode`].
+    #[deprecated(note = "use `net::udp::RecvMode` instead")]
+    pub type RecvMode = crate::net::udp::RecvMode;
+    /// Moved to [`net::udp::VDIFUDP`].
+    #[deprecated(note = "use `net::udp::VDIFUDP` instead")]
+    pub type VDIFUDP = crate::net::udp::VDIFUDP;
+    /// Moved to [`net::udp::VDIFOrderedUDP`].
+    #[deprecated(note = "use `net::udp::VDIFOrderedUDP` instead")]
+    pub type VDIFOrderedUDP = crate::net::udp::VDIFOrderedUDP;
+}
+
+/// Moved to [`net::vtp`].
+#[cfg(feature = "net")]
+#[deprecated(note = "use `net::vtp` instead")]
+pub mod vtp {
+    /// Moved to [`net::vtp::VDIFVTP`].
+    #[deprecated(note = "use `net::vtp::VDIFVTP` instead")]
+    pub type VDIFVTP = crate::net::vtp::VDIFVTP;
+    /// Moved to [`net::vtp::VDIFOrderedVTP`].
+    #[deprecated(note = "use `net::vtp::VDIFOrderedVTP` instead")]
+    pub type VDIFOrderedVTP = crate::net::vtp::VDIFOrderedVTP;
+}
 
-pub use frame::VDIFFrame;
-pub use io::{VDIFRead, VDIFReader, VDIFWrite, VDIFWriter};
+/// Moved to [`utils::sim`].
+#[cfg(feature = "utils")]
+#[deprecated(note = "use `utils::sim` instead")]
+pub mod sim {
+    /// Moved to [`utils::sim::VDIFSim`].
+    #[deprecated(note = "use `utils::sim::VDIFSim` instead")]
+    pub type VDIFSim = crate::utils::sim::VDIFSim;
+}
 
 // VDIF is an explicitly little endian format. This makes handling it finnicky on big endian targets. A lot of the unsafe
 // operations rely on being run on a little endian target and are faster as a result. If a user needs big-endian
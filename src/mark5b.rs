@@ -0,0 +1,388 @@
+//! Implements reading, writing and conversion of Mark5B frames, the format of older Mark5B recorders.
+//!
+//! A Mark5B frame is a 16 byte header followed by a 10000 byte payload, with the time given as a truncated Modified
+//! Julian Date and seconds of the day in BCD. Mark5B headers don't describe the data, so converting a frame to VDIF
+//! takes the channels, bits/sample and frame rate from a [`StreamSpec`], and the thousands of the MJD from a nearby
+//! reference date. The payload is copied unchanged, so the recorder's bit-stream mask should place the channels in
+//! VDIF order, as is usual for geodetic modes.
+//!
+//! Converted frames are 10032 byte VDIF frames with the same frame number, so the frame rate carries over.
+//! [`Mark5BReader`] and [`Mark5BWriter`] convert on the fly, so Mark5B recordings can be read and written wherever
+//! VDIF is expected.
+
+use alloc::boxed::Box;
+#[cfg(feature = "io")]
+use std::fs::File;
+#[cfg(feature = "io")]
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Result, Write};
+#[cfg(feature = "io")]
+use std::path::Path;
+
+use chrono::DateTime;
+
+use crate::consts::HEADER_SIZE;
+use crate::frame::VDIFFrame;
+use crate::header::VDIFHeader;
+#[cfg(feature = "io")]
+use crate::io::{VDIFRead, VDIFWrite};
+use crate::spec::StreamSpec;
+use crate::time::MJD_UNIX_EPOCH;
+
+/// The sync word starting every Mark5B frame.
+pub const MARK5B_SYNC_WORD: u32 = 0xABADDEED;
+/// The size in bytes of a Mark5B header.
+pub const MARK5B_HEADER_SIZE: usize = 16;
+/// The size in bytes of a Mark5B payload.
+pub const MARK5B_PAYLOAD_SIZE: usize = 10000;
+/// The size in bytes of a Mark5B frame.
+pub const MARK5B_FRAME_SIZE: usize = MARK5B_HEADER_SIZE + MARK5B_PAYLOAD_SIZE;
+/// The size in bytes of a VDIF frame converted from Mark5B.
+pub const MARK5B_VDIF_FRAME_SIZE: usize = HEADER_SIZE + MARK5B_PAYLOAD_SIZE;
+
+const MARK5B_FRAME_WORDS: usize = MARK5B_FRAME_SIZE / 4;
+
+/// A decoded Mark5B header.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Mark5BHeader {
+    /// The user-specified field.
+    pub user: u16,
+    /// Whether the payload holds test vector generator data.
+    pub is_tvg: bool,
+    /// The frame number within the second, 15 bits.
+    pub frameno: u16,
+    /// The last three digits of the Modified Julian Date.
+    pub mjd: u16,
+    /// The seconds since the start of the day.
+    pub seconds: u32,
+    /// The fraction of the second in units of 0.1 ms.
+    pub fraction: u16,
+    /// The CRC of the time fields, as recorded. Not checked when decoding.
+    pub crc: u16,
+}
+
+impl Mark5BHeader {
+    /// Decode a header from the first four words of a Mark5B frame, returning [`None`] if the sync word is missing.
+    pub fn decode(words: [u32; 4]) -> Option<Self> {
+        if words[0] != MARK5B_SYNC_WORD {
+            return None;
+        }
+        return Some(Self {
+            user: (words[1] >> 16) as u16,
+            is_tvg: words[1] & 0x8000 != 0,
+            frameno: (words[1] & 0x7FFF) as u16,
+            mjd: from_bcd(words[2] >> 20) as u16,
+            seconds: from_bcd(words[2] & 0xFFFFF),
+            fraction: from_bcd(words[3] >> 16) as u16,
+            crc: (words[3] & 0xFFFF) as u16,
+        });
+    }
+
+    /// Encode this header as four words, calculating its CRC.
+    pub fn encode(&self) -> [u32; 4] {
+        let w1 = ((self.user as u32) << 16)
+            | ((self.is_tvg as u32) << 15)
+            | (self.frameno as u32 & 0x7FFF);
+        let w2 = (to_bcd(self.mjd as u32 % 1000) << 20) | to_bcd(self.seconds % 86400);
+        let time = to_bcd(self.fraction as u32 % 10000) << 16;
+        return [MARK5B_SYNC_WORD, w1, w2, time | time_crc(w2, time) as u32];
+    }
+
+    /// Get the full Modified Julian Date, taking the thousands from whichever is closest to `reference_mjd`.
+    pub fn full_mjd(&self, reference_mjd: i64) -> i64 {
+        let candidate = reference_mjd - reference_mjd.rem_euclid(1000) + self.mjd as i64;
+        return [candidate - 1000, candidate, candidate + 1000]
+            .into_iter()
+            .min_by_key(|mjd| (mjd - reference_mjd).abs())
+            .unwrap();
+    }
+}
+
+/// Convert the Mark5B frame `words` to a VDIF frame, with the channels, bits/sample, station and frame rate of `spec`
+/// and the first of its threads. The thousands of the MJD are taken from whichever is closest to `reference_mjd`.
+///
+/// Returns [`None`] if `words` is not a whole Mark5B frame starting with the sync word, the frame rate of `spec` is
+/// zero, or its channel count isn't a power of two that VDIF can describe.
+pub fn to_vdif(words: &[u32], spec: &StreamSpec, reference_mjd: i64) -> Option<VDIFFrame> {
+    if words.len() != MARK5B_FRAME_WORDS || !valid_channels(spec.channels) {
+        return None;
+    }
+    let mark5b = Mark5BHeader::decode(words[0..4].try_into().unwrap())?;
+    let unix = (mark5b.full_mjd(reference_mjd) - MJD_UNIX_EPOCH) * 86400 + mark5b.seconds as i64;
+
+    let mut header = VDIFHeader {
+        is_valid: true,
+        size: (MARK5B_VDIF_FRAME_SIZE / 8) as u32,
+        channels: spec.channels.trailing_zeros() as u8,
+        bits_per_sample: spec.bits_per_sample.checked_sub(1)?,
+        is_real: spec.is_real,
+        thread: spec.threads.first().copied().unwrap_or(0),
+        station: spec.station,
+        ..Default::default()
+    };
+    header.set_timestamp(DateTime::from_timestamp(unix, 0)?, spec.frame_rate)?;
+    header.frameno = mark5b.frameno as u32;
+
+    let mut frame = VDIFFrame::from_header(header);
+    frame.get_mut_payload().copy_from_slice(&words[4..]);
+    return Some(frame);
+}
+
+/// Convert `frame` to a Mark5B frame, given `frame_rate` frames per second. The user field is set to the VDIF thread.
+///
+/// Returns [`None`] if the payload of `frame` is not the size of a Mark5B payload, or `frame_rate` is zero.
+pub fn from_vdif(frame: &VDIFFrame, frame_rate: u32) -> Option<Box<[u32]>> {
+    let payload = frame.get_payload();
+    if payload.len() * 4 != MARK5B_PAYLOAD_SIZE || frame_rate == 0 {
+        return None;
+    }
+    let header = frame.get_header();
+    let unix = header.epoch_seconds_to_unix();
+    let mark5b = Mark5BHeader {
+        user: header.thread,
+        is_tvg: false,
+        frameno: header.frameno as u16,
+        mjd: ((unix.div_euclid(86400) + MJD_UNIX_EPOCH) % 1000) as u16,
+        seconds: unix.rem_euclid(86400) as u32,
+        fraction: (header.frameno as u64 * 10000 / frame_rate as u64) as u16,
+        crc: 0,
+    };
+
+    let mut words: Box<[u32]> = Box::from([0u32; MARK5B_FRAME_WORDS]);
+    words[0..4].copy_from_slice(&mark5b.encode());
+    words[4..].copy_from_slice(payload);
+    return Some(words);
+}
+
+// Whether `channels` can be given by the log2 channel count of a VDIF header.
+fn valid_channels(channels: usize) -> bool {
+    return channels.is_power_of_two() && channels.trailing_zeros() < 32;
+}
+
+// Decode the BCD digits of `bcd`.
+fn from_bcd(mut bcd: u32) -> u32 {
+    let (mut value, mut scale) = (0, 1);
+    while bcd > 0 {
+        value += (bcd & 0xF) * scale;
+        bcd >>= 4;
+        scale *= 10;
+    }
+    return value;
+}
+
+// Encode `value` as BCD digits.
+fn to_bcd(mut value: u32) -> u32 {
+    let (mut bcd, mut shift) = (0, 0);
+    while value > 0 {
+        bcd |= (value % 10) << shift;
+        value /= 10;
+        shift += 4;
+    }
+    return bcd;
+}
+
+// The CRC-16 of the 48 time bits of a header, word 2 and the top of word 3, as calculated by Mark5B recorders.
+fn time_crc(w2: u32, w3: u32) -> u16 {
+    let bits = ((w2 as u64) << 16) | (w3 >> 16) as u64;
+    let mut state: u32 = 0;
+    for i in (0..48).rev() {
+        let bit = ((bits >> i) & 1) as u32;
+        if bit ^ (state & 1) == 0 {
+            state &= !1;
+        } else {
+            state ^= 0o40003;
+            state |= 1;
+        }
+        state = (state >> 1) | ((state & 1) << 15);
+    }
+    return state as u16;
+}
+
+/// Reads Mark5B frames from any source implementing [`Read`], converting each to VDIF with [`to_vdif`].
+#[cfg(feature = "io")]
+pub struct Mark5BReader<T: Read> {
+    inner: BufReader<T>,
+    spec: StreamSpec,
+    reference_mjd: i64,
+}
+
+#[cfg(feature = "io")]
+impl<T: Read> Mark5BReader<T> {
+    /// Construct a new [`Mark5BReader`] over `inner`. See [`to_vdif`] for the meaning of `spec` and `reference_mjd`.
+    pub fn new(inner: T, spec: StreamSpec, reference_mjd: i64) -> Self {
+        return Self {
+            inner: BufReader::with_capacity(10 * MARK5B_FRAME_SIZE, inner),
+            spec: spec,
+            reference_mjd: reference_mjd,
+        };
+    }
+
+    /// Read the next Mark5B frame unconverted, as its words. Returns an error of kind
+    /// [`InvalidData`](ErrorKind::InvalidData) if it doesn't start with the sync word.
+    pub fn read_mark5b_frame(&mut self) -> Result<Box<[u32]>> {
+        let mut bytes = [0u8; MARK5B_FRAME_SIZE];
+        self.inner.read_exact(&mut bytes)?;
+        let words: Box<[u32]> = bytes
+            .chunks_exact(4)
+            .map(|w| u32::from_le_bytes(w.try_into().unwrap()))
+            .collect();
+        if words[0] != MARK5B_SYNC_WORD {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Mark5B frame does not start with the sync word",
+            ));
+        }
+        return Ok(words);
+    }
+}
+
+#[cfg(feature = "io")]
+impl Mark5BReader<File> {
+    /// Open the Mark5B file at `path`.
+    pub fn open<P: AsRef<Path>>(path: P, spec: StreamSpec, reference_mjd: i64) -> Result<Self> {
+        return Ok(Self::new(File::open(path)?, spec, reference_mjd));
+    }
+}
+
+#[cfg(feature = "io")]
+impl<T: Read> VDIFRead for Mark5BReader<T> {
+    /// Read the next Mark5B frame and convert it to VDIF. Returns an error of kind
+    /// [`InvalidInput`](ErrorKind::InvalidInput) if the frame rate of the reader's spec is zero, or its channel count
+    /// isn't a power of two.
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        if self.spec.frame_rate == 0 {
+            return Err(zero_frame_rate());
+        }
+        if !valid_channels(self.spec.channels) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Channel count must be a power of two",
+            ));
+        }
+        let words = self.read_mark5b_frame()?;
+        return to_vdif(&words, &self.spec, self.reference_mjd).ok_or(Error::new(
+            ErrorKind::InvalidData,
+            "Mark5B frame time is out of range",
+        ));
+    }
+}
+
+/// Writes VDIF frames to any destination implementing [`Write`] as Mark5B frames, converting each with
+/// [`from_vdif`].
+#[cfg(feature = "io")]
+pub struct Mark5BWriter<T: Write> {
+    inner: BufWriter<T>,
+    frame_rate: u32,
+}
+
+#[cfg(feature = "io")]
+impl<T: Write> Mark5BWriter<T> {
+    /// Construct a new [`Mark5BWriter`] over `inner`, for frames at `frame_rate` frames per second.
+    pub fn new(inner: T, frame_rate: u32) -> Self {
+        return Self {
+            inner: BufWriter::with_capacity(10 * MARK5B_FRAME_SIZE, inner),
+            frame_rate: frame_rate,
+        };
+    }
+}
+
+#[cfg(feature = "io")]
+impl Mark5BWriter<File> {
+    /// Create a new Mark5B file at `path`.
+    pub fn create<P: AsRef<Path>>(path: P, frame_rate: u32) -> Result<Self> {
+        return Ok(Self::new(File::create(path)?, frame_rate));
+    }
+}
+
+#[cfg(feature = "io")]
+impl<T: Write> VDIFWrite for Mark5BWriter<T> {
+    /// Write `frame` as a Mark5B frame. Returns an error of kind [`InvalidInput`](ErrorKind::InvalidInput) if its
+    /// payload isn't the size of a Mark5B payload, or the writer's frame rate is zero.
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        if self.frame_rate == 0 {
+            return Err(zero_frame_rate());
+        }
+        let words = from_vdif(&frame, self.frame_rate).ok_or(Error::new(
+            ErrorKind::InvalidInput,
+            "Only frames with a 10000 byte payload can be written as Mark5B",
+        ))?;
+        for word in words.iter() {
+            self.inner.write_all(&word.to_le_bytes())?;
+        }
+        return Ok(());
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        return self.inner.flush();
+    }
+}
+
+#[cfg(feature = "io")]
+fn zero_frame_rate() -> Error {
+    return Error::new(ErrorKind::InvalidInput, "Frame rate must be positive");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mark5b() {
+        let header = Mark5BHeader {
+            user: 0xBEEF,
+            is_tvg: false,
+            frameno: 1234,
+            mjd: 123,
+            seconds: 45296,
+            fraction: 4936,
+            crc: 0,
+        };
+        let words = header.encode();
+        assert_eq!(&words[0..3], &[MARK5B_SYNC_WORD, 0xBEEF04D2, 0x12345296]);
+        assert_eq!(words[3] >> 16, 0x4936);
+        // CRC-16/UMTS of the bytes 12 34 52 96 49 36 is 0xACE0, which Mark5B recorders store bit-reversed
+        assert_eq!(words[3] & 0xFFFF, 0x0735);
+        assert_eq!(
+            Mark5BHeader::decode(words),
+            Some(Mark5BHeader {
+                crc: (words[3] & 0xFFFF) as u16,
+                ..header
+            })
+        );
+        assert_eq!(header.full_mjd(60990), 61123);
+        assert_eq!(header.full_mjd(61500), 61123);
+
+        // 2500 frames per second, i.e. 200 Mbit/s
+        let mut mark5b = vec![0u32; MARK5B_FRAME_WORDS];
+        mark5b[0..4].copy_from_slice(&words);
+        mark5b[4] = 7;
+        let mut spec = StreamSpec::from_header(&VDIFHeader::default(), 2500);
+        spec.channels = 16;
+        spec.bits_per_sample = 2;
+        spec.threads = vec![3];
+
+        let frame = to_vdif(&mark5b, &spec, 61000).unwrap();
+        let vdif = frame.get_header();
+        assert_eq!(frame.bytesize(), MARK5B_VDIF_FRAME_SIZE);
+        assert_eq!((vdif.frameno, vdif.thread, vdif.channelno()), (1234, 3, 16));
+        assert_eq!(
+            vdif.epoch_seconds_to_unix(),
+            (61123 - MJD_UNIX_EPOCH) * 86400 + 45296
+        );
+        assert_eq!(frame.get_data_word(0), 7);
+
+        let back = from_vdif(&frame, 2500).unwrap();
+        assert_eq!(&back[2..], &mark5b[2..]);
+        assert_eq!(
+            Mark5BHeader::decode(back[0..4].try_into().unwrap())
+                .unwrap()
+                .user,
+            3
+        );
+        assert!(from_vdif(&frame, 0).is_none());
+        spec.channels = 12;
+        assert!(to_vdif(&mark5b, &spec, 61000).is_none());
+        spec.channels = 16;
+        spec.frame_rate = 0;
+        assert!(to_vdif(&mark5b, &spec, 61000).is_none());
+    }
+}
@@ -0,0 +1,155 @@
+//! Implements [`MmapReader`], a memory-mapped VDIF file giving borrowed views of its frames without copying them.
+//!
+//! Requires the `mmap` feature.
+
+use std::fs::File;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::consts::HEADER_SIZE;
+use crate::error::VDIFError;
+use crate::frame::VDIFFrame;
+use crate::header::VDIFHeader;
+use crate::header_encoding::{decode_header, header_words};
+use crate::io::VDIFRead;
+
+/// A VDIF file of fixed-size frames mapped into memory.
+///
+/// Frames are accessed as borrowed `u32` slices straight out of the mapping, in order or by index, so repeated scans
+/// of a large recording cost no copies and no system calls once the pages are cached. Any partial frame at the end of
+/// the file is ignored.
+///
+/// [`VDIFRead`] is also implemented, copying each frame out in order, for use with the rest of the crate.
+pub struct MmapReader {
+    map: Mmap,
+    frame_size: usize,
+    len: usize,
+    position: usize,
+}
+
+impl MmapReader {
+    /// Map the VDIF file at `path`, holding frames of `frame_size` bytes.
+    ///
+    /// Returns an error of kind [`InvalidInput`](ErrorKind::InvalidInput) if `frame_size` is smaller than a header or
+    /// not a multiple of 8 bytes.
+    pub fn open<P: AsRef<Path>>(path: P, frame_size: usize) -> Result<Self> {
+        if frame_size < HEADER_SIZE || frame_size % 8 != 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                VDIFError::BadFrameSize(frame_size),
+            ));
+        }
+        let file = File::open(path)?;
+        // The mapping is only ever read. As with any mapped file, changes made to the file by other processes while
+        // it is mapped are visible through it.
+        let map = unsafe { Mmap::map(&file)? };
+        return Ok(Self {
+            len: map.len() / frame_size,
+            map: map,
+            frame_size: frame_size,
+            position: 0,
+        });
+    }
+
+    /// Get the number of whole frames in the file.
+    pub fn len(&self) -> usize {
+        return self.len;
+    }
+
+    /// Returns `true` if the file holds no whole frames.
+    pub fn is_empty(&self) -> bool {
+        return self.len == 0;
+    }
+
+    /// Get the size in bytes of each frame.
+    pub fn frame_size(&self) -> usize {
+        return self.frame_size;
+    }
+
+    /// Get a view of frame `i`, including its header. Panics if `i` is out of range.
+    pub fn frame(&self, i: usize) -> &[u32] {
+        assert!(i < self.len, "Frame index is out of range");
+        let bytes = &self.map[i * self.frame_size..(i + 1) * self.frame_size];
+        // Mappings are page aligned and frames are a multiple of 8 bytes, so every frame is aligned for u32s
+        return unsafe {
+            std::slice::from_raw_parts(bytes.as_ptr() as *const u32, self.frame_size / 4)
+        };
+    }
+
+    /// Get the header of frame `i`. Panics if `i` is out of range.
+    pub fn header(&self, i: usize) -> VDIFHeader {
+        return decode_header(self.frame(i)[0..8].try_into().unwrap());
+    }
+
+    /// Get a view of the payload of frame `i`. Panics if `i` is out of range.
+    pub fn payload(&self, i: usize) -> &[u32] {
+        let frame = self.frame(i);
+        return &frame[header_words(frame)..];
+    }
+
+    /// Iterate over views of every frame in the file.
+    pub fn iter(&self) -> impl Iterator<Item = &[u32]> + '_ {
+        return (0..self.len).map(|i| self.frame(i));
+    }
+
+    /// Get the index of the next frame [`read_frame`](VDIFRead::read_frame) returns.
+    pub fn position(&self) -> usize {
+        return self.position;
+    }
+
+    /// Set the index of the next frame [`read_frame`](VDIFRead::read_frame) returns.
+    pub fn set_position(&mut self, position: usize) {
+        self.position = position;
+    }
+}
+
+impl VDIFRead for MmapReader {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        if self.position >= self.len {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        let frame = VDIFFrame::from_slice(self.frame(self.position));
+        self.position += 1;
+        return Ok(frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_path;
+
+    #[test]
+    fn test_mmap_reader() {
+        let path = temp_path("mmap.vdif");
+        let mut contents = Vec::new();
+        for frameno in 0..3 {
+            let frame = VDIFFrame::from_header(VDIFHeader {
+                size: 8,
+                frameno: frameno,
+                ..Default::default()
+            });
+            contents.extend_from_slice(frame.as_bytes());
+        }
+        contents.extend_from_slice(&[0; 8]);
+        std::fs::write(&path, &contents).unwrap();
+
+        let mut reader = MmapReader::open(&path, 64).unwrap();
+        assert_eq!(reader.len(), 3);
+        assert_eq!(reader.header(2).frameno, 2);
+        assert_eq!(reader.payload(1).len(), 8);
+        assert_eq!(reader.iter().count(), 3);
+
+        reader.set_position(1);
+        assert_eq!(reader.read_frame().unwrap().get_header().frameno, 1);
+        reader.read_frame().unwrap();
+        assert!(reader.read_frame().is_err());
+        assert_eq!(
+            MmapReader::open(&path, 8).err().unwrap().kind(),
+            ErrorKind::InvalidInput
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,69 @@
+//! Types and methods for sending and receiving VDIF frames over the network.
+//!
+//! Requires the `net` feature (enabled by default).
+
+#[cfg(feature = "async")]
+pub mod codec;
+#[cfg(feature = "compress")]
+pub mod compress;
+#[cfg(feature = "control")]
+pub mod control;
+pub mod fragment;
+#[cfg(feature = "crc32c")]
+pub mod integrity;
+pub mod multicast;
+pub mod options;
+#[cfg(all(feature = "busy-poll", target_os = "linux"))]
+mod poll;
+pub mod stats;
+pub mod tcp;
+#[cfg(feature = "async")]
+pub mod tokio;
+pub mod udp;
+pub mod vtp;
+
+use std::io::{Error, ErrorKind, Result};
+
+use crate::consts::{HEADER_SIZE, MAX_UDP_PAYLOAD};
+use crate::error::VDIFError;
+
+/// The largest possible UDP payload, and so the largest datagram a receiver can be handed.
+pub(crate) const MAX_DATAGRAM_SIZE: usize = 65535;
+
+/// What a size-checking receive, such as [`VDIFUDP::recv_checked`](udp::VDIFUDP::recv_checked), got from the socket.
+#[derive(Debug)]
+pub enum RecvEvent<F> {
+    /// A frame of the configured frame size.
+    Frame(F),
+    /// A datagram carrying a frame whose size differs from the configured frame size.
+    FrameSizeChanged {
+        /// The configured frame size when the datagram arrived.
+        previous: usize,
+        /// The size of the received frame.
+        received: usize,
+        /// The received frame, if the receiver reconfigured itself to the new frame size. Otherwise it is discarded.
+        frame: Option<F>,
+    },
+}
+
+// Check frames of `size` bytes fit in a single datagram.
+pub(crate) fn check_fits_datagram(size: usize) -> Result<()> {
+    if size > MAX_UDP_PAYLOAD {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Datagrams of {} bytes exceed the largest UDP payload of {} bytes, use net::fragment to split frames across datagrams",
+                size, MAX_UDP_PAYLOAD
+            ),
+        ));
+    }
+    return Ok(());
+}
+
+// Check a received frame size could be a VDIF frame.
+pub(crate) fn check_datagram_frame_size(size: usize) -> Result<()> {
+    if size < HEADER_SIZE || size % 8 != 0 {
+        return Err(VDIFError::BadFrameSize(size).into());
+    }
+    return Ok(());
+}
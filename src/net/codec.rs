@@ -0,0 +1,140 @@
+//! Implements [`tokio_util::codec`] encoders and decoders for VDIF frames, so VDIF streams can be plugged into
+//! [`Framed`](tokio_util::codec::Framed) transports.
+//!
+//! Requires the `async` feature.
+
+use std::io::{Error, ErrorKind};
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::frame::VDIFFrame;
+
+/// A codec for a stream of VDIF frames of a fixed size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VDIFCodec {
+    frame_size: usize,
+}
+
+impl VDIFCodec {
+    /// Construct a new [`VDIFCodec`] for frames of `frame_size` bytes.
+    pub fn new(frame_size: usize) -> Self {
+        return Self {
+            frame_size: frame_size,
+        };
+    }
+}
+
+impl Decoder for VDIFCodec {
+    type Item = VDIFFrame;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.len() < self.frame_size {
+            src.reserve(self.frame_size - src.len());
+            return Ok(None);
+        }
+
+        let mut frame = VDIFFrame::empty(self.frame_size);
+        src.copy_to_slice(frame.as_mut_bytes());
+        return Ok(Some(frame));
+    }
+}
+
+impl Encoder<VDIFFrame> for VDIFCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: VDIFFrame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        check_frame_size(self.frame_size, &item)?;
+        dst.extend_from_slice(item.as_bytes());
+        return Ok(());
+    }
+}
+
+/// A codec for a stream of VDIF frames of a fixed size, each preceded by a VDIF Transport Protocol (VTP) `u64`
+/// sequence number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VTPCodec {
+    frame_size: usize,
+}
+
+impl VTPCodec {
+    /// Construct a new [`VTPCodec`]. Note that `frame_size` is still just the size of the VDIF frame in bytes.
+    pub fn new(frame_size: usize) -> Self {
+        return Self {
+            frame_size: frame_size,
+        };
+    }
+}
+
+impl Decoder for VTPCodec {
+    type Item = (u64, VDIFFrame);
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.len() < self.frame_size + 8 {
+            src.reserve(self.frame_size + 8 - src.len());
+            return Ok(None);
+        }
+
+        let sequence_number = src.get_u64_le();
+        let mut frame = VDIFFrame::empty(self.frame_size);
+        src.copy_to_slice(frame.as_mut_bytes());
+        return Ok(Some((sequence_number, frame)));
+    }
+}
+
+impl Encoder<(u64, VDIFFrame)> for VTPCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: (u64, VDIFFrame), dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let (sequence_number, frame) = item;
+        check_frame_size(self.frame_size, &frame)?;
+        dst.reserve(self.frame_size + 8);
+        dst.put_u64_le(sequence_number);
+        dst.extend_from_slice(frame.as_bytes());
+        return Ok(());
+    }
+}
+
+fn check_frame_size(frame_size: usize, frame: &VDIFFrame) -> Result<(), Error> {
+    if frame.bytesize() != frame_size {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "VDIF frames must be {} bytes in size for this codec",
+                frame_size
+            ),
+        ));
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::VDIFHeader;
+
+    #[test]
+    fn test_vtp_codec_roundtrip() {
+        let mut codec = VTPCodec::new(64);
+        let mut buf = BytesMut::new();
+
+        let mut frame = VDIFFrame::empty(64);
+        frame.set_header(VDIFHeader {
+            size: 8,
+            frameno: 7,
+            ..Default::default()
+        });
+        let expected = frame.as_slice().to_vec();
+        codec.encode((42, frame), &mut buf).unwrap();
+
+        let mut partial = buf.split_to(40);
+        assert!(codec.decode(&mut partial).unwrap().is_none());
+        partial.unsplit(buf);
+        let (seq, decoded) = codec.decode(&mut partial).unwrap().unwrap();
+        assert_eq!(seq, 42);
+        assert_eq!(decoded.as_slice(), expected.as_slice());
+        assert!(partial.is_empty());
+    }
+}
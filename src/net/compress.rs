@@ -0,0 +1,373 @@
+//! Implements experimental per-frame payload compression for sending VDIF frames over constrained links.
+//!
+//! Heavily quantised, low SNR data often compresses by 20-30%, which can matter on eVLBI links running close to
+//! capacity. Each datagram carries the uncompressed VDIF header, so it can still be inspected on the wire, followed by
+//! an 8 byte little endian extension header and the compressed payload:
+//!
+//! | Bytes | Type  | Contents                                                                  |
+//! |-------|-------|---------------------------------------------------------------------------|
+//! | 0-1   | `u16` | [`COMPRESSION_MAGIC`].                                                    |
+//! | 2     | `u8`  | The [`id`](PayloadCompressor::id) of the compressor.                      |
+//! | 3     | `u8`  | Flags. Bit 0 is set if the payload is compressed.                         |
+//! | 4-7   | `u32` | The length in bytes of the data following the extension header.          |
+//!
+//! Payloads that don't shrink are sent raw, so a frame never grows by more than the extension header. Compressors are
+//! pluggable through [`PayloadCompressor`], with LZ4, optionally preceded by a bitshuffle, provided as [`Lz4`].
+//!
+//! Requires the `compress` feature. The wire format is not part of any standard, so both ends must use this crate.
+
+use std::io::{Error, ErrorKind, Result};
+use std::net::{ToSocketAddrs, UdpSocket};
+
+use crate::consts::HEADER_SIZE;
+use crate::error::VDIFError;
+use crate::frame::VDIFFrame;
+use crate::io::{VDIFRead, VDIFWrite};
+use crate::net::{check_fits_datagram, MAX_DATAGRAM_SIZE};
+
+/// The size in bytes of the extension header following the VDIF header in each datagram.
+pub const EXTENSION_HEADER_SIZE: usize = 8;
+
+/// The magic number starting every extension header.
+pub const COMPRESSION_MAGIC: u16 = 0x5A56;
+
+const FLAG_COMPRESSED: u8 = 1;
+
+/// A method of compressing VDIF payloads.
+pub trait PayloadCompressor {
+    /// Get the identifier of this compressor, carried in each extension header so the receiver can check it matches.
+    fn id(&self) -> u8;
+
+    /// Compress `payload`, appending the result to `out`.
+    fn compress(&mut self, payload: &[u8], out: &mut Vec<u8>);
+
+    /// Decompress `data` into `out`, which is exactly the size of the original payload.
+    fn decompress(&mut self, data: &[u8], out: &mut [u8]) -> Result<()>;
+}
+
+/// LZ4 block compression, optionally preceded by a bitshuffle of the payload.
+///
+/// Bitshuffling groups the same bit of every byte together, which turns the few active bits of low bit depth samples
+/// into long runs LZ4 can exploit.
+#[derive(Debug, Default, Clone)]
+pub struct Lz4 {
+    bitshuffle: bool,
+    scratch: Vec<u8>,
+}
+
+impl Lz4 {
+    /// Construct a new [`Lz4`] compressor, bitshuffling payloads first if `bitshuffle` is `true`.
+    pub fn new(bitshuffle: bool) -> Self {
+        return Self {
+            bitshuffle: bitshuffle,
+            scratch: Vec::new(),
+        };
+    }
+}
+
+impl PayloadCompressor for Lz4 {
+    fn id(&self) -> u8 {
+        return if self.bitshuffle { 2 } else { 1 };
+    }
+
+    fn compress(&mut self, payload: &[u8], out: &mut Vec<u8>) {
+        let input = if self.bitshuffle {
+            self.scratch.resize(payload.len(), 0);
+            bitshuffle(payload, &mut self.scratch);
+            &self.scratch[..]
+        } else {
+            payload
+        };
+        let start = out.len();
+        out.resize(
+            start + lz4_flex::block::get_maximum_output_size(input.len()),
+            0,
+        );
+        let written = lz4_flex::block::compress_into(input, &mut out[start..])
+            .expect("Output is sized for the worst case");
+        out.truncate(start + written);
+    }
+
+    fn decompress(&mut self, data: &[u8], out: &mut [u8]) -> Result<()> {
+        let target = if self.bitshuffle {
+            self.scratch.resize(out.len(), 0);
+            &mut self.scratch[..]
+        } else {
+            &mut *out
+        };
+        let written = lz4_flex::block::decompress_into(data, target)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        if written != target.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Decompressed payload is the wrong size",
+            ));
+        }
+        if self.bitshuffle {
+            bitunshuffle(&self.scratch, out);
+        }
+        return Ok(());
+    }
+}
+
+/// Transpose the bits of `input` into `out`, so that bit `b` of every input byte is packed, in order, into the `b`th
+/// eighth of `out`. Both must be the same length, a multiple of 8 bytes.
+pub fn bitshuffle(input: &[u8], out: &mut [u8]) {
+    assert!(
+        input.len() == out.len() && input.len() % 8 == 0,
+        "Bitshuffled buffers must be the same multiple of 8 bytes in size"
+    );
+    let plane = input.len() / 8;
+    for (k, block) in input.chunks_exact(8).enumerate() {
+        let bits = transpose_bits(u64::from_le_bytes(block.try_into().unwrap())).to_le_bytes();
+        for b in 0..8 {
+            out[b * plane + k] = bits[b];
+        }
+    }
+}
+
+/// Reverse [`bitshuffle`].
+pub fn bitunshuffle(input: &[u8], out: &mut [u8]) {
+    assert!(
+        input.len() == out.len() && input.len() % 8 == 0,
+        "Bitshuffled buffers must be the same multiple of 8 bytes in size"
+    );
+    let plane = input.len() / 8;
+    for (k, block) in out.chunks_exact_mut(8).enumerate() {
+        let bits: [u8; 8] = core::array::from_fn(|b| input[b * plane + k]);
+        block.copy_from_slice(&transpose_bits(u64::from_le_bytes(bits)).to_le_bytes());
+    }
+}
+
+// Transpose the 8x8 bit matrix whose rows are the bytes of `x`, so bit `b` of byte `i` becomes bit `i` of byte `b`.
+fn transpose_bits(mut x: u64) -> u64 {
+    let t = (x ^ (x >> 7)) & 0x00AA00AA00AA00AA;
+    x ^= t ^ (t << 7);
+    let t = (x ^ (x >> 14)) & 0x0000CCCC0000CCCC;
+    x ^= t ^ (t << 14);
+    let t = (x ^ (x >> 28)) & 0x00000000F0F0F0F0;
+    x ^= t ^ (t << 28);
+    return x;
+}
+
+/// Encode `frame` as a datagram, compressing its payload with `compressor` unless that would make it larger.
+pub fn compress_frame<C: PayloadCompressor>(frame: &VDIFFrame, compressor: &mut C) -> Vec<u8> {
+    let bytes = frame.as_bytes();
+    let payload = &bytes[HEADER_SIZE..];
+    let mut datagram = Vec::with_capacity(bytes.len() + EXTENSION_HEADER_SIZE);
+    datagram.extend_from_slice(&bytes[0..HEADER_SIZE]);
+    datagram.extend_from_slice(&[0; EXTENSION_HEADER_SIZE]);
+    compressor.compress(payload, &mut datagram);
+
+    let mut flags = FLAG_COMPRESSED;
+    if datagram.len() - HEADER_SIZE - EXTENSION_HEADER_SIZE >= payload.len() {
+        datagram.truncate(HEADER_SIZE + EXTENSION_HEADER_SIZE);
+        datagram.extend_from_slice(payload);
+        flags = 0;
+    }
+    let length = (datagram.len() - HEADER_SIZE - EXTENSION_HEADER_SIZE) as u32;
+    let extension = &mut datagram[HEADER_SIZE..HEADER_SIZE + EXTENSION_HEADER_SIZE];
+    extension[0..2].copy_from_slice(&COMPRESSION_MAGIC.to_le_bytes());
+    extension[2] = compressor.id();
+    extension[3] = flags;
+    extension[4..8].copy_from_slice(&length.to_le_bytes());
+    return datagram;
+}
+
+/// Decode a datagram produced by [`compress_frame`] into a frame of `frame_size` bytes.
+///
+/// Returns an error of kind [`InvalidInput`](ErrorKind::InvalidInput) if `frame_size` isn't a valid VDIF frame size.
+pub fn decompress_frame<C: PayloadCompressor>(
+    datagram: &[u8],
+    frame_size: usize,
+    compressor: &mut C,
+) -> Result<VDIFFrame> {
+    check_frame_size(frame_size)?;
+    let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, msg.to_string());
+    if datagram.len() < HEADER_SIZE + EXTENSION_HEADER_SIZE {
+        return Err(invalid(
+            "Datagram is too short to hold a compressed VDIF frame",
+        ));
+    }
+    let extension = &datagram[HEADER_SIZE..HEADER_SIZE + EXTENSION_HEADER_SIZE];
+    if u16::from_le_bytes([extension[0], extension[1]]) != COMPRESSION_MAGIC {
+        return Err(invalid(
+            "Datagram does not start with a compression extension header",
+        ));
+    }
+    if extension[2] != compressor.id() {
+        return Err(invalid(
+            "Datagram was compressed with a different compressor",
+        ));
+    }
+    let data = &datagram[HEADER_SIZE + EXTENSION_HEADER_SIZE..];
+    if u32::from_le_bytes(extension[4..8].try_into().unwrap()) as usize != data.len() {
+        return Err(invalid(
+            "Datagram length does not match its extension header",
+        ));
+    }
+
+    let mut frame = VDIFFrame::empty(frame_size);
+    let bytes = frame.as_mut_bytes();
+    bytes[0..HEADER_SIZE].copy_from_slice(&datagram[0..HEADER_SIZE]);
+    if extension[3] & FLAG_COMPRESSED != 0 {
+        compressor.decompress(data, &mut bytes[HEADER_SIZE..])?;
+    } else if data.len() == frame_size - HEADER_SIZE {
+        bytes[HEADER_SIZE..].copy_from_slice(data);
+    } else {
+        return Err(invalid("Raw payload is the wrong size"));
+    }
+    return Ok(frame);
+}
+
+fn check_frame_size(frame_size: usize) -> Result<()> {
+    if frame_size < HEADER_SIZE || frame_size % 8 != 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            VDIFError::BadFrameSize(frame_size),
+        ));
+    }
+    return Ok(());
+}
+
+/// Sends and receives VDIF frames over UDP with compressed payloads, one frame per datagram.
+pub struct VDIFCompressedUDP<C: PayloadCompressor> {
+    /// The underlying [`UdpSocket`].
+    pub sock: UdpSocket,
+    frame_size: usize,
+    compressor: C,
+    scratch: Vec<u8>,
+    frame_bytes: u64,
+    wire_bytes: u64,
+}
+
+impl<C: PayloadCompressor> VDIFCompressedUDP<C> {
+    /// Construct a new [`VDIFCompressedUDP`] bound to `addr`, for frames of `frame_size` bytes compressed with
+    /// `compressor`.
+    pub fn new<A: ToSocketAddrs>(addr: A, frame_size: usize, compressor: C) -> Result<Self> {
+        check_frame_size(frame_size)?;
+        check_fits_datagram(frame_size + EXTENSION_HEADER_SIZE)?;
+        return Ok(Self {
+            sock: UdpSocket::bind(addr)?,
+            frame_size: frame_size,
+            compressor: compressor,
+            scratch: vec![0; MAX_DATAGRAM_SIZE],
+            frame_bytes: 0,
+            wire_bytes: 0,
+        });
+    }
+
+    /// Receive and decompress a [`VDIFFrame`].
+    pub fn recv_frame(&mut self) -> Result<VDIFFrame> {
+        let received = self.sock.recv(&mut self.scratch)?;
+        let frame = decompress_frame(
+            &self.scratch[0..received],
+            self.frame_size,
+            &mut self.compressor,
+        )?;
+        self.frame_bytes += self.frame_size as u64;
+        self.wire_bytes += received as u64;
+        return Ok(frame);
+    }
+
+    /// Compress and send a [`VDIFFrame`] to the connected peer.
+    pub fn send_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        let datagram = compress_frame(&frame, &mut self.compressor);
+        self.sock.send(&datagram)?;
+        self.frame_bytes += frame.bytesize() as u64;
+        self.wire_bytes += datagram.len() as u64;
+        return Ok(());
+    }
+
+    /// Get the ratio of bytes sent or received on the wire to the size of the frames they carried. Values below one
+    /// are a saving.
+    pub fn compression_ratio(&self) -> f64 {
+        if self.frame_bytes == 0 {
+            return 1.0;
+        }
+        return self.wire_bytes as f64 / self.frame_bytes as f64;
+    }
+}
+
+impl<C: PayloadCompressor> VDIFRead for VDIFCompressedUDP<C> {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        return self.recv_frame();
+    }
+}
+
+impl<C: PayloadCompressor> VDIFWrite for VDIFCompressedUDP<C> {
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        return self.send_frame(frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitshuffle() {
+        let input: Vec<u8> = (0..16).collect();
+        let mut shuffled = vec![0; 16];
+        bitshuffle(&input, &mut shuffled);
+        // Bit 0 is set in every odd byte
+        assert_eq!(&shuffled[0..2], &[0b10101010, 0b10101010]);
+        let mut out = vec![0; 16];
+        bitunshuffle(&shuffled, &mut out);
+        assert_eq!(out, input);
+
+        // Check every bit lands where the definition says, one bit at a time
+        let mut state = 1u32;
+        let input: Vec<u8> = (0..64)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 24) as u8
+            })
+            .collect();
+        let mut shuffled = vec![0; 64];
+        bitshuffle(&input, &mut shuffled);
+        for (i, byte) in input.iter().enumerate() {
+            for b in 0..8 {
+                assert_eq!((byte >> b) & 1, (shuffled[b * 8 + i / 8] >> (i % 8)) & 1);
+            }
+        }
+        let mut out = vec![0; 64];
+        bitunshuffle(&shuffled, &mut out);
+        assert_eq!(out, input);
+    }
+
+    #[test]
+    fn test_compressed_udp() {
+        let mut receiver = VDIFCompressedUDP::new("127.0.0.1:0", 8032, Lz4::new(true)).unwrap();
+        let mut sender = VDIFCompressedUDP::new("127.0.0.1:0", 8032, Lz4::new(true)).unwrap();
+        sender
+            .sock
+            .connect(receiver.sock.local_addr().unwrap())
+            .unwrap();
+        let mut frame = VDIFFrame::empty(8032);
+        for (i, word) in frame.get_mut_payload().iter_mut().enumerate() {
+            *word = if i % 7 == 0 { 0x55555555 } else { 0x11111111 };
+        }
+        sender.send_frame(frame.to_boxed()).unwrap();
+        assert_eq!(receiver.recv_frame().unwrap().as_bytes(), frame.as_bytes());
+        assert!(sender.compression_ratio() < 0.5);
+
+        // Incompressible payloads are sent raw
+        let mut state = 1u32;
+        for word in frame.get_mut_payload() {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            *word = state;
+        }
+        let datagram = compress_frame(&frame, &mut Lz4::new(false));
+        assert_eq!(datagram[HEADER_SIZE + 3], 0);
+        let out = decompress_frame(&datagram, 8032, &mut Lz4::new(false)).unwrap();
+        assert_eq!(out.as_bytes(), frame.as_bytes());
+        assert!(decompress_frame(&datagram, 8032, &mut Lz4::new(true)).is_err());
+        assert_eq!(
+            decompress_frame(&datagram, 8, &mut Lz4::new(false))
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+}
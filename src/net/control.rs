@@ -0,0 +1,237 @@
+//! Implements a minimal HTTP/JSON control plane, for starting, stopping and querying a recording pipeline from
+//! outside the process.
+//!
+//! Requires the `control` feature. The server understands three endpoints:
+//!
+//! - `GET /status` returns the JSON status reported by [`Controllable::status`].
+//! - `POST /start` calls [`Controllable::start`].
+//! - `POST /stop` calls [`Controllable::stop`].
+//!
+//! `start` and `stop` respond with `{"ok": true}`, or `{"ok": false, "error": "..."}` and a `500` status on failure.
+//! This is deliberately tiny; anything more elaborate belongs in a proper web framework.
+
+use std::io::{BufRead, BufReader, ErrorKind, Result, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::json::quote;
+
+/// The default time a [`ControlServer`] waits on a client before giving up on its request.
+pub const DEFAULT_CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A component that can be controlled by a [`ControlServer`].
+pub trait Controllable {
+    /// Start the component.
+    fn start(&mut self) -> Result<()>;
+    /// Stop the component.
+    fn stop(&mut self) -> Result<()>;
+    /// Describe the current status of the component as a JSON value.
+    fn status(&self) -> String;
+}
+
+impl<C: Controllable> Controllable for Arc<Mutex<C>> {
+    fn start(&mut self) -> Result<()> {
+        return self.lock().unwrap().start();
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        return self.lock().unwrap().stop();
+    }
+
+    fn status(&self) -> String {
+        return self.lock().unwrap().status();
+    }
+}
+
+/// A blocking HTTP server exposing a [`Controllable`] component.
+///
+/// ```rust,ignore
+/// let recorder = Arc::new(Mutex::new(MyRecorder::new()));
+/// let mut handle = recorder.clone();
+/// std::thread::spawn(move || {
+///     ControlServer::bind("127.0.0.1:8080").unwrap().serve(&mut handle).unwrap();
+/// });
+/// ```
+pub struct ControlServer {
+    listener: TcpListener,
+    timeout: Option<Duration>,
+}
+
+impl ControlServer {
+    /// Construct a new [`ControlServer`] listening on `addr`.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<Self> {
+        return Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            timeout: Some(DEFAULT_CLIENT_TIMEOUT),
+        });
+    }
+
+    /// Set how long to wait on a client sending its request or reading the response, or `None` to wait forever.
+    /// [`DEFAULT_CLIENT_TIMEOUT`] by default.
+    ///
+    /// Requests are handled one at a time, so this bounds how long an idle client can hold up the others.
+    pub fn set_client_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Get the address this server is listening on.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        return self.listener.local_addr();
+    }
+
+    /// Accept and handle a single request.
+    pub fn handle_one<C: Controllable>(&self, target: &mut C) -> Result<()> {
+        let (stream, _) = self.listener.accept()?;
+        return self.handle_connection(stream, target);
+    }
+
+    /// Handle requests forever, or until accepting a connection fails. Errors talking to a client, such as it timing
+    /// out or disconnecting, only end that client's connection.
+    pub fn serve<C: Controllable>(&self, target: &mut C) -> Result<()> {
+        loop {
+            let stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if is_client_error(&e) => continue,
+                Err(e) => return Err(e),
+            };
+            let _ = self.handle_connection(stream, target);
+        }
+    }
+
+    fn handle_connection<C: Controllable>(&self, stream: TcpStream, target: &mut C) -> Result<()> {
+        stream.set_read_timeout(self.timeout)?;
+        stream.set_write_timeout(self.timeout)?;
+        return handle_connection(stream, target);
+    }
+}
+
+// Whether an error accepting a connection is down to that client alone, so the server can carry on.
+fn is_client_error(e: &std::io::Error) -> bool {
+    return matches!(
+        e.kind(),
+        ErrorKind::ConnectionAborted | ErrorKind::ConnectionReset | ErrorKind::Interrupted
+    );
+}
+
+fn handle_connection<C: Controllable>(stream: TcpStream, target: &mut C) -> Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Skip the remaining request headers, we don't need them
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("");
+    let (code, body) = match (method, path) {
+        ("GET", "/status") => (200, target.status()),
+        ("POST", "/start") => outcome(target.start()),
+        ("POST", "/stop") => outcome(target.stop()),
+        _ => (404, "{\"ok\": false, \"error\": \"not found\"}".to_owned()),
+    };
+
+    let reason = match code {
+        200 => "OK",
+        404 => "Not Found",
+        _ => "Internal Server Error",
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code,
+        reason,
+        body.len(),
+        body
+    )?;
+    return stream.flush();
+}
+
+fn outcome(result: Result<()>) -> (u16, String) {
+    return match result {
+        Ok(()) => (200, "{\"ok\": true}".to_owned()),
+        Err(e) => (
+            500,
+            format!("{{\"ok\": false, \"error\": {}}}", quote(&e.to_string())),
+        ),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    struct Recorder {
+        running: bool,
+    }
+
+    impl Controllable for Recorder {
+        fn start(&mut self) -> Result<()> {
+            self.running = true;
+            return Ok(());
+        }
+
+        fn stop(&mut self) -> Result<()> {
+            self.running = false;
+            return Ok(());
+        }
+
+        fn status(&self) -> String {
+            return format!("{{\"running\": {}}}", self.running);
+        }
+    }
+
+    fn request(addr: SocketAddr, req: &'static str) -> std::thread::JoinHandle<String> {
+        return std::thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(req.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        });
+    }
+
+    #[test]
+    fn test_control_server() {
+        let server = ControlServer::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let mut recorder = Recorder { running: false };
+
+        let client = request(addr, "POST /start HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        server.handle_one(&mut recorder).unwrap();
+        assert!(client.join().unwrap().ends_with("{\"ok\": true}"));
+        assert!(recorder.running);
+
+        let client = request(addr, "GET /status HTTP/1.1\r\n\r\n");
+        server.handle_one(&mut recorder).unwrap();
+        assert!(client.join().unwrap().ends_with("{\"running\": true}"));
+
+        let error = std::io::Error::other("disk \"full\"\nretrying");
+        assert_eq!(
+            outcome(Err(error)).1,
+            "{\"ok\": false, \"error\": \"disk \\\"full\\\"\\nretrying\"}"
+        );
+    }
+
+    #[test]
+    fn test_serve_idle_client() {
+        let mut server = ControlServer::bind("127.0.0.1:0").unwrap();
+        server.set_client_timeout(Some(Duration::from_millis(50)));
+        let addr = server.local_addr().unwrap();
+
+        // A client that connects but never sends a request times out without stopping the server
+        let idle = TcpStream::connect(addr).unwrap();
+        let client = request(addr, "GET /status HTTP/1.1\r\n\r\n");
+        std::thread::spawn(move || {
+            let mut recorder = Recorder { running: false };
+            let _ = server.serve(&mut recorder);
+        });
+        assert!(client.join().unwrap().ends_with("{\"running\": false}"));
+        drop(idle);
+    }
+}
@@ -0,0 +1,246 @@
+//! Implements sending VDIF frames split across several UDP datagrams, and reassembling them on receipt, for frames too
+//! large for a single datagram or for networks without jumbo frames.
+//!
+//! Each datagram carries an 8 byte little endian fragment header followed by a chunk of the frame:
+//!
+//! | Bytes | Type  | Contents                                               |
+//! |-------|-------|--------------------------------------------------------|
+//! | 0-3   | `u32` | Sequence number of the frame, wrapping.                |
+//! | 4-5   | `u16` | Index of this fragment within the frame.               |
+//! | 6-7   | `u16` | Number of fragments the frame is split into.           |
+//!
+//! Every fragment but the last carries the same number of bytes. Fragments may arrive in any order, but a frame is
+//! dropped if a fragment of a later frame arrives before it is complete.
+
+use std::io::{Error, ErrorKind, Result};
+use std::net::{ToSocketAddrs, UdpSocket};
+
+use crate::frame::VDIFFrame;
+use crate::io::{VDIFRead, VDIFWrite};
+use crate::net::MAX_DATAGRAM_SIZE;
+
+/// The size in bytes of the header at the start of each fragment.
+pub const FRAGMENT_HEADER_SIZE: usize = 8;
+
+/// Split `frame` into datagrams of at most `max_datagram` bytes, each starting with a fragment header carrying
+/// `sequence`.
+pub fn fragment_frame(frame: &VDIFFrame, sequence: u32, max_datagram: usize) -> Vec<Vec<u8>> {
+    assert!(
+        max_datagram > FRAGMENT_HEADER_SIZE,
+        "Datagrams must be larger than the fragment header"
+    );
+    let chunks: Vec<&[u8]> = frame
+        .as_bytes()
+        .chunks(max_datagram - FRAGMENT_HEADER_SIZE)
+        .collect();
+    assert!(
+        chunks.len() <= u16::MAX as usize,
+        "Frame needs too many fragments"
+    );
+    return chunks
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut datagram = Vec::with_capacity(FRAGMENT_HEADER_SIZE + chunk.len());
+            datagram.extend_from_slice(&sequence.to_le_bytes());
+            datagram.extend_from_slice(&(i as u16).to_le_bytes());
+            datagram.extend_from_slice(&(chunks.len() as u16).to_le_bytes());
+            datagram.extend_from_slice(chunk);
+            datagram
+        })
+        .collect();
+}
+
+// A frame partway through reassembly.
+struct Partial {
+    sequence: u32,
+    frame: VDIFFrame,
+    received: Vec<bool>,
+    remaining: usize,
+}
+
+/// Reassembles frames of a fixed size from fragments produced by [`fragment_frame`].
+pub struct Reassembler {
+    frame_size: usize,
+    partial: Option<Partial>,
+    dropped: u64,
+}
+
+impl Reassembler {
+    /// Construct a new [`Reassembler`] for frames of `frame_size` bytes.
+    pub fn new(frame_size: usize) -> Self {
+        return Self {
+            frame_size: frame_size,
+            partial: None,
+            dropped: 0,
+        };
+    }
+
+    /// Get the number of frames dropped because they were incomplete when a later frame started arriving.
+    pub fn dropped(&self) -> u64 {
+        return self.dropped;
+    }
+
+    /// Add a received datagram, returning the frame it completes, if any.
+    pub fn push(&mut self, datagram: &[u8]) -> Result<Option<VDIFFrame>> {
+        if datagram.len() <= FRAGMENT_HEADER_SIZE {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Datagram is too small to hold a frame fragment",
+            ));
+        }
+        let sequence = u32::from_le_bytes(datagram[0..4].try_into().unwrap());
+        let index = u16::from_le_bytes(datagram[4..6].try_into().unwrap()) as usize;
+        let count = u16::from_le_bytes(datagram[6..8].try_into().unwrap()) as usize;
+        let chunk = &datagram[FRAGMENT_HEADER_SIZE..];
+        if index >= count {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Fragment index is out of range",
+            ));
+        }
+
+        if self.partial.as_ref().is_none_or(|p| p.sequence != sequence) {
+            if self.partial.is_some() {
+                self.dropped += 1;
+            }
+            self.partial = Some(Partial {
+                sequence: sequence,
+                frame: VDIFFrame::empty(self.frame_size),
+                received: vec![false; count],
+                remaining: count,
+            });
+        }
+        let partial = self.partial.as_mut().unwrap();
+        if partial.received.len() != count {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Fragments of a frame disagree on the fragment count",
+            ));
+        }
+
+        // Every fragment but the last is full, and the last ends the frame
+        let start = if index + 1 < count {
+            index * chunk.len()
+        } else {
+            self.frame_size.saturating_sub(chunk.len())
+        };
+        if start + chunk.len() > self.frame_size {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Fragments do not fit the configured frame size",
+            ));
+        }
+        if !partial.received[index] {
+            partial.frame.as_mut_bytes()[start..start + chunk.len()].copy_from_slice(chunk);
+            partial.received[index] = true;
+            partial.remaining -= 1;
+        }
+        if partial.remaining == 0 {
+            return Ok(self.partial.take().map(|p| p.frame));
+        }
+        return Ok(None);
+    }
+}
+
+/// Sends and receives VDIF frames split across several datagrams over a [`UdpSocket`].
+pub struct VDIFFragmentedUDP {
+    /// The underlying [`UdpSocket`].
+    pub sock: UdpSocket,
+    max_datagram: usize,
+    sequence: u32,
+    reassembler: Reassembler,
+    scratch: Vec<u8>,
+}
+
+impl VDIFFragmentedUDP {
+    /// Construct a new [`VDIFFragmentedUDP`] bound to `addr`, for frames of `frame_size` bytes sent in datagrams of at
+    /// most `max_datagram` bytes. Use a `max_datagram` that fits the path MTU, such as 1472 for a 1500 byte Ethernet
+    /// MTU over IPv4.
+    pub fn new<A: ToSocketAddrs>(addr: A, frame_size: usize, max_datagram: usize) -> Result<Self> {
+        return Ok(Self {
+            sock: UdpSocket::bind(addr)?,
+            max_datagram: max_datagram,
+            sequence: 0,
+            reassembler: Reassembler::new(frame_size),
+            scratch: vec![0; MAX_DATAGRAM_SIZE],
+        });
+    }
+
+    /// Get the number of frames dropped because some of their fragments never arrived.
+    pub fn dropped(&self) -> u64 {
+        return self.reassembler.dropped();
+    }
+
+    /// Receive datagrams until a complete [`VDIFFrame`] has been reassembled.
+    pub fn recv_frame(&mut self) -> Result<VDIFFrame> {
+        loop {
+            let received = self.sock.recv(&mut self.scratch)?;
+            if let Some(frame) = self.reassembler.push(&self.scratch[0..received])? {
+                return Ok(frame);
+            }
+        }
+    }
+
+    /// Send a [`VDIFFrame`] to the connected peer, split across as many datagrams as needed.
+    pub fn send_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        for datagram in fragment_frame(&frame, self.sequence, self.max_datagram) {
+            self.sock.send(&datagram)?;
+        }
+        self.sequence = self.sequence.wrapping_add(1);
+        return Ok(());
+    }
+}
+
+impl VDIFRead for VDIFFragmentedUDP {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        return self.recv_frame();
+    }
+}
+
+impl VDIFWrite for VDIFFragmentedUDP {
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        return self.send_frame(frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reassembly() {
+        let mut frame = VDIFFrame::empty(96);
+        for (i, byte) in frame.as_mut_bytes().iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let datagrams = fragment_frame(&frame, 5, 48);
+        assert_eq!(datagrams.len(), 3);
+
+        let mut reassembler = Reassembler::new(96);
+        // A stray fragment of an earlier frame, then the fragments out of order
+        assert!(reassembler
+            .push(&fragment_frame(&frame, 4, 48)[0])
+            .unwrap()
+            .is_none());
+        assert!(reassembler.push(&datagrams[2]).unwrap().is_none());
+        assert!(reassembler.push(&datagrams[0]).unwrap().is_none());
+        let out = reassembler.push(&datagrams[1]).unwrap().unwrap();
+        assert_eq!(out.as_bytes(), frame.as_bytes());
+        assert_eq!(reassembler.dropped(), 1);
+    }
+
+    #[test]
+    fn test_fragmented_udp() {
+        let mut receiver = VDIFFragmentedUDP::new("127.0.0.1:0", 8032, 1472).unwrap();
+        let mut sender = VDIFFragmentedUDP::new("127.0.0.1:0", 8032, 1472).unwrap();
+        sender
+            .sock
+            .connect(receiver.sock.local_addr().unwrap())
+            .unwrap();
+        let mut frame = VDIFFrame::empty(8032);
+        frame.get_mut_payload()[1999] = 7;
+        sender.send_frame(frame).unwrap();
+        assert_eq!(receiver.recv_frame().unwrap().get_data_word(1999), 7);
+    }
+}
@@ -0,0 +1,226 @@
+//! Types and methods for receiving VDIF frames sent to a multicast group, as many observatories use to distribute a
+//! stream to several consumers at once.
+//!
+//! A [`MulticastGroup`] describes the group to join, the local interface to join it on and, for source-specific
+//! multicast, the one sender to accept. Receivers can be bound straight to a group:
+//!
+//! ```rust,ignore
+//! let group = MulticastGroup::new("239.1.2.3".parse().unwrap())
+//!     .with_interface(MulticastInterface::Address("10.0.0.5".parse().unwrap()));
+//! let mut receiver = VDIFUDP::multicast(&group, 50000, 8032).unwrap();
+//! ```
+//!
+//! or join and leave groups on an existing socket with [`join`](MulticastGroup::join) and
+//! [`leave`](MulticastGroup::leave).
+
+use std::io::{Error, ErrorKind, Result};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+/// The local interface on which to join a multicast group.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MulticastInterface {
+    /// Let the system choose the interface. This is the default.
+    #[default]
+    Any,
+    /// The interface with this IPv4 address, for IPv4 groups.
+    Address(Ipv4Addr),
+    /// The interface with this index, for IPv6 groups.
+    Index(u32),
+}
+
+/// A multicast group to receive from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MulticastGroup {
+    /// The address of the group.
+    pub group: IpAddr,
+    /// The local interface on which to join the group.
+    pub interface: MulticastInterface,
+    /// For source-specific multicast, the only sender to receive from. Only supported for IPv4 groups.
+    pub source: Option<Ipv4Addr>,
+}
+
+impl MulticastGroup {
+    /// Construct a new [`MulticastGroup`] for `group`, joined on any interface and receiving from any source.
+    pub fn new(group: IpAddr) -> Self {
+        return Self {
+            group: group,
+            interface: MulticastInterface::Any,
+            source: None,
+        };
+    }
+
+    /// Join the group on `interface`.
+    pub fn with_interface(mut self, interface: MulticastInterface) -> Self {
+        self.interface = interface;
+        return self;
+    }
+
+    /// Receive only from `source`, using source-specific multicast.
+    pub fn with_source(mut self, source: Ipv4Addr) -> Self {
+        self.source = Some(source);
+        return self;
+    }
+
+    /// Join the group on `sock`.
+    ///
+    /// Returns an error of kind [`InvalidInput`](ErrorKind::InvalidInput) if the group isn't a multicast address, or
+    /// the interface or source don't suit the group's address family.
+    pub fn join(&self, sock: &UdpSocket) -> Result<()> {
+        return match (self.group, self.source) {
+            (IpAddr::V4(group), None) => sock.join_multicast_v4(&group, &self.v4_interface()?),
+            (IpAddr::V4(group), Some(source)) => {
+                set_ssm_v4(sock, true, &source, &group, &self.v4_interface()?)
+            }
+            (IpAddr::V6(group), None) => sock.join_multicast_v6(&group, self.v6_interface()?),
+            (IpAddr::V6(_), Some(_)) => Err(ssm_v6_error()),
+        };
+    }
+
+    /// Leave the group on `sock`.
+    pub fn leave(&self, sock: &UdpSocket) -> Result<()> {
+        return match (self.group, self.source) {
+            (IpAddr::V4(group), None) => sock.leave_multicast_v4(&group, &self.v4_interface()?),
+            (IpAddr::V4(group), Some(source)) => {
+                set_ssm_v4(sock, false, &source, &group, &self.v4_interface()?)
+            }
+            (IpAddr::V6(group), None) => sock.leave_multicast_v6(&group, self.v6_interface()?),
+            (IpAddr::V6(_), Some(_)) => Err(ssm_v6_error()),
+        };
+    }
+
+    fn v4_interface(&self) -> Result<Ipv4Addr> {
+        self.check_group()?;
+        return match self.interface {
+            MulticastInterface::Any => Ok(Ipv4Addr::UNSPECIFIED),
+            MulticastInterface::Address(addr) => Ok(addr),
+            MulticastInterface::Index(_) => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "IPv4 multicast groups are joined by interface address",
+            )),
+        };
+    }
+
+    fn v6_interface(&self) -> Result<u32> {
+        self.check_group()?;
+        return match self.interface {
+            MulticastInterface::Any => Ok(0),
+            MulticastInterface::Index(index) => Ok(index),
+            MulticastInterface::Address(_) => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "IPv6 multicast groups are joined by interface index",
+            )),
+        };
+    }
+
+    fn check_group(&self) -> Result<()> {
+        if !self.group.is_multicast() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} is not a multicast address", self.group),
+            ));
+        }
+        return Ok(());
+    }
+}
+
+/// Bind a socket to `port` on every local address and join `group` on it.
+///
+/// The socket is bound with `SO_REUSEADDR`, so several consumers on the same host can receive the same group.
+pub fn bind_multicast(group: &MulticastGroup, port: u16) -> Result<UdpSocket> {
+    group.check_group()?;
+    let addr = match group.group {
+        IpAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)),
+        IpAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)),
+    };
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    let sock: UdpSocket = socket.into();
+    group.join(&sock)?;
+    return Ok(sock);
+}
+
+fn ssm_v6_error() -> Error {
+    return Error::new(
+        ErrorKind::Unsupported,
+        "Source-specific multicast is only supported for IPv4 groups",
+    );
+}
+
+// Join or leave a source-specific group, on the platforms where socket2 supports it.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    windows
+))]
+fn set_ssm_v4(
+    sock: &UdpSocket,
+    join: bool,
+    source: &Ipv4Addr,
+    group: &Ipv4Addr,
+    interface: &Ipv4Addr,
+) -> Result<()> {
+    let sock = socket2::SockRef::from(sock);
+    if join {
+        return sock.join_ssm_v4(source, group, interface);
+    }
+    return sock.leave_ssm_v4(source, group, interface);
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    windows
+)))]
+fn set_ssm_v4(
+    _sock: &UdpSocket,
+    _join: bool,
+    _source: &Ipv4Addr,
+    _group: &Ipv4Addr,
+    _interface: &Ipv4Addr,
+) -> Result<()> {
+    return Err(Error::new(
+        ErrorKind::Unsupported,
+        "Source-specific multicast is not supported on this platform",
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multicast_group() {
+        let sock = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let unicast = MulticastGroup::new("10.0.0.1".parse().unwrap());
+        assert_eq!(
+            unicast.join(&sock).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        let group = MulticastGroup::new("239.1.2.3".parse().unwrap());
+        assert_eq!(
+            group
+                .with_interface(MulticastInterface::Index(1))
+                .join(&sock)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+        let v6 = MulticastGroup::new("ff02::1234".parse().unwrap());
+        assert_eq!(
+            v6.with_source(Ipv4Addr::LOCALHOST)
+                .join(&sock)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::Unsupported
+        );
+    }
+}
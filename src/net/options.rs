@@ -0,0 +1,116 @@
+//! Implements [`SocketOptions`], the socket settings most often tuned on VDIF receivers.
+//!
+//! High rate streams usually need a larger receive buffer than the system default, and hosts with several interfaces
+//! often need the receiver bound to one of them:
+//!
+//! ```rust,ignore
+//! let options = SocketOptions::new()
+//!     .with_recv_buffer(64 << 20)
+//!     .with_device("eth2")
+//!     .with_read_timeout(Duration::from_secs(1));
+//! let mut receiver = VDIFUDP::with_options("0.0.0.0:50000", 8032, &options).unwrap();
+//! ```
+
+use std::io::Result;
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use socket2::SockRef;
+
+/// Settings applied to the socket of a receiver. Options left unset keep the socket's current setting.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SocketOptions {
+    /// The size of the kernel's receive buffer (`SO_RCVBUF`) in bytes. Linux caps this at `net.core.rmem_max`.
+    pub recv_buffer: Option<usize>,
+    /// The network interface to receive on (`SO_BINDTODEVICE`), such as `eth2`. Only supported on Linux, and usually
+    /// needs `CAP_NET_RAW`.
+    pub device: Option<String>,
+    /// Whether the socket is non-blocking, so receives fail with [`WouldBlock`](std::io::ErrorKind::WouldBlock)
+    /// rather than waiting for a datagram.
+    pub nonblocking: Option<bool>,
+    /// How long a receive waits for a datagram before failing with [`WouldBlock`](std::io::ErrorKind::WouldBlock) or
+    /// [`TimedOut`](std::io::ErrorKind::TimedOut).
+    pub read_timeout: Option<Duration>,
+}
+
+impl SocketOptions {
+    /// Construct a new [`SocketOptions`] with every option unset.
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Set the size of the kernel's receive buffer.
+    pub fn with_recv_buffer(mut self, bytes: usize) -> Self {
+        self.recv_buffer = Some(bytes);
+        return self;
+    }
+
+    /// Receive only on the network interface named `device`.
+    pub fn with_device(mut self, device: &str) -> Self {
+        self.device = Some(device.to_owned());
+        return self;
+    }
+
+    /// Set whether the socket is non-blocking.
+    pub fn with_nonblocking(mut self, nonblocking: bool) -> Self {
+        self.nonblocking = Some(nonblocking);
+        return self;
+    }
+
+    /// Set how long a receive waits for a datagram.
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        return self;
+    }
+
+    /// Apply the options set to `sock`.
+    ///
+    /// Returns an error of kind [`Unsupported`](std::io::ErrorKind::Unsupported) if a device is set on a platform
+    /// other than Linux.
+    pub fn apply(&self, sock: &UdpSocket) -> Result<()> {
+        if let Some(bytes) = self.recv_buffer {
+            SockRef::from(sock).set_recv_buffer_size(bytes)?;
+        }
+        if let Some(device) = &self.device {
+            bind_device(sock, device)?;
+        }
+        if let Some(nonblocking) = self.nonblocking {
+            sock.set_nonblocking(nonblocking)?;
+        }
+        if let Some(timeout) = self.read_timeout {
+            sock.set_read_timeout(Some(timeout))?;
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn bind_device(sock: &UdpSocket, device: &str) -> Result<()> {
+    return SockRef::from(sock).bind_device(Some(device.as_bytes()));
+}
+
+#[cfg(not(target_os = "linux"))]
+fn bind_device(_sock: &UdpSocket, _device: &str) -> Result<()> {
+    return Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "Binding to a device is only supported on Linux",
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let sock = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let options = SocketOptions::new()
+            .with_recv_buffer(65536)
+            .with_read_timeout(Duration::from_millis(10));
+        options.apply(&sock).unwrap();
+        // The kernel rounds timeouts to its clock tick
+        assert!(sock.read_timeout().unwrap().is_some());
+        assert!(SockRef::from(&sock).recv_buffer_size().unwrap() >= 65536);
+        assert!(sock.recv(&mut [0; 8]).is_err());
+    }
+}
@@ -0,0 +1,70 @@
+//! Linux socket calls behind the busy-poll receive mode of [`VDIFUDP`](crate::net::udp::VDIFUDP).
+//!
+//! Requires the `busy-poll` feature.
+
+use std::io::{Error, Result};
+use std::net::UdpSocket;
+use std::os::fd::AsRawFd;
+
+// Set the SO_BUSY_POLL option of `sock`, the number of microseconds the kernel may busy-poll the device queue for.
+pub(crate) fn set_busy_poll(sock: &UdpSocket, usecs: u32) -> Result<()> {
+    let value = usecs as libc::c_int;
+    let ret = unsafe {
+        libc::setsockopt(
+            sock.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_BUSY_POLL,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret != 0 {
+        return Err(Error::last_os_error());
+    }
+    return Ok(());
+}
+
+// Receive up to `bufs.len()` datagrams with a single recvmmsg call that never blocks, returning the size of each one
+// received and whether it was truncated to fit its buffer. Fails with WouldBlock if no datagrams are waiting.
+pub(crate) fn recvmmsg_dontwait(
+    sock: &UdpSocket,
+    bufs: &mut [&mut [u8]],
+) -> Result<Vec<(usize, bool)>> {
+    let mut iovecs: Vec<libc::iovec> = bufs
+        .iter_mut()
+        .map(|buf| libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buf.len(),
+        })
+        .collect();
+    let mut msgs: Vec<libc::mmsghdr> = iovecs
+        .iter_mut()
+        .map(|iov| {
+            let mut msg: libc::mmsghdr = unsafe { std::mem::zeroed() };
+            msg.msg_hdr.msg_iov = iov;
+            msg.msg_hdr.msg_iovlen = 1;
+            msg
+        })
+        .collect();
+    let received = unsafe {
+        libc::recvmmsg(
+            sock.as_raw_fd(),
+            msgs.as_mut_ptr(),
+            msgs.len() as _,
+            libc::MSG_DONTWAIT as _,
+            std::ptr::null_mut(),
+        )
+    };
+    if received < 0 {
+        return Err(Error::last_os_error());
+    }
+    return Ok(msgs[0..received as usize]
+        .iter()
+        .map(|msg| {
+            (
+                msg.msg_len as usize,
+                msg.msg_hdr.msg_flags & libc::MSG_TRUNC != 0,
+            )
+        })
+        .collect());
+}
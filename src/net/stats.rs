@@ -0,0 +1,136 @@
+//! Implements [`ReceiverStats`], combining a receiver's own counters with the kernel's view of its socket.
+//!
+//! User-space counters only see the frames that made it to the socket, so can't tell loss on the network from drops
+//! on the host. The kernel counts datagrams it dropped because the socket's receive buffer was full, which on Linux
+//! are read from `/proc/net/udp` (per socket) and `/proc/net/snmp` (host wide).
+
+use std::io::{Error, ErrorKind, Result};
+use std::net::{IpAddr, UdpSocket};
+
+/// Statistics of a UDP receiver, such as [`VDIFUDP`](crate::net::udp::VDIFUDP).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReceiverStats {
+    /// The number of frames received.
+    pub frames: u64,
+    /// The number of bytes received.
+    pub bytes: u64,
+    /// The kernel's statistics of the socket, if they could be read.
+    pub kernel: Option<KernelUdpStats>,
+}
+
+/// The kernel's statistics of a UDP socket and of UDP on the host.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct KernelUdpStats {
+    /// Datagrams dropped by the kernel for this socket, typically because its receive buffer was full.
+    pub socket_drops: u64,
+    /// Bytes queued in the socket's receive buffer, waiting to be read.
+    pub rx_queue: u64,
+    /// Datagrams the host failed to deliver to any UDP socket, for any reason.
+    pub host_in_errors: u64,
+    /// Datagrams the host dropped because a UDP socket's receive buffer was full.
+    pub host_rcvbuf_errors: u64,
+}
+
+/// Read the kernel's statistics of `sock`. Only supported on Linux.
+#[cfg(target_os = "linux")]
+pub fn kernel_udp_stats(sock: &UdpSocket) -> Result<KernelUdpStats> {
+    let local = sock.local_addr()?;
+    let table = if local.is_ipv4() {
+        "/proc/net/udp"
+    } else {
+        "/proc/net/udp6"
+    };
+    let (socket_drops, rx_queue) =
+        find_socket(&std::fs::read_to_string(table)?, local.ip(), local.port()).ok_or_else(
+            || {
+                Error::new(
+                    ErrorKind::NotFound,
+                    "Socket not found in the kernel's UDP table",
+                )
+            },
+        )?;
+    let (host_in_errors, host_rcvbuf_errors) =
+        parse_snmp(&std::fs::read_to_string("/proc/net/snmp")?)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Could not parse /proc/net/snmp"))?;
+    return Ok(KernelUdpStats {
+        socket_drops: socket_drops,
+        rx_queue: rx_queue,
+        host_in_errors: host_in_errors,
+        host_rcvbuf_errors: host_rcvbuf_errors,
+    });
+}
+
+/// Read the kernel's statistics of `sock`. Only supported on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn kernel_udp_stats(_sock: &UdpSocket) -> Result<KernelUdpStats> {
+    return Err(Error::new(
+        ErrorKind::Unsupported,
+        "Kernel UDP statistics are only available on Linux",
+    ));
+}
+
+// Find the socket bound to `ip:port` in the contents of /proc/net/udp(6), returning its drops and receive queue.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn find_socket(table: &str, ip: IpAddr, port: u16) -> Option<(u64, u64)> {
+    for line in table.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 13 {
+            continue;
+        }
+        let (addr, line_port) = fields[1].split_once(':')?;
+        if u16::from_str_radix(line_port, 16).ok()? != port || parse_proc_addr(addr)? != ip {
+            continue;
+        }
+        let rx_queue = u64::from_str_radix(fields[4].split_once(':')?.1, 16).ok()?;
+        let drops = fields[12].parse().ok()?;
+        return Some((drops, rx_queue));
+    }
+    return None;
+}
+
+// Parse an address from /proc/net/udp(6), written as native-endian 32-bit words in hex.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_proc_addr(hex: &str) -> Option<IpAddr> {
+    let words = (0..hex.len() / 8)
+        .map(|i| u32::from_str_radix(&hex[i * 8..i * 8 + 8], 16).map(u32::to_ne_bytes))
+        .collect::<std::result::Result<Vec<[u8; 4]>, _>>()
+        .ok()?;
+    return match words.len() {
+        1 => Some(IpAddr::from(words[0])),
+        4 => Some(IpAddr::from(<[u8; 16]>::try_from(words.concat()).ok()?)),
+        _ => None,
+    };
+}
+
+// Parse the host-wide UDP InErrors and RcvbufErrors counters from the contents of /proc/net/snmp.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_snmp(snmp: &str) -> Option<(u64, u64)> {
+    let mut lines = snmp.lines().filter(|l| l.starts_with("Udp:"));
+    let names: Vec<&str> = lines.next()?.split_whitespace().collect();
+    let values: Vec<&str> = lines.next()?.split_whitespace().collect();
+    let get = |name: &str| -> Option<u64> {
+        let i = names.iter().position(|n| *n == name)?;
+        return values.get(i)?.parse().ok();
+    };
+    return Some((get("InErrors")?, get("RcvbufErrors")?));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc() {
+        let table = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode ref pointer drops\n\
+            12: 0100007F:1F90 00000000:0000 07 00000000:00000340 00:00000000 00000000     0        0 1234 2 0000000000000000 17\n";
+        assert_eq!(
+            find_socket(table, "127.0.0.1".parse().unwrap(), 8080),
+            Some((17, 0x340))
+        );
+        assert_eq!(find_socket(table, "127.0.0.1".parse().unwrap(), 8081), None);
+
+        let snmp =
+            "Udp: InDatagrams NoPorts InErrors OutDatagrams RcvbufErrors\nUdp: 252 0 5 252 3\n";
+        assert_eq!(parse_snmp(snmp), Some((5, 3)));
+    }
+}
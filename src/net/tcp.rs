@@ -0,0 +1,226 @@
+//! Types and methods for streaming VDIF frames over TCP, for links where the loss of UDP is unacceptable.
+//!
+//! Frames are sent back to back with no framing of their own, so both ends must agree on the frame size in advance, as
+//! with a VDIF file. Optionally each frame is preceded by a 64-bit VTP sequence number, as in
+//! [`VDIFVTP`](crate::net::vtp::VDIFVTP), which lets the receiver line the stream up with a UDP one.
+//!
+//! ```rust,ignore
+//! // Receiver
+//! let listener = VDIFTCPListener::bind("0.0.0.0:50000", 8032).unwrap();
+//! let (mut conn, peer) = listener.accept().unwrap();
+//! let frame = conn.read_frame().unwrap();
+//!
+//! // Sender
+//! let mut conn = VDIFTCP::connect("receiver:50000", 8032).unwrap();
+//! conn.write_frame(frame).unwrap();
+//! conn.flush().unwrap();
+//! ```
+
+use std::io::{BufReader, BufWriter, Read, Result, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+use crate::frame::VDIFFrame;
+use crate::io::{VDIFRead, VDIFWrite};
+use crate::provenance::{Provenance, Tagged};
+use crate::validation::ValidationLevel;
+
+/// Listens for incoming TCP connections streaming VDIF frames.
+pub struct VDIFTCPListener {
+    listener: TcpListener,
+    frame_size: usize,
+    vtp: bool,
+}
+
+impl VDIFTCPListener {
+    /// Construct a new [`VDIFTCPListener`] bound to `addr`, accepting streams of frames of `frame_size` bytes.
+    pub fn bind<A: ToSocketAddrs>(addr: A, frame_size: usize) -> Result<Self> {
+        return Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            frame_size: frame_size,
+            vtp: false,
+        });
+    }
+
+    /// Set whether accepted connections expect a VTP sequence number before each frame. Off by default.
+    pub fn set_vtp(&mut self, vtp: bool) {
+        self.vtp = vtp;
+    }
+
+    /// Get the local address of the listener.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        return self.listener.local_addr();
+    }
+
+    /// Wait for a sender to connect, returning the connection and its address.
+    pub fn accept(&self) -> Result<(VDIFTCP, SocketAddr)> {
+        let (stream, addr) = self.listener.accept()?;
+        let mut conn = VDIFTCP::from_stream(stream, self.frame_size)?;
+        conn.set_vtp(self.vtp);
+        return Ok((conn, addr));
+    }
+
+    /// Get a reference to the underlying [`TcpListener`].
+    pub fn listener_ref(&self) -> &TcpListener {
+        return &self.listener;
+    }
+}
+
+/// A TCP connection streaming VDIF frames of a fixed size in either direction.
+///
+/// Sent frames are buffered, so call [`flush`](VDIFWrite::flush) to be sure they have been handed to the socket.
+pub struct VDIFTCP {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+    peer: SocketAddr,
+    frame_size: usize,
+    vtp: bool,
+    validation: ValidationLevel,
+    sent: u64,
+    received: u64,
+}
+
+impl VDIFTCP {
+    /// Connect to a [`VDIFTCPListener`] at `addr`, to stream frames of `frame_size` bytes.
+    pub fn connect<A: ToSocketAddrs>(addr: A, frame_size: usize) -> Result<Self> {
+        return Self::from_stream(TcpStream::connect(addr)?, frame_size);
+    }
+
+    /// Construct a new [`VDIFTCP`] from a connected `stream`, to stream frames of `frame_size` bytes.
+    pub fn from_stream(stream: TcpStream, frame_size: usize) -> Result<Self> {
+        let peer = stream.peer_addr()?;
+        // Buffer 10 frames each way, as VDIFReader and VDIFWriter do
+        return Ok(Self {
+            reader: BufReader::with_capacity(10 * (frame_size + 8), stream.try_clone()?),
+            writer: BufWriter::with_capacity(10 * (frame_size + 8), stream),
+            peer: peer,
+            frame_size: frame_size,
+            vtp: false,
+            validation: ValidationLevel::None,
+            sent: 0,
+            received: 0,
+        });
+    }
+
+    /// Set whether each frame is preceded by a VTP sequence number, in both directions. Off by default. Both ends of
+    /// the connection must agree.
+    pub fn set_vtp(&mut self, vtp: bool) {
+        self.vtp = vtp;
+    }
+
+    /// Set how thoroughly to check each frame received, returning an [`InvalidData`](std::io::ErrorKind::InvalidData)
+    /// error for frames that fail. [`ValidationLevel::None`] by default.
+    pub fn set_validation(&mut self, validation: ValidationLevel) {
+        self.validation = validation;
+    }
+
+    /// Get the size of the frames streamed.
+    pub fn frame_size(&self) -> usize {
+        return self.frame_size;
+    }
+
+    /// Get the address of the other end of the connection.
+    pub fn peer_addr(&self) -> SocketAddr {
+        return self.peer;
+    }
+
+    /// Receive a [`VDIFFrame`] along with its sequence number. Without VTP, the sequence number is the position of the
+    /// frame in the stream.
+    ///
+    /// Returns an error of kind [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) when the sender closes the
+    /// connection.
+    pub fn recv_frame(&mut self) -> Result<(u64, VDIFFrame)> {
+        let sequence = if self.vtp {
+            let mut bytes = [0u8; 8];
+            self.reader.read_exact(&mut bytes)?;
+            u64::from_le_bytes(bytes)
+        } else {
+            self.received
+        };
+        let mut frame = VDIFFrame::empty(self.frame_size);
+        self.reader.read_exact(frame.as_mut_bytes())?;
+        self.validation.check(&frame)?;
+        self.received += 1;
+        return Ok((sequence, frame));
+    }
+
+    /// Send a [`VDIFFrame`], preceded by the next sequence number when using VTP.
+    pub fn send_frame(&mut self, frame: &VDIFFrame) -> Result<()> {
+        if self.vtp {
+            self.writer.write_all(&self.sent.to_le_bytes())?;
+        }
+        self.writer.write_all(frame.as_bytes())?;
+        self.sent += 1;
+        return Ok(());
+    }
+
+    /// Get a reference to the underlying [`TcpStream`].
+    pub fn socket_ref(&self) -> &TcpStream {
+        return self.writer.get_ref();
+    }
+}
+
+impl VDIFRead for VDIFTCP {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        return Ok(self.recv_frame()?.1);
+    }
+
+    fn read_tagged(&mut self) -> Result<Tagged> {
+        let (sequence, frame) = self.recv_frame()?;
+        let mut provenance = Provenance::now();
+        provenance.source = Some(self.peer);
+        provenance.sequence = sequence;
+        return Ok(Tagged {
+            frame: frame,
+            provenance: provenance,
+        });
+    }
+}
+
+impl VDIFWrite for VDIFTCP {
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        return self.send_frame(&frame);
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        return self.writer.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::VDIFHeader;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn test_tcp_stream() {
+        let mut listener = VDIFTCPListener::bind("127.0.0.1:0", 64).unwrap();
+        listener.set_vtp(true);
+        let addr = listener.local_addr().unwrap();
+        let sender = std::thread::spawn(move || {
+            let mut conn = VDIFTCP::connect(addr, 64).unwrap();
+            conn.set_vtp(true);
+            for frameno in 0..5 {
+                let frame = VDIFFrame::from_header(VDIFHeader {
+                    frameno: frameno,
+                    size: 8,
+                    ..Default::default()
+                });
+                conn.write_frame(frame).unwrap();
+            }
+            conn.flush().unwrap();
+        });
+
+        let (mut conn, _) = listener.accept().unwrap();
+        for frameno in 0..5 {
+            let tagged = conn.read_tagged().unwrap();
+            assert_eq!(tagged.frame.get_header().frameno, frameno);
+            assert_eq!(tagged.provenance.sequence, frameno as u64);
+        }
+        sender.join().unwrap();
+        assert_eq!(
+            conn.read_frame().unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+    }
+}
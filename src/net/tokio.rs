@@ -0,0 +1,167 @@
+//! Async equivalents of the VDIF readers, writers and UDP sockets, built on [`tokio`], so VDIF capture can run inside
+//! an existing async service without dedicated blocking threads.
+//!
+//! Requires the `async` feature.
+
+use std::io::{Error, ErrorKind, Result};
+use std::net::SocketAddr;
+
+use ::tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, Interest};
+use ::tokio::net::{ToSocketAddrs, UdpSocket};
+use socket2::SockRef;
+
+use crate::error::VDIFError;
+use crate::frame::VDIFFrame;
+use crate::net::udp::recv_datagram;
+
+/// Read a single [`VDIFFrame`] of `frame_size` bytes from `reader`. The async equivalent of
+/// [`VDIFReader::read_frame`](crate::io::VDIFReader::read_frame).
+pub async fn read_frame<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    frame_size: usize,
+) -> Result<VDIFFrame> {
+    let mut frame = VDIFFrame::empty(frame_size);
+    reader.read_exact(frame.as_mut_bytes()).await?;
+    return Ok(frame);
+}
+
+/// Write a single [`VDIFFrame`] to `writer`. The async equivalent of
+/// [`VDIFWriter::write_frame`](crate::io::VDIFWriter).
+pub async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, frame: VDIFFrame) -> Result<()> {
+    return writer.write_all(frame.as_bytes()).await;
+}
+
+/// The async equivalent of [`VDIFUDP`](crate::net::udp::VDIFUDP), wrapping a tokio [`UdpSocket`].
+pub struct AsyncVDIFUDP {
+    /// The underlying [`UdpSocket`].
+    pub sock: UdpSocket,
+    frame_size: usize,
+}
+
+impl AsyncVDIFUDP {
+    /// Construct a new [`AsyncVDIFUDP`] bound to `addr`.
+    pub async fn bind<A: ToSocketAddrs>(addr: A, frame_size: usize) -> Result<Self> {
+        return Ok(Self::from_socket(UdpSocket::bind(addr).await?, frame_size));
+    }
+
+    /// Construct a new [`AsyncVDIFUDP`] from an existing socket.
+    pub fn from_socket(sock: UdpSocket, frame_size: usize) -> Self {
+        return Self {
+            sock: sock,
+            frame_size: frame_size,
+        };
+    }
+
+    /// Get the frame size expected by this socket.
+    pub fn frame_size(&self) -> usize {
+        return self.frame_size;
+    }
+
+    /// Receive a [`VDIFFrame`].
+    pub async fn recv_frame(&self) -> Result<VDIFFrame> {
+        return self.recv_frame_from().await.map(|(frame, _)| frame);
+    }
+
+    /// Receive a [`VDIFFrame`], also returning the address it came from.
+    ///
+    /// Datagrams of any other size than the frame size are reported as [`InvalidData`](ErrorKind::InvalidData)
+    /// errors, as by [`VDIFUDP`](crate::net::udp::VDIFUDP).
+    pub async fn recv_frame_from(&self) -> Result<(VDIFFrame, SocketAddr)> {
+        let mut frame = VDIFFrame::empty(self.frame_size);
+        let (received, addr) = self
+            .sock
+            .async_io(Interest::READABLE, || {
+                recv_datagram(SockRef::from(&self.sock), frame.as_mut_bytes())
+            })
+            .await?;
+        if received != self.frame_size {
+            return Err(VDIFError::ShortFrame {
+                expected: self.frame_size,
+                received: Some(received),
+            }
+            .into());
+        }
+        return Ok((frame, addr));
+    }
+
+    /// Send a [`VDIFFrame`] to the connected peer.
+    pub async fn send_frame(&self, frame: VDIFFrame) -> Result<()> {
+        let sent = self.sock.send(frame.as_bytes()).await?;
+        if sent != frame.bytesize() {
+            return Err(Error::new(
+                ErrorKind::WriteZero,
+                "Failed to send the whole VDIF frame",
+            ));
+        }
+        return Ok(());
+    }
+
+    /// Send a [`VDIFFrame`] to `target`.
+    pub async fn send_frame_to<A: ToSocketAddrs>(&self, frame: VDIFFrame, target: A) -> Result<()> {
+        let sent = self.sock.send_to(frame.as_bytes(), target).await?;
+        if sent != frame.bytesize() {
+            return Err(Error::new(
+                ErrorKind::WriteZero,
+                "Failed to send the whole VDIF frame",
+            ));
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::VDIFHeader;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        return ::tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()
+            .unwrap()
+            .block_on(future);
+    }
+
+    #[test]
+    fn test_async_roundtrip() {
+        block_on(async {
+            let mut frame = VDIFFrame::empty(64);
+            frame.set_header(VDIFHeader {
+                size: 8,
+                frameno: 3,
+                ..Default::default()
+            });
+
+            let mut buf = Vec::new();
+            write_frame(&mut buf, VDIFFrame::from_slice(frame.as_slice()))
+                .await
+                .unwrap();
+            let read = read_frame(&mut buf.as_slice(), 64).await.unwrap();
+            assert_eq!(read.as_slice(), frame.as_slice());
+
+            let receiver = AsyncVDIFUDP::bind("127.0.0.1:0", 64).await.unwrap();
+            let sender = AsyncVDIFUDP::bind("127.0.0.1:0", 64).await.unwrap();
+            sender
+                .send_frame_to(frame, receiver.sock.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (received, addr) = receiver.recv_frame_from().await.unwrap();
+            assert_eq!(received.get_header().frameno, 3);
+            assert_eq!(addr, sender.sock.local_addr().unwrap());
+
+            let target = receiver.sock.local_addr().unwrap();
+            for size in [32, 96] {
+                let frame = VDIFFrame::empty(size);
+                sender.sock.send_to(frame.as_bytes(), target).await.unwrap();
+            }
+            assert!(matches!(
+                VDIFError::from_io(&receiver.recv_frame().await.unwrap_err()),
+                Some(VDIFError::ShortFrame { .. })
+            ));
+            assert_eq!(
+                receiver.recv_frame().await.unwrap_err().kind(),
+                ErrorKind::InvalidData
+            );
+        });
+    }
+}
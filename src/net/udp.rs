@@ -0,0 +1,650 @@
+//! Types and methods to assist in sending and receiving VDIF frames using UDP.
+//!
+//! This implementation assumes that one datagram consists of a single, complete VDIF frame.
+
+use std::io::{Error, ErrorKind, Result};
+use std::mem::MaybeUninit;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+#[cfg(all(feature = "mio", unix))]
+use std::os::fd::AsRawFd;
+
+#[cfg(all(feature = "mio", unix))]
+use mio::unix::SourceFd;
+#[cfg(target_os = "linux")]
+use socket2::{Domain, Protocol, Socket, Type};
+use socket2::{MaybeUninitSlice, SockRef};
+
+use crate::error::VDIFError;
+use crate::frame::VDIFFrame;
+use crate::header_encoding::MASK_FRAME_NO;
+use crate::io::VDIFRead;
+#[cfg(feature = "crc32c")]
+use crate::net::integrity::ChecksumSampler;
+use crate::net::multicast::{bind_multicast, MulticastGroup};
+use crate::net::options::SocketOptions;
+#[cfg(all(feature = "busy-poll", target_os = "linux"))]
+use crate::net::poll::{recvmmsg_dontwait, set_busy_poll};
+use crate::net::stats::{kernel_udp_stats, ReceiverStats};
+use crate::net::{check_datagram_frame_size, check_fits_datagram, RecvEvent, MAX_DATAGRAM_SIZE};
+use crate::provenance::{Provenance, Tagged};
+use crate::validation::ValidationLevel;
+
+/// How a [`VDIFUDP`] waits for datagrams.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RecvMode {
+    /// Block in the kernel until a datagram arrives, or until the socket's
+    /// [`read_timeout`](std::net::UdpSocket::read_timeout) expires. This is the default.
+    #[default]
+    Timeout,
+    /// Put the socket in non-blocking mode and poll it in a tight loop until a datagram arrives. This keeps a core
+    /// fully busy but avoids the wake-up latency of blocking, so suits deployments with a core dedicated to receiving.
+    /// Any read timeout is ignored.
+    BusyPoll,
+}
+
+/// A simple wrapper around a [`UdpSocket`] to [`recv`](std::net::UdpSocket::recv) frames.
+///
+/// Does not perform any logic or buffering, so all the normal rules and expectations around UDP apply.
+pub struct VDIFUDP {
+    /// The underlying [`UdpSocket`].
+    pub sock: UdpSocket,
+    frame_size: usize,
+    resize_on_change: bool,
+    scratch: Vec<u8>,
+    mode: RecvMode,
+    validation: ValidationLevel,
+    frames: u64,
+    bytes: u64,
+    #[cfg(feature = "crc32c")]
+    sampler: Option<ChecksumSampler>,
+}
+
+impl VDIFUDP {
+    /// Construct a new [`VDIFUDP`] type attached to a specific socket.
+    ///
+    /// Fails with [`InvalidInput`](ErrorKind::InvalidInput) if `frame_size` exceeds the largest UDP datagram. Frames
+    /// that large must be split across datagrams, see [`fragment`](crate::net::fragment).
+    pub fn new<A: ToSocketAddrs>(addr: A, frame_size: usize) -> Result<Self> {
+        return Self::from_socket(UdpSocket::bind(addr)?, frame_size);
+    }
+
+    /// Construct a new [`VDIFUDP`] bound to `addr`, applying `options` to its socket.
+    pub fn with_options<A: ToSocketAddrs>(
+        addr: A,
+        frame_size: usize,
+        options: &SocketOptions,
+    ) -> Result<Self> {
+        let receiver = Self::new(addr, frame_size)?;
+        options.apply(&receiver.sock)?;
+        return Ok(receiver);
+    }
+
+    /// Construct a new [`VDIFUDP`] bound to `port` and joined to a multicast `group`. See
+    /// [`bind_multicast`](crate::net::multicast::bind_multicast).
+    pub fn multicast(group: &MulticastGroup, port: u16, frame_size: usize) -> Result<Self> {
+        return Self::from_socket(bind_multicast(group, port)?, frame_size);
+    }
+
+    /// Open `shards` receivers bound to the same `addr` with `SO_REUSEPORT`, so the kernel spreads incoming datagrams
+    /// across them and each can be serviced on its own core. Only available on Linux.
+    ///
+    /// The kernel picks a receiver by hashing the addresses and ports of each datagram, so every datagram of one flow
+    /// goes to the same receiver. Sharding only helps when frames arrive from several senders or source ports, such as
+    /// one per thread.
+    #[cfg(target_os = "linux")]
+    pub fn sharded<A: ToSocketAddrs>(
+        addr: A,
+        frame_size: usize,
+        shards: usize,
+    ) -> Result<Vec<Self>> {
+        let mut addr = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "No address to bind to"))?;
+        let mut receivers = Vec::with_capacity(shards);
+        for _ in 0..shards {
+            let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+            socket.set_reuse_port(true)?;
+            socket.bind(&addr.into())?;
+            let sock: UdpSocket = socket.into();
+            // Bind the rest to the port the kernel chose for the first, if asked for any port
+            addr = sock.local_addr()?;
+            receivers.push(Self::from_socket(sock, frame_size)?);
+        }
+        return Ok(receivers);
+    }
+
+    /// Construct a new [`VDIFUDP`] from an already bound socket.
+    pub fn from_socket(sock: UdpSocket, frame_size: usize) -> Result<Self> {
+        check_fits_datagram(frame_size)?;
+        return Ok(Self {
+            sock: sock,
+            frame_size: frame_size,
+            resize_on_change: false,
+            scratch: Vec::new(),
+            mode: RecvMode::Timeout,
+            validation: ValidationLevel::None,
+            frames: 0,
+            bytes: 0,
+            #[cfg(feature = "crc32c")]
+            sampler: None,
+        });
+    }
+
+    /// Get the statistics of this receiver, including the kernel's drop counters for the socket where they can be
+    /// read (currently only on Linux).
+    pub fn stats(&self) -> ReceiverStats {
+        return ReceiverStats {
+            frames: self.frames,
+            bytes: self.bytes,
+            kernel: kernel_udp_stats(&self.sock).ok(),
+        };
+    }
+
+    /// Set a [`ChecksumSampler`] to checksum a sample of the frames sent or received through this socket, or `None` to
+    /// stop checksumming. Requires the `crc32c` feature.
+    #[cfg(feature = "crc32c")]
+    pub fn set_checksum_sampler(&mut self, sampler: Option<ChecksumSampler>) {
+        self.sampler = sampler;
+    }
+
+    /// Get the [`ChecksumSampler`] of this socket, if any, to read or publish its checksums. Requires the `crc32c`
+    /// feature.
+    #[cfg(feature = "crc32c")]
+    pub fn checksum_sampler(&mut self) -> Option<&mut ChecksumSampler> {
+        return self.sampler.as_mut();
+    }
+
+    /// Apply `options` to this receiver's socket.
+    pub fn set_socket_options(&self, options: &SocketOptions) -> Result<()> {
+        return options.apply(&self.sock);
+    }
+
+    /// Join a multicast `group` on this receiver's socket.
+    pub fn join_multicast(&self, group: &MulticastGroup) -> Result<()> {
+        return group.join(&self.sock);
+    }
+
+    /// Leave a multicast `group` on this receiver's socket.
+    pub fn leave_multicast(&self, group: &MulticastGroup) -> Result<()> {
+        return group.leave(&self.sock);
+    }
+
+    /// Get the frame size currently expected by this receiver.
+    pub fn frame_size(&self) -> usize {
+        return self.frame_size;
+    }
+
+    /// Set whether [`recv_checked`](VDIFUDP::recv_checked) adopts the new frame size when it receives a frame of a
+    /// different size. Off by default.
+    pub fn set_resize_on_change(&mut self, resize: bool) {
+        self.resize_on_change = resize;
+    }
+
+    /// Set how this receiver waits for datagrams. [`RecvMode::Timeout`] by default.
+    pub fn set_recv_mode(&mut self, mode: RecvMode) -> Result<()> {
+        self.sock.set_nonblocking(mode == RecvMode::BusyPoll)?;
+        self.mode = mode;
+        return Ok(());
+    }
+
+    /// Get how this receiver waits for datagrams.
+    pub fn recv_mode(&self) -> RecvMode {
+        return self.mode;
+    }
+
+    /// Set the `SO_BUSY_POLL` option of the socket, letting the kernel busy-poll the network device queue for up to
+    /// `usecs` microseconds on each receive. Raising it above the system default may need `CAP_NET_ADMIN`. Requires the
+    /// `busy-poll` feature, and is only available on Linux.
+    #[cfg(all(feature = "busy-poll", target_os = "linux"))]
+    pub fn set_busy_poll(&mut self, usecs: u32) -> Result<()> {
+        return set_busy_poll(&self.sock, usecs);
+    }
+
+    /// Set how thoroughly to check each frame received, returning an [`InvalidData`](std::io::ErrorKind::InvalidData)
+    /// error for frames that fail. [`ValidationLevel::None`] by default.
+    pub fn set_validation(&mut self, validation: ValidationLevel) {
+        self.validation = validation;
+    }
+
+    /// [`recv`](std::net::UdpSocket::recv) a [`VDIFFrame`], detecting datagrams whose size differs from the
+    /// configured frame size rather than truncating them or leaving part of the frame empty.
+    ///
+    /// This receives into an internal buffer large enough for any datagram, so costs one extra copy compared to
+    /// [`recv_frame`](VDIFUDP::recv_frame).
+    pub fn recv_checked(&mut self) -> Result<RecvEvent<VDIFFrame>> {
+        self.scratch.resize(MAX_DATAGRAM_SIZE, 0);
+        let (received, _) = recv_from(&self.sock, self.mode, &mut self.scratch)?;
+        check_datagram_frame_size(received)?;
+        self.frames += 1;
+        self.bytes += received as u64;
+
+        let mut frame = VDIFFrame::empty(received);
+        frame
+            .as_mut_bytes()
+            .copy_from_slice(&self.scratch[0..received]);
+        self.validation.check(&frame)?;
+        #[cfg(feature = "crc32c")]
+        if let Some(sampler) = self.sampler.as_mut() {
+            sampler.observe(&frame);
+        }
+        if received == self.frame_size {
+            return Ok(RecvEvent::Frame(frame));
+        }
+
+        let previous = self.frame_size;
+        if self.resize_on_change {
+            self.frame_size = received;
+        }
+        return Ok(RecvEvent::FrameSizeChanged {
+            previous: previous,
+            received: received,
+            frame: if self.resize_on_change {
+                Some(frame)
+            } else {
+                None
+            },
+        });
+    }
+
+    /// [`recv`](std::net::UdpSocket::recv) a [`VDIFFrame`].
+    pub fn recv_frame(&mut self) -> Result<VDIFFrame> {
+        return self.recv_frame_from().map(|(frame, _)| frame);
+    }
+
+    /// [`recv_from`](std::net::UdpSocket::recv_from) a [`VDIFFrame`], also returning the address it came from.
+    ///
+    /// Datagrams of any other size than the frame size are reported as [`InvalidData`](ErrorKind::InvalidData)
+    /// errors, so use [`recv_checked`](VDIFUDP::recv_checked) if the frame size may change.
+    pub fn recv_frame_from(&mut self) -> Result<(VDIFFrame, SocketAddr)> {
+        return self.recv_frame_with(self.mode);
+    }
+
+    /// Receive a [`VDIFFrame`] if one is waiting, returning an error of kind [`WouldBlock`](ErrorKind::WouldBlock)
+    /// otherwise. Never spins, whatever the [`RecvMode`].
+    ///
+    /// The socket must be in non-blocking mode, set with [`set_nonblocking`](VDIFUDP::set_nonblocking) or by
+    /// registering the receiver with an event loop, or this waits like [`recv_frame`](VDIFUDP::recv_frame).
+    pub fn try_read_frame(&mut self) -> Result<VDIFFrame> {
+        return self
+            .recv_frame_with(RecvMode::Timeout)
+            .map(|(frame, _)| frame);
+    }
+
+    /// Put the socket in or out of non-blocking mode, for use with [`try_read_frame`](VDIFUDP::try_read_frame).
+    pub fn set_nonblocking(&mut self, nonblocking: bool) -> Result<()> {
+        return self.sock.set_nonblocking(nonblocking);
+    }
+
+    fn recv_frame_with(&mut self, mode: RecvMode) -> Result<(VDIFFrame, SocketAddr)> {
+        let mut frame = VDIFFrame::empty(self.frame_size);
+        let (received, addr) = recv_from(&self.sock, mode, frame.as_mut_bytes())?;
+        if received != self.frame_size {
+            return Err(VDIFError::ShortFrame {
+                expected: self.frame_size,
+                received: Some(received),
+            }
+            .into());
+        }
+        self.frames += 1;
+        self.bytes += received as u64;
+        self.validation.check(&frame)?;
+        #[cfg(feature = "crc32c")]
+        if let Some(sampler) = self.sampler.as_mut() {
+            sampler.observe(&frame);
+        }
+        return Ok((frame, addr));
+    }
+
+    /// Receive up to `max` [`VDIFFrame`]s with a single `recvmmsg` call, polling the socket without blocking until at
+    /// least one frame arrives. Requires the `busy-poll` feature, and is only available on Linux.
+    ///
+    /// This is the receive loop of [`RecvMode::BusyPoll`] in batch form, so busy-waits whatever the receive mode.
+    /// Datagrams of any other size than the frame size are reported as [`InvalidData`](ErrorKind::InvalidData)
+    /// errors, and any frames received alongside them are lost.
+    #[cfg(all(feature = "busy-poll", target_os = "linux"))]
+    pub fn recv_frames(&mut self, max: usize) -> Result<Vec<VDIFFrame>> {
+        let mut frames: Vec<VDIFFrame> = (0..max)
+            .map(|_| VDIFFrame::empty(self.frame_size))
+            .collect();
+        let sizes = loop {
+            let mut bufs: Vec<&mut [u8]> = frames.iter_mut().map(|f| f.as_mut_bytes()).collect();
+            match recvmmsg_dontwait(&self.sock, &mut bufs) {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => std::hint::spin_loop(),
+                other => break other?,
+            }
+        };
+        frames.truncate(sizes.len());
+        for (frame, (size, truncated)) in frames.iter().zip(sizes) {
+            if truncated {
+                return Err(datagram_too_long(self.frame_size));
+            }
+            if size != self.frame_size {
+                return Err(VDIFError::ShortFrame {
+                    expected: self.frame_size,
+                    received: Some(size),
+                }
+                .into());
+            }
+            self.frames += 1;
+            self.bytes += size as u64;
+            self.validation.check(frame)?;
+            #[cfg(feature = "crc32c")]
+            if let Some(sampler) = self.sampler.as_mut() {
+                sampler.observe(frame);
+            }
+        }
+        return Ok(frames);
+    }
+
+    /// [`send`](std::net::UdpSocket::send) a [`VDIFFrame`].
+    pub fn send_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        #[cfg(feature = "crc32c")]
+        if let Some(sampler) = self.sampler.as_mut() {
+            sampler.observe(&frame);
+        }
+        let _ = self.sock.send(frame.as_bytes())?;
+        return Ok(());
+    }
+}
+
+impl VDIFRead for VDIFUDP {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        return self.recv_frame();
+    }
+
+    fn read_tagged(&mut self) -> Result<Tagged> {
+        let (frame, addr) = self.recv_frame_from()?;
+        let mut provenance = Provenance::now();
+        provenance.source = Some(addr);
+        return Ok(Tagged {
+            frame: frame,
+            provenance: provenance,
+        });
+    }
+}
+
+/// Registers the socket with a [`mio`] event loop, putting it in non-blocking mode. Read frames with
+/// [`try_read_frame`](VDIFUDP::try_read_frame) until it returns [`WouldBlock`](ErrorKind::WouldBlock).
+#[cfg(all(feature = "mio", unix))]
+impl mio::event::Source for VDIFUDP {
+    fn register(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> Result<()> {
+        self.sock.set_nonblocking(true)?;
+        return SourceFd(&self.sock.as_raw_fd()).register(registry, token, interests);
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> Result<()> {
+        return SourceFd(&self.sock.as_raw_fd()).reregister(registry, token, interests);
+    }
+
+    fn deregister(&mut self, registry: &mio::Registry) -> Result<()> {
+        return SourceFd(&self.sock.as_raw_fd()).deregister(registry);
+    }
+}
+
+// Receive a datagram into `buf`, spinning on a non-blocking socket in busy-poll mode.
+fn recv_from(sock: &UdpSocket, mode: RecvMode, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
+    loop {
+        match recv_datagram(SockRef::from(sock), buf) {
+            Err(e) if mode == RecvMode::BusyPoll && e.kind() == ErrorKind::WouldBlock => {
+                std::hint::spin_loop()
+            }
+            result => return result,
+        }
+    }
+}
+
+// Receive a single datagram into `buf`. Datagrams too long for `buf` are reported as errors rather than truncated.
+pub(crate) fn recv_datagram(sock: SockRef<'_>, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
+    let len = buf.len();
+    // Safety: the socket only writes initialised bytes into the buffer
+    let uninit = unsafe { &mut *(buf as *mut [u8] as *mut [MaybeUninit<u8>]) };
+    let (received, flags, addr) = sock.recv_from_vectored(&mut [MaybeUninitSlice::new(uninit)])?;
+    if flags.is_truncated() {
+        return Err(datagram_too_long(len));
+    }
+    let addr = addr
+        .as_socket()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Received from a non-IP address"))?;
+    return Ok((received, addr));
+}
+
+fn datagram_too_long(frame_size: usize) -> Error {
+    return Error::new(
+        ErrorKind::InvalidData,
+        format!(
+            "Received a datagram longer than the {} byte frame size",
+            frame_size
+        ),
+    );
+}
+
+/// Allows reading VDIF frames in order.
+///
+/// More specifically, [`VDIFOrderedUDP`] implements a simple sequence counting algorithm to ensure that the frame
+/// returned by [`next_frame`](VDIFOrderedUDP::next_frame) does not precede the frame previously fetched by the
+/// same function.
+///
+/// For example, say the user has received frame `i` from a call to [`next_frame`](VDIFOrderedUDP::next_frame).
+/// Upon calling [`next_frame`](VDIFOrderedUDP::next_frame) again, the value returned is guaranteed to be one of
+/// the following:
+///
+/// - The `i + 1` th frame (most likely).
+/// - The `i + n` th frame, where `n` is any *positive* integer.
+/// - A duplicate of the `i`th frame.
+/// - `None`
+///
+/// Frames received out of order are simply discarded.
+pub struct VDIFOrderedUDP {
+    vdifudp: VDIFUDP,
+
+    frame_rate: u32,
+    expecting_frame: u32,
+}
+
+impl VDIFOrderedUDP {
+    /// Construct a new [`VDIFOrderedUDP`] type. Note `frame_rate` is the the number of frames contained within one second *per* thread.
+    pub fn new<A: ToSocketAddrs>(addr: A, frame_size: usize, frame_rate: u32) -> Result<Self> {
+        let vdifudp = VDIFUDP::new(addr, frame_size)?;
+        return Ok(Self {
+            vdifudp: vdifudp,
+            frame_rate: frame_rate,
+            expecting_frame: 0,
+        });
+    }
+
+    /// Return the next frame in the stream, or `None` if the frame would be out of order.
+    pub fn next_frame(&mut self) -> Result<Option<VDIFFrame>> {
+        let in_frame = self.vdifudp.recv_frame()?;
+        let in_frame_no = check_frame_no(&in_frame);
+        if self.expecting_frame <= in_frame_no {
+            // Frame is good, increment the expected frame appropriately and
+            // return the frame
+            self.expecting_frame = if self.expecting_frame < self.frame_rate {
+                in_frame_no + 1
+            } else {
+                0
+            };
+            return Ok(Some(in_frame));
+        } else {
+            // Frame is not in order, so just discard it after setting the counter properly.
+            self.expecting_frame = if self.expecting_frame < self.frame_rate {
+                in_frame_no + 1
+            } else {
+                0
+            };
+
+            return Ok(None);
+        }
+    }
+
+    /// Get a reference to the underlying [`UdpSocket`].
+    pub fn socket_ref(&self) -> &UdpSocket {
+        return &self.vdifudp.sock;
+    }
+}
+
+/// Quickly check the frame number without decoding the whole header
+fn check_frame_no(frame: &VDIFFrame) -> u32 {
+    return frame.get_word(1) & MASK_FRAME_NO;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_read_frame() {
+        let mut receiver = VDIFUDP::new("127.0.0.1:0", 64).unwrap();
+        receiver.set_nonblocking(true).unwrap();
+        assert_eq!(
+            receiver.try_read_frame().unwrap_err().kind(),
+            ErrorKind::WouldBlock
+        );
+
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = receiver.sock.local_addr().unwrap();
+        let frame = VDIFFrame::from_header(crate::header::VDIFHeader {
+            frameno: 7,
+            size: 8,
+            ..Default::default()
+        });
+        sender.send_to(frame.as_bytes(), addr).unwrap();
+
+        #[cfg(all(feature = "mio", unix))]
+        {
+            let mut poll = mio::Poll::new().unwrap();
+            let mut events = mio::Events::with_capacity(4);
+            poll.registry()
+                .register(&mut receiver, mio::Token(0), mio::Interest::READABLE)
+                .unwrap();
+            poll.poll(&mut events, Some(std::time::Duration::from_secs(1)))
+                .unwrap();
+            assert_eq!(events.iter().next().unwrap().token(), mio::Token(0));
+        }
+        #[cfg(not(all(feature = "mio", unix)))]
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert_eq!(receiver.try_read_frame().unwrap().get_header().frameno, 7);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_sharded() {
+        let receivers = VDIFUDP::sharded("127.0.0.1:0", 64, 3).unwrap();
+        let addr = receivers[0].sock.local_addr().unwrap();
+        assert!(receivers
+            .iter()
+            .all(|r| r.sock.local_addr().unwrap() == addr));
+
+        // One sender per source port, so the datagrams spread over the receivers
+        let frame = VDIFFrame::from_header(crate::header::VDIFHeader {
+            size: 8,
+            ..Default::default()
+        });
+        for _ in 0..8 {
+            let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+            sender.send_to(frame.as_bytes(), addr).unwrap();
+        }
+        let mut received = 0;
+        for mut receiver in receivers {
+            receiver.sock.set_nonblocking(true).unwrap();
+            while receiver.recv_frame().is_ok() {
+                received += 1;
+            }
+        }
+        assert_eq!(received, 8);
+    }
+
+    #[test]
+    fn test_recv_frame_wrong_size() {
+        let mut receiver = VDIFUDP::new("127.0.0.1:0", 64).unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        sender.connect(receiver.sock.local_addr().unwrap()).unwrap();
+
+        for size in [32, 96, 64] {
+            sender.send(VDIFFrame::empty(size).as_bytes()).unwrap();
+        }
+        assert!(matches!(
+            VDIFError::from_io(&receiver.recv_frame().unwrap_err()),
+            Some(VDIFError::ShortFrame { .. })
+        ));
+        assert_eq!(
+            receiver.recv_frame().unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        assert_eq!(receiver.recv_frame().unwrap().bytesize(), 64);
+    }
+
+    #[test]
+    fn test_recv_checked() {
+        let mut receiver = VDIFUDP::new("127.0.0.1:0", 64).unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        sender.connect(receiver.sock.local_addr().unwrap()).unwrap();
+
+        for size in [64, 96, 96, 96] {
+            sender.send(VDIFFrame::empty(size).as_bytes()).unwrap();
+        }
+        assert!(matches!(
+            receiver.recv_checked().unwrap(),
+            RecvEvent::Frame(_)
+        ));
+        assert!(matches!(
+            receiver.recv_checked().unwrap(),
+            RecvEvent::FrameSizeChanged {
+                previous: 64,
+                received: 96,
+                frame: None
+            }
+        ));
+
+        receiver.set_resize_on_change(true);
+        match receiver.recv_checked().unwrap() {
+            RecvEvent::FrameSizeChanged { frame, .. } => assert_eq!(frame.unwrap().bytesize(), 96),
+            RecvEvent::Frame(_) => panic!("Expected a frame size change"),
+        }
+        assert_eq!(receiver.frame_size(), 96);
+        assert!(matches!(
+            receiver.recv_checked().unwrap(),
+            RecvEvent::Frame(_)
+        ));
+
+        let stats = receiver.stats();
+        assert_eq!((stats.frames, stats.bytes), (4, 352));
+        #[cfg(target_os = "linux")]
+        assert_eq!(stats.kernel.unwrap().socket_drops, 0);
+    }
+
+    #[test]
+    fn test_busy_poll() {
+        let mut receiver = VDIFUDP::new("127.0.0.1:0", 64).unwrap();
+        receiver.set_recv_mode(RecvMode::BusyPoll).unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        sender.connect(receiver.sock.local_addr().unwrap()).unwrap();
+
+        let handle = std::thread::spawn(move || {
+            for _ in 0..3 {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+                sender.send(VDIFFrame::empty(64).as_bytes()).unwrap();
+            }
+        });
+        assert_eq!(receiver.recv_frame().unwrap().bytesize(), 64);
+        #[cfg(all(feature = "busy-poll", target_os = "linux"))]
+        {
+            let mut received = 0;
+            while received < 2 {
+                received += receiver.recv_frames(4).unwrap().len();
+            }
+        }
+        handle.join().unwrap();
+    }
+}
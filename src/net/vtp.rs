@@ -0,0 +1,262 @@
+//! Types and methods to assist in sending and receiving VDIF frames over UDP, using the VDIF Transport Protocol (VTP).
+//!
+//! The VDIF Transport Protocol is defined [here](https://vlbi.org/wp-content/uploads/2019/03/2012.10.16_VTP_0.9.7.pdf)
+//!
+//! This implementation assumes that one datagram consists of a single, complete VDIF frame with an additional 64-bit integer
+//! inserted at the start of the datagram.
+
+use std::io::Result;
+use std::net::{ToSocketAddrs, UdpSocket};
+#[cfg(all(feature = "mio", unix))]
+use std::os::fd::AsRawFd;
+
+#[cfg(all(feature = "mio", unix))]
+use mio::unix::SourceFd;
+
+use crate::frame::VDIFFrame;
+use crate::io::VDIFRead;
+use crate::net::multicast::{bind_multicast, MulticastGroup};
+use crate::net::options::SocketOptions;
+use crate::net::{check_datagram_frame_size, check_fits_datagram, RecvEvent, MAX_DATAGRAM_SIZE};
+use crate::validation::ValidationLevel;
+
+/// A simple wrapper around a [`UdpSocket`] to [`recv`](std::net::UdpSocket::recv) frames.
+///
+/// Does not perform any logic or buffering, so all the normal rules and expectations around UDP apply.
+pub struct VDIFVTP {
+    /// The underlying [`UdpSocket`].
+    pub sock: UdpSocket,
+    frame_size: usize,
+    resize_on_change: bool,
+    scratch: Vec<u8>,
+    validation: ValidationLevel,
+}
+
+impl VDIFVTP {
+    /// Construct a new [`VDIFVTP`] type attached to a specific socket. Note that `frame_size` is still just the size of the
+    /// VDIF frame in bytes.
+    pub fn new<A: ToSocketAddrs>(addr: A, frame_size: usize) -> Result<Self> {
+        return Self::from_socket(UdpSocket::bind(addr)?, frame_size);
+    }
+
+    /// Construct a new [`VDIFVTP`] bound to `addr`, applying `options` to its socket.
+    pub fn with_options<A: ToSocketAddrs>(
+        addr: A,
+        frame_size: usize,
+        options: &SocketOptions,
+    ) -> Result<Self> {
+        let receiver = Self::new(addr, frame_size)?;
+        options.apply(&receiver.sock)?;
+        return Ok(receiver);
+    }
+
+    /// Construct a new [`VDIFVTP`] bound to `port` and joined to a multicast `group`. See
+    /// [`bind_multicast`](crate::net::multicast::bind_multicast).
+    pub fn multicast(group: &MulticastGroup, port: u16, frame_size: usize) -> Result<Self> {
+        return Self::from_socket(bind_multicast(group, port)?, frame_size);
+    }
+
+    /// Construct a new [`VDIFVTP`] from an already bound socket.
+    pub fn from_socket(sock: UdpSocket, frame_size: usize) -> Result<Self> {
+        check_fits_datagram(frame_size + 8)?;
+        return Ok(Self {
+            sock: sock,
+            frame_size: frame_size,
+            resize_on_change: false,
+            scratch: Vec::new(),
+            validation: ValidationLevel::None,
+        });
+    }
+
+    /// Apply `options` to this receiver's socket.
+    pub fn set_socket_options(&self, options: &SocketOptions) -> Result<()> {
+        return options.apply(&self.sock);
+    }
+
+    /// Join a multicast `group` on this receiver's socket.
+    pub fn join_multicast(&self, group: &MulticastGroup) -> Result<()> {
+        return group.join(&self.sock);
+    }
+
+    /// Leave a multicast `group` on this receiver's socket.
+    pub fn leave_multicast(&self, group: &MulticastGroup) -> Result<()> {
+        return group.leave(&self.sock);
+    }
+
+    /// Get the VDIF frame size currently expected by this receiver.
+    pub fn frame_size(&self) -> usize {
+        return self.frame_size;
+    }
+
+    /// Set whether [`recv_checked`](VDIFVTP::recv_checked) adopts the new frame size when it receives a frame of a
+    /// different size. Off by default.
+    pub fn set_resize_on_change(&mut self, resize: bool) {
+        self.resize_on_change = resize;
+    }
+
+    /// Set how thoroughly to check each frame received, returning an [`InvalidData`](std::io::ErrorKind::InvalidData)
+    /// error for frames that fail. [`ValidationLevel::None`] by default.
+    pub fn set_validation(&mut self, validation: ValidationLevel) {
+        self.validation = validation;
+    }
+
+    /// [`recv`](std::net::UdpSocket::recv) a [`VDIFFrame`] and the attached `u64` sequence number, detecting
+    /// datagrams whose size differs from the configured frame size. See
+    /// [`VDIFUDP::recv_checked`](crate::net::udp::VDIFUDP::recv_checked).
+    pub fn recv_checked(&mut self) -> Result<RecvEvent<(u64, VDIFFrame)>> {
+        self.scratch.resize(MAX_DATAGRAM_SIZE, 0);
+        let received = self.sock.recv(&mut self.scratch)?;
+        let frame_size = received.saturating_sub(8);
+        check_datagram_frame_size(frame_size)?;
+
+        let sequence_number = u64::from_le_bytes(self.scratch[0..8].try_into().unwrap());
+        let mut frame = VDIFFrame::empty(frame_size);
+        frame
+            .as_mut_bytes()
+            .copy_from_slice(&self.scratch[8..received]);
+        self.validation.check(&frame)?;
+        if frame_size == self.frame_size {
+            return Ok(RecvEvent::Frame((sequence_number, frame)));
+        }
+
+        let previous = self.frame_size;
+        if self.resize_on_change {
+            self.frame_size = frame_size;
+        }
+        return Ok(RecvEvent::FrameSizeChanged {
+            previous: previous,
+            received: frame_size,
+            frame: if self.resize_on_change {
+                Some((sequence_number, frame))
+            } else {
+                None
+            },
+        });
+    }
+
+    /// Receive a [`VDIFFrame`] if one is waiting, discarding the sequence number, and returning an error of kind
+    /// [`WouldBlock`](std::io::ErrorKind::WouldBlock) otherwise. Use [`recv_frame`](VDIFVTP::recv_frame) on a
+    /// non-blocking socket to keep the sequence number.
+    ///
+    /// The socket must be in non-blocking mode, set with [`set_nonblocking`](VDIFVTP::set_nonblocking) or by
+    /// registering the receiver with an event loop, or this waits like [`recv_frame`](VDIFVTP::recv_frame).
+    pub fn try_read_frame(&mut self) -> Result<VDIFFrame> {
+        return Ok(self.recv_frame()?.1);
+    }
+
+    /// Put the socket in or out of non-blocking mode, for use with [`try_read_frame`](VDIFVTP::try_read_frame).
+    pub fn set_nonblocking(&mut self, nonblocking: bool) -> Result<()> {
+        return self.sock.set_nonblocking(nonblocking);
+    }
+
+    /// [`recv`](std::net::UdpSocket::recv) a [`VDIFFrame`] and the attached `u64` sequence number.
+    pub fn recv_frame(&mut self) -> Result<(u64, VDIFFrame)> {
+        // Need to get the first u64 from a bunch of u32s. Allocate u64s instead to prevent alignment issues
+        // then we can just unsafely reinterpret the rest of the u64s as u32s.
+        let mut vtp_frame_buf: Box<[u64]> = vec![0; self.frame_size / 8 + 1].into_boxed_slice();
+        let out_frame: VDIFFrame;
+        unsafe {
+            // Read bytes into vtp_frame_buf
+            self.sock.recv(std::slice::from_raw_parts_mut(
+                vtp_frame_buf.as_mut_ptr() as *mut u8,
+                self.frame_size + 8,
+            ))?;
+            // Reinterpret all but the first u64 as u32s and copy them to a new VDIF frame.
+            out_frame = VDIFFrame::from_slice(std::slice::from_raw_parts(
+                (vtp_frame_buf.as_ptr().add(1)) as *const u32,
+                self.frame_size / 4,
+            ));
+        }
+
+        self.validation.check(&out_frame)?;
+        let sequence_number = vtp_frame_buf[0];
+        return Ok((sequence_number, out_frame));
+    }
+}
+
+/// Reads frames with [`recv_frame`](VDIFVTP::recv_frame), discarding the sequence number.
+impl VDIFRead for VDIFVTP {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        return Ok(self.recv_frame()?.1);
+    }
+}
+
+/// Registers the socket with a [`mio`] event loop, putting it in non-blocking mode. Read frames with
+/// [`recv_frame`](VDIFVTP::recv_frame) until it returns [`WouldBlock`](std::io::ErrorKind::WouldBlock).
+#[cfg(all(feature = "mio", unix))]
+impl mio::event::Source for VDIFVTP {
+    fn register(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> Result<()> {
+        self.sock.set_nonblocking(true)?;
+        return SourceFd(&self.sock.as_raw_fd()).register(registry, token, interests);
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> Result<()> {
+        return SourceFd(&self.sock.as_raw_fd()).reregister(registry, token, interests);
+    }
+
+    fn deregister(&mut self, registry: &mio::Registry) -> Result<()> {
+        return SourceFd(&self.sock.as_raw_fd()).deregister(registry);
+    }
+}
+
+/// Allows reading VDIF frames in order. Uses the VTP sequence number instead of the VDIF frame number.
+///
+/// More specifically, [`VDIFOrderedVTP`] implements a simple sequence counting algorithm to ensure that the frame
+/// returned by [`next_frame`](VDIFOrderedVTP::next_frame) does not precede the frame previously fetched by the
+/// same function.
+///
+/// For example, say the user has received frame `i` from a call to [`next_frame`](VDIFOrderedVTP::next_frame).
+/// Upon calling [`next_frame`](VDIFOrderedVTP::next_frame) again, the value returned is guaranteed to be one of
+/// the following:
+///
+/// - The `i + 1` th frame (most likely).
+/// - The `i + n` th frame, where `n` is any *positive* integer.
+/// - A duplicate of the `i`th frame.
+/// - `None`
+///
+/// Frames received out of order are simply discarded.
+pub struct VDIFOrderedVTP {
+    vdifvtp: VDIFVTP,
+    expecting_frame: u64,
+}
+
+impl VDIFOrderedVTP {
+    /// Construct a new [`VDIFOrderedVTP`] type.
+    pub fn new<A: ToSocketAddrs>(addr: A, frame_size: usize) -> Result<Self> {
+        let vdifvtp = VDIFVTP::new(addr, frame_size)?;
+        return Ok(Self {
+            vdifvtp: vdifvtp,
+            expecting_frame: 0,
+        });
+    }
+
+    /// Return the next frame in the stream along with its sequence number, or `None` if the frame would be out of order.
+    pub fn next_frame(&mut self) -> Result<Option<(u64, VDIFFrame)>> {
+        let (seq, in_frame) = self.vdifvtp.recv_frame()?;
+        if self.expecting_frame <= seq {
+            // Frame is good, increment the expected frame appropriately and
+            // return the frame
+            self.expecting_frame = seq + 1;
+            return Ok(Some((seq, in_frame)));
+        } else {
+            // Frame is not in order, so just discard it after setting the counter properly.
+            self.expecting_frame = seq + 1;
+            return Ok(None);
+        }
+    }
+
+    /// Get a reference to the underlying [`UdpSocket`].
+    pub fn socket_ref(&self) -> &UdpSocket {
+        return &self.vdifvtp.sock;
+    }
+}
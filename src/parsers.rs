@@ -0,0 +1,55 @@
+//! Implements [`nom`] parsers for VDIF headers and frames, for composing with other [`nom`] parsers.
+//!
+//! Requires the `nom` feature. These parsers produce the same results as the allocation-free
+//! [`decode_header_bytes`] and [`parse_frame`](crate::header_encoding::parse_frame), which don't need [`nom`].
+
+use nom::bytes::complete::take;
+use nom::combinator::verify;
+use nom::IResult;
+
+use crate::header::VDIFHeader;
+use crate::header_encoding::{decode_header_bytes, MASK_IS_LEGACY};
+
+/// Parse a little-endian VDIF header, which is 16 bytes for a legacy header or else 32.
+pub fn header(input: &[u8]) -> IResult<&[u8], VDIFHeader> {
+    let (rest, start) = take(16usize)(input)?;
+    let mut bytes = [0u8; 32];
+    bytes[0..16].copy_from_slice(start);
+    if u32::from_le_bytes(bytes[0..4].try_into().unwrap()) & MASK_IS_LEGACY != 0 {
+        return Ok((rest, decode_header_bytes(&bytes)));
+    }
+    let (rest, edv) = take(16usize)(rest)?;
+    bytes[16..32].copy_from_slice(edv);
+    return Ok((rest, decode_header_bytes(&bytes)));
+}
+
+/// Parse a VDIF frame whose size is taken from its header, returning the header and the payload.
+pub fn frame(input: &[u8]) -> IResult<&[u8], (VDIFHeader, &[u8])> {
+    let (rest, header) =
+        verify(header, |h: &VDIFHeader| h.bytesize() >= h.header_bytesize())(input)?;
+    let (rest, payload) = take(header.data_bytesize() as usize)(rest)?;
+    return Ok((rest, (header, payload)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::VDIFFrame;
+    use crate::header_encoding::parse_frame;
+
+    #[test]
+    fn test_nom_matches_parse_frame() {
+        let mut frame = VDIFFrame::empty(64);
+        frame.set_header(VDIFHeader {
+            size: 8,
+            thread: 3,
+            ..Default::default()
+        });
+        let mut bytes = frame.as_bytes().to_vec();
+        bytes.push(9);
+
+        let (rest, (header, payload)) = super::frame(&bytes).unwrap();
+        assert_eq!(Some((header, payload, rest)), parse_frame(&bytes));
+        assert!(super::frame(&bytes[0..40]).is_err());
+    }
+}
@@ -0,0 +1,18 @@
+//! A prelude exporting the core types and traits of this crate.
+//!
+//! Importing the prelude is the recommended way of using `rustvdif`, since its contents won't move around when the
+//! internal module layout changes:
+//!
+//! ```rust,ignore
+//! use rustvdif::prelude::*;
+//! ```
+
+pub use crate::error::VDIFError;
+pub use crate::frame::VDIFFrame;
+pub use crate::header::VDIFHeader;
+#[cfg(feature = "io")]
+pub use crate::io::{VDIFFileWriter, VDIFRead, VDIFReader, VDIFWrite, VDIFWriter};
+pub use crate::spec::StreamSpec;
+#[cfg(feature = "utils")]
+pub use crate::utils::fifo::{frame_fifo, FifoConsumer, FifoProducer};
+pub use crate::validation::{ValidationLevel, ValidationProfile};
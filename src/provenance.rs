@@ -0,0 +1,67 @@
+//! Implements [`Provenance`], small metadata that can travel alongside a frame without modifying its bytes.
+//!
+//! Provenance records when and where a frame was received, and which pipeline stages it has passed through, for
+//! measuring end-to-end latency and debugging. Sources attach it through [`VDIFRead::read_tagged`], and
+//! [`Pipeline`](crate::utils::pipeline::Pipeline)s carry it between stages.
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use crate::frame::VDIFFrame;
+#[cfg(doc)]
+use crate::io::VDIFRead;
+
+/// Where a frame came from, and what has happened to it since.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Provenance {
+    /// When the frame was received.
+    pub received: Instant,
+    /// The address the frame was received from, if it came over the network.
+    pub source: Option<SocketAddr>,
+    /// The position of the frame in the order it was received, starting at zero. Sources that don't count frames
+    /// leave this at zero.
+    pub sequence: u64,
+    /// One bit per pipeline stage the frame has passed through, with bit `i` for the `i`th stage after the source.
+    pub stages: u64,
+}
+
+impl Provenance {
+    /// Construct a [`Provenance`] for a frame received just now.
+    pub fn now() -> Self {
+        return Self {
+            received: Instant::now(),
+            source: None,
+            sequence: 0,
+            stages: 0,
+        };
+    }
+
+    /// Get the time since the frame was received.
+    pub fn age(&self) -> Duration {
+        return self.received.elapsed();
+    }
+
+    /// Returns `true` if the frame has passed through stage `stage`.
+    pub fn passed(&self, stage: usize) -> bool {
+        return stage < 64 && self.stages & (1 << stage) != 0;
+    }
+}
+
+/// A frame along with its [`Provenance`].
+#[derive(Debug)]
+pub struct Tagged {
+    /// The frame.
+    pub frame: VDIFFrame,
+    /// The provenance of the frame.
+    pub provenance: Provenance,
+}
+
+impl Tagged {
+    /// Tag `frame` as received just now.
+    pub fn now(frame: VDIFFrame) -> Self {
+        return Self {
+            frame: frame,
+            provenance: Provenance::now(),
+        };
+    }
+}
@@ -0,0 +1,158 @@
+//! Implements [`InvalidRegions`], marking damaged parts of a frame's payload rather than invalidating the whole frame.
+//!
+//! Regions are carried alongside a frame rather than in it, so repair and decode stages can zero only the damaged
+//! words. They can optionally be stored in the header as a non-standard extension of EDV 4. Standard EDV 4, as written
+//! by DiFX, gives one validity bit per channel of a multiplexed frame; here the same mask instead holds one bit per
+//! equal segment of the payload, so it should only be exchanged with tools that know this convention. Since the mask
+//! only has 64 bits, this is coarser than the regions themselves: a segment is marked invalid if any of its words are.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::edv::{ExtendedData, EDV_SYNC_WORD};
+use crate::frame::VDIFFrame;
+use crate::header::VDIFHeader;
+
+/// The extended data version used to store invalid regions in a header.
+pub const EDV_VALIDITY: u8 = 4;
+
+/// A set of suspect payload word ranges within one frame.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct InvalidRegions {
+    ranges: Vec<Range<usize>>,
+}
+
+impl InvalidRegions {
+    /// Construct an empty set of regions.
+    pub fn new() -> Self {
+        return Self { ranges: Vec::new() };
+    }
+
+    /// Mark the payload words in `words` as suspect. Overlapping and adjacent regions are merged.
+    pub fn mark(&mut self, words: Range<usize>) {
+        if words.is_empty() {
+            return;
+        }
+        let mut merged = words;
+        self.ranges.retain(|r| {
+            if r.start <= merged.end && merged.start <= r.end {
+                merged = merged.start.min(r.start)..merged.end.max(r.end);
+                return false;
+            }
+            return true;
+        });
+        let at = self.ranges.partition_point(|r| r.start < merged.start);
+        self.ranges.insert(at, merged);
+    }
+
+    /// Get the suspect regions, in order and not overlapping.
+    pub fn ranges(&self) -> &[Range<usize>] {
+        return &self.ranges;
+    }
+
+    /// Returns `true` if no words are marked.
+    pub fn is_empty(&self) -> bool {
+        return self.ranges.is_empty();
+    }
+
+    /// Returns `true` if payload word `word` is marked.
+    pub fn contains(&self, word: usize) -> bool {
+        return self.ranges.iter().any(|r| r.contains(&word));
+    }
+
+    /// Get the number of marked words.
+    pub fn marked_words(&self) -> usize {
+        return self.ranges.iter().map(|r| r.len()).sum();
+    }
+
+    /// Zero the marked words of the payload of `frame`, leaving the rest untouched.
+    pub fn zero_invalid(&self, frame: &mut VDIFFrame) {
+        let payload = frame.get_mut_payload();
+        for r in &self.ranges {
+            let end = r.end.min(payload.len());
+            if r.start < end {
+                payload[r.start..end].fill(0);
+            }
+        }
+    }
+
+    /// Store the regions in the extended data of `header` as an EDV 4 mask of payload segments, for a payload of
+    /// `payload_words` words. This overwrites any existing extended data.
+    ///
+    /// This is not the standard meaning of the EDV 4 mask, which marks channels rather than segments, so software
+    /// such as DiFX will misread it.
+    pub fn write_edv4_segments(&self, header: &mut VDIFHeader, payload_words: usize) {
+        let segment = edv4_segment(payload_words);
+        let segments = payload_words.div_ceil(segment);
+        let mut mask = u64::MAX >> (64 - segments.max(1));
+        for r in &self.ranges {
+            for s in r.start / segment..r.end.div_ceil(segment).min(segments) {
+                mask &= !(1 << s);
+            }
+        }
+        header.set_extended_data(ExtendedData::Validity {
+            mask_length: segments as u8,
+            sync: EDV_SYNC_WORD,
+            mask: mask,
+        });
+    }
+
+    /// Read regions stored by [`write_edv4_segments`](InvalidRegions::write_edv4_segments) from `header`, for a
+    /// payload of `payload_words` words. Returns `None` if the header does not use EDV 4.
+    ///
+    /// A standard EDV 4 mask of channels is read as though it marked segments, so only use this on headers known to
+    /// follow this convention.
+    pub fn read_edv4_segments(header: &VDIFHeader, payload_words: usize) -> Option<Self> {
+        let ExtendedData::Validity {
+            mask_length, mask, ..
+        } = header.extended_data()
+        else {
+            return None;
+        };
+        let segment = edv4_segment(payload_words);
+        let segments = (mask_length as usize).min(64);
+        let mut regions = Self::new();
+        for s in 0..segments {
+            if mask & (1 << s) == 0 {
+                regions.mark(s * segment..((s + 1) * segment).min(payload_words));
+            }
+        }
+        return Some(regions);
+    }
+}
+
+// The number of payload words covered by each bit of the EDV 4 validity mask.
+fn edv4_segment(payload_words: usize) -> usize {
+    return payload_words.div_ceil(64).max(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_regions() {
+        let mut regions = InvalidRegions::new();
+        regions.mark(10..12);
+        regions.mark(2..4);
+        regions.mark(11..20);
+        assert_eq!(regions.ranges(), &[2..4, 10..20]);
+        assert_eq!(regions.marked_words(), 12);
+
+        let mut frame = VDIFFrame::empty(32 + 4 * 128);
+        frame.get_mut_payload().fill(u32::MAX);
+        regions.zero_invalid(&mut frame);
+        assert_eq!(frame.get_data_word(1), u32::MAX);
+        assert_eq!(frame.get_data_word(2), 0);
+        assert_eq!(frame.get_data_word(19), 0);
+        assert_eq!(frame.get_data_word(20), u32::MAX);
+
+        // 128 words gives 2 words per mask bit, so the regions round out to whole segments
+        let mut header = VDIFHeader::default();
+        regions.write_edv4_segments(&mut header, 128);
+        assert_eq!(header.edv0 >> 24, 4);
+        let decoded = InvalidRegions::read_edv4_segments(&header, 128).unwrap();
+        assert_eq!(decoded.ranges(), &[2..4, 10..20]);
+        assert!(InvalidRegions::read_edv4_segments(&VDIFHeader::default(), 128).is_none());
+    }
+}
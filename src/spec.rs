@@ -0,0 +1,71 @@
+//! Implements [`StreamSpec`], a description of the layout of a VDIF stream.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::header::VDIFHeader;
+use crate::layout::FrameLayout;
+
+/// Describes the layout of a VDIF stream, i.e. everything needed to interpret its frames that isn't necessarily
+/// carried in each header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamSpec {
+    /// The size in bytes of each frame (header **and** payload).
+    pub frame_size: usize,
+    /// The number of frames contained within one second *per* thread.
+    pub frame_rate: u32,
+    /// The thread IDs present in the stream.
+    pub threads: Vec<u16>,
+    /// The frame rate of each thread in [`threads`](StreamSpec::threads), for streams mixing threads of different
+    /// bandwidths. Empty if every thread runs at [`frame_rate`](StreamSpec::frame_rate).
+    pub thread_frame_rates: Vec<u32>,
+    /// The number of channels within each frame.
+    pub channels: usize,
+    /// The number of bits in each sample, or each component of a complex sample. Unlike the raw header field, this is
+    /// the bit depth itself.
+    pub bits_per_sample: u8,
+    /// Whether the encoded data is real or complex.
+    pub is_real: bool,
+    /// The station ID of the stream.
+    pub station: u16,
+}
+
+impl StreamSpec {
+    /// Construct a [`StreamSpec`] describing the stream `header` belongs to. Only the thread of `header` is listed
+    /// in [`threads`](StreamSpec::threads).
+    pub fn from_header(header: &VDIFHeader, frame_rate: u32) -> Self {
+        return Self {
+            frame_size: header.bytesize() as usize,
+            frame_rate: frame_rate,
+            threads: vec![header.thread],
+            thread_frame_rates: Vec::new(),
+            channels: header.channelno(),
+            bits_per_sample: header.bits() as u8,
+            is_real: header.is_real,
+            station: header.station,
+        };
+    }
+
+    /// Get the frame rate of `thread`, falling back to [`frame_rate`](StreamSpec::frame_rate) if the thread has no
+    /// rate of its own.
+    pub fn frame_rate_of(&self, thread: u16) -> u32 {
+        return self
+            .threads
+            .iter()
+            .position(|t| *t == thread)
+            .and_then(|i| self.thread_frame_rates.get(i))
+            .copied()
+            .unwrap_or(self.frame_rate);
+    }
+
+    /// Get the number of samples per channel contained within each frame, or 0 if the spec doesn't describe a usable
+    /// sample format.
+    pub fn samples_per_frame(&self) -> usize {
+        return FrameLayout::from_spec(self).map_or(0, |layout| layout.samples_per_channel());
+    }
+
+    /// Get the sample rate in samples per second of each channel.
+    pub fn sample_rate(&self) -> u64 {
+        return self.samples_per_frame() as u64 * self.frame_rate as u64;
+    }
+}
@@ -0,0 +1,130 @@
+//! Implements [`StationRegistry`], mapping station IDs to station names for human readable labels and file names.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+
+/// Two character station codes and names of some common VLBI stations, loaded by [`StationRegistry::builtin`].
+const BUILTIN_STATIONS: [(&str, &str); 22] = [
+    ("Br", "Brewster"),
+    ("Fd", "Fort Davis"),
+    ("Hn", "Hancock"),
+    ("Kp", "Kitt Peak"),
+    ("La", "Los Alamos"),
+    ("Mk", "Mauna Kea"),
+    ("Nl", "North Liberty"),
+    ("Ov", "Owens Valley"),
+    ("Pt", "Pie Town"),
+    ("Sc", "Saint Croix"),
+    ("Ef", "Effelsberg"),
+    ("Jb", "Jodrell Bank"),
+    ("Wb", "Westerbork"),
+    ("On", "Onsala"),
+    ("Mc", "Medicina"),
+    ("Nt", "Noto"),
+    ("Tr", "Torun"),
+    ("Ys", "Yebes"),
+    ("Hh", "Hartebeesthoek"),
+    ("Sh", "Shanghai"),
+    ("Ur", "Urumqi"),
+    ("Mh", "Metsahovi"),
+];
+
+/// A registry of station names, keyed by the station ID of the VDIF header.
+///
+/// Station IDs are either two ASCII characters or a plain number. The registry starts empty, or holding some common
+/// VLBI stations with [`builtin`](StationRegistry::builtin), and can be extended with your own stations.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct StationRegistry {
+    names: BTreeMap<u16, String>,
+}
+
+impl StationRegistry {
+    /// Construct an empty [`StationRegistry`].
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Construct a [`StationRegistry`] holding some common VLBI stations.
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        for (code, name) in BUILTIN_STATIONS {
+            registry.insert_code(code, name);
+        }
+        return registry;
+    }
+
+    /// Register `name` for the numeric station ID `station`, replacing any existing name.
+    pub fn insert(&mut self, station: u16, name: &str) {
+        self.names.insert(station, name.to_string());
+    }
+
+    /// Register `name` for the two character station code `code`, replacing any existing name.
+    pub fn insert_code(&mut self, code: &str, name: &str) {
+        let bytes: [u8; 2] = code
+            .as_bytes()
+            .try_into()
+            .expect("Station codes must be two ASCII characters");
+        self.insert(u16::from_be_bytes(bytes), name);
+    }
+
+    /// Get the name registered for `station`, if any.
+    pub fn name(&self, station: u16) -> Option<&str> {
+        return self.names.get(&station).map(|name| name.as_str());
+    }
+
+    /// Get a label for `station` for summaries and logs, such as `Jb (Jodrell Bank)`. Unregistered stations are
+    /// labelled with their code, or their number if the ID isn't two printable ASCII characters.
+    pub fn label(&self, station: u16) -> String {
+        return match self.name(station) {
+            Some(name) => format!("{} ({})", station_code(station), name),
+            None => station_code(station),
+        };
+    }
+
+    /// Get a label for `station` that is safe to use in file names, such as `jodrell_bank`. Unregistered stations use
+    /// their code, or their number if the ID isn't two printable ASCII characters.
+    pub fn file_label(&self, station: u16) -> String {
+        let label = match self.name(station) {
+            Some(name) => name.to_string(),
+            None => station_code(station),
+        };
+        return label
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+    }
+}
+
+/// Get the two character code of `station`, or its number if the ID isn't two printable ASCII characters.
+pub fn station_code(station: u16) -> String {
+    let bytes = station.to_be_bytes();
+    if bytes.iter().all(|b| b.is_ascii_graphic()) {
+        return bytes.iter().map(|b| *b as char).collect();
+    }
+    return station.to_string();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_station_registry() {
+        let mut registry = StationRegistry::builtin();
+        let jb = u16::from_be_bytes(*b"Jb");
+        assert_eq!(registry.label(jb), "Jb (Jodrell Bank)");
+        assert_eq!(registry.file_label(jb), "jodrell_bank");
+        assert_eq!(registry.label(134), "134");
+
+        registry.insert(134, "Test Station");
+        assert_eq!(registry.label(134), "134 (Test Station)");
+        assert_eq!(registry.file_label(u16::from_be_bytes(*b"Xx")), "xx");
+    }
+}
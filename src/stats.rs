@@ -0,0 +1,242 @@
+//! Implements [`StreamStats`], which accumulates a summary of a VDIF stream as its frames go by, in the spirit of
+//! `vdifsum`.
+//!
+//! The resulting [`StreamSummary`] describes each thread: how many frames it holds, their first and last timestamps,
+//! the frame rate, and how many frames were invalid, missing or out of order. It prints as a small table, and with the
+//! `serde` feature can be serialised.
+//!
+//! ```rust,ignore
+//! let mut reader = VDIFReader::open("path/to/mystery.vdif", 8032).unwrap();
+//! println!("{}", StreamStats::summarise(&mut reader).unwrap());
+//! ```
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::frame::VDIFFrame;
+use crate::header::{FrameInstant, VDIFHeader};
+#[cfg(feature = "io")]
+use crate::io::VDIFRead;
+
+/// The summary of one thread of a stream.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThreadSummary {
+    /// The thread ID.
+    pub thread: u16,
+    /// The number of frames seen.
+    pub frames: u64,
+    /// The number of frames marked invalid.
+    pub invalid: u64,
+    /// The timestamp of the earliest frame.
+    pub first: FrameInstant,
+    /// The timestamp of the latest frame.
+    pub last: FrameInstant,
+    /// The frame rate in frames per second, estimated as one more than the largest frame number seen.
+    pub frame_rate: u32,
+    /// The number of jumps forward in time by more than one frame.
+    pub gaps: u64,
+    /// The number of frames skipped by those jumps.
+    pub missing: u64,
+    /// The number of frames no later than the frame before them.
+    pub out_of_order: u64,
+}
+
+/// The summary of a whole stream, accumulated by [`StreamStats`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StreamSummary {
+    /// The total number of frames seen.
+    pub frames: u64,
+    /// The total number of bytes in those frames.
+    pub bytes: u64,
+    /// The distinct frame sizes seen, in bytes, in increasing order.
+    pub frame_sizes: Vec<usize>,
+    /// The summary of each thread, ordered by thread ID.
+    pub threads: Vec<ThreadSummary>,
+}
+
+impl StreamSummary {
+    /// Get the total number of invalid frames across all threads.
+    pub fn invalid(&self) -> u64 {
+        return self.threads.iter().map(|t| t.invalid).sum();
+    }
+
+    /// Get the total number of missing frames across all threads.
+    pub fn missing(&self) -> u64 {
+        return self.threads.iter().map(|t| t.missing).sum();
+    }
+
+    /// Get the total number of out of order frames across all threads.
+    pub fn out_of_order(&self) -> u64 {
+        return self.threads.iter().map(|t| t.out_of_order).sum();
+    }
+}
+
+impl fmt::Display for StreamSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} frames, {} bytes, frame sizes {:?}",
+            self.frames, self.bytes, self.frame_sizes
+        )?;
+        writeln!(
+            f,
+            "{:>6} {:>10} {:>14} {:>14} {:>8} {:>8} {:>8} {:>8}",
+            "thread", "frames", "first", "last", "rate", "invalid", "missing", "disorder"
+        )?;
+        for t in &self.threads {
+            writeln!(
+                f,
+                "{:>6} {:>10} {:>14} {:>14} {:>8} {:>8} {:>8} {:>8}",
+                t.thread,
+                t.frames,
+                format_instant(t.first),
+                format_instant(t.last),
+                t.frame_rate,
+                t.invalid,
+                t.missing,
+                t.out_of_order
+            )?;
+        }
+        return Ok(());
+    }
+}
+
+// Format an instant as epoch:seconds.frame
+fn format_instant(instant: FrameInstant) -> alloc::string::String {
+    return alloc::format!("{}:{}.{}", instant.epoch, instant.time, instant.frameno);
+}
+
+/// Accumulates a [`StreamSummary`] from the frames of a stream.
+#[derive(Debug, Default, Clone)]
+pub struct StreamStats {
+    frames: u64,
+    bytes: u64,
+    frame_sizes: Vec<usize>,
+    threads: BTreeMap<u16, ThreadSummary>,
+}
+
+impl StreamStats {
+    /// Construct a new, empty [`StreamStats`].
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Add a frame to the statistics.
+    pub fn ingest(&mut self, frame: &VDIFFrame) {
+        self.ingest_header(&frame.get_header(), frame.bytesize());
+    }
+
+    /// Add a frame to the statistics from its header and size in bytes, for when the payload isn't at hand.
+    pub fn ingest_header(&mut self, header: &VDIFHeader, size: usize) {
+        self.frames += 1;
+        self.bytes += size as u64;
+        if let Err(i) = self.frame_sizes.binary_search(&size) {
+            self.frame_sizes.insert(i, size);
+        }
+
+        let instant = header.instant();
+        let thread = self.threads.entry(header.thread).or_insert(ThreadSummary {
+            thread: header.thread,
+            first: instant,
+            last: instant,
+            ..Default::default()
+        });
+        if thread.frames > 0 {
+            if instant <= thread.last {
+                thread.out_of_order += 1;
+            } else {
+                let skipped = frames_between(thread.last, instant, thread.frame_rate);
+                if skipped > 0 {
+                    thread.gaps += 1;
+                    thread.missing += skipped;
+                }
+            }
+        }
+        thread.frames += 1;
+        thread.invalid += !header.is_valid as u64;
+        thread.frame_rate = thread.frame_rate.max(header.frameno + 1);
+        thread.first = thread.first.min(instant);
+        thread.last = thread.last.max(instant);
+    }
+
+    /// Get the summary of the frames seen so far.
+    pub fn summary(&self) -> StreamSummary {
+        return StreamSummary {
+            frames: self.frames,
+            bytes: self.bytes,
+            frame_sizes: self.frame_sizes.clone(),
+            threads: self.threads.values().copied().collect(),
+        };
+    }
+
+    /// Summarise every frame read from `reader` until the end of the stream.
+    #[cfg(feature = "io")]
+    pub fn summarise<R: VDIFRead>(reader: &mut R) -> std::io::Result<StreamSummary> {
+        let mut stats = Self::new();
+        loop {
+            match reader.read_frame() {
+                Ok(frame) => stats.ingest(&frame),
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+        }
+        return Ok(stats.summary());
+    }
+}
+
+// Count the frames strictly between `earlier` and `later`, given `frame_rate` frames per second. Timestamps are
+// compared within an epoch, so a change of epoch is not counted as a gap.
+fn frames_between(earlier: FrameInstant, later: FrameInstant, frame_rate: u32) -> u64 {
+    if earlier.epoch != later.epoch {
+        return 0;
+    }
+    let position = |i: FrameInstant| i.time as u64 * frame_rate as u64 + i.frameno as u64;
+    return (position(later) - position(earlier)).saturating_sub(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_stats() {
+        let mut stats = StreamStats::new();
+        // Thread 0 at 4 frames/s over two seconds, losing one frame, with an invalid frame and a repeat
+        for (time, frameno) in [
+            (10, 0),
+            (10, 1),
+            (10, 2),
+            (10, 3),
+            (11, 0),
+            (11, 2),
+            (11, 3),
+            (11, 3),
+        ] {
+            let header = VDIFHeader {
+                time: time,
+                frameno: frameno,
+                is_valid: frameno != 1,
+                size: 8,
+                ..Default::default()
+            };
+            stats.ingest_header(&header, 64);
+        }
+        stats.ingest(&VDIFFrame::from_header(VDIFHeader {
+            thread: 3,
+            size: 16,
+            ..Default::default()
+        }));
+
+        let summary = stats.summary();
+        assert_eq!((summary.frames, summary.bytes), (9, 8 * 64 + 128));
+        assert_eq!(summary.frame_sizes, [64, 128]);
+        let t0 = summary.threads[0];
+        assert_eq!((t0.frames, t0.frame_rate), (8, 4));
+        assert_eq!((t0.first.time, t0.last.time, t0.last.frameno), (10, 11, 3));
+        assert_eq!((t0.gaps, t0.missing, t0.out_of_order), (1, 1, 1));
+        assert_eq!((summary.invalid(), summary.threads[1].thread), (2, 3));
+    }
+}
@@ -0,0 +1,23 @@
+//! Fixtures shared by the unit tests of several modules.
+
+use std::path::PathBuf;
+
+/// A [`VDIFRead`](crate::io::VDIFRead) source yielding a fixed list of frames, then
+/// [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) errors.
+#[cfg(feature = "utils")]
+pub(crate) struct Source(pub(crate) std::vec::IntoIter<crate::frame::VDIFFrame>);
+
+#[cfg(feature = "utils")]
+impl crate::io::VDIFRead for Source {
+    fn read_frame(&mut self) -> std::io::Result<crate::frame::VDIFFrame> {
+        return self
+            .0
+            .next()
+            .ok_or(std::io::ErrorKind::UnexpectedEof.into());
+    }
+}
+
+/// Get a path in the temporary directory for the file or directory `name`, unique to this test process.
+pub(crate) fn temp_path(name: &str) -> PathBuf {
+    return std::env::temp_dir().join(format!("rustvdif_{}_{}", std::process::id(), name));
+}
@@ -0,0 +1,125 @@
+//! Converts the timestamps of VDIF frames to and from calendar time.
+//!
+//! A frame's timestamp is its reference epoch, the seconds since that epoch and its frame number within the second.
+//! Given the number of frames per second per thread, these pin down the UTC time the frame's first sample was taken.
+//!
+//! Conversions to Modified Julian Dates, as used by correlator logs and VEX files, are also provided.
+
+use chrono::{DateTime, TimeDelta, Timelike, Utc};
+use num_traits::Float;
+
+use crate::header::{vdiftime_from_date, vdiftime_to_date, FrameInstant};
+
+/// Get the UTC time of the start of the frame at `instant`, given `frame_rate` frames per second per thread.
+///
+/// Frame start times are rounded down to the nanosecond when the frame rate doesn't divide a second evenly. Returns
+/// [`None`] if `frame_rate` is zero.
+pub fn to_datetime(instant: FrameInstant, frame_rate: u32) -> Option<DateTime<Utc>> {
+    let nanos = (instant.frameno as u64 * 1_000_000_000).checked_div(frame_rate as u64)?;
+    return Some(
+        (vdiftime_to_date(instant.epoch, instant.time) + TimeDelta::nanoseconds(nanos as i64))
+            .and_utc(),
+    );
+}
+
+/// Get the [`FrameInstant`] of the frame covering `datetime`, given `frame_rate` frames per second per thread. This
+/// is the inverse of [`to_datetime`]. Returns [`None`] if `frame_rate` is zero.
+pub fn from_datetime(datetime: DateTime<Utc>, frame_rate: u32) -> Option<FrameInstant> {
+    if frame_rate == 0 {
+        return None;
+    }
+    let (epoch, time) = vdiftime_from_date(datetime.naive_utc());
+    // The last frame starting at or before `datetime`, using the same rounding as to_datetime
+    let nanos = datetime.nanosecond().min(999_999_999) as u64;
+    let frameno = ((nanos + 1) * frame_rate as u64 - 1) / 1_000_000_000;
+    return Some(FrameInstant {
+        epoch: epoch,
+        time: time,
+        frameno: frameno as u32,
+    });
+}
+
+/// The Modified Julian Date of the UNIX epoch, 1970-01-01.
+pub const MJD_UNIX_EPOCH: i64 = 40587;
+
+/// Convert `datetime` to a Modified Julian Date and the seconds since the start of that day, the convention used by
+/// correlator logs and VEX files.
+pub fn to_mjd(datetime: DateTime<Utc>) -> (i64, f64) {
+    let seconds = datetime.timestamp();
+    let day = seconds.div_euclid(86400);
+    let second_of_day =
+        seconds.rem_euclid(86400) as f64 + datetime.timestamp_subsec_nanos() as f64 * 1e-9;
+    return (day + MJD_UNIX_EPOCH, second_of_day);
+}
+
+/// Convert a Modified Julian Date `mjd` and `seconds` since the start of that day to a [`DateTime<Utc>`], rounded to
+/// the nearest nanosecond.
+pub fn from_mjd(mjd: i64, seconds: f64) -> DateTime<Utc> {
+    let nanos = Float::round(seconds * 1e9) as i64;
+    return DateTime::UNIX_EPOCH
+        + TimeDelta::days(mjd - MJD_UNIX_EPOCH)
+        + TimeDelta::nanoseconds(nanos);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::VDIFHeader;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_frame_datetime() {
+        let instant = FrameInstant {
+            epoch: 48,
+            time: 3600,
+            frameno: 1,
+        };
+        let datetime = to_datetime(instant, 3).unwrap();
+        let expected = NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_nano_opt(1, 0, 0, 333_333_333)
+            .unwrap()
+            .and_utc();
+        assert_eq!(datetime, expected);
+        assert_eq!(from_datetime(datetime, 3), Some(instant));
+        assert_eq!(
+            from_datetime(datetime - TimeDelta::nanoseconds(1), 3)
+                .unwrap()
+                .frameno,
+            0
+        );
+        assert_eq!(
+            (to_datetime(instant, 0), from_datetime(datetime, 0)),
+            (None, None)
+        );
+
+        let mut header = VDIFHeader::default();
+        assert_eq!(header.set_timestamp(expected, 0), None);
+        assert_eq!(header.instant(), FrameInstant::default());
+        header
+            .set_timestamp(expected + TimeDelta::milliseconds(200), 3)
+            .unwrap();
+        assert_eq!(header.instant(), instant);
+        assert_eq!(header.timestamp(3), Some(expected));
+    }
+
+    #[test]
+    fn test_mjd() {
+        // 2024-01-01 is MJD 60310
+        let datetime = NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_milli_opt(6, 0, 0, 500)
+            .unwrap()
+            .and_utc();
+        assert_eq!(to_mjd(datetime), (60310, 21600.5));
+        assert_eq!(from_mjd(60310, 21600.5), datetime);
+
+        let header = VDIFHeader {
+            epoch: 48,
+            time: 86400 + 30,
+            ..Default::default()
+        };
+        assert_eq!(header.epoch_seconds_to_unix(), 1704067200 + 86430);
+        assert_eq!(header.mjd(), (60311, 30));
+    }
+}
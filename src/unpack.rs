@@ -0,0 +1,370 @@
+//! Implements bulk unpacking of payload words into raw sample values, with SIMD paths for 1, 2, 4 and 8-bit data.
+//!
+//! Unpacking the samples of a whole payload at once, rather than a word at a time as the functions in
+//! [`data_encoding`](crate::data_encoding) do, lets the work be spread across SIMD lanes. The fastest [`Backend`]
+//! the CPU supports is picked at runtime: AVX2 or SSE2 on x86_64, and NEON on aarch64, with a scalar fallback
+//! everywhere else. A lookup table backend is also available, which can be the faster choice on CPUs without wide
+//! SIMD units. Every backend produces identical output.
+//!
+//! [`decode_payload`](crate::decoding::decode_payload) uses these paths automatically for real data whose samples
+//! fill each word exactly, which covers the common cases of 1, 2, 4 or 8 bits/sample with a power of two channels.
+
+/// A way of unpacking samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Plain Rust, available everywhere.
+    Scalar,
+    /// SSE2 intrinsics, available on every x86_64 CPU.
+    Sse2,
+    /// AVX2 intrinsics, on x86_64 CPUs that support them.
+    Avx2,
+    /// NEON intrinsics, available on every aarch64 CPU.
+    Neon,
+    /// A 256 entry table per bit depth, mapping each byte to its samples. Available everywhere.
+    Lut,
+}
+
+impl Backend {
+    /// Get the fastest backend supported by this CPU.
+    pub fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if has_avx2() {
+                return Backend::Avx2;
+            }
+            return Backend::Sse2;
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            return Backend::Neon;
+        }
+        #[allow(unreachable_code)]
+        return Backend::Scalar;
+    }
+
+    /// Returns `true` if this backend can run on this CPU.
+    pub fn is_supported(&self) -> bool {
+        return match self {
+            Backend::Scalar | Backend::Lut => true,
+            #[cfg(target_arch = "x86_64")]
+            Backend::Sse2 => true,
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 => has_avx2(),
+            #[cfg(target_arch = "aarch64")]
+            Backend::Neon => true,
+            #[allow(unreachable_patterns)]
+            _ => false,
+        };
+    }
+}
+
+// Without `std` the CPU can't be queried at runtime, so AVX2 is only used when the build targets it.
+#[cfg(all(target_arch = "x86_64", feature = "std"))]
+fn has_avx2() -> bool {
+    return std::arch::is_x86_feature_detected!("avx2");
+}
+
+#[cfg(all(target_arch = "x86_64", not(feature = "std")))]
+fn has_avx2() -> bool {
+    return cfg!(target_feature = "avx2");
+}
+
+/// Unpack every sample of `bits` bits within `words` into `out`, oldest first, using the fastest [`Backend`] this
+/// CPU supports.
+///
+/// `bits` must be 1, 2, 4 or 8, and `out` must hold exactly `words.len() * 32 / bits` samples.
+pub fn unpack_u8(words: &[u32], bits: u32, out: &mut [u8]) {
+    unpack_u8_with(Backend::detect(), words, bits, out);
+}
+
+/// Unpack samples as [`unpack_u8`] does, using `backend`. Panics if `backend` is not supported by this CPU.
+pub fn unpack_u8_with(backend: Backend, words: &[u32], bits: u32, out: &mut [u8]) {
+    assert!(
+        matches!(bits, 1 | 2 | 4 | 8),
+        "Only 1, 2, 4 or 8 bits/sample can be unpacked in bulk"
+    );
+    assert_eq!(
+        out.len(),
+        words.len() * (32 / bits as usize),
+        "Output must hold every sample of the input"
+    );
+    assert!(
+        backend.is_supported(),
+        "Backend is not supported by this CPU"
+    );
+
+    // VDIF is little endian, as is every supported target, so each byte holds consecutive samples
+    let bytes =
+        unsafe { core::slice::from_raw_parts(words.as_ptr() as *const u8, words.len() * 4) };
+    if bits == 8 {
+        out.copy_from_slice(bytes);
+        return;
+    }
+
+    let per_byte = 8 / bits as usize;
+    let done = match backend {
+        #[cfg(target_arch = "x86_64")]
+        Backend::Sse2 => unsafe { x86::unpack_sse2(bytes, bits, out) },
+        #[cfg(target_arch = "x86_64")]
+        Backend::Avx2 => unsafe { x86::unpack_avx2(bytes, bits, out) },
+        #[cfg(target_arch = "aarch64")]
+        Backend::Neon => unsafe { arm::unpack_neon(bytes, bits, out) },
+        Backend::Lut => {
+            unpack_lut(bytes, bits, out);
+            bytes.len()
+        }
+        _ => 0,
+    };
+    unpack_scalar(&bytes[done..], bits, &mut out[done * per_byte..]);
+}
+
+fn unpack_scalar(bytes: &[u8], bits: u32, out: &mut [u8]) {
+    let per_byte = 8 / bits as usize;
+    let mask = (1u8 << bits) - 1;
+    for (byte, samples) in bytes.iter().zip(out.chunks_exact_mut(per_byte)) {
+        for (k, sample) in samples.iter_mut().enumerate() {
+            *sample = (byte >> (k as u32 * bits)) & mask;
+        }
+    }
+}
+
+// The samples of every possible byte, for each bit depth below 8
+static LUT_1BIT: [[u8; 8]; 256] = lut(1);
+static LUT_2BIT: [[u8; 8]; 256] = lut(2);
+static LUT_4BIT: [[u8; 8]; 256] = lut(4);
+
+const fn lut(bits: u32) -> [[u8; 8]; 256] {
+    let mut out = [[0; 8]; 256];
+    let mask = (1u32 << bits) - 1;
+    let mut byte = 0;
+    while byte < 256 {
+        let mut k = 0;
+        while k < 8 / bits {
+            out[byte][k as usize] = ((byte as u32 >> (k * bits)) & mask) as u8;
+            k += 1;
+        }
+        byte += 1;
+    }
+    return out;
+}
+
+fn unpack_lut(bytes: &[u8], bits: u32, out: &mut [u8]) {
+    let per_byte = 8 / bits as usize;
+    let table = match bits {
+        1 => &LUT_1BIT,
+        2 => &LUT_2BIT,
+        _ => &LUT_4BIT,
+    };
+    for (byte, samples) in bytes.iter().zip(out.chunks_exact_mut(per_byte)) {
+        samples.copy_from_slice(&table[*byte as usize][0..per_byte]);
+    }
+}
+
+// Each SIMD backend splits a register of bytes into 8 / bits planes, plane k holding sample k of every byte, then
+// interleaves the planes back together. Interleaving merges groups of registers pairwise, doubling the element width
+// each round, so after log2(planes) rounds the registers hold the samples in order.
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use core::arch::x86_64::*;
+
+    macro_rules! interleave {
+        ($planes:expr, $count:expr, $lo8:ident, $hi8:ident, $lo16:ident, $hi16:ident, $lo32:ident, $hi32:ident) => {{
+            let mut regs = $planes;
+            let mut group = 1;
+            let mut round = 0;
+            while group < $count {
+                let mut next = regs;
+                for start in (0..$count).step_by(group * 2) {
+                    for r in 0..group {
+                        let (a, b) = (regs[start + r], regs[start + group + r]);
+                        let (lo, hi) = match round {
+                            0 => ($lo8(a, b), $hi8(a, b)),
+                            1 => ($lo16(a, b), $hi16(a, b)),
+                            _ => ($lo32(a, b), $hi32(a, b)),
+                        };
+                        next[start + 2 * r] = lo;
+                        next[start + 2 * r + 1] = hi;
+                    }
+                }
+                regs = next;
+                group *= 2;
+                round += 1;
+            }
+            regs
+        }};
+    }
+
+    // Unpack whole 16 byte blocks of `bytes`, returning the number of bytes consumed.
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn unpack_sse2(bytes: &[u8], bits: u32, out: &mut [u8]) -> usize {
+        let count = 8 / bits as usize;
+        let mask = _mm_set1_epi8(((1u32 << bits) - 1) as i8);
+        let blocks = bytes.len() / 16;
+        for block in 0..blocks {
+            let x = _mm_loadu_si128(bytes.as_ptr().add(block * 16) as *const __m128i);
+            let mut planes = [_mm_setzero_si128(); 8];
+            for (k, plane) in planes.iter_mut().enumerate().take(count) {
+                let shift = _mm_cvtsi32_si128((k as u32 * bits) as i32);
+                *plane = _mm_and_si128(_mm_srl_epi16(x, shift), mask);
+            }
+            let regs = interleave!(
+                planes,
+                count,
+                _mm_unpacklo_epi8,
+                _mm_unpackhi_epi8,
+                _mm_unpacklo_epi16,
+                _mm_unpackhi_epi16,
+                _mm_unpacklo_epi32,
+                _mm_unpackhi_epi32
+            );
+            let dst = out.as_mut_ptr().add(block * 16 * count) as *mut __m128i;
+            for (r, reg) in regs.iter().enumerate().take(count) {
+                _mm_storeu_si128(dst.add(r), *reg);
+            }
+        }
+        return blocks * 16;
+    }
+
+    // Unpack whole 32 byte blocks of `bytes`, returning the number of bytes consumed.
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn unpack_avx2(bytes: &[u8], bits: u32, out: &mut [u8]) -> usize {
+        let count = 8 / bits as usize;
+        let mask = _mm256_set1_epi8(((1u32 << bits) - 1) as i8);
+        let blocks = bytes.len() / 32;
+        for block in 0..blocks {
+            let x = _mm256_loadu_si256(bytes.as_ptr().add(block * 32) as *const __m256i);
+            let mut planes = [_mm256_setzero_si256(); 8];
+            for (k, plane) in planes.iter_mut().enumerate().take(count) {
+                let shift = _mm_cvtsi32_si128((k as u32 * bits) as i32);
+                *plane = _mm256_and_si256(_mm256_srl_epi16(x, shift), mask);
+            }
+            let regs = interleave!(
+                planes,
+                count,
+                _mm256_unpacklo_epi8,
+                _mm256_unpackhi_epi8,
+                _mm256_unpacklo_epi16,
+                _mm256_unpackhi_epi16,
+                _mm256_unpacklo_epi32,
+                _mm256_unpackhi_epi32
+            );
+            // The AVX2 unpacks work within each 128 bit lane, so the low lanes hold the samples of the first 16 bytes
+            // and the high lanes those of the last 16
+            let dst = out.as_mut_ptr().add(block * 32 * count) as *mut __m256i;
+            for r in (0..count).step_by(2) {
+                _mm256_storeu_si256(
+                    dst.add(r / 2),
+                    _mm256_permute2x128_si256(regs[r], regs[r + 1], 0x20),
+                );
+                _mm256_storeu_si256(
+                    dst.add(count / 2 + r / 2),
+                    _mm256_permute2x128_si256(regs[r], regs[r + 1], 0x31),
+                );
+            }
+        }
+        return blocks * 32;
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arm {
+    use core::arch::aarch64::*;
+
+    unsafe fn zip16(a: uint8x16_t, b: uint8x16_t) -> (uint8x16_t, uint8x16_t) {
+        let (a, b) = (vreinterpretq_u16_u8(a), vreinterpretq_u16_u8(b));
+        return (
+            vreinterpretq_u8_u16(vzip1q_u16(a, b)),
+            vreinterpretq_u8_u16(vzip2q_u16(a, b)),
+        );
+    }
+
+    unsafe fn zip32(a: uint8x16_t, b: uint8x16_t) -> (uint8x16_t, uint8x16_t) {
+        let (a, b) = (vreinterpretq_u32_u8(a), vreinterpretq_u32_u8(b));
+        return (
+            vreinterpretq_u8_u32(vzip1q_u32(a, b)),
+            vreinterpretq_u8_u32(vzip2q_u32(a, b)),
+        );
+    }
+
+    // Unpack whole 16 byte blocks of `bytes`, returning the number of bytes consumed.
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn unpack_neon(bytes: &[u8], bits: u32, out: &mut [u8]) -> usize {
+        let count = 8 / bits as usize;
+        let mask = vdupq_n_u8(((1u32 << bits) - 1) as u8);
+        let blocks = bytes.len() / 16;
+        for block in 0..blocks {
+            let x = vld1q_u8(bytes.as_ptr().add(block * 16));
+            let mut regs = [vdupq_n_u8(0); 8];
+            for (k, plane) in regs.iter_mut().enumerate().take(count) {
+                let shift = vdupq_n_s8(-((k as u32 * bits) as i8));
+                *plane = vandq_u8(vshlq_u8(x, shift), mask);
+            }
+
+            let mut group = 1;
+            let mut round = 0;
+            while group < count {
+                let mut next = regs;
+                for start in (0..count).step_by(group * 2) {
+                    for r in 0..group {
+                        let (a, b) = (regs[start + r], regs[start + group + r]);
+                        let (lo, hi) = match round {
+                            0 => (vzip1q_u8(a, b), vzip2q_u8(a, b)),
+                            1 => zip16(a, b),
+                            _ => zip32(a, b),
+                        };
+                        next[start + 2 * r] = lo;
+                        next[start + 2 * r + 1] = hi;
+                    }
+                }
+                regs = next;
+                group *= 2;
+                round += 1;
+            }
+
+            let dst = out.as_mut_ptr().add(block * 16 * count);
+            for (r, reg) in regs.iter().enumerate().take(count) {
+                vst1q_u8(dst.add(r * 16), *reg);
+            }
+        }
+        return blocks * 16;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backends_agree() {
+        let mut state = 12345u32;
+        let words: Vec<u32> = (0..37)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                state
+            })
+            .collect();
+        for bits in [1, 2, 4, 8] {
+            let mut expected = Vec::new();
+            for word in &words {
+                for k in 0..32 / bits {
+                    expected.push(((word >> (k * bits)) & ((1u64 << bits) - 1) as u32) as u8);
+                }
+            }
+            for backend in [
+                Backend::Scalar,
+                Backend::Sse2,
+                Backend::Avx2,
+                Backend::Neon,
+                Backend::Lut,
+            ] {
+                if !backend.is_supported() {
+                    continue;
+                }
+                let mut out = vec![0u8; expected.len()];
+                unpack_u8_with(backend, &words, bits, &mut out);
+                assert_eq!(out, expected, "{:?} at {} bits", backend, bits);
+            }
+        }
+    }
+}
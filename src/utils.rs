@@ -0,0 +1,31 @@
+//! Utilities for working with VDIF streams, such as simulation, redaction, filtering and file manipulation tools.
+//!
+//! Requires the `utils` feature (enabled by default).
+
+pub mod align;
+pub mod byteswap;
+pub mod clock;
+pub mod fanout;
+pub mod fifo;
+pub mod fill;
+pub mod filter;
+pub mod journal;
+pub mod mark6;
+pub mod monotonic;
+pub mod mux;
+pub mod null;
+pub mod pipeline;
+pub mod queue;
+pub mod redact;
+pub mod ring;
+pub mod session;
+#[cfg(all(feature = "shm", target_os = "linux"))]
+pub mod shm;
+pub mod sidecar;
+pub mod sim;
+pub mod tools;
+pub mod trigger;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub mod uring;
+pub mod vbs;
+pub mod watchdog;
@@ -0,0 +1,192 @@
+//! Implements [`ThreadAligner`], which groups the frames of different threads covering the same span of time.
+//!
+//! Threads are aligned on absolute time rather than on equal frame numbers, so threads with different frame rates,
+//! such as a mix of 32 MHz and 64 MHz channels, can be aligned. Each group spans one frame of the slowest thread, and
+//! holds as many frames of each faster thread as cover the same time. The frame rate of every thread must be a
+//! multiple of the slowest.
+
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind, Result};
+
+use crate::frame::VDIFFrame;
+use crate::io::VDIFRead;
+use crate::spec::StreamSpec;
+
+/// The frames of every thread covering one span of time.
+#[derive(Debug)]
+pub struct AlignedGroup {
+    /// The reference epoch of the group.
+    pub epoch: u8,
+    /// The seconds since the reference epoch of the group.
+    pub time: u32,
+    /// The index of the group within its second, counting in frames of the slowest thread.
+    pub slot: u32,
+    /// The frames of the group, ordered by thread and then frame number.
+    pub frames: Vec<VDIFFrame>,
+    /// Whether every frame expected in the group arrived.
+    pub complete: bool,
+}
+
+/// Groups the frames of a multi-threaded stream by the time they cover. See the [module documentation](self).
+pub struct ThreadAligner<R: VDIFRead> {
+    source: R,
+    spec: StreamSpec,
+    base_rate: u32,
+    group_size: usize,
+    max_pending: usize,
+    pending: BTreeMap<(u8, u32, u32), Vec<VDIFFrame>>,
+}
+
+impl<R: VDIFRead> ThreadAligner<R> {
+    /// Construct a new [`ThreadAligner`] over `source`, taking the threads and their frame rates from `spec`.
+    ///
+    /// Returns an error of kind [`InvalidInput`](ErrorKind::InvalidInput) if `spec` lists no threads, or their frame
+    /// rates are zero or not all multiples of the slowest.
+    pub fn new(source: R, spec: StreamSpec) -> Result<Self> {
+        let invalid = |msg: &str| Error::new(ErrorKind::InvalidInput, msg.to_string());
+        let rates: Vec<u32> = spec
+            .threads
+            .iter()
+            .map(|t| spec.frame_rate_of(*t))
+            .collect();
+        let base_rate = *rates
+            .iter()
+            .min()
+            .ok_or_else(|| invalid("The spec must list its threads"))?;
+        if base_rate == 0 {
+            return Err(invalid("Every thread's frame rate must be positive"));
+        }
+        if rates.iter().any(|r| r % base_rate != 0) {
+            return Err(invalid(
+                "Every thread's frame rate must be a multiple of the slowest",
+            ));
+        }
+        return Ok(Self {
+            source: source,
+            spec: spec,
+            base_rate: base_rate,
+            group_size: rates.iter().map(|r| (r / base_rate) as usize).sum(),
+            max_pending: 8,
+            pending: BTreeMap::new(),
+        });
+    }
+
+    /// Set how many incomplete groups may be held waiting for late frames before the oldest is released incomplete.
+    /// Defaults to 8.
+    pub fn set_max_pending(&mut self, max_pending: usize) {
+        self.max_pending = max_pending.max(1);
+    }
+
+    /// Return the next group of aligned frames, or an [`UnexpectedEof`](ErrorKind::UnexpectedEof) error once the
+    /// source has ended and every held group has been returned.
+    pub fn next_group(&mut self) -> Result<AlignedGroup> {
+        loop {
+            if let Some(key) = self.ready() {
+                return Ok(self.release(key));
+            }
+            match self.source.read_frame() {
+                Ok(frame) => self.insert(frame),
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                    return match self.pending.keys().next().copied() {
+                        Some(key) => Ok(self.release(key)),
+                        None => Err(e),
+                    };
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Return the wrapped source.
+    pub fn into_inner(self) -> R {
+        return self.source;
+    }
+
+    fn insert(&mut self, frame: VDIFFrame) {
+        let header = frame.get_header();
+        if !self.spec.threads.contains(&header.thread) {
+            return;
+        }
+        let ratio = self.spec.frame_rate_of(header.thread) / self.base_rate;
+        let key = (header.epoch, header.time, header.frameno / ratio);
+        self.pending.entry(key).or_default().push(frame);
+    }
+
+    // The oldest group that is complete, or that has been held for too long.
+    fn ready(&self) -> Option<(u8, u32, u32)> {
+        let (key, frames) = self.pending.iter().next()?;
+        if frames.len() >= self.group_size || self.pending.len() > self.max_pending {
+            return Some(*key);
+        }
+        return None;
+    }
+
+    fn release(&mut self, key: (u8, u32, u32)) -> AlignedGroup {
+        let mut frames = self.pending.remove(&key).unwrap();
+        frames.sort_by_key(|f| {
+            let header = f.get_header();
+            (header.thread, header.frameno)
+        });
+        return AlignedGroup {
+            epoch: key.0,
+            time: key.1,
+            slot: key.2,
+            complete: frames.len() == self.group_size,
+            frames: frames,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::VDIFHeader;
+    use crate::testing::Source;
+
+    fn frame(thread: u16, frameno: u32) -> VDIFFrame {
+        return VDIFFrame::from_header(VDIFHeader {
+            thread: thread,
+            frameno: frameno,
+            size: 8,
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn test_multi_rate_alignment() {
+        // Thread 1 runs at twice the frame rate of thread 0, and its frame 3 is lost
+        let frames = vec![
+            frame(1, 0),
+            frame(0, 0),
+            frame(1, 1),
+            frame(1, 2),
+            frame(0, 1),
+        ];
+        let mut spec = StreamSpec::from_header(&frames[1].get_header(), 2);
+        spec.threads = vec![0, 1];
+        spec.thread_frame_rates = vec![2, 4];
+
+        let mut aligner = ThreadAligner::new(Source(frames.into_iter()), spec.clone()).unwrap();
+        let group = aligner.next_group().unwrap();
+        assert!(group.complete);
+        let members: Vec<(u16, u32)> = group
+            .frames
+            .iter()
+            .map(|f| (f.get_header().thread, f.get_header().frameno))
+            .collect();
+        assert_eq!(members, vec![(0, 0), (1, 0), (1, 1)]);
+
+        let group = aligner.next_group().unwrap();
+        assert_eq!(
+            (group.slot, group.frames.len(), group.complete),
+            (1, 2, false)
+        );
+        assert!(aligner.next_group().is_err());
+
+        for rates in [vec![0, 4], vec![2, 3]] {
+            spec.thread_frame_rates = rates;
+            let error = ThreadAligner::new(Source(Vec::new().into_iter()), spec.clone());
+            assert_eq!(error.err().unwrap().kind(), ErrorKind::InvalidInput);
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! Implements heuristics for recognising VDIF streams that have been byte-swapped or word-swapped by buggy capture
+//! tools, along with a corrective transform that can be inserted into the reader path.
+//!
+//! Each candidate transform is applied to a header and the result is scored for plausibility. The transform giving
+//! the most plausible header is assumed to be the one that was (accidentally) applied to the stream. All of the
+//! transforms are their own inverse, so applying the detected transform again restores the original data.
+
+use std::io::Result;
+
+use chrono::Utc;
+
+use crate::frame::VDIFFrame;
+use crate::header::vdiftime_from_date;
+use crate::header_encoding::{decode_header, MASK_IS_LEGACY};
+use crate::io::VDIFRead;
+
+// The number of seconds in the longest possible reference epoch (184 days).
+const MAX_EPOCH_SECONDS: u32 = 184 * 86400;
+// Bits 30 and 31 of word 1 are unassigned and should always be zero.
+const MASK_W1_UNASSIGNED: u32 = 0b11000000000000000000000000000000;
+
+/// A byte order transform that may have been applied to a VDIF stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrderTransform {
+    /// The data is as it should be.
+    Identity,
+    /// The bytes within each `u32` word have been reversed.
+    SwapBytes,
+    /// Each pair of `u32` words has been swapped, as happens when data is mistakenly handled as `u64`s.
+    SwapWords,
+    /// The bytes within each `u64` have been reversed, i.e. both [`SwapBytes`](ByteOrderTransform::SwapBytes) and
+    /// [`SwapWords`](ByteOrderTransform::SwapWords).
+    SwapBoth,
+}
+
+impl ByteOrderTransform {
+    /// All possible transforms, in the order they are tried during detection.
+    pub const ALL: [ByteOrderTransform; 4] = [
+        ByteOrderTransform::Identity,
+        ByteOrderTransform::SwapBytes,
+        ByteOrderTransform::SwapWords,
+        ByteOrderTransform::SwapBoth,
+    ];
+
+    /// Apply this transform to a slice of `u32` words in place. `words` must have an even length for the word
+    /// swapping transforms.
+    pub fn apply_words(&self, words: &mut [u32]) {
+        match self {
+            Self::Identity => {}
+            Self::SwapBytes => words.iter_mut().for_each(|w| *w = w.swap_bytes()),
+            Self::SwapWords => words.chunks_exact_mut(2).for_each(|pair| pair.swap(0, 1)),
+            Self::SwapBoth => {
+                Self::SwapBytes.apply_words(words);
+                Self::SwapWords.apply_words(words);
+            }
+        }
+    }
+
+    /// Apply this transform to an entire [`VDIFFrame`] in place.
+    pub fn apply(&self, frame: &mut VDIFFrame) {
+        self.apply_words(frame.as_mut_slice());
+    }
+}
+
+/// Configures how strictly header plausibility is judged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeuristicConfig {
+    /// The expected frame size in bytes, if known. A matching size field is strong evidence for a transform.
+    pub frame_size: Option<usize>,
+    /// The largest reference epoch considered plausible.
+    pub max_epoch: u8,
+    /// The largest VDIF version considered plausible.
+    pub max_version: u8,
+}
+
+impl Default for HeuristicConfig {
+    fn default() -> Self {
+        // Allow one epoch of slack for clocks that are slightly ahead
+        let (epoch, _) = vdiftime_from_date(Utc::now().naive_utc());
+        return Self {
+            frame_size: None,
+            max_epoch: epoch.saturating_add(1),
+            max_version: 1,
+        };
+    }
+}
+
+/// Score the plausibility of a header. Higher scores indicate a more plausible header.
+pub fn score_header(words: [u32; 8], config: &HeuristicConfig) -> u32 {
+    let header = decode_header(words);
+    let mut score = 0;
+
+    if header.version <= config.max_version {
+        score += 1;
+    }
+    if header.epoch <= config.max_epoch {
+        score += 1;
+    }
+    if header.time < MAX_EPOCH_SECONDS {
+        score += 1;
+    }
+    if words[1] & MASK_W1_UNASSIGNED == 0 {
+        score += 1;
+    }
+    if words[0] & MASK_IS_LEGACY == 0 {
+        score += 1;
+    }
+    if header.size != 0 {
+        score += 1;
+        if config.frame_size == Some(header.bytesize() as usize) {
+            score += 2;
+        }
+    }
+
+    return score;
+}
+
+/// Detect which [`ByteOrderTransform`] was most likely applied to the header in the first eight words of `words`.
+///
+/// Ties are broken in favour of the earlier entry in [`ByteOrderTransform::ALL`], so clean data is preferred.
+pub fn detect_transform(words: &[u32], config: &HeuristicConfig) -> ByteOrderTransform {
+    let mut best = ByteOrderTransform::Identity;
+    let mut best_score = 0;
+    for transform in ByteOrderTransform::ALL {
+        let mut candidate: [u32; 8] = words[0..8].try_into().unwrap();
+        transform.apply_words(&mut candidate);
+        let score = score_header(candidate, config);
+        if score > best_score {
+            best = transform;
+            best_score = score;
+        }
+    }
+    return best;
+}
+
+/// Wraps a [`VDIFRead`] type and undoes any byte order transform applied to the frames read from it.
+///
+/// Unless constructed with a fixed transform, the transform is detected from the first frame read and then applied
+/// to every frame thereafter.
+pub struct VDIFByteOrderFix<R: VDIFRead> {
+    inner: R,
+    config: HeuristicConfig,
+    transform: Option<ByteOrderTransform>,
+}
+
+impl<R: VDIFRead> VDIFByteOrderFix<R> {
+    /// Construct a new [`VDIFByteOrderFix`] that detects the transform from the first frame it reads.
+    pub fn new(inner: R, config: HeuristicConfig) -> Self {
+        return Self {
+            inner: inner,
+            config: config,
+            transform: None,
+        };
+    }
+
+    /// Construct a new [`VDIFByteOrderFix`] that always undoes `transform`.
+    pub fn with_transform(inner: R, transform: ByteOrderTransform) -> Self {
+        return Self {
+            inner: inner,
+            config: HeuristicConfig::default(),
+            transform: Some(transform),
+        };
+    }
+
+    /// Get the transform being corrected, or `None` if no frames have been read yet.
+    pub fn transform(&self) -> Option<ByteOrderTransform> {
+        return self.transform;
+    }
+}
+
+impl<R: VDIFRead> VDIFRead for VDIFByteOrderFix<R> {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        let mut frame = self.inner.read_frame()?;
+        let transform = match self.transform {
+            Some(transform) => transform,
+            None => {
+                let detected = detect_transform(frame.as_slice(), &self.config);
+                self.transform = Some(detected);
+                detected
+            }
+        };
+        transform.apply(&mut frame);
+        return Ok(frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::sim::VDIFSim;
+
+    #[test]
+    fn test_detect_transform() {
+        let mut sim = VDIFSim::new(1032, 100, 2);
+        for _ in 0..57 {
+            sim.generate_frame();
+        }
+        let config = HeuristicConfig {
+            frame_size: Some(1032),
+            ..Default::default()
+        };
+
+        for transform in ByteOrderTransform::ALL {
+            let frame = sim.generate_frame();
+            let mut swapped = VDIFFrame::from_slice(frame.as_slice());
+            transform.apply(&mut swapped);
+
+            assert_eq!(detect_transform(swapped.as_slice(), &config), transform);
+            transform.apply(&mut swapped);
+            assert_eq!(swapped.as_slice(), frame.as_slice());
+        }
+    }
+}
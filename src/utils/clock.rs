@@ -0,0 +1,290 @@
+//! Implements the [`Clock`] trait, a pluggable source of UTC time for anything that paces or timestamps frames.
+//!
+//! [`SystemClock`] reads the system clock, [`MockClock`] is set and advanced by hand for deterministic tests, and with
+//! the `ptp` feature [`PtpClock`] reads a PTP hardware clock directly, for replay disciplined by hardware.
+
+use std::io::Result;
+use std::sync::{Arc, Mutex};
+
+use chrono::{NaiveDateTime, TimeDelta, Timelike, Utc};
+
+use crate::frame::VDIFFrame;
+use crate::io::VDIFWrite;
+
+// How long before a deadline to stop sleeping and start spinning, covering the scheduler's wakeup latency.
+const SPIN_MARGIN: TimeDelta = TimeDelta::milliseconds(2);
+
+/// A source of UTC time.
+pub trait Clock: Send {
+    /// Get the current time.
+    fn now(&self) -> NaiveDateTime;
+
+    /// Block until the clock reaches `time`. Returns immediately if `time` has already passed.
+    fn sleep_until(&self, time: NaiveDateTime) {
+        loop {
+            let remaining = time - self.now();
+            if remaining <= TimeDelta::zero() {
+                return;
+            }
+            std::thread::sleep(remaining.to_std().unwrap());
+        }
+    }
+
+    /// Busy-wait until the clock reaches `time`. This is far more precise than [`sleep_until`](Clock::sleep_until)
+    /// but occupies a core, so should only be used for short waits.
+    fn spin_until(&self, time: NaiveDateTime) {
+        while self.now() < time {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+impl<C: Clock + Sync + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> NaiveDateTime {
+        return (**self).now();
+    }
+
+    fn sleep_until(&self, time: NaiveDateTime) {
+        (**self).sleep_until(time);
+    }
+
+    fn spin_until(&self, time: NaiveDateTime) {
+        (**self).spin_until(time);
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Box<C> {
+    fn now(&self) -> NaiveDateTime {
+        return (**self).now();
+    }
+
+    fn sleep_until(&self, time: NaiveDateTime) {
+        (**self).sleep_until(time);
+    }
+
+    fn spin_until(&self, time: NaiveDateTime) {
+        (**self).spin_until(time);
+    }
+}
+
+/// The system clock.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveDateTime {
+        return Utc::now().naive_utc();
+    }
+}
+
+/// A clock that only moves when told to, for deterministic tests.
+///
+/// Clones share the same time, so a test can keep a handle to advance a clock it has handed to the code under test.
+/// [`sleep_until`](Clock::sleep_until) jumps straight to the requested time rather than blocking.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    time: Arc<Mutex<NaiveDateTime>>,
+}
+
+impl MockClock {
+    /// Construct a new [`MockClock`] reading `time`.
+    pub fn new(time: NaiveDateTime) -> Self {
+        return Self {
+            time: Arc::new(Mutex::new(time)),
+        };
+    }
+
+    /// Set the time of the clock.
+    pub fn set(&self, time: NaiveDateTime) {
+        *self.time.lock().unwrap() = time;
+    }
+
+    /// Move the clock forward by `delta`.
+    pub fn advance(&self, delta: TimeDelta) {
+        *self.time.lock().unwrap() += delta;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> NaiveDateTime {
+        return *self.time.lock().unwrap();
+    }
+
+    fn sleep_until(&self, time: NaiveDateTime) {
+        let mut now = self.time.lock().unwrap();
+        if *now < time {
+            *now = time;
+        }
+    }
+
+    fn spin_until(&self, time: NaiveDateTime) {
+        self.sleep_until(time);
+    }
+}
+
+/// A PTP hardware clock, such as one exposed by a NIC at `/dev/ptp0`. Requires the `ptp` feature, and Linux.
+///
+/// PTP hardware clocks usually count TAI rather than UTC, so an offset (TAI - UTC, 37 seconds since 2017) can be set
+/// with [`set_offset`](PtpClock::set_offset) and is subtracted from every reading.
+///
+/// [`Clock::now`] can't fail, so should the hardware clock become unreadable, for instance because the NIC was reset,
+/// it falls back to the system clock and counts the failure in [`failures`](PtpClock::failures). Use
+/// [`try_now`](PtpClock::try_now) to see the error instead.
+#[cfg(all(feature = "ptp", target_os = "linux"))]
+pub struct PtpClock {
+    device: std::fs::File,
+    offset: TimeDelta,
+    failures: std::sync::atomic::AtomicU64,
+}
+
+#[cfg(all(feature = "ptp", target_os = "linux"))]
+impl PtpClock {
+    /// Open the PTP hardware clock at `path`, with no offset.
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Self> {
+        let clock = Self {
+            device: std::fs::File::open(path)?,
+            offset: TimeDelta::zero(),
+            failures: std::sync::atomic::AtomicU64::new(0),
+        };
+        clock.try_now()?;
+        return Ok(clock);
+    }
+
+    /// Set the offset subtracted from every reading of the hardware clock.
+    pub fn set_offset(&mut self, offset: TimeDelta) {
+        self.offset = offset;
+    }
+
+    /// Get the number of readings of the hardware clock that failed, and fell back to the system clock.
+    pub fn failures(&self) -> u64 {
+        return self.failures.load(std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Read the hardware clock, returning the error if it can't be read rather than falling back to the system clock.
+    pub fn try_now(&self) -> std::io::Result<NaiveDateTime> {
+        use std::os::fd::AsRawFd;
+
+        // The dynamic clock ID of an open clock device, as defined by FD_TO_CLOCKID in the kernel
+        let clockid = ((!self.device.as_raw_fd()) << 3) | 3;
+        let mut ts = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        if unsafe { libc::clock_gettime(clockid, &mut ts) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        return chrono::DateTime::from_timestamp(ts.tv_sec, ts.tv_nsec as u32)
+            .map(|t| t.naive_utc() - self.offset)
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "PTP hardware clock is out of range",
+                )
+            });
+    }
+}
+
+#[cfg(all(feature = "ptp", target_os = "linux"))]
+impl Clock for PtpClock {
+    fn now(&self) -> NaiveDateTime {
+        return self.try_now().unwrap_or_else(|_| {
+            self.failures
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Utc::now().naive_utc()
+        });
+    }
+}
+
+/// Block until the next integer second of `clock`, returning that second.
+///
+/// The wait sleeps until shortly before the second boundary and then spins, so returns within a few microseconds of
+/// the boundary on an unloaded host. Use it to start a replayed stream on a second edge, as real stations do.
+pub fn wait_for_next_second<C: Clock + ?Sized>(clock: &C) -> NaiveDateTime {
+    let now = clock.now();
+    let second = now.with_nanosecond(0).unwrap() + TimeDelta::seconds(1);
+    clock.sleep_until(second - SPIN_MARGIN);
+    clock.spin_until(second);
+    return second;
+}
+
+/// Delays the first frame written to a [`VDIFWrite`] until the next integer second of a clock, then passes every
+/// frame straight through.
+pub struct AlignedStart<W: VDIFWrite, C: Clock> {
+    inner: W,
+    clock: C,
+    started: Option<NaiveDateTime>,
+}
+
+impl<W: VDIFWrite, C: Clock> AlignedStart<W, C> {
+    /// Construct a new [`AlignedStart`] aligning the start of writes to `inner` with `clock`.
+    pub fn new(inner: W, clock: C) -> Self {
+        return Self {
+            inner: inner,
+            clock: clock,
+            started: None,
+        };
+    }
+
+    /// Get the second the first frame was released at, if one has been written.
+    pub fn started(&self) -> Option<NaiveDateTime> {
+        return self.started;
+    }
+
+    /// Return the wrapped writer.
+    pub fn into_inner(self) -> W {
+        return self.inner;
+    }
+}
+
+impl<W: VDIFWrite, C: Clock> VDIFWrite for AlignedStart<W, C> {
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        if self.started.is_none() {
+            self.started = Some(wait_for_next_second(&self.clock));
+        }
+        return self.inner.write_frame(frame);
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        return self.inner.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_mock_clock() {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let clock = MockClock::new(start);
+        let shared: Box<dyn Clock> = Box::new(clock.clone());
+
+        clock.advance(TimeDelta::seconds(2));
+        assert_eq!(shared.now(), start + TimeDelta::seconds(2));
+        shared.sleep_until(start + TimeDelta::seconds(5));
+        assert_eq!(clock.now(), start + TimeDelta::seconds(5));
+        shared.sleep_until(start);
+        assert_eq!(clock.now(), start + TimeDelta::seconds(5));
+    }
+
+    #[test]
+    fn test_aligned_start() {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_milli_opt(0, 0, 3, 250)
+            .unwrap();
+        let clock = MockClock::new(start);
+        let mut writer = AlignedStart::new(Vec::new(), clock.clone());
+        writer.write_frame(VDIFFrame::empty(64)).unwrap();
+        writer.write_frame(VDIFFrame::empty(64)).unwrap();
+
+        let second = start.with_nanosecond(0).unwrap() + TimeDelta::seconds(1);
+        assert_eq!(writer.started(), Some(second));
+        assert_eq!(clock.now(), second);
+        assert_eq!(writer.into_inner().len(), 2);
+    }
+}
@@ -0,0 +1,170 @@
+//! Implements [`FifoFanout`], routing the frames of a multi-thread stream into a separate queue for each VDIF thread so
+//! each can be processed in parallel by its own consumer.
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+
+use crate::frame::VDIFFrame;
+use crate::io::VDIFWrite;
+
+// A bounded queue, along with its receiving end until a consumer takes it.
+struct Queue {
+    sender: SyncSender<VDIFFrame>,
+    receiver: Option<Receiver<VDIFFrame>>,
+    dropped: u64,
+}
+
+impl Queue {
+    fn new(capacity: usize) -> Self {
+        let (sender, receiver) = sync_channel(capacity);
+        return Self {
+            sender: sender,
+            receiver: Some(receiver),
+            dropped: 0,
+        };
+    }
+}
+
+/// Routes frames by thread ID into a bounded single-consumer queue per thread.
+///
+/// A queue is created up front for each expected thread, and frames of any other thread go to a shared "unknown
+/// thread" queue. Consumers take the receiving end of their queue with [`take_receiver`](FifoFanout::take_receiver),
+/// which implements [`VDIFRead`](crate::io::VDIFRead), and move it onto their own thread.
+///
+/// [`write_frame`](VDIFWrite::write_frame) blocks while the destination queue is full, so a slow consumer applies
+/// backpressure to the whole stream. Use [`try_route`](FifoFanout::try_route) to drop frames instead.
+pub struct FifoFanout {
+    queues: HashMap<u16, Queue>,
+    unknown: Queue,
+}
+
+impl FifoFanout {
+    /// Construct a new [`FifoFanout`] with a queue of `capacity` frames for each of `threads`, and for unknown threads.
+    pub fn new(threads: &[u16], capacity: usize) -> Self {
+        return Self {
+            queues: threads
+                .iter()
+                .map(|thread| (*thread, Queue::new(capacity)))
+                .collect(),
+            unknown: Queue::new(capacity),
+        };
+    }
+
+    /// Take the receiving end of the queue for `thread`. Returns `None` if `thread` has no queue, or its receiver has
+    /// already been taken.
+    pub fn take_receiver(&mut self, thread: u16) -> Option<Receiver<VDIFFrame>> {
+        return self.queues.get_mut(&thread)?.receiver.take();
+    }
+
+    /// Take the receiving end of the queue for unknown threads. Returns `None` if it has already been taken.
+    pub fn take_unknown(&mut self) -> Option<Receiver<VDIFFrame>> {
+        return self.unknown.receiver.take();
+    }
+
+    /// Get the number of frames of `thread` dropped by [`try_route`](FifoFanout::try_route). Frames of unknown
+    /// threads are counted under any thread without a queue.
+    pub fn dropped(&self, thread: u16) -> u64 {
+        return self.queue(thread).dropped;
+    }
+
+    fn queue(&self, thread: u16) -> &Queue {
+        return self.queues.get(&thread).unwrap_or(&self.unknown);
+    }
+
+    fn queue_mut(&mut self, thread: u16) -> &mut Queue {
+        return self.queues.get_mut(&thread).unwrap_or(&mut self.unknown);
+    }
+
+    /// Route `frame` to the queue of its thread, blocking while the queue is full.
+    ///
+    /// Returns an error of kind [`BrokenPipe`](ErrorKind::BrokenPipe) if the consumer of the queue has been dropped.
+    pub fn route(&mut self, frame: VDIFFrame) -> Result<()> {
+        let thread = frame.get_header().thread;
+        return self
+            .queue_mut(thread)
+            .sender
+            .send(frame)
+            .map_err(|_| consumer_gone(thread));
+    }
+
+    /// Route `frame` to the queue of its thread, dropping it if the queue is full. Returns whether the frame was
+    /// queued.
+    ///
+    /// Returns an error of kind [`BrokenPipe`](ErrorKind::BrokenPipe) if the consumer of the queue has been dropped.
+    pub fn try_route(&mut self, frame: VDIFFrame) -> Result<bool> {
+        let thread = frame.get_header().thread;
+        let queue = self.queue_mut(thread);
+        return match queue.sender.try_send(frame) {
+            Ok(()) => Ok(true),
+            Err(TrySendError::Full(_)) => {
+                queue.dropped += 1;
+                Ok(false)
+            }
+            Err(TrySendError::Disconnected(_)) => Err(consumer_gone(thread)),
+        };
+    }
+}
+
+fn consumer_gone(thread: u16) -> Error {
+    return Error::new(
+        ErrorKind::BrokenPipe,
+        format!("The consumer of thread {} has disconnected", thread),
+    );
+}
+
+impl VDIFWrite for FifoFanout {
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        return self.route(frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::VDIFHeader;
+    use crate::io::VDIFRead;
+
+    fn frame(thread: u16) -> VDIFFrame {
+        return VDIFFrame::from_header(VDIFHeader {
+            size: 4,
+            thread: thread,
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn test_fanout() {
+        let mut fanout = FifoFanout::new(&[0, 1], 2);
+        let mut consumers: Vec<_> = [0, 1]
+            .iter()
+            .map(|thread| {
+                let mut rx = fanout.take_receiver(*thread).unwrap();
+                std::thread::spawn(move || {
+                    let mut count = 0;
+                    while let Ok(frame) = rx.read_frame() {
+                        assert_eq!(frame.get_header().thread, *thread);
+                        count += 1;
+                    }
+                    count
+                })
+            })
+            .collect();
+        assert!(fanout.take_receiver(0).is_none());
+
+        for i in 0..10 {
+            fanout.write_frame(frame(i % 2)).unwrap();
+        }
+        let unknown = fanout.take_unknown().unwrap();
+        assert!(fanout.try_route(frame(7)).unwrap());
+        assert!(fanout.try_route(frame(8)).unwrap());
+        assert!(!fanout.try_route(frame(9)).unwrap());
+        assert_eq!(fanout.dropped(9), 1);
+        assert_eq!(unknown.recv().unwrap().get_header().thread, 7);
+
+        drop(fanout);
+        for consumer in consumers.drain(..) {
+            assert_eq!(consumer.join().unwrap(), 5);
+        }
+    }
+}
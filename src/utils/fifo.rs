@@ -0,0 +1,312 @@
+//! Implements a lock-free single-producer, single-consumer ring of frame slots, whose slots can be filled and read in
+//! place.
+//!
+//! The ring's memory is allocated once, up front. Rather than moving a [`VDIFFrame`] in and out, the producer borrows
+//! the next free slot with [`grab_slot`](FifoProducer::grab_slot), fills it, for instance by receiving a datagram
+//! straight into it, and publishes it with [`commit`](FifoProducer::commit). The consumer likewise borrows the oldest
+//! slot with [`read_slot`](FifoConsumer::read_slot), decodes it where it lies, and hands it back with
+//! [`release`](FifoConsumer::release):
+//!
+//! ```rust,ignore
+//! let (mut producer, mut consumer) = frame_fifo(1024, 8032);
+//!
+//! // Capture thread
+//! if let Some(slot) = producer.grab_slot_bytes() {
+//!     sock.recv(slot).unwrap();
+//!     producer.commit();
+//! }
+//!
+//! // Processing thread
+//! if let Some(slot) = consumer.read_slot() {
+//!     process(slot);
+//!     consumer.release();
+//! }
+//! ```
+//!
+//! Both halves also implement [`VDIFWrite`] and [`VDIFRead`], copying whole frames in and out, for use where a copy
+//! doesn't matter.
+
+use std::cell::UnsafeCell;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::frame::VDIFFrame;
+use crate::io::{VDIFRead, VDIFWrite};
+
+// The ring, shared by both halves. `head` counts the slots released by the consumer and `tail` the slots committed by
+// the producer, so slot `i % capacity` belongs to the consumer while `head <= i < tail` and to the producer otherwise.
+struct Ring {
+    slots: Box<[UnsafeCell<u32>]>,
+    words: usize,
+    capacity: usize,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    // Set when each half is dropped, with release ordering so the other half sees everything it did beforehand
+    producer_closed: AtomicBool,
+    consumer_closed: AtomicBool,
+}
+
+// Safety: each slot is only ever accessed by the half that currently owns it, as tracked by `head` and `tail`
+unsafe impl Sync for Ring {}
+
+impl Ring {
+    // Safety: the caller must own slot `index`, and hold no other reference to it
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn slot_mut(&self, index: usize) -> &mut [u32] {
+        let start = (index % self.capacity) * self.words;
+        let ptr = self.slots[start..start + self.words].as_ptr() as *mut u32;
+        return core::slice::from_raw_parts_mut(ptr, self.words);
+    }
+
+    // Safety: the caller must own slot `index`
+    unsafe fn slot(&self, index: usize) -> &[u32] {
+        let start = (index % self.capacity) * self.words;
+        let ptr = self.slots[start..start + self.words].as_ptr() as *const u32;
+        return core::slice::from_raw_parts(ptr, self.words);
+    }
+}
+
+/// Construct a ring of `capacity` slots, each holding a frame of `frame_size` bytes, returning its two halves.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero or `frame_size` is not a multiple of 8 bytes.
+pub fn frame_fifo(capacity: usize, frame_size: usize) -> (FifoProducer, FifoConsumer) {
+    assert!(capacity > 0, "A frame FIFO needs at least one slot");
+    assert!(
+        frame_size % 8 == 0,
+        "VDIF frame sizes must be a multiple of 8 bytes"
+    );
+    let words = frame_size / 4;
+    let ring = Arc::new(Ring {
+        slots: (0..capacity * words).map(|_| UnsafeCell::new(0)).collect(),
+        words: words,
+        capacity: capacity,
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        producer_closed: AtomicBool::new(false),
+        consumer_closed: AtomicBool::new(false),
+    });
+    let producer = FifoProducer {
+        ring: ring.clone(),
+        tail: 0,
+    };
+    let consumer = FifoConsumer {
+        ring: ring,
+        head: 0,
+    };
+    return (producer, consumer);
+}
+
+/// The writing half of a [`frame_fifo`].
+pub struct FifoProducer {
+    ring: Arc<Ring>,
+    // Our copy of the ring's tail, which only we advance
+    tail: usize,
+}
+
+impl FifoProducer {
+    /// Get the size in bytes of each slot.
+    pub fn frame_size(&self) -> usize {
+        return self.ring.words * 4;
+    }
+
+    /// Check whether the consumer has been dropped.
+    pub fn is_abandoned(&self) -> bool {
+        return self.ring.consumer_closed.load(Ordering::Acquire);
+    }
+
+    /// Borrow the next free slot to fill in place, or `None` if the ring is full. The slot is not seen by the consumer
+    /// until it is [`commit`](FifoProducer::commit)ted, and grabbing again before then returns the same slot.
+    ///
+    /// Slots are reused without being cleared, so hold whatever was last written to them.
+    pub fn grab_slot(&mut self) -> Option<&mut [u32]> {
+        if self.tail - self.ring.head.load(Ordering::Acquire) == self.ring.capacity {
+            return None;
+        }
+        // Safety: the slot lies outside the consumer's range, and the borrow of `self` prevents a second reference
+        return Some(unsafe { self.ring.slot_mut(self.tail) });
+    }
+
+    /// Borrow the next free slot as bytes, as [`grab_slot`](FifoProducer::grab_slot) does.
+    pub fn grab_slot_bytes(&mut self) -> Option<&mut [u8]> {
+        let slot = self.grab_slot()?;
+        // Safety: any bytes are a valid u32, and u8 has no alignment requirement
+        return Some(unsafe {
+            core::slice::from_raw_parts_mut(slot.as_mut_ptr() as *mut u8, slot.len() * 4)
+        });
+    }
+
+    /// Publish the slot last returned by [`grab_slot`](FifoProducer::grab_slot) to the consumer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ring is full, so there is no slot to commit.
+    pub fn commit(&mut self) {
+        assert!(
+            self.tail - self.ring.head.load(Ordering::Acquire) < self.ring.capacity,
+            "Committed a slot of a full frame FIFO"
+        );
+        self.tail += 1;
+        self.ring.tail.store(self.tail, Ordering::Release);
+    }
+}
+
+impl VDIFWrite for FifoProducer {
+    /// Copy `frame` into the next free slot, waiting while the ring is full.
+    ///
+    /// Returns an error of kind [`InvalidInput`](ErrorKind::InvalidInput) if the frame doesn't fit the slots, or
+    /// [`BrokenPipe`](ErrorKind::BrokenPipe) if the consumer has been dropped.
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        if frame.bytesize() != self.frame_size() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Frame of {} bytes doesn't fit a FIFO of {} byte slots",
+                    frame.bytesize(),
+                    self.frame_size()
+                ),
+            ));
+        }
+        loop {
+            if self.is_abandoned() {
+                return Err(Error::new(
+                    ErrorKind::BrokenPipe,
+                    "The consumer of the FIFO has disconnected",
+                ));
+            }
+            if let Some(slot) = self.grab_slot() {
+                slot.copy_from_slice(frame.as_slice());
+                self.commit();
+                return Ok(());
+            }
+            std::thread::yield_now();
+        }
+    }
+}
+
+impl Drop for FifoProducer {
+    fn drop(&mut self) {
+        self.ring.producer_closed.store(true, Ordering::Release);
+    }
+}
+
+/// The reading half of a [`frame_fifo`].
+pub struct FifoConsumer {
+    ring: Arc<Ring>,
+    // Our copy of the ring's head, which only we advance
+    head: usize,
+}
+
+impl FifoConsumer {
+    /// Get the size in bytes of each slot.
+    pub fn frame_size(&self) -> usize {
+        return self.ring.words * 4;
+    }
+
+    /// Get the number of committed slots waiting to be read.
+    pub fn len(&self) -> usize {
+        return self.ring.tail.load(Ordering::Acquire) - self.head;
+    }
+
+    /// Check whether the producer has been dropped. Once this returns `true`, every slot the producer committed is
+    /// visible to [`read_slot`](FifoConsumer::read_slot).
+    pub fn is_abandoned(&self) -> bool {
+        return self.ring.producer_closed.load(Ordering::Acquire);
+    }
+
+    /// Borrow the oldest committed slot in place, or `None` if the ring is empty. The slot stays in the ring until it
+    /// is [`release`](FifoConsumer::release)d.
+    pub fn read_slot(&self) -> Option<&[u32]> {
+        if self.len() == 0 {
+            return None;
+        }
+        // Safety: the slot lies within the consumer's range, so the producer won't touch it until it is released
+        return Some(unsafe { self.ring.slot(self.head) });
+    }
+
+    /// Hand the slot last returned by [`read_slot`](FifoConsumer::read_slot) back to the producer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ring is empty, so there is no slot to release.
+    pub fn release(&mut self) {
+        assert!(self.len() > 0, "Released a slot of an empty frame FIFO");
+        self.head += 1;
+        self.ring.head.store(self.head, Ordering::Release);
+    }
+}
+
+impl VDIFRead for FifoConsumer {
+    /// Copy the oldest committed slot out as a [`VDIFFrame`], waiting while the ring is empty.
+    ///
+    /// Returns an error of kind [`UnexpectedEof`](ErrorKind::UnexpectedEof) once the producer has been dropped and
+    /// the ring is empty.
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        loop {
+            // Check for the producer first, so a slot it committed just before leaving is still read
+            let abandoned = self.is_abandoned();
+            if let Some(slot) = self.read_slot() {
+                let frame = VDIFFrame::from_slice(slot);
+                self.release();
+                return Ok(frame);
+            }
+            if abandoned {
+                return Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "The producer of the FIFO has disconnected",
+                ));
+            }
+            std::thread::yield_now();
+        }
+    }
+}
+
+impl Drop for FifoConsumer {
+    fn drop(&mut self) {
+        self.ring.consumer_closed.store(true, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::VDIFHeader;
+
+    #[test]
+    fn test_frame_fifo() {
+        let (mut producer, mut consumer) = frame_fifo(2, 32);
+        let slot = producer.grab_slot().unwrap();
+        slot[0] = 7;
+        producer.commit();
+        producer.grab_slot_bytes().unwrap()[0] = 8;
+        producer.commit();
+        assert!(producer.grab_slot().is_none());
+        assert_eq!(consumer.len(), 2);
+        assert_eq!(consumer.read_slot().unwrap()[0], 7);
+        consumer.release();
+        assert_eq!(consumer.read_slot().unwrap()[0], 8);
+        consumer.release();
+        assert!(consumer.read_slot().is_none());
+
+        let writer = std::thread::spawn(move || {
+            for frameno in 0..100 {
+                let frame = VDIFFrame::from_header(VDIFHeader {
+                    frameno: frameno,
+                    size: 4,
+                    ..Default::default()
+                });
+                producer.write_frame(frame).unwrap();
+            }
+        });
+        for frameno in 0..100 {
+            assert_eq!(consumer.read_frame().unwrap().get_header().frameno, frameno);
+        }
+        writer.join().unwrap();
+        assert_eq!(
+            consumer.read_frame().unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! Implements [`GapFiller`], which replaces missing frames with invalid placeholders so a stream is continuous.
+
+use std::collections::HashMap;
+use std::io::Result;
+
+use crate::frame::VDIFFrame;
+use crate::header::VDIFHeader;
+use crate::io::VDIFRead;
+use crate::spec::StreamSpec;
+
+/// Counters kept by a [`GapFiller`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FillStats {
+    /// The number of placeholder frames inserted.
+    pub filled: u64,
+    /// The number of frames at or before the latest frame of their thread, passed through unchanged.
+    pub late: u64,
+    /// The number of gaps too large to fill, after which the thread continued from the next frame.
+    pub skipped_gaps: u64,
+}
+
+/// Wraps a [`VDIFRead`] source, inserting a placeholder for every missing frame so each thread is gap-free.
+///
+/// Gaps are found per thread from the time and frame number of consecutive frames, using the frame rate of each thread
+/// from a [`StreamSpec`]. Each placeholder is a copy of the header of the frame before the gap, with the time and
+/// frame number of the missing frame and the invalid bit set, followed by a zeroed payload.
+///
+/// Gaps are only found between frames of a thread, so a thread's frames missing from the start of the stream, or from
+/// its end, are not filled. Frames of threads not listed in the spec, or with a frame rate of 0, pass through
+/// untouched.
+///
+/// Placeholders are generated one at a time as they are read, so filling a gap takes no more memory than reading a
+/// single frame.
+pub struct GapFiller<R: VDIFRead> {
+    source: R,
+    spec: StreamSpec,
+    max_gap: Option<u64>,
+    latest: HashMap<u16, VDIFHeader>,
+    gap: Option<Gap>,
+    stats: FillStats,
+}
+
+// A gap being filled: the header of the last frame returned, the number of placeholders still to return, and the
+// frame that follows them.
+struct Gap {
+    previous: VDIFHeader,
+    rate: u32,
+    remaining: u64,
+    frame: VDIFFrame,
+}
+
+impl<R: VDIFRead> GapFiller<R> {
+    /// Construct a new [`GapFiller`] over `source`, taking the threads and their frame rates from `spec`.
+    pub fn new(source: R, spec: StreamSpec) -> Self {
+        return Self {
+            source: source,
+            spec: spec,
+            max_gap: None,
+            latest: HashMap::new(),
+            gap: None,
+            stats: FillStats::default(),
+        };
+    }
+
+    /// Set the largest gap, in frames, that will be filled. Larger gaps, which usually come from a corrupted timestamp
+    /// or a restarted recorder, are counted but left unfilled. Defaults to one second of frames of each thread.
+    pub fn set_max_gap(&mut self, frames: u64) {
+        self.max_gap = Some(frames);
+    }
+
+    /// Get the counters of this filler.
+    pub fn stats(&self) -> FillStats {
+        return self.stats;
+    }
+
+    /// Return the wrapped source.
+    pub fn into_inner(self) -> R {
+        return self.source;
+    }
+
+    // Take note of `frame`, returning it unless it follows a gap to fill, in which case the gap is set up to be
+    // filled before it.
+    fn fill(&mut self, frame: VDIFFrame) -> Option<VDIFFrame> {
+        let header = frame.get_header();
+        let rate = self.spec.frame_rate_of(header.thread);
+        if !self.spec.threads.contains(&header.thread) || rate == 0 {
+            return Some(frame);
+        }
+        let Some(previous) = self.latest.insert(header.thread, header) else {
+            return Some(frame);
+        };
+
+        let distance = frame_distance(&previous, &header, rate);
+        if distance <= 0 {
+            self.stats.late += 1;
+            self.latest.insert(header.thread, previous);
+            return Some(frame);
+        }
+        let missing = distance as u64 - 1;
+        if missing == 0 {
+            return Some(frame);
+        } else if missing > self.max_gap.unwrap_or(rate as u64) {
+            self.stats.skipped_gaps += 1;
+            return Some(frame);
+        }
+        let mut previous = previous;
+        previous.is_valid = false;
+        self.gap = Some(Gap {
+            previous: previous,
+            rate: rate,
+            remaining: missing,
+            frame: frame,
+        });
+        return None;
+    }
+}
+
+// The number of frames from `from` to `to`, in a thread of `rate` frames per second.
+pub(crate) fn frame_distance(from: &VDIFHeader, to: &VDIFHeader, rate: u32) -> i64 {
+    return to.seconds_since(from) * rate as i64 + to.frameno as i64 - from.frameno as i64;
+}
+
+impl<R: VDIFRead> VDIFRead for GapFiller<R> {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        if let Some(mut gap) = self.gap.take() {
+            if gap.remaining == 0 {
+                return Ok(gap.frame);
+            }
+            gap.previous = gap.previous.next(gap.rate);
+            gap.remaining -= 1;
+            self.stats.filled += 1;
+            let placeholder = VDIFFrame::from_header(gap.previous);
+            self.gap = Some(gap);
+            return Ok(placeholder);
+        }
+        let frame = self.source.read_frame()?;
+        return match self.fill(frame) {
+            Some(frame) => Ok(frame),
+            None => self.read_frame(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Source;
+    use crate::utils::sim::VDIFSim;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn test_gap_filler() {
+        // Two threads at 4 frames/s. Thread 0 loses frames 3 to 5, across a second boundary
+        let mut sim = VDIFSim::new(64, 4, 2);
+        let mut frames: Vec<VDIFFrame> = (0..16).map(|_| sim.generate_frame()).collect();
+        frames.retain(|f| {
+            let h = f.get_header();
+            !(h.thread == 0 && ((h.time == 0 && h.frameno == 3) || (h.time == 1 && h.frameno < 2)))
+        });
+        let mut spec = StreamSpec::from_header(&frames[0].get_header(), 4);
+        spec.threads = vec![0, 1];
+
+        let mut filler = GapFiller::new(Source(frames.into_iter()), spec);
+        let mut thread0 = Vec::new();
+        loop {
+            match filler.read_frame() {
+                Ok(frame) => {
+                    let h = frame.get_header();
+                    if h.thread == 0 {
+                        thread0.push((h.time, h.frameno, h.is_valid));
+                    }
+                }
+                Err(e) => {
+                    assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+                    break;
+                }
+            }
+        }
+        assert_eq!(
+            thread0,
+            vec![
+                (0, 0, true),
+                (0, 1, true),
+                (0, 2, true),
+                (0, 3, false),
+                (1, 0, false),
+                (1, 1, false),
+                (1, 2, true),
+                (1, 3, true),
+            ]
+        );
+        assert_eq!(filler.stats().filled, 3);
+
+        // A corrupted timestamp far in the future is beyond the default limit of a second of frames
+        let mut sim = VDIFSim::new(64, 4, 1);
+        let first = sim.generate_frame();
+        let mut jumped = sim.generate_frame();
+        let mut header = jumped.get_header();
+        header.time += 1_000_000;
+        jumped.set_header(header);
+        let spec = StreamSpec::from_header(&first.get_header(), 4);
+        let mut filler = GapFiller::new(Source(vec![first, jumped].into_iter()), spec);
+        filler.read_frame().unwrap();
+        assert_eq!(filler.read_frame().unwrap().get_header().time, header.time);
+        assert_eq!(filler.stats().skipped_gaps, 1);
+    }
+}
@@ -0,0 +1,383 @@
+//! Implements adapters over [`VDIFRead`] sources that discard unwanted frames, such as those of other threads or with
+//! implausible timestamps.
+//!
+//! These work over any source, including the UDP and VTP receivers in [`net`](crate::net).
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Error, ErrorKind, Result};
+use std::ops::Range;
+
+use chrono::{NaiveDateTime, TimeDelta};
+
+use crate::frame::VDIFFrame;
+use crate::header::{FrameInstant, VDIFHeader};
+use crate::io::VDIFRead;
+use crate::utils::clock::{Clock, SystemClock};
+
+/// Passes on only the frames matching a set of criteria, such as a set of threads or a time range.
+///
+/// A new filter passes every frame, with each criterion added narrowing the selection:
+///
+/// ```rust,ignore
+/// let mut filter = FrameFilter::new(reader)
+///     .with_threads(&[0, 2])
+///     .with_station(station)
+///     .valid_only();
+/// ```
+pub struct FrameFilter<R: VDIFRead> {
+    inner: R,
+    threads: Option<HashSet<u16>>,
+    station: Option<u16>,
+    time_range: Option<Range<FrameInstant>>,
+    valid_only: bool,
+    discarded: u64,
+    // The threads seen so far, and those that have passed the end of the time range
+    seen: HashSet<u16>,
+    finished: HashSet<u16>,
+}
+
+impl<R: VDIFRead> FrameFilter<R> {
+    /// Construct a new [`FrameFilter`] passing every frame from `inner`.
+    pub fn new(inner: R) -> Self {
+        return Self {
+            inner: inner,
+            threads: None,
+            station: None,
+            time_range: None,
+            valid_only: false,
+            discarded: 0,
+            seen: HashSet::new(),
+            finished: HashSet::new(),
+        };
+    }
+
+    /// Pass only frames of the given threads.
+    pub fn with_threads(mut self, threads: &[u16]) -> Self {
+        self.threads = Some(threads.iter().copied().collect());
+        return self;
+    }
+
+    /// Pass only frames from the given station ID.
+    pub fn with_station(mut self, station: u16) -> Self {
+        self.station = Some(station);
+        return self;
+    }
+
+    /// Pass only frames whose timestamp lies within `range`.
+    ///
+    /// Each thread is assumed to be in time order, so once a frame of every selected thread (or every thread seen, if
+    /// none were selected) from the selected station has reached the end of the range, reads return an error of kind
+    /// [`UnexpectedEof`](ErrorKind::UnexpectedEof) rather than reading the rest of the source.
+    pub fn with_time_range(mut self, range: Range<FrameInstant>) -> Self {
+        self.time_range = Some(range);
+        return self;
+    }
+
+    /// Pass only frames marked valid.
+    pub fn valid_only(mut self) -> Self {
+        self.valid_only = true;
+        return self;
+    }
+
+    /// Check whether a frame with `header` would be passed on.
+    pub fn matches(&self, header: &VDIFHeader) -> bool {
+        return self
+            .threads
+            .as_ref()
+            .is_none_or(|threads| threads.contains(&header.thread))
+            && self.station.is_none_or(|station| header.station == station)
+            && self
+                .time_range
+                .as_ref()
+                .is_none_or(|range| range.contains(&header.instant()))
+            && (header.is_valid || !self.valid_only);
+    }
+
+    /// Get the number of frames discarded so far.
+    pub fn discarded(&self) -> u64 {
+        return self.discarded;
+    }
+
+    /// Return the wrapped source.
+    pub fn into_inner(self) -> R {
+        return self.inner;
+    }
+}
+
+impl<R: VDIFRead> VDIFRead for FrameFilter<R> {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        loop {
+            let frame = self.inner.read_frame()?;
+            let header = frame.get_header();
+            let finished = self.track(&header);
+            if self.matches(&header) {
+                return Ok(frame);
+            }
+            self.discarded += 1;
+            if finished {
+                return Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "Reached the end of the time range",
+                ));
+            }
+        }
+    }
+}
+
+impl<R: VDIFRead> FrameFilter<R> {
+    // Record the progress of the thread of `header`, returning whether every thread has passed the end of the time
+    // range
+    fn track(&mut self, header: &VDIFHeader) -> bool {
+        let Some(range) = &self.time_range else {
+            return false;
+        };
+        if self
+            .station
+            .is_some_and(|station| header.station != station)
+        {
+            return false;
+        }
+        if let Some(threads) = &self.threads {
+            if !threads.contains(&header.thread) {
+                return false;
+            }
+        }
+        self.seen.insert(header.thread);
+        if header.instant() >= range.end {
+            self.finished.insert(header.thread);
+        }
+        let threads = self.threads.as_ref().unwrap_or(&self.seen);
+        return self.finished.len() == threads.len();
+    }
+}
+
+/// Counters kept by a [`TimeWindowFilter`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TimeWindowStats {
+    /// The number of frames passed on.
+    pub passed: u64,
+    /// The number of frames discarded for being too far in the past.
+    pub too_early: u64,
+    /// The number of frames discarded for being too far in the future.
+    pub too_late: u64,
+}
+
+/// Discards frames whose timestamp lies more than a given duration either side of a reference time, which is the
+/// current time by default.
+///
+/// This protects recordings from stations replaying stale data or with broken clocks.
+pub struct TimeWindowFilter<R: VDIFRead> {
+    inner: R,
+    window: TimeDelta,
+    reference: Option<NaiveDateTime>,
+    clock: Box<dyn Clock>,
+    stats: TimeWindowStats,
+}
+
+impl<R: VDIFRead> TimeWindowFilter<R> {
+    /// Construct a new [`TimeWindowFilter`] passing only frames within `window` of the current time.
+    pub fn new(inner: R, window: std::time::Duration) -> Self {
+        return Self {
+            inner: inner,
+            window: TimeDelta::from_std(window).expect("Time window is too large"),
+            reference: None,
+            clock: Box::new(SystemClock),
+            stats: TimeWindowStats::default(),
+        };
+    }
+
+    /// Centre the window on a fixed time instead of the current time, for example when checking a recording after
+    /// the fact. Passing `None` returns to using the current time.
+    pub fn set_reference(&mut self, reference: Option<NaiveDateTime>) {
+        self.reference = reference;
+    }
+
+    /// Read the current time from `clock` instead of the system clock.
+    pub fn set_clock<C: Clock + 'static>(&mut self, clock: C) {
+        self.clock = Box::new(clock);
+    }
+
+    /// Get the counters accumulated so far.
+    pub fn stats(&self) -> TimeWindowStats {
+        return self.stats;
+    }
+
+    /// Return the wrapped source.
+    pub fn into_inner(self) -> R {
+        return self.inner;
+    }
+}
+
+impl<R: VDIFRead> VDIFRead for TimeWindowFilter<R> {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        loop {
+            let frame = self.inner.read_frame()?;
+            let reference = self.reference.unwrap_or_else(|| self.clock.now());
+            let offset = frame.get_header().date() - reference;
+            if offset < -self.window {
+                self.stats.too_early += 1;
+            } else if offset > self.window {
+                self.stats.too_late += 1;
+            } else {
+                self.stats.passed += 1;
+                return Ok(frame);
+            }
+        }
+    }
+}
+
+/// Counters kept by a [`ReplayGuard`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayStats {
+    /// The number of frames accepted.
+    pub accepted: u64,
+    /// The number of frames dropped for lagging too far behind their thread.
+    pub dropped: u64,
+}
+
+/// Drops frames that arrive more than a given number of seconds behind the newest frame already accepted from the
+/// same thread, guarding against replayed or badly out of order data.
+pub struct ReplayGuard<R: VDIFRead> {
+    inner: R,
+    max_lag: Option<u32>,
+    newest: HashMap<u16, NaiveDateTime>,
+    stats: ReplayStats,
+}
+
+impl<R: VDIFRead> ReplayGuard<R> {
+    /// Construct a new [`ReplayGuard`] dropping frames more than `max_lag` seconds behind. A `max_lag` of `None`
+    /// turns the guard off, so it only counts frames.
+    pub fn new(inner: R, max_lag: Option<u32>) -> Self {
+        return Self {
+            inner: inner,
+            max_lag: max_lag,
+            newest: HashMap::new(),
+            stats: ReplayStats::default(),
+        };
+    }
+
+    /// Change the maximum lag, or turn the guard off with `None`.
+    pub fn set_max_lag(&mut self, max_lag: Option<u32>) {
+        self.max_lag = max_lag;
+    }
+
+    /// Get the counters accumulated so far.
+    pub fn stats(&self) -> ReplayStats {
+        return self.stats;
+    }
+
+    /// Return the wrapped source.
+    pub fn into_inner(self) -> R {
+        return self.inner;
+    }
+}
+
+impl<R: VDIFRead> VDIFRead for ReplayGuard<R> {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        loop {
+            let frame = self.inner.read_frame()?;
+            let header = frame.get_header();
+            let date = header.date();
+            let newest = self.newest.entry(header.thread).or_insert(date);
+            if let Some(max_lag) = self.max_lag {
+                if (*newest - date).num_seconds() > max_lag as i64 {
+                    self.stats.dropped += 1;
+                    continue;
+                }
+            }
+            if date > *newest {
+                *newest = date;
+            }
+            self.stats.accepted += 1;
+            return Ok(frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::vdiftime_to_date;
+    use crate::testing::Source;
+    use crate::utils::sim::VDIFSim;
+
+    #[test]
+    fn test_frame_filter() {
+        // Four threads at 4 frames/s for 2 seconds
+        let mut sim = VDIFSim::new(64, 4, 4);
+        let start = sim.generate_frame().get_header().instant();
+        let end = FrameInstant {
+            time: start.time + 1,
+            frameno: 2,
+            ..start
+        };
+        let mut filter = FrameFilter::new(sim)
+            .with_threads(&[1, 3])
+            .with_time_range(start..end);
+        let mut seen = Vec::new();
+        for _ in 0..12 {
+            let header = filter.read_frame().unwrap().get_header();
+            assert!(header.instant() < end);
+            seen.push(header.thread);
+        }
+        assert!(seen.iter().all(|t| *t == 1 || *t == 3));
+        assert_eq!(
+            filter.read_frame().unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+
+        // Every other frame is flagged invalid
+        let mut sim = VDIFSim::new(64, 4, 1);
+        let frames: Vec<VDIFFrame> = (0..6)
+            .map(|i| {
+                let mut frame = sim.generate_frame();
+                let mut header = frame.get_header();
+                header.is_valid = i % 2 == 0;
+                frame.set_header(header);
+                frame
+            })
+            .collect();
+        let mut filter = FrameFilter::new(Source(frames.into_iter())).valid_only();
+        let mut passed = Vec::new();
+        while let Ok(frame) = filter.read_frame() {
+            passed.push(frame.get_header().frameno);
+        }
+        assert_eq!(passed, vec![0, 2, 0]);
+        assert_eq!(filter.discarded(), 3);
+    }
+
+    #[test]
+    fn test_time_window_filter() {
+        let mut filter =
+            TimeWindowFilter::new(VDIFSim::new(64, 1, 1), std::time::Duration::from_secs(2));
+        filter.set_reference(Some(vdiftime_to_date(3, 10)));
+
+        let frame = filter.read_frame().unwrap();
+        assert_eq!(frame.get_header().time, 8);
+        assert_eq!(filter.stats().too_early, 8);
+        for _ in 0..4 {
+            filter.read_frame().unwrap();
+        }
+        assert_eq!(filter.stats().passed, 5);
+    }
+
+    #[test]
+    fn test_replay_guard() {
+        let mut frames: Vec<VDIFFrame> = Vec::new();
+        let mut sim = VDIFSim::new(64, 1, 2);
+        for _ in 0..10 {
+            frames.push(sim.generate_frame());
+        }
+        // Replay thread 0's first second after everything else
+        frames.push(VDIFFrame::from_slice(frames[0].as_slice()));
+        frames.push(VDIFFrame::from_slice(frames[8].as_slice()));
+
+        let mut guard = ReplayGuard::new(Source(frames.into_iter()), Some(2));
+        let mut accepted = 0;
+        while guard.read_frame().is_ok() {
+            accepted += 1;
+        }
+        assert_eq!(accepted, 11);
+        assert_eq!(guard.stats().dropped, 1);
+    }
+}
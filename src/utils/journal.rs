@@ -0,0 +1,342 @@
+//! Implements [`RunJournal`], an append-only, machine-readable record of what a recorder did during a run.
+//!
+//! The journal is written as JSON lines, one event per line, each with the UTC time it was recorded:
+//!
+//! ```text
+//! {"time": "2026-10-16T12:00:00.000Z", "event": "start", "label": "scan001"}
+//! {"time": "2026-10-16T12:00:00.002Z", "event": "file_created", "path": "/data/scan001.vdif"}
+//! {"time": "2026-10-16T12:10:00.000Z", "event": "stop", "frames": 7500000, "dropped": 12}
+//! ```
+//!
+//! Since every event is flushed as it is written, a journal stays readable after a crash.
+//!
+//! A recorder keeps its journal by writing through a [`JournalingWriter`], which records the start and stop of the run,
+//! the file it creates, periodic frame counts and any error from the writer it wraps:
+//!
+//! ```rust,ignore
+//! let journal = RunJournal::open("scan001.jsonl")?;
+//! let mut writer = JournalingWriter::create("/data/scan001.vdif", 8032, journal, "scan001")?;
+//! writer.set_count_interval(100000);
+//! writer.write_frame(frame)?;
+//! writer.finish()?;
+//! ```
+
+use std::fs::{File, OpenOptions};
+use std::io::{Result, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+
+use crate::frame::VDIFFrame;
+use crate::io::{VDIFFileWriter, VDIFWrite};
+use crate::json::quote;
+use crate::utils::pipeline::PipelineStats;
+
+/// An event recorded in a [`RunJournal`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JournalEvent {
+    /// A run started.
+    Start {
+        /// A label for the run, such as the scan name.
+        label: String,
+    },
+    /// A run stopped, having written `frames` frames and dropped `dropped`.
+    Stop {
+        /// The total number of frames written.
+        frames: u64,
+        /// The total number of frames dropped.
+        dropped: u64,
+    },
+    /// An output file was created.
+    FileCreated {
+        /// The path of the file.
+        path: PathBuf,
+    },
+    /// A periodic snapshot of frame counts.
+    Counts {
+        /// The total number of frames written so far.
+        frames: u64,
+        /// The total number of frames dropped so far.
+        dropped: u64,
+    },
+    /// An error occurred.
+    Error {
+        /// A description of the error.
+        message: String,
+    },
+}
+
+impl JournalEvent {
+    /// Construct a [`Counts`](JournalEvent::Counts) event from the counters of a pipeline, counting the frames
+    /// processed by its sink.
+    pub fn counts_from(stats: &PipelineStats) -> Self {
+        return JournalEvent::Counts {
+            frames: stats.stages.last().map_or(0, |s| s.processed),
+            dropped: stats.total_dropped(),
+        };
+    }
+
+    fn to_json(&self, time: &str) -> String {
+        let fields = match self {
+            JournalEvent::Start { label } => {
+                format!("\"event\": \"start\", \"label\": {}", quote(label))
+            }
+            JournalEvent::Stop { frames, dropped } => {
+                format!(
+                    "\"event\": \"stop\", \"frames\": {}, \"dropped\": {}",
+                    frames, dropped
+                )
+            }
+            JournalEvent::FileCreated { path } => format!(
+                "\"event\": \"file_created\", \"path\": {}",
+                quote(&path.to_string_lossy())
+            ),
+            JournalEvent::Counts { frames, dropped } => {
+                format!(
+                    "\"event\": \"counts\", \"frames\": {}, \"dropped\": {}",
+                    frames, dropped
+                )
+            }
+            JournalEvent::Error { message } => {
+                format!("\"event\": \"error\", \"message\": {}", quote(message))
+            }
+        };
+        return format!("{{\"time\": {}, {}}}", quote(time), fields);
+    }
+}
+
+/// An append-only journal of [`JournalEvent`]s, written as JSON lines.
+pub struct RunJournal {
+    file: File,
+}
+
+impl RunJournal {
+    /// Open the journal at `path`, creating it if it doesn't exist and appending to it otherwise.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        return Ok(Self { file: file });
+    }
+
+    /// Record `event`, timestamped with the current time.
+    pub fn record(&mut self, event: &JournalEvent) -> Result<()> {
+        let time = Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
+        // Write each line in one call so concurrent appenders can't interleave within a line
+        let line = event.to_json(&time) + "\n";
+        self.file.write_all(line.as_bytes())?;
+        return self.file.flush();
+    }
+}
+
+/// A [`VDIFWrite`] adaptor recording a run in a [`RunJournal`] as frames are written to the inner writer.
+///
+/// A [`Start`](JournalEvent::Start) event is recorded on construction and a [`Stop`](JournalEvent::Stop) event by
+/// [`finish`](JournalingWriter::finish), or on drop if the writer wasn't finished. Any error from the inner writer is
+/// recorded as an [`Error`](JournalEvent::Error) event before being returned.
+pub struct JournalingWriter<W: VDIFWrite> {
+    // Only taken by `finish`
+    inner: Option<W>,
+    journal: RunJournal,
+    frames: u64,
+    dropped: u64,
+    count_interval: u64,
+}
+
+impl<W: VDIFWrite> JournalingWriter<W> {
+    /// Wrap `inner`, recording the start of a run labelled `label` in `journal`.
+    pub fn new(inner: W, mut journal: RunJournal, label: &str) -> Result<Self> {
+        journal.record(&JournalEvent::Start {
+            label: label.to_owned(),
+        })?;
+        return Ok(Self {
+            inner: Some(inner),
+            journal: journal,
+            frames: 0,
+            dropped: 0,
+            count_interval: 0,
+        });
+    }
+
+    /// Record a [`Counts`](JournalEvent::Counts) event every `frames` frames written, or never if zero. Never by
+    /// default.
+    pub fn set_count_interval(&mut self, frames: u64) {
+        self.count_interval = frames;
+    }
+
+    /// Set the total number of frames dropped before reaching the writer, such as by a queue or pipeline feeding it,
+    /// to be included in later counts.
+    pub fn set_dropped(&mut self, dropped: u64) {
+        self.dropped = dropped;
+    }
+
+    /// Get the number of frames written through this writer.
+    pub fn frames(&self) -> u64 {
+        return self.frames;
+    }
+
+    /// Record any other event in the journal, such as a [`FileCreated`](JournalEvent::FileCreated) event when the inner
+    /// writer moves on to a new file.
+    pub fn record(&mut self, event: &JournalEvent) -> Result<()> {
+        return self.journal.record(event);
+    }
+
+    /// Flush the inner writer and record the end of the run, returning the inner writer.
+    pub fn finish(mut self) -> Result<W> {
+        let mut inner = self.inner.take().unwrap();
+        let flushed = inner.flush();
+        if let Err(e) = &flushed {
+            let _ = self.journal.record(&JournalEvent::Error {
+                message: e.to_string(),
+            });
+        }
+        self.journal.record(&self.stop_event())?;
+        flushed?;
+        return Ok(inner);
+    }
+
+    fn stop_event(&self) -> JournalEvent {
+        return JournalEvent::Stop {
+            frames: self.frames,
+            dropped: self.dropped,
+        };
+    }
+
+    // Record `result` if it is an error, returning it unchanged
+    fn check(&mut self, result: Result<()>) -> Result<()> {
+        if let Err(e) = &result {
+            // The writer's error matters more than any failure to journal it
+            let _ = self.journal.record(&JournalEvent::Error {
+                message: e.to_string(),
+            });
+        }
+        return result;
+    }
+}
+
+impl JournalingWriter<VDIFFileWriter> {
+    /// Create a new VDIF file at `path` of `frame_size` byte frames, recording the start of a run labelled `label` and
+    /// the creation of the file in `journal`. A failure to create the file is recorded too.
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        frame_size: usize,
+        mut journal: RunJournal,
+        label: &str,
+    ) -> Result<Self> {
+        journal.record(&JournalEvent::Start {
+            label: label.to_owned(),
+        })?;
+        let inner = match VDIFFileWriter::create(&path, frame_size) {
+            Ok(inner) => inner,
+            Err(e) => {
+                let _ = journal.record(&JournalEvent::Error {
+                    message: e.to_string(),
+                });
+                return Err(e);
+            }
+        };
+        journal.record(&JournalEvent::FileCreated {
+            path: path.as_ref().to_path_buf(),
+        })?;
+        return Ok(Self {
+            inner: Some(inner),
+            journal: journal,
+            frames: 0,
+            dropped: 0,
+            count_interval: 0,
+        });
+    }
+}
+
+impl<W: VDIFWrite> VDIFWrite for JournalingWriter<W> {
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        let result = self.inner.as_mut().unwrap().write_frame(frame);
+        self.check(result)?;
+        self.frames += 1;
+        if self.count_interval > 0 && self.frames % self.count_interval == 0 {
+            self.journal.record(&JournalEvent::Counts {
+                frames: self.frames,
+                dropped: self.dropped,
+            })?;
+        }
+        return Ok(());
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let result = self.inner.as_mut().unwrap().flush();
+        return self.check(result);
+    }
+}
+
+impl<W: VDIFWrite> Drop for JournalingWriter<W> {
+    // A writer dropped without being finished still ends its run, though any error is lost
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.as_mut() {
+            let flushed = inner.flush();
+            let _ = self.check(flushed);
+            let _ = self.journal.record(&self.stop_event());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_path;
+
+    #[test]
+    fn test_journal_lines() {
+        let path = temp_path("journal.jsonl");
+        let _ = std::fs::remove_file(&path);
+        let mut journal = RunJournal::open(&path).unwrap();
+        journal
+            .record(&JournalEvent::Start {
+                label: "scan \"1\"".to_owned(),
+            })
+            .unwrap();
+        journal
+            .record(&JournalEvent::Stop {
+                frames: 10,
+                dropped: 2,
+            })
+            .unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("\"event\": \"start\", \"label\": \"scan \\\"1\\\"\"}"));
+        assert!(lines[1].ends_with("\"event\": \"stop\", \"frames\": 10, \"dropped\": 2}"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_journaling_writer() {
+        let path = temp_path("journaled.jsonl");
+        let data = temp_path("journaled.vdif");
+        let _ = std::fs::remove_file(&path);
+        let journal = RunJournal::open(&path).unwrap();
+        let mut writer = JournalingWriter::create(&data, 32, journal, "scan001").unwrap();
+        writer.set_count_interval(2);
+        writer.set_dropped(3);
+        for _ in 0..5 {
+            writer.write_frame(VDIFFrame::empty(32)).unwrap();
+        }
+        assert!(writer.write_frame(VDIFFrame::empty(64)).is_err());
+        let inner = writer.finish().unwrap();
+        assert_eq!(inner.frames(), 5);
+        assert_eq!(std::fs::metadata(&data).unwrap().len(), 5 * 32);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let events: Vec<&str> = text
+            .lines()
+            .map(|line| line.split("\"event\": \"").nth(1).unwrap())
+            .collect();
+        assert_eq!(events.len(), 6);
+        assert!(events[0].starts_with("start\", \"label\": \"scan001\""));
+        assert!(events[1].starts_with("file_created"));
+        assert_eq!(events[2], "counts\", \"frames\": 2, \"dropped\": 3}");
+        assert_eq!(events[3], "counts\", \"frames\": 4, \"dropped\": 3}");
+        assert!(events[4].starts_with("error"));
+        assert_eq!(events[5], "stop\", \"frames\": 5, \"dropped\": 3}");
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(data).unwrap();
+    }
+}
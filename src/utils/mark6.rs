@@ -0,0 +1,277 @@
+//! Implements reading of VDIF recordings made by Mark6 recorders in scatter/gather mode.
+//!
+//! A Mark6 scatter/gather recording of a scan is a group of files with the same name, one on each disk, such as:
+//!
+//! ```text
+//! /mnt/disks/1/0/data/scan.vdif
+//! /mnt/disks/1/1/data/scan.vdif
+//! ...
+//! ```
+//!
+//! Each file starts with a file header giving the block size and the packet size, followed by blocks of packets. Every
+//! block starts with a block header holding its number, and the recorder writes successive blocks to whichever disk is
+//! free, so the stream is recovered by reading the blocks of every file in block number order. [`Mark6Stream`] does
+//! this, presenting the group as one continuous byte stream which can then be read by a [`VDIFReader`].
+
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::io::VDIFReader;
+
+/// The sync word starting every Mark6 scatter/gather file.
+pub const MARK6_SYNC_WORD: u32 = 0xFEED6666;
+
+// The file header is the sync word, version, block size, packet format and packet size, each 4 bytes.
+const FILE_HEADER_SIZE: u64 = 20;
+
+/// The format of the packets of a Mark6 recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mark6PacketFormat {
+    /// VDIF frames.
+    VDIF,
+    /// Mark5B frames.
+    Mark5B,
+    /// Any other format, with its raw code.
+    Other(u32),
+}
+
+// The location of the data of one block.
+struct Block {
+    number: u32,
+    file: usize,
+    offset: u64,
+    len: u64,
+}
+
+/// A continuous byte stream over the blocks of a Mark6 scatter/gather file group, in block number order.
+pub struct Mark6Stream {
+    files: Vec<File>,
+    blocks: Vec<Block>,
+    packet_format: Mark6PacketFormat,
+    packet_size: usize,
+    next_block: usize,
+    remaining: u64,
+}
+
+impl Mark6Stream {
+    /// Open the files of a scatter/gather group, indexing the blocks of each.
+    ///
+    /// Returns an error of kind [`InvalidData`](ErrorKind::InvalidData) if any file lacks a Mark6 file header, or the
+    /// files disagree on the packet format or size, and of kind [`NotFound`](ErrorKind::NotFound) if `files` is empty.
+    pub fn open<P: AsRef<Path>>(files: &[P]) -> Result<Self> {
+        let mut opened = Vec::new();
+        let mut blocks = Vec::new();
+        let mut format = None;
+        for (i, path) in files.iter().enumerate() {
+            let mut file = File::open(path)?;
+            let header = read_words::<5>(&mut file)?;
+            if header[0] != MARK6_SYNC_WORD {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "File does not start with the Mark6 sync word",
+                ));
+            }
+            let (version, block_size) = (header[1], header[2] as u64);
+            if *format.get_or_insert((header[3], header[4])) != (header[3], header[4]) {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "Mark6 files disagree on the packet format",
+                ));
+            }
+            index_blocks(&mut file, i, version, block_size, &mut blocks)?;
+            opened.push(file);
+        }
+        let Some((format, packet_size)) = format else {
+            return Err(Error::new(ErrorKind::NotFound, "No Mark6 files given"));
+        };
+        blocks.sort_by_key(|b| b.number);
+
+        return Ok(Self {
+            files: opened,
+            blocks: blocks,
+            packet_format: match format {
+                0 => Mark6PacketFormat::VDIF,
+                1 => Mark6PacketFormat::Mark5B,
+                other => Mark6PacketFormat::Other(other),
+            },
+            packet_size: packet_size as usize,
+            next_block: 0,
+            remaining: 0,
+        });
+    }
+
+    /// Find the files named `name` under each of `roots`, such as the `data` directories of each disk, and open them
+    /// as one group.
+    pub fn find<P: AsRef<Path>>(roots: &[P], name: &str) -> Result<Self> {
+        let files: Vec<PathBuf> = roots
+            .iter()
+            .map(|root| root.as_ref().join(name))
+            .filter(|path| path.is_file())
+            .collect();
+        return Self::open(&files);
+    }
+
+    /// Get the format of the recorded packets.
+    pub fn packet_format(&self) -> Mark6PacketFormat {
+        return self.packet_format;
+    }
+
+    /// Get the size in bytes of the recorded packets, which for VDIF is the frame size.
+    pub fn packet_size(&self) -> usize {
+        return self.packet_size;
+    }
+
+    /// Get the block numbers missing between the first and last block found, e.g. from a failed disk.
+    pub fn missing_blocks(&self) -> Vec<u32> {
+        let mut missing = Vec::new();
+        for pair in self.blocks.windows(2) {
+            missing.extend(pair[0].number + 1..pair[1].number);
+        }
+        return missing;
+    }
+
+    fn open_next(&mut self) -> Result<bool> {
+        let Some(block) = self.blocks.get(self.next_block) else {
+            return Ok(false);
+        };
+        self.files[block.file].seek(SeekFrom::Start(block.offset))?;
+        self.remaining = block.len;
+        self.next_block += 1;
+        return Ok(true);
+    }
+}
+
+impl Read for Mark6Stream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        while self.remaining == 0 {
+            if buf.is_empty() || !self.open_next()? {
+                return Ok(0);
+            }
+        }
+        let block = &self.blocks[self.next_block - 1];
+        let len = buf.len().min(self.remaining as usize);
+        let n = self.files[block.file].read(&mut buf[..len])?;
+        if n == 0 {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "Mark6 file ended partway through a block",
+            ));
+        }
+        self.remaining -= n as u64;
+        return Ok(n);
+    }
+}
+
+impl VDIFReader<Mark6Stream> {
+    /// Open a Mark6 scatter/gather group of VDIF recordings as a single stream of VDIF frames, taking the frame size
+    /// from the file headers.
+    pub fn open_mark6<P: AsRef<Path>>(files: &[P]) -> Result<Self> {
+        let stream = Mark6Stream::open(files)?;
+        if stream.packet_format() != Mark6PacketFormat::VDIF {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Mark6 recording does not hold VDIF",
+            ));
+        }
+        let frame_size = stream.packet_size();
+        return Ok(Self::new(stream, frame_size));
+    }
+}
+
+// Index the blocks of `file`, positioned just after its file header. Version 1 block headers hold only the block
+// number, with every block the block size, while version 2 headers also hold the size of their block.
+fn index_blocks(
+    file: &mut File,
+    index: usize,
+    version: u32,
+    block_size: u64,
+    blocks: &mut Vec<Block>,
+) -> Result<()> {
+    let end = file.metadata()?.len();
+    let header_size = if version >= 2 { 8 } else { 4 };
+    let mut pos = FILE_HEADER_SIZE;
+    while pos + header_size <= end {
+        file.seek(SeekFrom::Start(pos))?;
+        let (number, size) = if version >= 2 {
+            let words = read_words::<2>(file)?;
+            (words[0], words[1] as u64)
+        } else {
+            (read_words::<1>(file)?[0], block_size)
+        };
+        if size <= header_size {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Mark6 block is smaller than its header",
+            ));
+        }
+        blocks.push(Block {
+            number: number,
+            file: index,
+            offset: pos + header_size,
+            len: size.min(end - pos) - header_size,
+        });
+        pos += size;
+    }
+    return Ok(());
+}
+
+fn read_words<const N: usize>(file: &mut File) -> Result<[u32; N]> {
+    let mut bytes = [0u8; 4];
+    let mut words = [0u32; N];
+    for word in words.iter_mut() {
+        file.read_exact(&mut bytes)?;
+        *word = u32::from_le_bytes(bytes);
+    }
+    return Ok(words);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::VDIFRead;
+    use crate::testing::temp_path;
+    use crate::utils::sim::VDIFSim;
+    use std::fs;
+
+    #[test]
+    fn test_mark6_stream() {
+        let base = temp_path("mark6");
+        let roots = [base.join("0"), base.join("1")];
+        let mut files: Vec<Vec<u8>> = (0..2)
+            .map(|_| {
+                [MARK6_SYNC_WORD, 2, 8 + 2 * 64, 0, 64]
+                    .iter()
+                    .flat_map(|w| w.to_le_bytes())
+                    .collect()
+            })
+            .collect();
+        // Blocks of two frames, with blocks 0, 3 and 4 on the first disk, 1 and 2 on the second
+        let mut sim = VDIFSim::new(64, 10, 1);
+        for (number, disk) in [(0u32, 0), (1, 1), (2, 1), (3, 0), (4, 0)] {
+            files[disk].extend_from_slice(&number.to_le_bytes());
+            files[disk].extend_from_slice(&(8u32 + 2 * 64).to_le_bytes());
+            for _ in 0..2 {
+                files[disk].extend_from_slice(sim.generate_frame().as_bytes());
+            }
+        }
+        for (root, bytes) in roots.iter().zip(files) {
+            fs::create_dir_all(root).unwrap();
+            fs::write(root.join("scan.vdif"), bytes).unwrap();
+        }
+
+        let stream = Mark6Stream::find(&roots, "scan.vdif").unwrap();
+        assert_eq!(stream.packet_format(), Mark6PacketFormat::VDIF);
+        assert!(stream.missing_blocks().is_empty());
+        let mut reader = VDIFReader::new(stream, 64);
+        for frameno in 0..10 {
+            assert_eq!(reader.read_frame().unwrap().get_header().frameno, frameno);
+        }
+        assert_eq!(
+            reader.read_frame().unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+
+        fs::remove_dir_all(base).unwrap();
+    }
+}
@@ -0,0 +1,159 @@
+//! Implements [`MonotonicWriter`], a writer that guarantees the frames it outputs never go backwards in time.
+
+use std::collections::VecDeque;
+use std::io::Result;
+
+use crate::frame::VDIFFrame;
+use crate::header::FrameInstant;
+use crate::io::VDIFWrite;
+
+/// What a [`MonotonicWriter`] does with frames that go backwards in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonotonicPolicy {
+    /// Drop any frame earlier than the last frame written.
+    Drop,
+    /// Hold back up to this many frames so late frames can be written in order. Frames that arrive too late to be
+    /// reordered are dropped.
+    Reorder(usize),
+}
+
+/// Counters kept by a [`MonotonicWriter`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MonotonicStats {
+    /// The number of frames written.
+    pub written: u64,
+    /// The number of frames dropped for going backwards in time.
+    pub dropped: u64,
+    /// The number of late frames that were written in order after being reordered.
+    pub reordered: u64,
+}
+
+/// Wraps a [`VDIFWrite`] type so that the [`FrameInstant`]s of written frames never decrease.
+///
+/// Frames with equal instants, such as those of different threads, are written in the order they arrive. When
+/// reordering, frames are held back until the window is full or the writer is flushed, so remember to call
+/// [`flush`](VDIFWrite::flush) once finished.
+pub struct MonotonicWriter<W: VDIFWrite> {
+    inner: W,
+    policy: MonotonicPolicy,
+    pending: VecDeque<(FrameInstant, bool, VDIFFrame)>,
+    latest_seen: Option<FrameInstant>,
+    last_written: Option<FrameInstant>,
+    stats: MonotonicStats,
+}
+
+impl<W: VDIFWrite> MonotonicWriter<W> {
+    /// Construct a new [`MonotonicWriter`] applying `policy` to frames written to `inner`.
+    pub fn new(inner: W, policy: MonotonicPolicy) -> Self {
+        return Self {
+            inner: inner,
+            policy: policy,
+            pending: VecDeque::new(),
+            latest_seen: None,
+            last_written: None,
+            stats: MonotonicStats::default(),
+        };
+    }
+
+    /// Get the counters accumulated so far.
+    pub fn stats(&self) -> MonotonicStats {
+        return self.stats;
+    }
+
+    /// Flush any held back frames and return the inner writer.
+    pub fn into_inner(mut self) -> Result<W> {
+        self.flush()?;
+        return Ok(self.inner);
+    }
+
+    fn emit(&mut self, instant: FrameInstant, late: bool, frame: VDIFFrame) -> Result<()> {
+        if self.last_written.is_some_and(|last| instant < last) {
+            self.stats.dropped += 1;
+            return Ok(());
+        }
+        self.inner.write_frame(frame)?;
+        self.last_written = Some(instant);
+        self.stats.written += 1;
+        if late {
+            self.stats.reordered += 1;
+        }
+        return Ok(());
+    }
+}
+
+impl<W: VDIFWrite> VDIFWrite for MonotonicWriter<W> {
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        let instant = frame.get_header().instant();
+        let late = self.latest_seen.is_some_and(|latest| instant < latest);
+        if !late {
+            self.latest_seen = Some(instant);
+        }
+
+        match self.policy {
+            MonotonicPolicy::Drop => return self.emit(instant, false, frame),
+            MonotonicPolicy::Reorder(window) => {
+                // Insert after any frames with an equal instant to keep their arrival order
+                let index = self.pending.partition_point(|(i, _, _)| *i <= instant);
+                self.pending.insert(index, (instant, late, frame));
+                while self.pending.len() > window {
+                    let (instant, late, frame) = self.pending.pop_front().unwrap();
+                    self.emit(instant, late, frame)?;
+                }
+                return Ok(());
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        while let Some((instant, late, frame)) = self.pending.pop_front() {
+            self.emit(instant, late, frame)?;
+        }
+        return self.inner.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::VDIFHeader;
+
+    fn frame(time: u32, frameno: u32) -> VDIFFrame {
+        let mut frame = VDIFFrame::empty(64);
+        frame.set_header(VDIFHeader {
+            time: time,
+            frameno: frameno,
+            size: 8,
+            ..Default::default()
+        });
+        return frame;
+    }
+
+    fn written(frames: &[VDIFFrame]) -> Vec<u32> {
+        return frames.iter().map(|f| f.get_header().frameno).collect();
+    }
+
+    #[test]
+    fn test_monotonic_writer() {
+        let order = [0, 1, 3, 2, 4, 0, 5];
+
+        let mut writer = MonotonicWriter::new(Vec::new(), MonotonicPolicy::Drop);
+        for n in order {
+            writer.write_frame(frame(0, n)).unwrap();
+        }
+        assert_eq!(writer.stats().dropped, 2);
+        assert_eq!(written(&writer.into_inner().unwrap()), vec![0, 1, 3, 4, 5]);
+
+        let mut writer = MonotonicWriter::new(Vec::new(), MonotonicPolicy::Reorder(2));
+        for n in order {
+            writer.write_frame(frame(0, n)).unwrap();
+        }
+        writer.flush().unwrap();
+        let stats = writer.stats();
+        assert_eq!(stats.reordered, 1);
+        assert_eq!(stats.dropped, 1);
+        assert_eq!(
+            written(&writer.into_inner().unwrap()),
+            vec![0, 1, 2, 3, 4, 5]
+        );
+    }
+}
@@ -0,0 +1,222 @@
+//! Implements [`VDIFMux`], a corner turner combining single-channel threads into multi-channel frames, like DiFX's
+//! `vmux`.
+//!
+//! Many recorders write each channel as its own VDIF thread, while many correlators expect one thread carrying every
+//! channel. Each output frame holds the samples of one frame of every input thread, interleaved following the VDIF
+//! packing rules (see [`FrameLayout`]), so its payload is as many times larger as there are channels. The channel count
+//! of a VDIF frame is a power of two, so if the number of threads isn't, the remaining channels are zero-filled.
+
+use std::io::{Error, ErrorKind, Result};
+
+use crate::consts::HEADER_SIZE;
+use crate::decoding::mask;
+use crate::frame::VDIFFrame;
+use crate::io::VDIFRead;
+use crate::layout::FrameLayout;
+use crate::spec::StreamSpec;
+use crate::utils::align::ThreadAligner;
+
+/// Combines the single-channel threads of a stream into multi-channel frames of a single thread.
+///
+/// Channel `i` of the output holds the samples of thread `spec.threads[i]`. Frames of each thread covering the same
+/// time are grouped with a [`ThreadAligner`], so threads may arrive in any order. If a thread's frame is missing, or
+/// any input frame is marked invalid, its channel is zero-filled and the output frame is marked invalid.
+pub struct VDIFMux<R: VDIFRead> {
+    aligner: ThreadAligner<R>,
+    threads: Vec<u16>,
+    input: FrameLayout,
+    output: FrameLayout,
+    output_thread: u16,
+}
+
+impl<R: VDIFRead> VDIFMux<R> {
+    /// Construct a new [`VDIFMux`] over `source`, whose threads, frame size and sample format are described by `spec`.
+    ///
+    /// Every thread must carry a single channel at the same frame rate. Returns an error of kind
+    /// [`InvalidInput`](ErrorKind::InvalidInput) if that isn't so, or if the samples of a frame of every thread can't
+    /// be packed into a single output frame.
+    pub fn new(source: R, spec: StreamSpec) -> Result<Self> {
+        let invalid = |msg: &str| Error::new(ErrorKind::InvalidInput, msg.to_string());
+        if spec.channels != 1 {
+            return Err(invalid("Only single-channel threads can be multiplexed"));
+        }
+        if spec.threads.is_empty() {
+            return Err(invalid("The spec must list its threads"));
+        }
+        if spec
+            .threads
+            .iter()
+            .any(|t| spec.frame_rate_of(*t) != spec.frame_rate)
+        {
+            return Err(invalid("Every thread must have the same frame rate"));
+        }
+
+        let bits = spec.bits_per_sample as u32;
+        let channels = spec.threads.len().next_power_of_two();
+        let input = FrameLayout::new(bits, spec.is_real, 1, spec.frame_size)
+            .ok_or_else(|| invalid("The spec doesn't describe a usable sample format"))?;
+        let output_size = HEADER_SIZE + input.payload_words * 4 * channels;
+        let output = FrameLayout::new(bits, spec.is_real, channels, output_size)
+            .ok_or_else(|| invalid("The spec doesn't describe a usable sample format"))?;
+        if output.samples_per_channel() != input.samples_per_channel() {
+            return Err(invalid(
+                "The sample format can't be multiplexed without changing the number of samples per frame",
+            ));
+        }
+        if output_size / 8 > 0xFFFFFF {
+            return Err(invalid("Multiplexed frames would be too large"));
+        }
+
+        return Ok(Self {
+            aligner: ThreadAligner::new(source, spec.clone())?,
+            threads: spec.threads,
+            input: input,
+            output: output,
+            output_thread: 0,
+        });
+    }
+
+    /// Set the thread ID of output frames. Defaults to 0.
+    pub fn set_output_thread(&mut self, thread: u16) {
+        self.output_thread = thread;
+    }
+
+    /// Get the size in bytes of the output frames.
+    pub fn output_frame_size(&self) -> usize {
+        return HEADER_SIZE + self.output.payload_words * 4;
+    }
+
+    /// Combine the next group of input frames into a multi-channel frame, or return an
+    /// [`UnexpectedEof`](ErrorKind::UnexpectedEof) error once the source has ended.
+    pub fn next_frame(&mut self) -> Result<VDIFFrame> {
+        let group = self.aligner.next_group()?;
+        let mut out = VDIFFrame::empty(self.output_frame_size());
+        let mut valid = group.complete;
+        for frame in &group.frames {
+            let header = frame.get_header();
+            let channel = self
+                .threads
+                .iter()
+                .position(|t| *t == header.thread)
+                .unwrap();
+            if header.is_valid {
+                interleave(frame, &self.input, &mut out, &self.output, channel);
+            } else {
+                valid = false;
+            }
+        }
+
+        let mut header = group.frames[0].get_header();
+        header.is_valid = valid;
+        header.frameno = group.slot;
+        header.thread = self.output_thread;
+        header.channels = self.output.channels.trailing_zeros() as u8;
+        header.size = (self.output_frame_size() / 8) as u32;
+        out.set_header(header);
+        return Ok(out);
+    }
+
+    /// Return the wrapped source.
+    pub fn into_inner(self) -> R {
+        return self.aligner.into_inner();
+    }
+}
+
+impl<R: VDIFRead> VDIFRead for VDIFMux<R> {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        return self.next_frame();
+    }
+}
+
+// Copy the samples of single-channel `frame` into `channel` of `out`.
+fn interleave(
+    frame: &VDIFFrame,
+    input: &FrameLayout,
+    out: &mut VDIFFrame,
+    output: &FrameLayout,
+    channel: usize,
+) {
+    let bits = input.bits_per_sample;
+    let mask = mask(bits);
+    let components: &[bool] = if input.is_real {
+        &[false]
+    } else {
+        &[false, true]
+    };
+    let payload = frame.get_payload();
+    let out_payload = out.get_mut_payload();
+    for n in 0..input.samples_per_channel() {
+        for imag in components {
+            let ((in_word, in_shift), (out_word, out_shift)) = if input.is_real {
+                (input.sample_index(0, n), output.sample_index(channel, n))
+            } else {
+                (
+                    input.component_index(0, n, *imag),
+                    output.component_index(channel, n, *imag),
+                )
+            };
+            let value = (payload[in_word] >> in_shift) & mask;
+            out_payload[out_word] |= value << out_shift;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoding::get_sample;
+    use crate::header::VDIFHeader;
+    use crate::testing::Source;
+
+    // A 2-bit frame of `thread` whose samples all hold `value`
+    fn frame(thread: u16, frameno: u32, value: u32) -> VDIFFrame {
+        let mut frame = VDIFFrame::from_header(VDIFHeader {
+            is_valid: true,
+            thread: thread,
+            frameno: frameno,
+            size: 8,
+            is_real: true,
+            bits_per_sample: 1,
+            ..Default::default()
+        });
+        frame.get_mut_payload().fill(value * 0x55555555);
+        return frame;
+    }
+
+    #[test]
+    fn test_mux() {
+        // Three threads, so a fourth zero-filled channel, and thread 2's second frame is lost
+        let frames = vec![
+            frame(5, 0, 1),
+            frame(2, 0, 2),
+            frame(9, 0, 3),
+            frame(9, 1, 3),
+            frame(5, 1, 1),
+        ];
+        let mut spec = StreamSpec::from_header(&frames[0].get_header(), 2);
+        spec.threads = vec![2, 5, 9];
+
+        let mut mux = VDIFMux::new(Source(frames.into_iter()), spec).unwrap();
+        mux.set_output_thread(1);
+        assert_eq!(mux.output_frame_size(), 32 + 32 * 4);
+
+        let out = mux.read_frame().unwrap();
+        let header = out.get_header();
+        assert!(header.is_valid);
+        assert_eq!(
+            (header.thread, header.channelno(), header.bytesize()),
+            (1, 4, 160)
+        );
+        for n in [0, 63, 127] {
+            let samples: Vec<u16> = (0..4).map(|c| get_sample(&out, c, n).unwrap()).collect();
+            assert_eq!(samples, vec![2, 1, 3, 0]);
+        }
+
+        let out = mux.read_frame().unwrap();
+        assert!(!out.get_header().is_valid);
+        assert_eq!(out.get_header().frameno, 1);
+        assert_eq!(get_sample(&out, 0, 5), Some(0));
+        assert_eq!(get_sample(&out, 2, 5), Some(3));
+        assert!(mux.read_frame().is_err());
+    }
+}
@@ -0,0 +1,94 @@
+//! Implements [`NullSource`] and [`NullSink`], the fastest possible ends of a pipeline, for measuring the throughput of
+//! the stages between them.
+
+use std::io::{ErrorKind, Result};
+
+use crate::frame::VDIFFrame;
+use crate::io::{VDIFRead, VDIFWrite};
+
+/// A [`VDIFRead`] producing copies of a template frame as fast as possible.
+pub struct NullSource {
+    template: VDIFFrame,
+    limit: Option<u64>,
+    frames: u64,
+}
+
+impl NullSource {
+    /// Construct a new [`NullSource`] producing copies of `template` forever.
+    pub fn new(template: VDIFFrame) -> Self {
+        return Self {
+            template: template,
+            limit: None,
+            frames: 0,
+        };
+    }
+
+    /// Stop after `limit` frames, returning [`UnexpectedEof`](ErrorKind::UnexpectedEof) errors afterwards.
+    pub fn with_limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        return self;
+    }
+
+    /// Get the number of frames produced so far.
+    pub fn frames(&self) -> u64 {
+        return self.frames;
+    }
+}
+
+impl VDIFRead for NullSource {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        if self.limit.is_some_and(|limit| self.frames >= limit) {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        self.frames += 1;
+        return Ok(VDIFFrame::from_slice(self.template.as_slice()));
+    }
+}
+
+/// A [`VDIFWrite`] that counts and discards every frame written to it.
+#[derive(Debug, Default)]
+pub struct NullSink {
+    frames: u64,
+    bytes: u64,
+}
+
+impl NullSink {
+    /// Construct a new [`NullSink`].
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Get the number of frames written so far.
+    pub fn frames(&self) -> u64 {
+        return self.frames;
+    }
+
+    /// Get the number of bytes written so far.
+    pub fn bytes(&self) -> u64 {
+        return self.bytes;
+    }
+}
+
+impl VDIFWrite for NullSink {
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        self.frames += 1;
+        self.bytes += frame.bytesize() as u64;
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_null_source_sink() {
+        let mut source = NullSource::new(VDIFFrame::empty(64)).with_limit(10);
+        let mut sink = NullSink::new();
+        while let Ok(frame) = source.read_frame() {
+            sink.write_frame(frame).unwrap();
+        }
+        assert_eq!((sink.frames(), sink.bytes()), (10, 640));
+        assert_eq!(source.frames(), 10);
+    }
+}
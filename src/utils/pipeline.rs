@@ -0,0 +1,518 @@
+//! Implements [`Pipeline`], which runs a chain of frame processing stages on their own threads, connected by bounded
+//! queues.
+//!
+//! A pipeline reads frames from a [`VDIFRead`] source, passes them through any number of stages, and writes them to a
+//! [`VDIFWrite`] sink, for example:
+//!
+//! ```rust,ignore
+//! let source = VDIFUDP::new("0.0.0.0:50000", 8032)?;
+//! let sink = VDIFWriter::create("path/to/my/vdif", 8032)?;
+//! let pipeline = Pipeline::builder(source)
+//!     .stage("redact", |mut frame| {
+//!         zero_payload(&mut frame);
+//!         Some(frame)
+//!     })
+//!     .sink_with(StageOptions { queue_depth: 1000, drop_when_full: true }, sink)
+//!     .spawn();
+//! // Later on
+//! println!("{:?}", pipeline.stats());
+//! ```
+//!
+//! Every stage after the source has its own input queue, configured with [`StageOptions`], and the frames dropped at
+//! each queue are counted in one [`PipelineStats`].
+//!
+//! Frames carry their [`Provenance`] between stages, which [`tagged_stage`](PipelineBuilder::tagged_stage) can
+//! inspect, and which the sink uses to measure the end-to-end latency reported in [`PipelineStats::latency`].
+
+use std::io::{ErrorKind, Result};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::frame::VDIFFrame;
+use crate::io::{VDIFRead, VDIFWrite};
+use crate::provenance::{Provenance, Tagged};
+
+// How long the source waits before retrying a non-blocking read that had no frame.
+const SOURCE_BACKOFF: Duration = Duration::from_millis(1);
+
+/// Configures the input queue of a pipeline stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StageOptions {
+    /// The number of frames the queue can hold.
+    pub queue_depth: usize,
+    /// Drop frames arriving while the queue is full, rather than blocking the previous stage until there is room.
+    pub drop_when_full: bool,
+}
+
+impl Default for StageOptions {
+    fn default() -> Self {
+        return Self {
+            queue_depth: 64,
+            drop_when_full: false,
+        };
+    }
+}
+
+/// Counters for a single pipeline stage.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StageStats {
+    /// The name of the stage. The source and sink are named `"source"` and `"sink"`.
+    pub name: String,
+    /// The number of frames the stage has processed.
+    pub processed: u64,
+    /// The number of frames the stage has discarded itself.
+    pub filtered: u64,
+    /// The number of frames dropped because the stage's input queue was full.
+    pub dropped: u64,
+}
+
+/// The time frames took to pass from the source to the sink of a pipeline.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// The number of frames measured.
+    pub frames: u64,
+    /// The mean latency.
+    pub mean: Duration,
+    /// The largest latency.
+    pub max: Duration,
+}
+
+/// Counters for every stage of a pipeline, in order from source to sink.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PipelineStats {
+    /// The counters of each stage.
+    pub stages: Vec<StageStats>,
+    /// The latency of frames written to the sink.
+    pub latency: LatencyStats,
+}
+
+impl PipelineStats {
+    /// Get the total number of frames dropped at full queues across the whole pipeline.
+    pub fn total_dropped(&self) -> u64 {
+        return self.stages.iter().map(|s| s.dropped).sum();
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    processed: AtomicU64,
+    filtered: AtomicU64,
+    dropped: AtomicU64,
+    latency_total: AtomicU64,
+    latency_max: AtomicU64,
+}
+
+/// A user-defined pipeline stage, which returns the frame to pass on, or `None` to discard it.
+///
+/// Closures of the form `FnMut(VDIFFrame) -> Option<VDIFFrame>` implement this automatically. Implement it directly
+/// for stages that carry configuration or state, such as site-specific fixes, without forking the crate.
+pub trait FrameTransform: Send {
+    /// Process one frame.
+    fn process(&mut self, frame: VDIFFrame) -> Option<VDIFFrame>;
+}
+
+impl<F: FnMut(VDIFFrame) -> Option<VDIFFrame> + Send> FrameTransform for F {
+    fn process(&mut self, frame: VDIFFrame) -> Option<VDIFFrame> {
+        return self(frame);
+    }
+}
+
+type StageFn = Box<dyn FnMut(VDIFFrame, &mut Provenance) -> Option<VDIFFrame> + Send>;
+
+/// Builds a [`Pipeline`]. Constructed with [`Pipeline::builder`].
+pub struct PipelineBuilder<R: VDIFRead + Send + 'static> {
+    source: R,
+    stages: Vec<(String, StageOptions, StageFn)>,
+}
+
+impl<R: VDIFRead + Send + 'static> PipelineBuilder<R> {
+    /// Add a stage with the default [`StageOptions`]. The stage returns the frame to pass on, or `None` to discard it.
+    pub fn stage<F>(self, name: &str, f: F) -> Self
+    where
+        F: FnMut(VDIFFrame) -> Option<VDIFFrame> + Send + 'static,
+    {
+        return self.transform_with(name, StageOptions::default(), f);
+    }
+
+    /// Add a stage with the given [`StageOptions`].
+    pub fn stage_with<F>(self, name: &str, options: StageOptions, f: F) -> Self
+    where
+        F: FnMut(VDIFFrame) -> Option<VDIFFrame> + Send + 'static,
+    {
+        return self.transform_with(name, options, f);
+    }
+
+    /// Add a user-defined [`FrameTransform`] stage with the default [`StageOptions`].
+    pub fn transform<T: FrameTransform + 'static>(self, name: &str, transform: T) -> Self {
+        return self.transform_with(name, StageOptions::default(), transform);
+    }
+
+    /// Add a user-defined [`FrameTransform`] stage with the given [`StageOptions`].
+    pub fn transform_with<T: FrameTransform + 'static>(
+        self,
+        name: &str,
+        options: StageOptions,
+        mut transform: T,
+    ) -> Self {
+        return self.tagged_stage_with(name, options, move |frame, _: &mut Provenance| {
+            transform.process(frame)
+        });
+    }
+
+    /// Add a stage with the default [`StageOptions`] that can also read and update the [`Provenance`] of each frame.
+    pub fn tagged_stage<F>(self, name: &str, f: F) -> Self
+    where
+        F: FnMut(VDIFFrame, &mut Provenance) -> Option<VDIFFrame> + Send + 'static,
+    {
+        return self.tagged_stage_with(name, StageOptions::default(), f);
+    }
+
+    /// Add a stage with the given [`StageOptions`] that can also read and update the [`Provenance`] of each frame.
+    pub fn tagged_stage_with<F>(mut self, name: &str, options: StageOptions, f: F) -> Self
+    where
+        F: FnMut(VDIFFrame, &mut Provenance) -> Option<VDIFFrame> + Send + 'static,
+    {
+        self.stages.push((name.to_owned(), options, Box::new(f)));
+        return self;
+    }
+
+    /// Finish the pipeline with `sink`, using the default [`StageOptions`].
+    pub fn sink<W: VDIFWrite + Send + 'static>(self, sink: W) -> PipelineSpawner<R, W> {
+        return self.sink_with(StageOptions::default(), sink);
+    }
+
+    /// Finish the pipeline with `sink`, using the given [`StageOptions`].
+    pub fn sink_with<W: VDIFWrite + Send + 'static>(
+        self,
+        options: StageOptions,
+        sink: W,
+    ) -> PipelineSpawner<R, W> {
+        return PipelineSpawner {
+            builder: self,
+            sink: sink,
+            sink_options: options,
+        };
+    }
+}
+
+/// A fully described pipeline, ready to [`spawn`](PipelineSpawner::spawn).
+pub struct PipelineSpawner<R: VDIFRead + Send + 'static, W: VDIFWrite + Send + 'static> {
+    builder: PipelineBuilder<R>,
+    sink: W,
+    sink_options: StageOptions,
+}
+
+impl<R: VDIFRead + Send + 'static, W: VDIFWrite + Send + 'static> PipelineSpawner<R, W> {
+    /// Start every stage on its own thread.
+    pub fn spawn(self) -> Pipeline {
+        let PipelineBuilder { mut source, stages } = self.builder;
+        let mut names = vec!["source".to_owned()];
+        let mut counters = vec![Arc::new(Counters::default())];
+        let mut threads: Vec<JoinHandle<Result<()>>> = Vec::new();
+
+        // Build the chain backwards from the sink, so each stage can be handed the queue it feeds
+        let sink_counters = Arc::new(Counters::default());
+        let (tx, rx) = sync_channel(self.sink_options.queue_depth);
+        let mut next = Queue {
+            tx: tx,
+            options: self.sink_options,
+            counters: sink_counters.clone(),
+        };
+        let mut sink = self.sink;
+        let thread_counters = sink_counters.clone();
+        threads.push(std::thread::spawn(move || {
+            let result = run_sink(rx, &mut sink, &thread_counters);
+            let flushed = sink.flush();
+            return result.and(flushed);
+        }));
+
+        let mut stage_counters = Vec::new();
+        let stage_count = stages.len();
+        for (i, (name, options, mut f)) in stages.into_iter().rev().enumerate() {
+            let stage = stage_count - 1 - i;
+            let these_counters = Arc::new(Counters::default());
+            let (tx, rx) = sync_channel::<Tagged>(options.queue_depth);
+            let output = std::mem::replace(
+                &mut next,
+                Queue {
+                    tx: tx,
+                    options: options,
+                    counters: these_counters.clone(),
+                },
+            );
+            let thread_counters = these_counters.clone();
+            threads.push(std::thread::spawn(move || {
+                for mut tagged in rx {
+                    thread_counters.processed.fetch_add(1, Ordering::Relaxed);
+                    match f(tagged.frame, &mut tagged.provenance) {
+                        Some(frame) => {
+                            if stage < 64 {
+                                tagged.provenance.stages |= 1 << stage;
+                            }
+                            let tagged = Tagged {
+                                frame: frame,
+                                provenance: tagged.provenance,
+                            };
+                            if !output.push(tagged) {
+                                break;
+                            }
+                        }
+                        None => {
+                            thread_counters.filtered.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }
+                return Ok(());
+            }));
+            stage_counters.push((name, these_counters));
+        }
+        for (name, c) in stage_counters.into_iter().rev() {
+            names.push(name);
+            counters.push(c);
+        }
+        names.push("sink".to_owned());
+        counters.push(sink_counters);
+
+        let source_counters = counters[0].clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let mut sequence = 0;
+        threads.push(std::thread::spawn(move || loop {
+            if thread_stop.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            let mut tagged = match source.read_tagged() {
+                Ok(tagged) => tagged,
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+                // A source with a read timeout, such as a socket, has simply had no frames yet
+                Err(e) if e.kind() == ErrorKind::TimedOut => continue,
+                // A non-blocking source returns at once, so back off rather than spin
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    std::thread::sleep(SOURCE_BACKOFF);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            source_counters.processed.fetch_add(1, Ordering::Relaxed);
+            tagged.provenance.sequence = sequence;
+            sequence += 1;
+            if !next.push(tagged) {
+                return Ok(());
+            }
+        }));
+
+        return Pipeline {
+            names: names,
+            counters: counters,
+            threads: threads,
+            stop: stop,
+        };
+    }
+}
+
+// The input queue of a stage, as seen by the stage feeding it.
+struct Queue {
+    tx: SyncSender<Tagged>,
+    options: StageOptions,
+    counters: Arc<Counters>,
+}
+
+impl Queue {
+    // Push a frame onto the queue, returning false if the receiving stage has stopped.
+    fn push(&self, frame: Tagged) -> bool {
+        if !self.options.drop_when_full {
+            return self.tx.send(frame).is_ok();
+        }
+        return match self.tx.try_send(frame) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        };
+    }
+}
+
+fn run_sink<W: VDIFWrite>(rx: Receiver<Tagged>, sink: &mut W, counters: &Counters) -> Result<()> {
+    for tagged in rx {
+        counters.processed.fetch_add(1, Ordering::Relaxed);
+        sink.write_frame(tagged.frame)?;
+        let latency = tagged.provenance.age().as_nanos() as u64;
+        counters.latency_total.fetch_add(latency, Ordering::Relaxed);
+        counters.latency_max.fetch_max(latency, Ordering::Relaxed);
+    }
+    return Ok(());
+}
+
+/// A running chain of frame processing stages. See the [module documentation](self) for details.
+pub struct Pipeline {
+    names: Vec<String>,
+    counters: Vec<Arc<Counters>>,
+    threads: Vec<JoinHandle<Result<()>>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Pipeline {
+    /// Start building a [`Pipeline`] reading frames from `source`.
+    pub fn builder<R: VDIFRead + Send + 'static>(source: R) -> PipelineBuilder<R> {
+        return PipelineBuilder {
+            source: source,
+            stages: Vec::new(),
+        };
+    }
+
+    /// Get a snapshot of the counters of every stage.
+    pub fn stats(&self) -> PipelineStats {
+        return snapshot(&self.names, &self.counters);
+    }
+
+    /// Ask the source to stop reading, after which the rest of the pipeline drains and finishes.
+    ///
+    /// The source only notices between frames, so a source that can block indefinitely, such as a socket, should be
+    /// given a read timeout for this to take effect promptly.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether every stage has finished.
+    pub fn is_finished(&self) -> bool {
+        return self.threads.iter().all(|t| t.is_finished());
+    }
+
+    /// Wait for the source to reach EOF (or be stopped) and every stage to finish, returning the final counters or the
+    /// first error encountered by the source or sink.
+    pub fn join(self) -> Result<PipelineStats> {
+        let mut result = Ok(());
+        for thread in self.threads {
+            let outcome = thread.join().expect("A pipeline stage panicked");
+            if result.is_ok() {
+                result = outcome;
+            }
+        }
+        return result.map(|_| snapshot(&self.names, &self.counters));
+    }
+}
+
+fn snapshot(names: &[String], counters: &[Arc<Counters>]) -> PipelineStats {
+    let stages = names
+        .iter()
+        .zip(counters)
+        .map(|(name, c)| StageStats {
+            name: name.clone(),
+            processed: c.processed.load(Ordering::Relaxed),
+            filtered: c.filtered.load(Ordering::Relaxed),
+            dropped: c.dropped.load(Ordering::Relaxed),
+        })
+        .collect();
+    let sink = counters.last().unwrap();
+    let frames = sink.processed.load(Ordering::Relaxed);
+    let latency = LatencyStats {
+        frames: frames,
+        mean: Duration::from_nanos(sink.latency_total.load(Ordering::Relaxed) / frames.max(1)),
+        max: Duration::from_nanos(sink.latency_max.load(Ordering::Relaxed)),
+    };
+    return PipelineStats {
+        stages: stages,
+        latency: latency,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    use crate::utils::sim::VDIFSim;
+
+    struct Limited(VDIFSim, usize);
+    impl VDIFRead for Limited {
+        fn read_frame(&mut self) -> Result<VDIFFrame> {
+            if self.1 == 0 {
+                return Err(std::io::Error::from(ErrorKind::UnexpectedEof));
+            }
+            self.1 -= 1;
+            return self.0.read_frame();
+        }
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let (tx, rx) = channel();
+        let pipeline = Pipeline::builder(Limited(VDIFSim::new(64, 10, 1), 20))
+            .stage("odd", |frame| {
+                if frame.get_header().frameno % 2 == 1 {
+                    Some(frame)
+                } else {
+                    None
+                }
+            })
+            .stage_with(
+                "tiny",
+                StageOptions {
+                    queue_depth: 1,
+                    drop_when_full: false,
+                },
+                Some,
+            )
+            .sink(tx)
+            .spawn();
+
+        let stats = pipeline.join().unwrap();
+        let names: Vec<&str> = stats.stages.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["source", "odd", "tiny", "sink"]);
+        assert_eq!(stats.stages[0].processed, 20);
+        assert_eq!(stats.stages[1].filtered, 10);
+        assert_eq!(stats.stages[3].processed, 10);
+        assert_eq!(stats.total_dropped(), 0);
+        assert_eq!(stats.latency.frames, 10);
+        assert!(rx.iter().all(|f| f.get_header().frameno % 2 == 1));
+    }
+
+    #[test]
+    fn test_provenance() {
+        let (tx, rx) = channel();
+        let pipeline = Pipeline::builder(Limited(VDIFSim::new(64, 10, 1), 4))
+            .stage("all", Some)
+            .tagged_stage("check", |frame, provenance| {
+                assert!(provenance.passed(0) && !provenance.passed(1));
+                if provenance.sequence == frame.get_header().frameno as u64 {
+                    Some(frame)
+                } else {
+                    None
+                }
+            })
+            .sink(tx)
+            .spawn();
+        let stats = pipeline.join().unwrap();
+        assert_eq!(stats.stages[2].filtered, 0);
+        assert!(stats.latency.max >= stats.latency.mean);
+        assert_eq!(rx.iter().count(), 4);
+    }
+
+    struct SetStation(u16);
+
+    impl FrameTransform for SetStation {
+        fn process(&mut self, mut frame: VDIFFrame) -> Option<VDIFFrame> {
+            let mut header = frame.get_header();
+            header.station = self.0;
+            frame.set_header(header);
+            return Some(frame);
+        }
+    }
+
+    #[test]
+    fn test_frame_transform() {
+        let (tx, rx) = channel();
+        Pipeline::builder(Limited(VDIFSim::new(64, 10, 1), 3))
+            .transform("station", SetStation(7))
+            .sink(tx)
+            .spawn()
+            .join()
+            .unwrap();
+        assert!(rx.iter().all(|f| f.get_header().station == 7));
+    }
+}
@@ -0,0 +1,293 @@
+//! Implements a sharded multi-producer, single-consumer frame queue, for feeding one consumer from several capture
+//! threads, such as one per socket returned by [`VDIFUDP::sharded`](crate::net::udp::VDIFUDP::sharded).
+//!
+//! Each producer pushes into its own bounded lane, so producers never contend with each other, and the consumer takes
+//! frames from the lanes in turn. A consumer with nothing to read parks its thread until a producer wakes it.
+//!
+//! ```rust,ignore
+//! let sockets = VDIFUDP::sharded("0.0.0.0:50000", 8032, 4).unwrap();
+//! let (mut consumer, _threads) = capture(sockets, 1024);
+//! let frame = consumer.read_frame().unwrap();
+//! ```
+
+use std::io::{Error, ErrorKind, Result};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TryRecvError, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle, Thread};
+
+use crate::frame::VDIFFrame;
+use crate::io::{VDIFRead, VDIFWrite};
+
+// State shared between the producers and the consumer.
+struct Shared {
+    // The consumer's thread, and whether it is parked waiting for a frame
+    consumer: Mutex<Option<Thread>>,
+    waiting: AtomicBool,
+    dropped: AtomicU64,
+}
+
+impl Shared {
+    fn wake(&self) {
+        if self.waiting.swap(false, Ordering::SeqCst) {
+            if let Some(thread) = self.consumer.lock().unwrap().as_ref() {
+                thread.unpark();
+            }
+        }
+    }
+}
+
+/// Construct a queue of `shards` lanes of `capacity` frames each, returning a producer for each lane and the consumer.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero.
+pub fn sharded_queue(shards: usize, capacity: usize) -> (Vec<QueueProducer>, QueueConsumer) {
+    assert!(capacity > 0, "Each lane of a queue needs room for a frame");
+    let shared = Arc::new(Shared {
+        consumer: Mutex::new(None),
+        waiting: AtomicBool::new(false),
+        dropped: AtomicU64::new(0),
+    });
+    let (producers, lanes) = (0..shards)
+        .map(|_| {
+            let (sender, receiver) = sync_channel(capacity);
+            let producer = QueueProducer {
+                sender: sender,
+                shared: shared.clone(),
+                drop_when_full: false,
+            };
+            (producer, receiver)
+        })
+        .unzip();
+    let consumer = QueueConsumer {
+        lanes: lanes,
+        next: 0,
+        shared: shared,
+    };
+    return (producers, consumer);
+}
+
+/// Spawn a thread for each of `readers`, copying its frames into a lane of a [`sharded_queue`] of `capacity` frames per
+/// lane, and return the consumer along with the threads.
+///
+/// Each thread runs until its reader fails, returning the error, or the consumer is dropped. A reader reaching the end
+/// of its stream ends its thread with `Ok`.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero.
+pub fn capture<R: VDIFRead + Send + 'static>(
+    readers: Vec<R>,
+    capacity: usize,
+) -> (QueueConsumer, Vec<JoinHandle<Result<()>>>) {
+    let (producers, consumer) = sharded_queue(readers.len(), capacity);
+    let threads = readers
+        .into_iter()
+        .zip(producers)
+        .map(|(mut reader, mut producer)| {
+            thread::spawn(move || loop {
+                let frame = match reader.read_frame() {
+                    Ok(frame) => frame,
+                    Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+                    Err(e) => return Err(e),
+                };
+                match producer.push(frame) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == ErrorKind::BrokenPipe => return Ok(()),
+                    Err(e) => return Err(e),
+                }
+            })
+        })
+        .collect();
+    return (consumer, threads);
+}
+
+/// The sending end of one lane of a [`sharded_queue`]. Each producer should be owned by a single capture thread.
+pub struct QueueProducer {
+    sender: SyncSender<VDIFFrame>,
+    shared: Arc<Shared>,
+    drop_when_full: bool,
+}
+
+impl QueueProducer {
+    /// Set whether [`write_frame`](VDIFWrite::write_frame) drops frames while the lane is full, rather than waiting
+    /// for the consumer to make room. Off by default.
+    pub fn set_drop_when_full(&mut self, drop_when_full: bool) {
+        self.drop_when_full = drop_when_full;
+    }
+
+    /// Push `frame` onto the lane, blocking while it is full.
+    ///
+    /// Returns an error of kind [`BrokenPipe`](ErrorKind::BrokenPipe) if the consumer has been dropped.
+    pub fn push(&mut self, frame: VDIFFrame) -> Result<()> {
+        self.sender.send(frame).map_err(|_| consumer_gone())?;
+        self.shared.wake();
+        return Ok(());
+    }
+
+    /// Push `frame` onto the lane, dropping it if the lane is full. Returns whether the frame was queued.
+    ///
+    /// Returns an error of kind [`BrokenPipe`](ErrorKind::BrokenPipe) if the consumer has been dropped.
+    pub fn try_push(&mut self, frame: VDIFFrame) -> Result<bool> {
+        return match self.sender.try_send(frame) {
+            Ok(()) => {
+                self.shared.wake();
+                Ok(true)
+            }
+            Err(TrySendError::Full(_)) => {
+                self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+                Ok(false)
+            }
+            Err(TrySendError::Disconnected(_)) => Err(consumer_gone()),
+        };
+    }
+}
+
+impl VDIFWrite for QueueProducer {
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        if self.drop_when_full {
+            return self.try_push(frame).map(|_| ());
+        }
+        return self.push(frame);
+    }
+}
+
+impl Drop for QueueProducer {
+    // Disconnect the lane before waking the consumer, so it doesn't go back to sleep waiting on it
+    fn drop(&mut self) {
+        let (sender, _) = sync_channel(0);
+        drop(std::mem::replace(&mut self.sender, sender));
+        self.shared.wake();
+    }
+}
+
+fn consumer_gone() -> Error {
+    return Error::new(
+        ErrorKind::BrokenPipe,
+        "The consumer of the queue has disconnected",
+    );
+}
+
+/// The receiving end of a [`sharded_queue`], taking frames from each lane in turn.
+///
+/// Frames from one lane arrive in the order they were pushed, but frames from different lanes are interleaved as they
+/// become available. Reads return an error of kind [`UnexpectedEof`](ErrorKind::UnexpectedEof) once every producer has
+/// been dropped and the queue is empty.
+pub struct QueueConsumer {
+    lanes: Vec<Receiver<VDIFFrame>>,
+    next: usize,
+    shared: Arc<Shared>,
+}
+
+impl QueueConsumer {
+    /// Get the number of lanes still connected to a producer, or holding frames.
+    pub fn lanes(&self) -> usize {
+        return self.lanes.len();
+    }
+
+    /// Get the total number of frames dropped by producers because their lane was full.
+    pub fn dropped(&self) -> u64 {
+        return self.shared.dropped.load(Ordering::Relaxed);
+    }
+
+    /// Take a [`VDIFFrame`] if one is waiting in any lane, returning an error of kind
+    /// [`WouldBlock`](ErrorKind::WouldBlock) otherwise.
+    pub fn try_read_frame(&mut self) -> Result<VDIFFrame> {
+        let mut i = 0;
+        while i < self.lanes.len() {
+            let lane = (self.next + i) % self.lanes.len();
+            match self.lanes[lane].try_recv() {
+                Ok(frame) => {
+                    self.next = (lane + 1) % self.lanes.len();
+                    return Ok(frame);
+                }
+                Err(TryRecvError::Empty) => i += 1,
+                Err(TryRecvError::Disconnected) => {
+                    self.lanes.remove(lane);
+                    if lane < self.next {
+                        self.next -= 1;
+                    }
+                    self.next = self.next.checked_rem(self.lanes.len()).unwrap_or(0);
+                }
+            }
+        }
+        if self.lanes.is_empty() {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "Every producer of the queue has disconnected",
+            ));
+        }
+        return Err(ErrorKind::WouldBlock.into());
+    }
+}
+
+impl VDIFRead for QueueConsumer {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        *self.shared.consumer.lock().unwrap() = Some(thread::current());
+        loop {
+            match self.try_read_frame() {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                result => return result,
+            }
+            // Announce that we're waiting, then look again so a frame pushed in between isn't missed
+            self.shared.waiting.store(true, Ordering::SeqCst);
+            match self.try_read_frame() {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => thread::park(),
+                result => {
+                    self.shared.waiting.store(false, Ordering::SeqCst);
+                    return result;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::VDIFHeader;
+    use crate::testing::Source;
+
+    fn source(thread: u16) -> Source {
+        let frames: Vec<VDIFFrame> = (0..100)
+            .map(|frameno| {
+                VDIFFrame::from_header(VDIFHeader {
+                    thread: thread,
+                    frameno: frameno,
+                    size: 4,
+                    ..Default::default()
+                })
+            })
+            .collect();
+        return Source(frames.into_iter());
+    }
+
+    #[test]
+    fn test_sharded_queue() {
+        let (mut consumer, threads) = capture((0..4).map(source).collect(), 8);
+        let mut next = [0u32; 4];
+        while let Ok(frame) = consumer.read_frame() {
+            let header = frame.get_header();
+            assert_eq!(header.frameno, next[header.thread as usize]);
+            next[header.thread as usize] += 1;
+        }
+        assert_eq!(next, [100; 4]);
+        assert_eq!(consumer.lanes(), 0);
+        for thread in threads {
+            thread.join().unwrap().unwrap();
+        }
+
+        let (mut producers, consumer) = sharded_queue(1, 1);
+        producers[0].set_drop_when_full(true);
+        for _ in 0..3 {
+            producers[0].write_frame(VDIFFrame::empty(32)).unwrap();
+        }
+        assert_eq!(consumer.dropped(), 2);
+        drop(consumer);
+        assert_eq!(
+            producers[0].push(VDIFFrame::empty(32)).unwrap_err().kind(),
+            ErrorKind::BrokenPipe
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! Implements functionality for redacting VDIF frames, so that problematic streams can be shared publicly without
+//! shipping raw telescope data.
+//!
+//! Redaction only ever touches the payload and the station ID, so the timing and layout information needed to
+//! reproduce most packet-level problems is preserved.
+
+use std::io::Result;
+
+use crate::frame::VDIFFrame;
+use crate::header_encoding::MASK_STATION_ID;
+use crate::io::VDIFRead;
+
+/// Describes how a frame should be redacted.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RedactOptions {
+    /// Zero the payload of each frame, leaving the header intact.
+    pub zero_payload: bool,
+    /// Replace the station ID of each frame with this value, if set.
+    pub station: Option<u16>,
+}
+
+/// Zero the payload of `frame`, leaving the header intact.
+pub fn zero_payload(frame: &mut VDIFFrame) {
+    frame.get_mut_payload().fill(0);
+}
+
+/// Replace the station ID of `frame` with `station`, leaving the rest of the header intact.
+pub fn replace_station(frame: &mut VDIFFrame, station: u16) {
+    let w3 = &mut frame.as_mut_slice()[3];
+    *w3 = (*w3 & !MASK_STATION_ID) | station as u32;
+}
+
+/// Redact `frame` in place according to `options`.
+pub fn redact_frame(frame: &mut VDIFFrame, options: RedactOptions) {
+    if options.zero_payload {
+        zero_payload(frame);
+    }
+    if let Some(station) = options.station {
+        replace_station(frame, station);
+    }
+}
+
+/// Wraps a [`VDIFRead`] type and redacts every frame read from it.
+///
+/// ```rust,ignore
+/// // Produce an anonymised copy of a file
+/// let options = RedactOptions { zero_payload: true, station: Some(0) };
+/// let mut reader = VDIFRedactor::new(VDIFReader::open("path/to/my/vdif", 8032).unwrap(), options);
+/// let mut writer = VDIFWriter::create("path/to/redacted/vdif", 8032).unwrap();
+/// while let Ok(frame) = reader.read_frame() {
+///     writer.write_frame(frame).unwrap();
+/// }
+/// ```
+pub struct VDIFRedactor<R: VDIFRead> {
+    inner: R,
+    options: RedactOptions,
+}
+
+impl<R: VDIFRead> VDIFRedactor<R> {
+    /// Construct a new [`VDIFRedactor`] wrapping `inner`.
+    pub fn new(inner: R, options: RedactOptions) -> Self {
+        return Self {
+            inner: inner,
+            options: options,
+        };
+    }
+
+    /// Consume this [`VDIFRedactor`], returning the wrapped reader.
+    pub fn into_inner(self) -> R {
+        return self.inner;
+    }
+}
+
+impl<R: VDIFRead> VDIFRead for VDIFRedactor<R> {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        let mut frame = self.inner.read_frame()?;
+        redact_frame(&mut frame, self.options);
+        return Ok(frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::sim::VDIFSim;
+
+    #[test]
+    fn test_redact_frame() {
+        let mut sim = VDIFSim::new(64, 10, 1);
+        let mut frame = sim.generate_frame();
+        frame.get_mut_payload().fill(0xDEADBEEF);
+        let header = frame.get_header();
+
+        redact_frame(
+            &mut frame,
+            RedactOptions {
+                zero_payload: true,
+                station: Some(0x4A42),
+            },
+        );
+
+        assert!(frame.get_payload().iter().all(|w| *w == 0));
+        let redacted = frame.get_header();
+        assert_eq!(redacted.station, 0x4A42);
+        assert_eq!(redacted.thread, header.thread);
+        assert_eq!(redacted.frameno, header.frameno);
+        assert_eq!(redacted.bits_per_sample, header.bits_per_sample);
+    }
+}
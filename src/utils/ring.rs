@@ -0,0 +1,187 @@
+//! Implements [`RingRecorder`], a circular recorder that keeps the most recent frames of a stream and can be queried
+//! by time while it is still recording.
+//!
+//! The newest frames sit in an in-memory FIFO, and older frames spill into a fixed-size ring file on disk that wraps
+//! around when full. [`frames_between`](RingRecorder::frames_between) combines both, which is what a triggered
+//! transient dump needs: the frames leading up to the trigger as well as those just after it.
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use chrono::{NaiveDateTime, TimeDelta};
+
+use crate::error::VDIFError;
+use crate::frame::VDIFFrame;
+use crate::io::VDIFWrite;
+
+/// Records a stream into an in-memory FIFO backed by a ring file on disk, keeping only the most recent frames.
+pub struct RingRecorder {
+    file: File,
+    frame_size: usize,
+    frame_rate: u32,
+    // The time span covered by each slot of the ring file, if any
+    slots: Vec<Option<(NaiveDateTime, NaiveDateTime)>>,
+    next_slot: usize,
+    memory: VecDeque<VDIFFrame>,
+    memory_frames: usize,
+}
+
+impl RingRecorder {
+    /// Create a ring file at `path` holding `disk_frames` frames of `frame_size` bytes, fronted by a FIFO of
+    /// `memory_frames` frames. `frame_rate` is the number of frames per second per thread, used to work out the time
+    /// each frame covers.
+    ///
+    /// Returns an error of kind [`InvalidInput`](ErrorKind::InvalidInput) if `frame_rate` is zero.
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        frame_size: usize,
+        frame_rate: u32,
+        disk_frames: usize,
+        memory_frames: usize,
+    ) -> Result<Self> {
+        if frame_rate == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Frame rate must be positive",
+            ));
+        }
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len((disk_frames * frame_size) as u64)?;
+        return Ok(Self {
+            file: file,
+            frame_size: frame_size,
+            frame_rate: frame_rate,
+            slots: vec![None; disk_frames],
+            next_slot: 0,
+            memory: VecDeque::with_capacity(memory_frames + 1),
+            memory_frames: memory_frames,
+        });
+    }
+
+    /// Get the number of frames currently held, in memory and on disk.
+    pub fn len(&self) -> usize {
+        return self.memory.len() + self.slots.iter().filter(|s| s.is_some()).count();
+    }
+
+    /// Get copies of every held frame that overlaps the time range `start..=end`, oldest first.
+    pub fn frames_between(
+        &mut self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<Vec<VDIFFrame>> {
+        let mut out = Vec::new();
+        let disk_frames = self.slots.len();
+        for i in 0..disk_frames {
+            // Walk the ring from the oldest slot to the newest
+            let slot = (self.next_slot + i) % disk_frames;
+            if let Some((from, to)) = self.slots[slot] {
+                if from <= end && to > start {
+                    let mut frame = VDIFFrame::empty(self.frame_size);
+                    self.file
+                        .seek(SeekFrom::Start((slot * self.frame_size) as u64))?;
+                    self.file.read_exact(frame.as_mut_bytes())?;
+                    out.push(frame);
+                }
+            }
+        }
+        for frame in &self.memory {
+            let (from, to) = self.span(frame);
+            if from <= end && to > start {
+                out.push(VDIFFrame::from_slice(frame.as_slice()));
+            }
+        }
+        return Ok(out);
+    }
+
+    // The time range covered by `frame`. The frame rate is checked to be non-zero on creation.
+    fn span(&self, frame: &VDIFFrame) -> (NaiveDateTime, NaiveDateTime) {
+        let header = frame.get_header();
+        let period = 1_000_000_000 / self.frame_rate as i64;
+        let start = header.date() + TimeDelta::nanoseconds(header.frameno as i64 * period);
+        return (start, start + TimeDelta::nanoseconds(period));
+    }
+
+    fn spill(&mut self, frame: VDIFFrame) -> Result<()> {
+        if self.slots.is_empty() {
+            return Ok(());
+        }
+        let slot = self.next_slot;
+        self.file
+            .seek(SeekFrom::Start((slot * self.frame_size) as u64))?;
+        self.file.write_all(frame.as_bytes())?;
+        self.slots[slot] = Some(self.span(&frame));
+        self.next_slot = (slot + 1) % self.slots.len();
+        return Ok(());
+    }
+}
+
+impl VDIFWrite for RingRecorder {
+    /// Record `frame`, returning an error of kind [`InvalidInput`](ErrorKind::InvalidInput) carrying a
+    /// [`VDIFError::BadFrameSize`] if it isn't the recorder's frame size.
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        if frame.bytesize() != self.frame_size {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                VDIFError::BadFrameSize(frame.bytesize()),
+            ));
+        }
+        self.memory.push_back(frame);
+        if self.memory.len() > self.memory_frames {
+            let oldest = self.memory.pop_front().unwrap();
+            self.spill(oldest)?;
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::vdiftime_to_date;
+    use crate::testing::temp_path;
+    use crate::utils::sim::VDIFSim;
+
+    #[test]
+    fn test_ring_recorder() {
+        let path = temp_path("ring.vdif");
+        let mut ring = RingRecorder::create(&path, 64, 4, 4, 2).unwrap();
+        let mut sim = VDIFSim::new(64, 4, 1);
+        for _ in 0..10 {
+            ring.write_frame(sim.generate_frame()).unwrap();
+        }
+        // The ring holds the newest 6 frames: second 1 frames 0-3 on disk, and second 2 frames 0-1 in memory
+        assert_eq!(ring.len(), 6);
+
+        let second = |s: u32| vdiftime_to_date(3, s);
+        let frames = ring
+            .frames_between(second(1) + TimeDelta::milliseconds(600), second(2))
+            .unwrap();
+        let times: Vec<(u32, u32)> = frames
+            .iter()
+            .map(|f| (f.get_header().time, f.get_header().frameno))
+            .collect();
+        assert_eq!(times, vec![(1, 2), (1, 3), (2, 0)]);
+        assert_eq!(
+            ring.write_frame(VDIFFrame::empty(32)).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(ring.len(), 6);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            RingRecorder::create(&path, 64, 0, 4, 2)
+                .err()
+                .unwrap()
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+        assert!(!path.exists());
+    }
+}
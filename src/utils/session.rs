@@ -0,0 +1,302 @@
+//! Implements [`CaptureSession`] and [`PlaybackSession`], single entry points bundling a source or sink with its
+//! stream spec, counters and lifecycle.
+//!
+//! The sessions are built from the crate's other parts, for applications that just want frames in or out:
+//!
+//! ```rust,ignore
+//! let mut session = CaptureSession::new(VDIFUDP::new("0.0.0.0:50000", 8032)?);
+//! let control = session.control();
+//! for frame in &mut session {
+//!     let frame = frame?;
+//!     // ...
+//! }
+//! ```
+
+use std::collections::BTreeSet;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::frame::VDIFFrame;
+use crate::header::VDIFHeader;
+use crate::io::{VDIFRead, VDIFWrite};
+use crate::spec::StreamSpec;
+
+/// Counters kept by a session.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SessionStats {
+    /// The number of frames passed through the session.
+    pub frames: u64,
+    /// The number of bytes passed through the session.
+    pub bytes: u64,
+    /// The number of frames read and discarded while the session was paused.
+    pub skipped: u64,
+}
+
+/// A handle for pausing, resuming and stopping a session from another thread.
+#[derive(Debug, Clone, Default)]
+pub struct SessionControl {
+    paused: Arc<AtomicBool>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl SessionControl {
+    /// Pause the session.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    /// Resume a paused session.
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if the session is paused.
+    pub fn is_paused(&self) -> bool {
+        return self.paused.load(Ordering::Relaxed);
+    }
+
+    /// Stop the session. A stopped session can't be restarted.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if the session has been stopped.
+    pub fn is_stopped(&self) -> bool {
+        return self.stopped.load(Ordering::Relaxed);
+    }
+}
+
+/// Captures frames from a [`VDIFRead`] source, detecting the stream's [`StreamSpec`] as frames arrive.
+///
+/// The spec is known once the first second boundary has been crossed, since the frame rate is only known then. While
+/// paused, frames are still read, so a network source doesn't back up, but are discarded. Iterating a session yields
+/// frames until the source ends or the session is stopped.
+pub struct CaptureSession<R: VDIFRead> {
+    source: R,
+    control: SessionControl,
+    stats: SessionStats,
+    spec: Option<StreamSpec>,
+    first: Option<VDIFHeader>,
+    threads: BTreeSet<u16>,
+    max_frameno: u32,
+}
+
+impl<R: VDIFRead> CaptureSession<R> {
+    /// Construct a new [`CaptureSession`] reading from `source`.
+    pub fn new(source: R) -> Self {
+        return Self {
+            source: source,
+            control: SessionControl::default(),
+            stats: SessionStats::default(),
+            spec: None,
+            first: None,
+            threads: BTreeSet::new(),
+            max_frameno: 0,
+        };
+    }
+
+    /// Get the spec of the stream, once it has been detected.
+    pub fn spec(&self) -> Option<&StreamSpec> {
+        return self.spec.as_ref();
+    }
+
+    /// Get the counters accumulated so far.
+    pub fn stats(&self) -> SessionStats {
+        return self.stats;
+    }
+
+    /// Get a handle to pause, resume or stop this session.
+    pub fn control(&self) -> SessionControl {
+        return self.control.clone();
+    }
+
+    /// Pause the session. Equivalent to `control().pause()`.
+    pub fn pause(&self) {
+        self.control.pause();
+    }
+
+    /// Resume the session. Equivalent to `control().resume()`.
+    pub fn resume(&self) {
+        self.control.resume();
+    }
+
+    /// Return the wrapped source.
+    pub fn into_inner(self) -> R {
+        return self.source;
+    }
+
+    fn detect(&mut self, header: &VDIFHeader) {
+        let first = *self.first.get_or_insert(*header);
+        if header.time == first.time && header.epoch == first.epoch {
+            self.threads.insert(header.thread);
+            self.max_frameno = self.max_frameno.max(header.frameno);
+            return;
+        }
+        let mut spec = StreamSpec::from_header(&first, self.max_frameno + 1);
+        spec.threads = self.threads.iter().copied().collect();
+        self.spec = Some(spec);
+    }
+}
+
+impl<R: VDIFRead> VDIFRead for CaptureSession<R> {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        loop {
+            if self.control.is_stopped() {
+                return Err(std::io::Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "The session has been stopped",
+                ));
+            }
+            let frame = self.source.read_frame()?;
+            if self.control.is_paused() {
+                self.stats.skipped += 1;
+                continue;
+            }
+            if self.spec.is_none() {
+                self.detect(&frame.get_header());
+            }
+            self.stats.frames += 1;
+            self.stats.bytes += frame.bytesize() as u64;
+            return Ok(frame);
+        }
+    }
+}
+
+impl<R: VDIFRead> Iterator for CaptureSession<R> {
+    type Item = Result<VDIFFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        return match self.read_frame() {
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => None,
+            other => Some(other),
+        };
+    }
+}
+
+/// Plays frames from a [`VDIFRead`] source into a [`VDIFWrite`] sink, with counters and pause/resume.
+pub struct PlaybackSession<R: VDIFRead, W: VDIFWrite> {
+    source: R,
+    sink: W,
+    spec: Option<StreamSpec>,
+    control: SessionControl,
+    stats: SessionStats,
+}
+
+impl<R: VDIFRead, W: VDIFWrite> PlaybackSession<R, W> {
+    /// Construct a new [`PlaybackSession`] copying frames from `source` to `sink`.
+    pub fn new(source: R, sink: W) -> Self {
+        return Self {
+            source: source,
+            sink: sink,
+            spec: None,
+            control: SessionControl::default(),
+            stats: SessionStats::default(),
+        };
+    }
+
+    /// Get the spec of the stream being played. Until one is set, this is taken from the first frame, with a frame
+    /// rate of zero.
+    pub fn spec(&self) -> Option<&StreamSpec> {
+        return self.spec.as_ref();
+    }
+
+    /// Set the spec of the stream being played, for example from a [`Sidecar`](crate::utils::sidecar::Sidecar).
+    ///
+    /// Returns an error of kind [`InvalidInput`](ErrorKind::InvalidInput) if the spec has a frame rate of zero.
+    pub fn set_spec(&mut self, spec: StreamSpec) -> Result<()> {
+        if spec.frame_rate == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Frame rate must be positive",
+            ));
+        }
+        self.spec = Some(spec);
+        return Ok(());
+    }
+
+    /// Get the counters accumulated so far.
+    pub fn stats(&self) -> SessionStats {
+        return self.stats;
+    }
+
+    /// Get a handle to pause, resume or stop this session.
+    pub fn control(&self) -> SessionControl {
+        return self.control.clone();
+    }
+
+    /// Copy one frame from the source to the sink, regardless of whether the session is paused.
+    pub fn step(&mut self) -> Result<()> {
+        let frame = self.source.read_frame()?;
+        if self.spec.is_none() {
+            self.spec = Some(StreamSpec::from_header(&frame.get_header(), 0));
+        }
+        self.stats.bytes += frame.bytesize() as u64;
+        self.sink.write_frame(frame)?;
+        self.stats.frames += 1;
+        return Ok(());
+    }
+
+    /// Copy frames until the source ends or the session is stopped, waiting while it is paused, then flush the sink.
+    pub fn run(&mut self) -> Result<SessionStats> {
+        while !self.control.is_stopped() {
+            if self.control.is_paused() {
+                std::thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+            match self.step() {
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                other => other?,
+            }
+        }
+        self.sink.flush()?;
+        return Ok(self.stats);
+    }
+
+    /// Return the wrapped source and sink.
+    pub fn into_inner(self) -> (R, W) {
+        return (self.source, self.sink);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Source;
+    use crate::utils::sim::VDIFSim;
+
+    #[test]
+    fn test_capture_session() {
+        let mut session = CaptureSession::new(VDIFSim::new(64, 4, 2));
+        for _ in 0..8 {
+            session.next().unwrap().unwrap();
+        }
+        assert!(session.spec().is_none());
+        session.next().unwrap().unwrap();
+        let spec = session.spec().unwrap();
+        assert_eq!((spec.frame_rate, spec.threads.clone()), (4, vec![0, 1]));
+
+        session.pause();
+        session.resume();
+        session.control().stop();
+        assert!(session.next().is_none());
+        assert_eq!(session.stats().frames, 9);
+    }
+
+    #[test]
+    fn test_playback_session() {
+        let source: Vec<VDIFFrame> = (0..3).map(|_| VDIFFrame::empty(64)).collect();
+        let mut session = PlaybackSession::new(Source(source.into_iter()), Vec::new());
+        let mut spec = StreamSpec::from_header(&VDIFHeader::default(), 0);
+        assert_eq!(
+            session.set_spec(spec.clone()).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        spec.frame_rate = 4;
+        session.set_spec(spec).unwrap();
+        let stats = session.run().unwrap();
+        assert_eq!((stats.frames, stats.bytes), (3, 192));
+        assert_eq!(session.into_inner().1.len(), 3);
+    }
+}
@@ -0,0 +1,436 @@
+//! Implements a single-producer, single-consumer ring of VDIF frames in shared memory, so a capture process and a
+//! processing process can exchange frames without sockets. Requires the `shm` feature, and is only available on Linux.
+//!
+//! The ring lives in a file under `/dev/shm` (a POSIX shared memory object) or an anonymous `memfd`, which the
+//! processes map into memory. The layout is fixed so a process written in any language can attach to it. All values
+//! are little endian:
+//!
+//! | Offset | Type  | Contents                                                           |
+//! |--------|-------|--------------------------------------------------------------------|
+//! | 0      | `u32` | Magic number, the bytes `VDSR`. Written last by the producer.      |
+//! | 4      | `u32` | Layout version, currently 1.                                       |
+//! | 8      | `u32` | Frame size in bytes.                                               |
+//! | 12     | `u32` | Capacity, the number of frame slots.                               |
+//! | 64     | `u64` | Write count: the number of frames the producer has committed.      |
+//! | 128    | `u64` | Read count: the number of frames the consumer has released.        |
+//! | 192    | `u32` | State flags: see below.                                            |
+//! | 256    |       | `capacity` frame slots. Frame `n` sits in slot `n % capacity`.     |
+//!
+//! The state flags are bit 0, set when a consumer attaches, bit 1, set when the producer closes, and bit 2, set when
+//! the consumer detaches.
+//!
+//! The handshake is:
+//!
+//! 1. The producer creates the segment, writes the header and stores the magic number last, with release ordering.
+//! 2. The consumer maps the segment, checks the magic number and version, and sets the attached flag.
+//! 3. The producer fills slot `write % capacity` whenever `write - read < capacity`, then increments the write count.
+//!    The consumer reads slot `read % capacity` whenever `read < write`, then increments the read count.
+//! 4. The producer sets the closed flag when it is done, and the consumer reports the end of the stream once it has
+//!    drained the ring.
+//! 5. The consumer sets the detached flag when it is done, and the producer reports a broken pipe rather than waiting
+//!    for a slot that will never be freed.
+
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+use std::io::{Error, ErrorKind, Result};
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use crate::consts::HEADER_SIZE;
+use crate::error::VDIFError;
+use crate::frame::VDIFFrame;
+use crate::io::{VDIFRead, VDIFWrite};
+
+const MAGIC: u32 = u32::from_le_bytes(*b"VDSR");
+const VERSION: u32 = 1;
+
+const MAGIC_OFFSET: usize = 0;
+const VERSION_OFFSET: usize = 4;
+const FRAME_SIZE_OFFSET: usize = 8;
+const CAPACITY_OFFSET: usize = 12;
+const WRITE_OFFSET: usize = 64;
+const READ_OFFSET: usize = 128;
+const STATE_OFFSET: usize = 192;
+const DATA_OFFSET: usize = 256;
+
+const STATE_ATTACHED: u32 = 1;
+const STATE_CLOSED: u32 = 2;
+const STATE_DETACHED: u32 = 4;
+
+// A shared memory segment mapped into this process.
+struct Segment {
+    file: File,
+    ptr: *mut u8,
+    len: usize,
+    frame_size: usize,
+    capacity: u64,
+}
+
+// The mapping is only accessed through atomics and the slots each side owns under the protocol
+unsafe impl Send for Segment {}
+
+impl Segment {
+    fn map(file: File) -> Result<Self> {
+        let len = file.metadata()?.len() as usize;
+        if len < DATA_OFFSET {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Shared memory segment is too small to hold a frame ring",
+            ));
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(Error::last_os_error());
+        }
+        return Ok(Self {
+            file: file,
+            ptr: ptr as *mut u8,
+            len: len,
+            frame_size: 0,
+            capacity: 0,
+        });
+    }
+
+    fn u32_at(&self, offset: usize) -> &AtomicU32 {
+        return unsafe { &*(self.ptr.add(offset) as *const AtomicU32) };
+    }
+
+    fn u64_at(&self, offset: usize) -> &AtomicU64 {
+        return unsafe { &*(self.ptr.add(offset) as *const AtomicU64) };
+    }
+
+    fn slot(&self, n: u64) -> *mut u8 {
+        let index = (n % self.capacity) as usize;
+        return unsafe { self.ptr.add(DATA_OFFSET + index * self.frame_size) };
+    }
+}
+
+impl Drop for Segment {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.len) };
+    }
+}
+
+/// The producing side of a shared memory frame ring.
+pub struct ShmProducer {
+    segment: Segment,
+}
+
+impl ShmProducer {
+    /// Create a ring of `capacity` frames of `frame_size` bytes in the file at `path`, usually under `/dev/shm`.
+    pub fn create<P: AsRef<Path>>(path: P, frame_size: usize, capacity: usize) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        return Self::init(file, frame_size, capacity);
+    }
+
+    /// Create a ring of `capacity` frames of `frame_size` bytes in an anonymous `memfd`. Share it with the consumer by
+    /// passing the descriptor from [`file`](ShmProducer::file), for example by inheritance or over a Unix socket.
+    pub fn create_memfd(name: &str, frame_size: usize, capacity: usize) -> Result<Self> {
+        let name = CString::new(name)
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "memfd name contains a nul byte"))?;
+        let fd = unsafe { libc::memfd_create(name.as_ptr(), 0) };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        return Self::init(unsafe { File::from_raw_fd(fd) }, frame_size, capacity);
+    }
+
+    // Returns an error of kind InvalidInput if the geometry can't be described by the header
+    fn init(file: File, frame_size: usize, capacity: usize) -> Result<Self> {
+        if frame_size < HEADER_SIZE || frame_size % 8 != 0 || u32::try_from(frame_size).is_err() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                VDIFError::BadFrameSize(frame_size),
+            ));
+        }
+        if capacity == 0 || u32::try_from(capacity).is_err() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "A frame ring must hold between 1 and 2^32 - 1 frames",
+            ));
+        }
+        let len = frame_size
+            .checked_mul(capacity)
+            .and_then(|bytes| bytes.checked_add(DATA_OFFSET))
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    "Frame ring is too large to map into memory",
+                )
+            })?;
+        file.set_len(len as u64)?;
+        let mut segment = Segment::map(file)?;
+        segment.frame_size = frame_size;
+        segment.capacity = capacity as u64;
+        segment
+            .u32_at(VERSION_OFFSET)
+            .store(VERSION, Ordering::Relaxed);
+        segment
+            .u32_at(FRAME_SIZE_OFFSET)
+            .store(frame_size as u32, Ordering::Relaxed);
+        segment
+            .u32_at(CAPACITY_OFFSET)
+            .store(capacity as u32, Ordering::Relaxed);
+        segment.u32_at(MAGIC_OFFSET).store(MAGIC, Ordering::Release);
+        return Ok(Self { segment: segment });
+    }
+
+    /// Get the file backing the ring, to share it with the consumer.
+    pub fn file(&self) -> &File {
+        return &self.segment.file;
+    }
+
+    /// Returns `true` once a consumer has attached to the ring.
+    pub fn is_attached(&self) -> bool {
+        return self.segment.u32_at(STATE_OFFSET).load(Ordering::Acquire) & STATE_ATTACHED != 0;
+    }
+
+    /// Returns `true` once the consumer has detached from the ring.
+    pub fn is_detached(&self) -> bool {
+        return self.segment.u32_at(STATE_OFFSET).load(Ordering::Acquire) & STATE_DETACHED != 0;
+    }
+
+    /// Wait for a free slot and fill it in place with `f`, without an intermediate frame. The slot holds the previous
+    /// contents of the ring, so `f` should overwrite the whole frame.
+    ///
+    /// Returns an error of kind [`BrokenPipe`](ErrorKind::BrokenPipe) if the consumer detaches while the ring is full.
+    pub fn write_with<F: FnOnce(&mut VDIFFrame<&mut [u32]>)>(&mut self, f: F) -> Result<()> {
+        let segment = &self.segment;
+        let write = segment.u64_at(WRITE_OFFSET).load(Ordering::Relaxed);
+        while write - segment.u64_at(READ_OFFSET).load(Ordering::Acquire) >= segment.capacity {
+            if self.is_detached() {
+                return Err(Error::new(
+                    ErrorKind::BrokenPipe,
+                    "The consumer of the frame ring has detached",
+                ));
+            }
+            std::thread::yield_now();
+        }
+        let words = unsafe {
+            std::slice::from_raw_parts_mut(segment.slot(write) as *mut u32, segment.frame_size / 4)
+        };
+        f(&mut VDIFFrame::with_storage(words));
+        segment
+            .u64_at(WRITE_OFFSET)
+            .store(write + 1, Ordering::Release);
+        return Ok(());
+    }
+
+    /// Mark the stream as finished. This happens automatically when the producer is dropped.
+    pub fn close(&mut self) {
+        self.segment
+            .u32_at(STATE_OFFSET)
+            .fetch_or(STATE_CLOSED, Ordering::Release);
+    }
+}
+
+impl VDIFWrite for ShmProducer {
+    /// Copy `frame` into the next free slot, waiting while the ring is full.
+    ///
+    /// Returns an error of kind [`InvalidInput`](ErrorKind::InvalidInput) if the frame doesn't fit the slots, or
+    /// [`BrokenPipe`](ErrorKind::BrokenPipe) if the consumer has detached.
+    fn write_frame(&mut self, frame: VDIFFrame) -> Result<()> {
+        if frame.bytesize() != self.segment.frame_size {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Frame of {} bytes doesn't fit a ring of {} byte slots",
+                    frame.bytesize(),
+                    self.segment.frame_size
+                ),
+            ));
+        }
+        return self.write_with(|slot| slot.as_mut_slice().copy_from_slice(frame.as_slice()));
+    }
+}
+
+impl Drop for ShmProducer {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// The consuming side of a shared memory frame ring.
+pub struct ShmConsumer {
+    segment: Segment,
+}
+
+impl ShmConsumer {
+    /// Attach to the ring in the file at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        return Self::from_file(OpenOptions::new().read(true).write(true).open(path)?);
+    }
+
+    /// Attach to the ring in `file`, such as a `memfd` passed from the producer.
+    pub fn from_file(file: File) -> Result<Self> {
+        let mut segment = Segment::map(file)?;
+        if segment.u32_at(MAGIC_OFFSET).load(Ordering::Acquire) != MAGIC {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Shared memory segment does not hold a frame ring",
+            ));
+        }
+        if segment.u32_at(VERSION_OFFSET).load(Ordering::Relaxed) != VERSION {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "Unsupported frame ring layout version",
+            ));
+        }
+        // The geometry comes from another process, so check it before trusting any slot offsets
+        let frame_size = segment.u32_at(FRAME_SIZE_OFFSET).load(Ordering::Relaxed) as usize;
+        let capacity = segment.u32_at(CAPACITY_OFFSET).load(Ordering::Relaxed) as usize;
+        if frame_size < HEADER_SIZE || frame_size % 8 != 0 || capacity == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Shared memory segment has an invalid frame size or capacity",
+            ));
+        }
+        let needed = frame_size
+            .checked_mul(capacity)
+            .and_then(|bytes| bytes.checked_add(DATA_OFFSET));
+        if needed.is_none_or(|needed| segment.len < needed) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Shared memory segment is smaller than its frame ring",
+            ));
+        }
+        segment.frame_size = frame_size;
+        segment.capacity = capacity as u64;
+        segment
+            .u32_at(STATE_OFFSET)
+            .fetch_or(STATE_ATTACHED, Ordering::Release);
+        return Ok(Self { segment: segment });
+    }
+
+    /// Get the size in bytes of the frames in the ring.
+    pub fn frame_size(&self) -> usize {
+        return self.segment.frame_size;
+    }
+
+    /// Wait for the next frame and pass it to `f` in place, without copying it out of the ring. Returns an
+    /// [`UnexpectedEof`](ErrorKind::UnexpectedEof) error once the producer has closed and the ring is drained.
+    pub fn read_with<T, F: FnOnce(&VDIFFrame<&mut [u32]>) -> T>(&mut self, f: F) -> Result<T> {
+        let segment = &self.segment;
+        let read = segment.u64_at(READ_OFFSET).load(Ordering::Relaxed);
+        while segment.u64_at(WRITE_OFFSET).load(Ordering::Acquire) <= read {
+            if segment.u32_at(STATE_OFFSET).load(Ordering::Acquire) & STATE_CLOSED != 0
+                && segment.u64_at(WRITE_OFFSET).load(Ordering::Acquire) <= read
+            {
+                return Err(ErrorKind::UnexpectedEof.into());
+            }
+            std::thread::yield_now();
+        }
+        let words = unsafe {
+            std::slice::from_raw_parts_mut(segment.slot(read) as *mut u32, segment.frame_size / 4)
+        };
+        let out = f(&VDIFFrame::with_storage(words));
+        segment
+            .u64_at(READ_OFFSET)
+            .store(read + 1, Ordering::Release);
+        return Ok(out);
+    }
+}
+
+impl VDIFRead for ShmConsumer {
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        return self.read_with(|frame| frame.to_boxed());
+    }
+}
+
+impl Drop for ShmConsumer {
+    fn drop(&mut self) {
+        self.segment
+            .u32_at(STATE_OFFSET)
+            .fetch_or(STATE_DETACHED, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::sim::VDIFSim;
+
+    #[test]
+    fn test_shm_ring() {
+        let mut producer = ShmProducer::create_memfd("rustvdif_test", 64, 4).unwrap();
+        let mut consumer = ShmConsumer::from_file(producer.file().try_clone().unwrap()).unwrap();
+        assert!(producer.is_attached());
+        assert_eq!(consumer.frame_size(), 64);
+
+        let handle = std::thread::spawn(move || {
+            let mut sim = VDIFSim::new(64, 100, 1);
+            for _ in 0..50 {
+                producer.write_frame(sim.generate_frame()).unwrap();
+            }
+        });
+        for n in 0..50 {
+            assert_eq!(consumer.read_frame().unwrap().get_header().frameno, n);
+        }
+        handle.join().unwrap();
+        assert_eq!(
+            consumer.read_frame().unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+
+        // Bad geometry and wrong-sized frames are refused, and a detached consumer breaks the pipe once the ring fills
+        for (frame_size, capacity) in [(8, 4), (60, 4), (64, 0), (64, 1 << 32)] {
+            let error = ShmProducer::create_memfd("rustvdif_test", frame_size, capacity)
+                .err()
+                .unwrap();
+            assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        }
+        let mut producer = ShmProducer::create_memfd("rustvdif_test", 64, 1).unwrap();
+        drop(ShmConsumer::from_file(producer.file().try_clone().unwrap()).unwrap());
+        assert!(producer.is_detached());
+        assert_eq!(
+            producer
+                .write_frame(VDIFFrame::empty(32))
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+        producer.write_frame(VDIFFrame::empty(64)).unwrap();
+        assert_eq!(
+            producer
+                .write_frame(VDIFFrame::empty(64))
+                .unwrap_err()
+                .kind(),
+            ErrorKind::BrokenPipe
+        );
+
+        // A segment whose geometry has been corrupted is refused rather than mapped
+        use std::os::unix::fs::FileExt;
+        let producer = ShmProducer::create_memfd("rustvdif_test", 64, 4).unwrap();
+        let file = producer.file().try_clone().unwrap();
+        for (offset, value) in [
+            (CAPACITY_OFFSET, 0),
+            (CAPACITY_OFFSET, u32::MAX),
+            (FRAME_SIZE_OFFSET, 8),
+        ] {
+            let mut saved = [0u8; 4];
+            file.read_exact_at(&mut saved, offset as u64).unwrap();
+            file.write_all_at(&value.to_le_bytes(), offset as u64)
+                .unwrap();
+            let error = ShmConsumer::from_file(file.try_clone().unwrap())
+                .err()
+                .unwrap();
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+            file.write_all_at(&saved, offset as u64).unwrap();
+        }
+    }
+}
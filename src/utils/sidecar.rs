@@ -0,0 +1,349 @@
+//! Implements a small, self-describing sidecar metadata file that sits next to a VDIF file.
+//!
+//! A sidecar carries a [`StreamSpec`] along with station and scan information, so downstream tools don't have to
+//! guess sample rates and the like. Sidecars are written in a simple subset of TOML, for example:
+//!
+//! ```toml
+//! frame_size = 8032
+//! frame_rate = 25600
+//! threads = [0, 1]
+//! channels = 1
+//! bits_per_sample = 2
+//! is_real = true
+//! station = 19010
+//! station_name = "Jodrell Bank"
+//! scan_labels = ["no0001", "no0002"]
+//! ```
+//!
+//! The sidecar for `path/to/my.vdif` lives at `path/to/my.vdif.toml`.
+
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::path::{Path, PathBuf};
+
+use crate::io::{VDIFReader, VDIFWriter};
+use crate::json::quote;
+use crate::spec::StreamSpec;
+use crate::station::StationRegistry;
+
+/// The metadata stored in a sidecar file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sidecar {
+    /// The layout of the associated stream.
+    pub spec: StreamSpec,
+    /// A human readable name for the station, if known.
+    pub station_name: Option<String>,
+    /// Labels for the scans contained in the associated stream.
+    pub scan_labels: Vec<String>,
+}
+
+impl Sidecar {
+    /// Construct a new [`Sidecar`] carrying `spec` and no station or scan information.
+    pub fn new(spec: StreamSpec) -> Self {
+        return Self {
+            spec: spec,
+            station_name: None,
+            scan_labels: Vec::new(),
+        };
+    }
+
+    /// Fill in [`station_name`](Sidecar::station_name) from `registry`, if it isn't already set and the station is
+    /// registered.
+    pub fn fill_station_name(&mut self, registry: &StationRegistry) {
+        if self.station_name.is_none() {
+            self.station_name = registry
+                .name(self.spec.station)
+                .map(|name| name.to_string());
+        }
+    }
+
+    /// Parse a [`Sidecar`] from its textual representation. Unknown keys are ignored.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut frame_size = None;
+        let mut frame_rate = None;
+        let mut threads = None;
+        let mut thread_frame_rates = Vec::new();
+        let mut channels = None;
+        let mut bits_per_sample = None;
+        let mut is_real = None;
+        let mut station = None;
+        let mut station_name = None;
+        let mut scan_labels = Vec::new();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid("Sidecar line is not a key = value pair"))?;
+            let value = value.trim();
+            match key.trim() {
+                "frame_size" => frame_size = Some(parse_int(value)?),
+                "frame_rate" => frame_rate = Some(parse_rate(value)?),
+                "threads" => {
+                    threads = Some(
+                        parse_array(value)?
+                            .iter()
+                            .map(|v| parse_int(v))
+                            .collect::<Result<Vec<u16>>>()?,
+                    )
+                }
+                "thread_frame_rates" => {
+                    thread_frame_rates = parse_array(value)?
+                        .iter()
+                        .map(|v| parse_rate(v))
+                        .collect::<Result<Vec<u32>>>()?
+                }
+                "channels" => channels = Some(parse_int(value)?),
+                "bits_per_sample" => bits_per_sample = Some(parse_int(value)?),
+                "is_real" => is_real = Some(parse_bool(value)?),
+                "station" => station = Some(parse_int(value)?),
+                "station_name" => station_name = Some(parse_string(value)?),
+                "scan_labels" => {
+                    scan_labels = parse_array(value)?
+                        .iter()
+                        .map(|v| parse_string(v))
+                        .collect::<Result<Vec<String>>>()?
+                }
+                _ => {}
+            }
+        }
+
+        let spec = StreamSpec {
+            frame_size: frame_size.ok_or_else(|| invalid("Sidecar is missing frame_size"))?,
+            frame_rate: frame_rate.ok_or_else(|| invalid("Sidecar is missing frame_rate"))?,
+            threads: threads.unwrap_or_default(),
+            thread_frame_rates: thread_frame_rates,
+            channels: channels.ok_or_else(|| invalid("Sidecar is missing channels"))?,
+            bits_per_sample: bits_per_sample
+                .ok_or_else(|| invalid("Sidecar is missing bits_per_sample"))?,
+            is_real: is_real.ok_or_else(|| invalid("Sidecar is missing is_real"))?,
+            station: station.ok_or_else(|| invalid("Sidecar is missing station"))?,
+        };
+
+        return Ok(Self {
+            spec: spec,
+            station_name: station_name,
+            scan_labels: scan_labels,
+        });
+    }
+
+    /// Read a [`Sidecar`] from the file at `path`.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        return Self::parse(&text);
+    }
+
+    /// Write this [`Sidecar`] to a file at `path`, overwriting it if it exists.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(self.to_string().as_bytes())?;
+        return Ok(());
+    }
+
+    /// Read the [`Sidecar`] associated with the VDIF file at `vdif_path`.
+    pub fn read_for<P: AsRef<Path>>(vdif_path: P) -> Result<Self> {
+        return Self::read(sidecar_path(vdif_path));
+    }
+
+    /// Write this [`Sidecar`] next to the VDIF file at `vdif_path`.
+    pub fn write_for<P: AsRef<Path>>(&self, vdif_path: P) -> Result<()> {
+        return self.write(sidecar_path(vdif_path));
+    }
+}
+
+impl std::fmt::Display for Sidecar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let threads: Vec<String> = self.spec.threads.iter().map(|t| t.to_string()).collect();
+        writeln!(f, "frame_size = {}", self.spec.frame_size)?;
+        writeln!(f, "frame_rate = {}", self.spec.frame_rate)?;
+        writeln!(f, "threads = [{}]", threads.join(", "))?;
+        if !self.spec.thread_frame_rates.is_empty() {
+            let rates: Vec<String> = self
+                .spec
+                .thread_frame_rates
+                .iter()
+                .map(|r| r.to_string())
+                .collect();
+            writeln!(f, "thread_frame_rates = [{}]", rates.join(", "))?;
+        }
+        writeln!(f, "channels = {}", self.spec.channels)?;
+        writeln!(f, "bits_per_sample = {}", self.spec.bits_per_sample)?;
+        writeln!(f, "is_real = {}", self.spec.is_real)?;
+        writeln!(f, "station = {}", self.spec.station)?;
+        if let Some(name) = &self.station_name {
+            writeln!(f, "station_name = {}", quote(name))?;
+        }
+        let labels: Vec<String> = self.scan_labels.iter().map(|l| quote(l)).collect();
+        writeln!(f, "scan_labels = [{}]", labels.join(", "))
+    }
+}
+
+impl VDIFReader<File> {
+    /// Open a VDIF file on disk, taking the frame size from its [`Sidecar`]. The sidecar is returned alongside the
+    /// reader.
+    pub fn open_with_sidecar<P: AsRef<Path>>(path: P) -> Result<(Self, Sidecar)> {
+        let sidecar = Sidecar::read_for(&path)?;
+        let reader = Self::open(path, sidecar.spec.frame_size)?;
+        return Ok((reader, sidecar));
+    }
+}
+
+impl VDIFWriter<File> {
+    /// Create a new VDIF file on disk and write `sidecar` next to it, then attach a [`VDIFWriter`] using the frame
+    /// size from the sidecar.
+    pub fn create_with_sidecar<P: AsRef<Path>>(path: P, sidecar: &Sidecar) -> Result<Self> {
+        sidecar.write_for(&path)?;
+        return Self::create(path, sidecar.spec.frame_size);
+    }
+}
+
+/// Get the path of the sidecar file associated with the VDIF file at `vdif_path`.
+pub fn sidecar_path<P: AsRef<Path>>(vdif_path: P) -> PathBuf {
+    let mut path = vdif_path.as_ref().as_os_str().to_owned();
+    path.push(".toml");
+    return PathBuf::from(path);
+}
+
+fn invalid(msg: &str) -> Error {
+    return Error::new(ErrorKind::InvalidData, msg.to_owned());
+}
+
+fn parse_int<T: TryFrom<u64>>(value: &str) -> Result<T> {
+    let value: u64 = value
+        .trim()
+        .parse()
+        .map_err(|_| invalid("Sidecar value is not an integer"))?;
+    return T::try_from(value).map_err(|_| invalid("Sidecar value is out of range"));
+}
+
+// Parse a frame rate, which must be positive and fit a u32.
+fn parse_rate(value: &str) -> Result<u32> {
+    return match parse_int(value)? {
+        0 => Err(invalid("Sidecar frame rate is zero")),
+        rate => Ok(rate),
+    };
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    return value
+        .trim()
+        .parse()
+        .map_err(|_| invalid("Sidecar value is not a boolean"));
+}
+
+fn parse_string(value: &str) -> Result<String> {
+    let inner = value
+        .trim()
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .ok_or_else(|| invalid("Sidecar value is not a string"))?;
+
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('"') => out.push('"'),
+                Some('\\') => out.push('\\'),
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some('t') => out.push('\t'),
+                Some('u') => {
+                    let code: String = chars.by_ref().take(4).collect();
+                    let c = u32::from_str_radix(&code, 16)
+                        .ok()
+                        .filter(|_| code.len() == 4)
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| invalid("Sidecar string contains an invalid escape"))?;
+                    out.push(c);
+                }
+                _ => return Err(invalid("Sidecar string contains an unsupported escape")),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    return Ok(out);
+}
+
+// Split a single line array into its elements, respecting quoted strings.
+fn parse_array(value: &str) -> Result<Vec<String>> {
+    let inner = value
+        .trim()
+        .strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .ok_or_else(|| invalid("Sidecar value is not an array"))?;
+
+    let mut elements = Vec::new();
+    let mut current = String::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in inner.chars() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            current.push(c);
+        } else if c == ',' {
+            elements.push(current.trim().to_owned());
+            current.clear();
+        } else {
+            in_string = c == '"';
+            current.push(c);
+        }
+    }
+    if !current.trim().is_empty() {
+        elements.push(current.trim().to_owned());
+    }
+    return Ok(elements);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sidecar_roundtrip() {
+        let sidecar = Sidecar {
+            spec: StreamSpec {
+                frame_size: 8032,
+                frame_rate: 25600,
+                threads: vec![0, 1],
+                thread_frame_rates: vec![25600, 51200],
+                channels: 1,
+                bits_per_sample: 2,
+                is_real: true,
+                station: 19010,
+            },
+            station_name: Some("Jodrell \"Bank\"".to_owned()),
+            scan_labels: vec!["no0001".to_owned(), "a, b".to_owned(), "c\td\n".to_owned()],
+        };
+
+        assert_eq!(Sidecar::parse(&sidecar.to_string()).unwrap(), sidecar);
+        for line in [
+            "thread_frame_rates = [0, 1]",
+            "thread_frame_rates = [4294967296]",
+            "frame_rate = 0",
+            "station = 65536",
+            "bits_per_sample = 256",
+        ] {
+            let text = sidecar.to_string() + line;
+            assert_eq!(
+                Sidecar::parse(&text).unwrap_err().kind(),
+                ErrorKind::InvalidData
+            );
+        }
+        assert_eq!(
+            sidecar_path("data/my.vdif"),
+            PathBuf::from("data/my.vdif.toml")
+        );
+    }
+}
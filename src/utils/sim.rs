@@ -1,6 +1,10 @@
 //! Implements functionality for generating a stream of VDIF frames for testing purposes.
 
-use crate::{header::VDIFHeader, header_encoding::encode_header, io::VDIFRead, VDIFFrame};
+use crate::{
+    frame::VDIFFrame,
+    header::{epoch_seconds, VDIFHeader},
+    io::VDIFRead,
+};
 
 /// Allows the generation of test VDIF frames.
 pub struct VDIFSim {
@@ -11,6 +15,7 @@ pub struct VDIFSim {
     current_frame: u32,
     current_thread: u16,
     current_time: u32,
+    current_epoch: u8,
 }
 
 impl VDIFSim {
@@ -25,9 +30,16 @@ impl VDIFSim {
             current_frame: 0,
             current_thread: 0,
             current_time: 0,
+            current_epoch: 3,
         };
     }
 
+    /// Set the `epoch` and `time` of the next generated second of data.
+    pub fn set_time(&mut self, epoch: u8, time: u32) {
+        self.current_epoch = epoch;
+        self.current_time = time;
+    }
+
     /// Generate a [`VDIFFrame`].
     ///
     /// The generated VDIF frame contains the following header fields:
@@ -36,7 +48,7 @@ impl VDIFSim {
     /// is_valid: true,
     /// is_legacy: false,
     /// time: [current_time],
-    /// epoch: 3,
+    /// epoch: [current_epoch],
     /// frameno: [current_frame],
     /// version: 0,
     /// channels: 0,
@@ -53,15 +65,15 @@ impl VDIFSim {
     ///
     /// All data samples are set to zero, and `current_` variables are incremented properly when this function is called.
     /// The internal counters are incremented in the following order: [current_frame] -> [current_thread] -> [current_time].
-    /// The generated VDIF frames are only valid for six months since the `epoch` field is not
-    /// handled; you wouldn't generate six months worth of data, would you?
+    /// [current_epoch] starts at 3 and is incremented, resetting [current_time], when the end of the reference epoch is
+    /// reached.
     pub fn generate_frame(&mut self) -> VDIFFrame {
         let mut out = VDIFFrame::empty(self.frame_size as usize);
         let outheader = VDIFHeader {
             is_valid: true,
             is_legacy: false,
             time: self.current_time,
-            epoch: 3,
+            epoch: self.current_epoch,
             frameno: self.current_frame,
             version: 0,
             channels: 0,
@@ -76,16 +88,17 @@ impl VDIFSim {
             edv3: 0,
         };
 
-        let encoded_header = encode_header(outheader);
-        for i in 0..8 {
-            out.as_mut_slice()[i] = encoded_header[i];
-        }
+        out.set_header(outheader);
 
         if self.current_frame >= (self.frame_rate as u32) - 1 {
             self.current_frame = 0;
             if self.current_thread == (self.thread_no - 1) as u16 {
                 self.current_thread = 0;
                 self.current_time += 1;
+                if self.current_time >= epoch_seconds(self.current_epoch) {
+                    self.current_epoch += 1;
+                    self.current_time = 0;
+                }
             } else {
                 self.current_thread += 1;
             }
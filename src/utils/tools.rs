@@ -0,0 +1,814 @@
+//! Implements tools for manipulating and analysing whole VDIF streams and files, such as splitting, concatenation,
+//! merging, trimming and duplicate detection.
+//!
+//! All of these tools operate on complete frames, so a stream is never cut mid-frame.
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use crate::consts::HEADER_SIZE;
+use crate::error::VDIFError;
+use crate::frame::VDIFFrame;
+use crate::header::{FrameInstant, VDIFHeader};
+use crate::io::{VDIFRead, VDIFReader, VDIFWrite};
+use crate::spec::StreamSpec;
+use crate::utils::fill::frame_distance;
+
+/// How often [`split`] starts a new part.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitEvery {
+    /// Start a new part after this many frames.
+    Frames(usize),
+    /// Start a new part whenever this many seconds of data time have elapsed since the start of the current part.
+    Seconds(u32),
+}
+
+/// Split the frames read from `reader` into parts, until `reader` reaches EOF.
+///
+/// `make_writer` is called with the index of each new part and should return the writer for that part, for example:
+///
+/// ```rust,ignore
+/// let mut reader = VDIFReader::open("path/to/my/vdif", 8032).unwrap();
+/// let parts = split(&mut reader, SplitEvery::Seconds(10), |i| {
+///     VDIFWriter::create(format!("path/to/part{}.vdif", i), 8032)
+/// }).unwrap();
+/// ```
+///
+/// Returns the number of parts written.
+pub fn split<R, W, F>(reader: &mut R, every: SplitEvery, mut make_writer: F) -> Result<usize>
+where
+    R: VDIFRead,
+    W: VDIFWrite,
+    F: FnMut(usize) -> Result<W>,
+{
+    let mut parts = 0;
+    let mut writer: Option<W> = None;
+    let mut part_frames = 0;
+    let mut part_start = 0;
+
+    loop {
+        let frame = match reader.read_frame() {
+            Ok(frame) => frame,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        };
+        let seconds = frame.get_header().epoch_seconds_to_unix();
+
+        let new_part = match every {
+            SplitEvery::Frames(n) => part_frames >= n,
+            SplitEvery::Seconds(n) => seconds - part_start >= n as i64,
+        };
+        if writer.is_none() || new_part {
+            if let Some(mut finished) = writer.take() {
+                finished.flush()?;
+            }
+            writer = Some(make_writer(parts)?);
+            parts += 1;
+            part_frames = 0;
+            part_start = seconds;
+        }
+
+        writer.as_mut().unwrap().write_frame(frame)?;
+        part_frames += 1;
+    }
+
+    if let Some(mut finished) = writer {
+        finished.flush()?;
+    }
+    return Ok(parts);
+}
+
+/// A report produced by [`concat`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ConcatReport {
+    /// The total number of frames written.
+    pub frames: usize,
+    /// The indices of the inputs whose first frame does not continue on from the last frame of the previous input.
+    pub discontinuities: Vec<usize>,
+}
+
+/// Concatenate the VDIF files at `paths` into `writer`, checking header continuity at each join.
+///
+/// The first frame of a file continues on from the last frame of the previous file if it has the same timestamp
+/// and frame number (i.e. belongs to another thread), the next frame number, or frame zero of the next second.
+pub fn concat<P: AsRef<Path>, W: VDIFWrite>(
+    paths: &[P],
+    frame_size: usize,
+    writer: &mut W,
+) -> Result<ConcatReport> {
+    let mut report = ConcatReport::default();
+    let mut last: Option<VDIFHeader> = None;
+
+    for (i, path) in paths.iter().enumerate() {
+        let mut reader = VDIFReader::open(path, frame_size)?;
+        let mut first = true;
+        loop {
+            let frame = match reader.read_frame() {
+                Ok(frame) => frame,
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            };
+            let header = frame.get_header();
+            if first {
+                if let Some(previous) = last {
+                    if !continues(&previous, &header) {
+                        report.discontinuities.push(i);
+                    }
+                }
+                first = false;
+            }
+
+            writer.write_frame(frame)?;
+            report.frames += 1;
+            last = Some(header);
+        }
+    }
+
+    writer.flush()?;
+    return Ok(report);
+}
+
+/// A frame found more than once by [`find_duplicates`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Duplicate {
+    /// The thread ID of the duplicated frame.
+    pub thread: u16,
+    /// The epoch of the duplicated frame.
+    pub epoch: u8,
+    /// The seconds since the epoch of the duplicated frame.
+    pub time: u32,
+    /// The frame number of the duplicated frame.
+    pub frameno: u32,
+    /// The byte offset of the first occurrence of the frame.
+    pub first_offset: u64,
+    /// The byte offset of this repeated occurrence.
+    pub offset: u64,
+}
+
+/// A report produced by [`find_duplicates`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DuplicateReport {
+    /// The total number of frames scanned.
+    pub frames: usize,
+    /// Every repeated occurrence of a frame, in the order they were found.
+    pub duplicates: Vec<Duplicate>,
+}
+
+/// Scan the VDIF file at `path` for frames that share a thread, timestamp and frame number with an earlier frame.
+///
+/// Repeated frames are a common artefact of recorder retries, and will silently corrupt a correlation if left in.
+pub fn find_duplicates<P: AsRef<Path>>(path: P, frame_size: usize) -> Result<DuplicateReport> {
+    let mut reader = VDIFReader::open(path, frame_size)?;
+    return find_duplicates_in(&mut reader);
+}
+
+/// Scan the frames read from `reader` for duplicates, as in [`find_duplicates`]. Byte offsets are relative to the first
+/// frame read.
+pub fn find_duplicates_in<R: VDIFRead>(reader: &mut R) -> Result<DuplicateReport> {
+    let mut report = DuplicateReport::default();
+    let mut seen: HashMap<(u16, u8, u32, u32), u64> = HashMap::new();
+    let mut offset: u64 = 0;
+
+    loop {
+        let frame = match reader.read_frame() {
+            Ok(frame) => frame,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        };
+        let header = frame.get_header();
+        let key = (header.thread, header.epoch, header.time, header.frameno);
+        match seen.get(&key) {
+            Some(first_offset) => report.duplicates.push(Duplicate {
+                thread: header.thread,
+                epoch: header.epoch,
+                time: header.time,
+                frameno: header.frameno,
+                first_offset: *first_offset,
+                offset: offset,
+            }),
+            None => {
+                seen.insert(key, offset);
+            }
+        }
+
+        report.frames += 1;
+        offset += frame.bytesize() as u64;
+    }
+
+    return Ok(report);
+}
+
+/// A report produced by [`merge`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MergeReport {
+    /// The total number of frames written, including fill frames.
+    pub frames: usize,
+    /// The number of frames taken from the primary recording.
+    pub from_primary: usize,
+    /// The number of frames taken from the backup recording.
+    pub from_backup: usize,
+    /// The number of frames present in both recordings where the primary's copy was invalid and the backup's wasn't.
+    pub replaced_invalid: usize,
+    /// The number of invalid frames written to fill gaps missing from both recordings.
+    pub filled: usize,
+    /// The number of gaps longer than a second, which were left unfilled.
+    pub skipped_gaps: usize,
+}
+
+/// Merge two overlapping recordings of the same stream, such as from a primary and a backup recorder, into the
+/// best-of union of the two.
+///
+/// Both recordings must be in time order. Where a frame is present in both, the valid copy is preferred, falling back
+/// to the primary's copy. Gaps missing from both recordings within each thread are filled with invalid frames, using
+/// `frame_rate` (the number of frames per second per thread) to work out which frames are missing. Gaps of more than a
+/// second of frames, which usually come from a corrupted timestamp, are counted but left unfilled.
+///
+/// Returns an error of kind [`InvalidInput`](ErrorKind::InvalidInput) if `frame_rate` is zero.
+pub fn merge<A, B, W>(
+    primary: &mut A,
+    backup: &mut B,
+    frame_rate: u32,
+    writer: &mut W,
+) -> Result<MergeReport>
+where
+    A: VDIFRead,
+    B: VDIFRead,
+    W: VDIFWrite,
+{
+    if frame_rate == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Frame rate must be positive",
+        ));
+    }
+    let mut report = MergeReport::default();
+    let mut last: HashMap<u16, VDIFHeader> = HashMap::new();
+    let mut next_primary = next_or_eof(primary)?;
+    let mut next_backup = next_or_eof(backup)?;
+
+    loop {
+        let primary_key = next_primary.as_ref().map(merge_key);
+        let backup_key = next_backup.as_ref().map(merge_key);
+        let frame = match (primary_key, backup_key) {
+            (None, None) => break,
+            (Some(p), Some(b)) if p == b => {
+                let primary_frame = next_primary.take().unwrap();
+                let backup_frame = next_backup.take().unwrap();
+                next_primary = next_or_eof(primary)?;
+                next_backup = next_or_eof(backup)?;
+                if !primary_frame.get_header().is_valid && backup_frame.get_header().is_valid {
+                    report.replaced_invalid += 1;
+                    report.from_backup += 1;
+                    backup_frame
+                } else {
+                    report.from_primary += 1;
+                    primary_frame
+                }
+            }
+            (Some(p), b) if b.is_none() || Some(p) < b => {
+                report.from_primary += 1;
+                let frame = next_primary.take().unwrap();
+                next_primary = next_or_eof(primary)?;
+                frame
+            }
+            _ => {
+                report.from_backup += 1;
+                let frame = next_backup.take().unwrap();
+                next_backup = next_or_eof(backup)?;
+                frame
+            }
+        };
+
+        let header = frame.get_header();
+        if let Some(previous) = last.get(&header.thread) {
+            let missing = frame_distance(previous, &header, frame_rate) - 1;
+            if missing > frame_rate as i64 {
+                report.skipped_gaps += 1;
+            } else {
+                let mut fill = *previous;
+                fill.is_valid = false;
+                for _ in 0..missing {
+                    fill = fill.next(frame_rate);
+                    writer.write_frame(VDIFFrame::from_header(fill))?;
+                    report.filled += 1;
+                    report.frames += 1;
+                }
+            }
+        }
+
+        writer.write_frame(frame)?;
+        report.frames += 1;
+        last.insert(header.thread, header);
+    }
+
+    writer.flush()?;
+    return Ok(report);
+}
+
+/// Wraps a [`VDIFWrite`] type, rewriting the timestamp and frame number of every frame so each thread continues on
+/// from one input to the next at the frame rate of a target [`StreamSpec`].
+///
+/// This removes the jumps left when concatenating separate captures of the same scan. Call
+/// [`next_input`](RenumberingWriter::next_input) before the frames of each capture, for example:
+///
+/// ```rust,ignore
+/// let mut writer = RenumberingWriter::new(VDIFWriter::create("path/to/out.vdif", 8032)?, &spec);
+/// for path in ["part0.vdif", "part1.vdif"] {
+///     writer.next_input();
+///     let mut reader = VDIFReader::open(path, 8032)?;
+///     while let Ok(frame) = reader.read_frame() {
+///         writer.write_frame(frame)?;
+///     }
+/// }
+/// ```
+///
+/// The first frame of each thread in an input follows on from the last frame written for that thread, and the rest of
+/// the input is shifted by the same amount, so gaps within a capture, such as lost frames, are kept. Every thread
+/// starts from the timestamp of the first frame written, unless another start is given with
+/// [`set_start`](RenumberingWriter::set_start).
+pub struct RenumberingWriter<W: VDIFWrite> {
+    inner: W,
+    frame_rate: u32,
+    start: Option<FrameInstant>,
+    last: HashMap<u16, VDIFHeader>,
+    // The first frame of each thread in the current input, as read and as renumbered
+    inputs: HashMap<u16, (VDIFHeader, VDIFHeader)>,
+    renumbered: usize,
+}
+
+impl<W: VDIFWrite> RenumberingWriter<W> {
+    /// Construct a new [`RenumberingWriter`] numbering frames according to `spec`.
+    pub fn new(inner: W, spec: &StreamSpec) -> Self {
+        return Self {
+            inner: inner,
+            frame_rate: spec.frame_rate,
+            start: None,
+            last: HashMap::new(),
+            inputs: HashMap::new(),
+            renumbered: 0,
+        };
+    }
+
+    /// Number each thread from `start` rather than from the first frame written. Must be called before writing.
+    pub fn set_start(&mut self, start: FrameInstant) {
+        self.start = Some(start);
+    }
+
+    /// Start a new input, so the next frame of each thread follows on from the last frame written for it.
+    pub fn next_input(&mut self) {
+        self.inputs.clear();
+    }
+
+    /// Get the number of frames whose timestamp or frame number had to be changed.
+    pub fn renumbered(&self) -> usize {
+        return self.renumbered;
+    }
+
+    /// Return the wrapped writer.
+    pub fn into_inner(self) -> W {
+        return self.inner;
+    }
+
+    // The header `header` is renumbered to, or `None` if that is out of range
+    fn renumber(&mut self, header: &VDIFHeader) -> Option<VDIFHeader> {
+        let rate = self.frame_rate as i64;
+        let start = *self.start.get_or_insert(header.instant());
+        let (first, target) = *self.inputs.entry(header.thread).or_insert_with(|| {
+            let numbering = match self.last.get(&header.thread) {
+                Some(previous) => previous.next(self.frame_rate).instant(),
+                None => start,
+            };
+            // Only the timestamp and frame number are rewritten
+            let target = VDIFHeader {
+                epoch: numbering.epoch,
+                time: numbering.time,
+                frameno: numbering.frameno,
+                ..*header
+            };
+            (*header, target)
+        });
+
+        // Shift by the same number of frames as the first frame of the input
+        let frames =
+            header.seconds_since(&first) * rate + header.frameno as i64 - first.frameno as i64;
+        let frames = target.frameno as i64 + frames;
+        let mut renumbered = VDIFHeader {
+            time: u32::try_from(target.time as i64 + frames.div_euclid(rate)).ok()?,
+            frameno: frames.rem_euclid(rate) as u32,
+            epoch: target.epoch,
+            ..*header
+        };
+        renumbered.normalise_epoch();
+        return Some(renumbered);
+    }
+}
+
+impl<W: VDIFWrite> VDIFWrite for RenumberingWriter<W> {
+    /// Renumber and write `frame`. Returns an error of kind [`InvalidInput`](ErrorKind::InvalidInput) if the frame
+    /// rate of the spec is zero, or the frame would be renumbered outside the range of a VDIF timestamp.
+    fn write_frame(&mut self, mut frame: VDIFFrame) -> Result<()> {
+        if self.frame_rate == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Frame rate must be positive",
+            ));
+        }
+        let header = frame.get_header();
+        let renumbered = self.renumber(&header).ok_or(Error::new(
+            ErrorKind::InvalidInput,
+            "Frame can't be renumbered within the range of a VDIF timestamp",
+        ))?;
+
+        if renumbered != header {
+            frame.set_header(renumbered);
+            self.renumbered += 1;
+        }
+        self.last.insert(header.thread, renumbered);
+        return self.inner.write_frame(frame);
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        return self.inner.flush();
+    }
+}
+
+/// Copy the frames of the VDIF file at `input` from `start` up to but not including `end` into `output`, returning
+/// the number of frames written.
+///
+/// The file must contain frames of a constant size in time order, so the first frame to copy can be found by
+/// [`seek_to_instant`](VDIFReader::seek_to_instant) rather than reading everything before it. Frames are never split.
+/// Returns an error of kind [`InvalidInput`](ErrorKind::InvalidInput) if `frame_size` is smaller than a header or not a
+/// multiple of 8 bytes.
+pub fn trim<P: AsRef<Path>, W: VDIFWrite>(
+    input: P,
+    frame_size: usize,
+    output: &mut W,
+    start: FrameInstant,
+    end: FrameInstant,
+) -> Result<usize> {
+    if frame_size < HEADER_SIZE || frame_size % 8 != 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            VDIFError::BadFrameSize(frame_size),
+        ));
+    }
+    let mut reader = VDIFReader::open(input, frame_size)?;
+    let mut frames = 0;
+    match reader.seek_to_instant(start) {
+        // Every frame is earlier than `start`, so there is nothing to copy
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => {}
+        Err(e) => return Err(e),
+        Ok(_) => {
+            while let Some(frame) = next_or_eof(&mut reader)? {
+                if frame.get_header().instant() >= end {
+                    break;
+                }
+                output.write_frame(frame)?;
+                frames += 1;
+            }
+        }
+    }
+
+    output.flush()?;
+    return Ok(frames);
+}
+
+/// How a frame of the stream under test differs from the reference stream, as found by [`verify_against`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difference {
+    /// The frame is in the reference stream but not the test stream.
+    Missing,
+    /// The frame is in the test stream but not the reference stream.
+    Extra,
+    /// The frame is in both streams, but with different headers.
+    HeaderMismatch,
+    /// The frame is in both streams with identical headers, but this many payload words differ.
+    PayloadMismatch(usize),
+}
+
+/// A difference between a frame of the stream under test and the reference stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameDifference {
+    /// The thread ID of the frame.
+    pub thread: u16,
+    /// The instant of the frame.
+    pub instant: FrameInstant,
+    /// How the frame differs.
+    pub difference: Difference,
+}
+
+/// A report produced by [`verify_against`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// The number of frames identical in both streams.
+    pub matched: usize,
+    /// Every frame that differs, in stream order.
+    pub differences: Vec<FrameDifference>,
+}
+
+impl VerifyReport {
+    /// Whether the stream under test was identical to the reference stream.
+    pub fn is_identical(&self) -> bool {
+        return self.differences.is_empty();
+    }
+}
+
+/// Compare a stream under test against a trusted reference stream of the same input frame by frame, for example to
+/// qualify new recorder hardware.
+///
+/// Both streams must be in time order. Frames are matched up by thread and instant.
+pub fn verify_against<A: VDIFRead, B: VDIFRead>(
+    reference: &mut A,
+    test: &mut B,
+) -> Result<VerifyReport> {
+    let mut report = VerifyReport::default();
+    let mut next_reference = next_or_eof(reference)?;
+    let mut next_test = next_or_eof(test)?;
+
+    loop {
+        let reference_key = next_reference.as_ref().map(merge_key);
+        let test_key = next_test.as_ref().map(merge_key);
+        let (key, difference) = match (reference_key, test_key) {
+            (None, None) => break,
+            (Some(r), Some(t)) if r == t => {
+                let reference_frame = next_reference.take().unwrap();
+                let test_frame = next_test.take().unwrap();
+                next_reference = next_or_eof(reference)?;
+                next_test = next_or_eof(test)?;
+
+                let difference = if reference_frame.as_slice()[0..8] != test_frame.as_slice()[0..8]
+                {
+                    Some(Difference::HeaderMismatch)
+                } else {
+                    let words = reference_frame
+                        .get_payload()
+                        .iter()
+                        .zip(test_frame.get_payload())
+                        .filter(|(r, t)| r != t)
+                        .count();
+                    if words > 0 {
+                        Some(Difference::PayloadMismatch(words))
+                    } else {
+                        None
+                    }
+                };
+                (r, difference)
+            }
+            (Some(r), t) if t.is_none() || Some(r) < t => {
+                next_reference = next_or_eof(reference)?;
+                (r, Some(Difference::Missing))
+            }
+            (_, Some(t)) => {
+                next_test = next_or_eof(test)?;
+                (t, Some(Difference::Extra))
+            }
+            (_, None) => unreachable!(),
+        };
+
+        match difference {
+            None => report.matched += 1,
+            Some(difference) => report.differences.push(FrameDifference {
+                thread: key.1,
+                instant: key.0,
+                difference: difference,
+            }),
+        }
+    }
+
+    return Ok(report);
+}
+
+// The order frames are merged and compared in.
+fn merge_key(frame: &VDIFFrame) -> (FrameInstant, u16) {
+    let header = frame.get_header();
+    return (header.instant(), header.thread);
+}
+
+// Read the next frame from `reader`, or `None` at EOF.
+fn next_or_eof<R: VDIFRead>(reader: &mut R) -> Result<Option<VDIFFrame>> {
+    return match reader.read_frame() {
+        Ok(frame) => Ok(Some(frame)),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    };
+}
+
+// Whether `next` could immediately follow `previous` in a stream.
+fn continues(previous: &VDIFHeader, next: &VDIFHeader) -> bool {
+    if next.epoch != previous.epoch {
+        return false;
+    }
+    if next.time == previous.time {
+        return next.frameno == previous.frameno || next.frameno == previous.frameno + 1;
+    }
+    return next.time == previous.time + 1 && next.frameno == 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{temp_path, Source};
+    use crate::utils::sim::VDIFSim;
+
+    #[test]
+    fn test_split() {
+        let mut frames: Vec<VDIFFrame> = Vec::new();
+        let mut sim = VDIFSim::new(64, 10, 1);
+        for _ in 0..30 {
+            frames.write_frame(sim.generate_frame()).unwrap();
+        }
+
+        let mut indices = Vec::new();
+        let mut source = Source(frames.into_iter());
+        let n = split(&mut source, SplitEvery::Seconds(2), |i| {
+            indices.push(i);
+            return Ok(Vec::new());
+        })
+        .unwrap();
+        assert_eq!(n, 2);
+        assert_eq!(indices, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_continues() {
+        let mut sim = VDIFSim::new(64, 3, 1);
+        let a = sim.generate_frame().get_header();
+        let b = sim.generate_frame().get_header();
+        let c = sim.generate_frame().get_header();
+        assert!(continues(&a, &b));
+        assert!(continues(&b, &c));
+        assert!(!continues(&a, &c));
+        assert!(continues(&c, &sim.generate_frame().get_header()));
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let mut sim = VDIFSim::new(64, 10, 1);
+        let mut frames: Vec<VDIFFrame> = (0..4).map(|_| sim.generate_frame()).collect();
+        frames.insert(3, VDIFFrame::from_slice(frames[1].as_slice()));
+
+        let mut reader = frames
+            .iter()
+            .map(|f| f.as_bytes())
+            .collect::<Vec<_>>()
+            .concat();
+        let mut reader = VDIFReader::new(std::io::Cursor::new(&mut reader), 64);
+        let report = find_duplicates_in(&mut reader).unwrap();
+        assert_eq!(report.frames, 5);
+        assert_eq!(report.duplicates.len(), 1);
+        assert_eq!(report.duplicates[0].frameno, 1);
+        assert_eq!(report.duplicates[0].first_offset, 64);
+        assert_eq!(report.duplicates[0].offset, 192);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut sim = VDIFSim::new(64, 4, 1);
+        let frames: Vec<VDIFFrame> = (0..8).map(|_| sim.generate_frame()).collect();
+        let copy = |i: usize| VDIFFrame::from_slice(frames[i].as_slice());
+
+        let mut invalid = copy(2);
+        invalid.set_header(VDIFHeader {
+            is_valid: false,
+            ..invalid.get_header()
+        });
+        // The primary has an invalid frame 2 and misses 5-6, the backup misses 0 and 6
+        let primary: Vec<VDIFFrame> = vec![copy(0), copy(1), invalid, copy(3), copy(4), copy(7)];
+        let backup: Vec<VDIFFrame> = vec![copy(1), copy(2), copy(3), copy(5), copy(7)];
+
+        let mut out: Vec<VDIFFrame> = Vec::new();
+        let report = merge(
+            &mut Source(primary.into_iter()),
+            &mut Source(backup.into_iter()),
+            4,
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(report.frames, 8);
+        assert_eq!(report.replaced_invalid, 1);
+        assert_eq!(report.filled, 1);
+        assert_eq!(report.from_backup, 2);
+        for (i, frame) in out.iter().enumerate() {
+            let header = frame.get_header();
+            assert_eq!(header.instant(), frames[i].get_header().instant());
+            assert_eq!(header.is_valid, i != 6);
+        }
+
+        // A frame far in the future leaves its gap unfilled, rather than writing every frame up to it
+        let mut corrupted = copy(1);
+        corrupted.set_header(VDIFHeader {
+            time: 1_000_000,
+            ..corrupted.get_header()
+        });
+        let mut out: Vec<VDIFFrame> = Vec::new();
+        let report = merge(
+            &mut Source(vec![copy(0), corrupted].into_iter()),
+            &mut Source(Vec::new().into_iter()),
+            4,
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!((report.frames, report.skipped_gaps), (2, 1));
+        assert_eq!(
+            merge(
+                &mut Source(Vec::new().into_iter()),
+                &mut Source(Vec::new().into_iter()),
+                0,
+                &mut out,
+            )
+            .unwrap_err()
+            .kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn test_verify_against() {
+        let mut sim = VDIFSim::new(64, 4, 1);
+        let frames: Vec<VDIFFrame> = (0..5).map(|_| sim.generate_frame()).collect();
+        let copy = |i: usize| VDIFFrame::from_slice(frames[i].as_slice());
+
+        let mut corrupted = copy(3);
+        corrupted.get_mut_payload()[0] = 1;
+        let reference: Vec<VDIFFrame> = vec![copy(0), copy(1), copy(2), copy(3)];
+        let test: Vec<VDIFFrame> = vec![copy(0), copy(2), corrupted, copy(4)];
+
+        let report = verify_against(
+            &mut Source(reference.into_iter()),
+            &mut Source(test.into_iter()),
+        )
+        .unwrap();
+        assert_eq!(report.matched, 2);
+        let differences: Vec<(u32, Difference)> = report
+            .differences
+            .iter()
+            .map(|d| (d.instant.frameno, d.difference))
+            .collect();
+        assert_eq!(
+            differences,
+            vec![
+                (1, Difference::Missing),
+                (3, Difference::PayloadMismatch(1)),
+                (0, Difference::Extra)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trim() {
+        let path = temp_path("trim.vdif");
+        let mut sim = VDIFSim::new(64, 4, 1);
+        let mut bytes = Vec::new();
+        for _ in 0..40 {
+            bytes.extend_from_slice(sim.generate_frame().as_bytes());
+        }
+        std::fs::write(&path, bytes).unwrap();
+
+        let instant = |time, frameno| FrameInstant {
+            epoch: 3,
+            time: time,
+            frameno: frameno,
+        };
+        let mut out: Vec<VDIFFrame> = Vec::new();
+        let n = trim(&path, 64, &mut out, instant(1, 2), instant(3, 1)).unwrap();
+        // Frames (1, 2) to (3, 0) inclusive
+        assert_eq!(n, 7);
+        assert_eq!(out[0].get_header().instant(), instant(1, 2));
+        assert_eq!(out[6].get_header().instant(), instant(3, 0));
+        assert_eq!(
+            trim(&path, 64, &mut out, instant(20, 0), instant(21, 0)).unwrap(),
+            0
+        );
+        assert_eq!(
+            trim(&path, 0, &mut out, instant(1, 2), instant(3, 1))
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_renumbering_writer() {
+        let mut sim = VDIFSim::new(64, 4, 1);
+        let frames: Vec<VDIFFrame> = (0..12).map(|_| sim.generate_frame()).collect();
+        let spec = StreamSpec::from_header(&frames[0].get_header(), 4);
+
+        // Two captures with a gap of a second and a half between them, the second missing a frame
+        let mut writer = RenumberingWriter::new(Vec::new(), &spec);
+        for input in [vec![0, 1, 2], vec![9, 11]] {
+            writer.next_input();
+            for i in input {
+                writer
+                    .write_frame(VDIFFrame::from_slice(frames[i].as_slice()))
+                    .unwrap();
+            }
+        }
+        assert_eq!(writer.renumbered(), 2);
+        let out = writer.into_inner();
+        for (frame, i) in out.iter().zip([0, 1, 2, 3, 5]) {
+            assert_eq!(frame.get_header(), frames[i].get_header());
+        }
+    }
+}
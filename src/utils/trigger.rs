@@ -0,0 +1,190 @@
+//! Implements [`TriggerSet`], invoking callbacks as the data time of a stream crosses integer seconds, longer periods
+//! or configured times such as scan boundaries.
+//!
+//! Triggering on data time rather than wall time keeps statistics dumps, file rotation and external hardware in step
+//! with the data itself, however late or bursty its delivery. Wrap a reader or receiver in a [`Triggered`] to check
+//! every frame it returns, or call [`TriggerSet::observe`] from your own loop.
+
+use std::io::Result;
+use std::num::NonZeroU32;
+
+use chrono::{DateTime, Utc};
+
+use crate::frame::VDIFFrame;
+use crate::header::VDIFHeader;
+use crate::io::VDIFRead;
+
+type Callback = Box<dyn FnMut(DateTime<Utc>) + Send>;
+
+enum When {
+    // Every `period` seconds since the UNIX epoch.
+    Every(i64),
+    // Once, at the given UNIX second.
+    At(i64),
+}
+
+struct Trigger {
+    when: When,
+    callback: Callback,
+    done: bool,
+}
+
+/// A set of callbacks invoked as data time crosses their boundaries.
+///
+/// Data time is taken from the reference epoch and seconds of each header, so boundaries fall on whole seconds. A
+/// boundary is crossed by the first frame at or after it, and a callback is passed the time of the boundary rather
+/// than of the frame. Periodic boundaries are counted from the UNIX epoch, so a period of 60 seconds fires on every
+/// UTC minute.
+///
+/// Data time only ever moves forward: frames older than the latest seen, such as those of a lagging thread, never fire
+/// a callback. If a gap in the data skips several boundaries of a periodic trigger, its callback fires once, for the
+/// latest of them. The first frame observed only sets the starting time, unless a one-off time has already passed.
+#[derive(Default)]
+pub struct TriggerSet {
+    triggers: Vec<Trigger>,
+    latest: Option<i64>,
+}
+
+impl TriggerSet {
+    /// Construct a new, empty [`TriggerSet`].
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Invoke `callback` each time data time crosses an integer second.
+    pub fn on_second<F: FnMut(DateTime<Utc>) + Send + 'static>(self, callback: F) -> Self {
+        return self.on_every(NonZeroU32::MIN, callback);
+    }
+
+    /// Invoke `callback` each time data time crosses a multiple of `period` seconds since the UNIX epoch.
+    pub fn on_every<F: FnMut(DateTime<Utc>) + Send + 'static>(
+        mut self,
+        period: NonZeroU32,
+        callback: F,
+    ) -> Self {
+        self.triggers.push(Trigger {
+            when: When::Every(period.get() as i64),
+            callback: Box::new(callback),
+            done: false,
+        });
+        return self;
+    }
+
+    /// Invoke `callback` once, when data time reaches `time`, for example the start of a scan. Any fraction of a second
+    /// in `time` is ignored.
+    pub fn at<F: FnMut(DateTime<Utc>) + Send + 'static>(
+        mut self,
+        time: DateTime<Utc>,
+        callback: F,
+    ) -> Self {
+        self.triggers.push(Trigger {
+            when: When::At(time.timestamp()),
+            callback: Box::new(callback),
+            done: false,
+        });
+        return self;
+    }
+
+    /// Get the latest data time observed, as seconds since the UNIX epoch.
+    pub fn latest(&self) -> Option<i64> {
+        return self.latest;
+    }
+
+    /// Advance data time to that of `header`, invoking the callback of every boundary crossed.
+    pub fn observe(&mut self, header: &VDIFHeader) {
+        let now = header.epoch_seconds_to_unix();
+        let previous = self.latest;
+        if previous.is_some_and(|previous| now <= previous) {
+            return;
+        }
+        self.latest = Some(now);
+
+        for trigger in self.triggers.iter_mut().filter(|t| !t.done) {
+            let boundary = match trigger.when {
+                When::Every(period) => {
+                    let boundary = now.div_euclid(period) * period;
+                    match previous {
+                        Some(previous) if previous < boundary => boundary,
+                        _ => continue,
+                    }
+                }
+                When::At(time) => {
+                    if now < time {
+                        continue;
+                    }
+                    trigger.done = true;
+                    time
+                }
+            };
+            (trigger.callback)(DateTime::from_timestamp(boundary, 0).unwrap());
+        }
+    }
+}
+
+/// Wraps a [`VDIFRead`] source, passing the header of every frame read to a [`TriggerSet`].
+pub struct Triggered<R: VDIFRead> {
+    inner: R,
+    triggers: TriggerSet,
+}
+
+impl<R: VDIFRead> Triggered<R> {
+    /// Construct a new [`Triggered`] reading from `inner`.
+    pub fn new(inner: R, triggers: TriggerSet) -> Self {
+        return Self {
+            inner: inner,
+            triggers: triggers,
+        };
+    }
+
+    /// Get a mutable reference to the [`TriggerSet`].
+    pub fn get_mut_triggers(&mut self) -> &mut TriggerSet {
+        return &mut self.triggers;
+    }
+
+    /// Get the underlying source.
+    pub fn into_inner(self) -> R {
+        return self.inner;
+    }
+}
+
+impl<R: VDIFRead> VDIFRead for Triggered<R> {
+    /// Read a frame, invoking any triggered callbacks before returning it.
+    fn read_frame(&mut self) -> Result<VDIFFrame> {
+        let frame = self.inner.read_frame()?;
+        self.triggers.observe(&frame.get_header());
+        return Ok(frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::sim::VDIFSim;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_triggers() {
+        let seconds = Arc::new(Mutex::new(Vec::new()));
+        let scans = Arc::new(Mutex::new(Vec::new()));
+        let (s, c) = (seconds.clone(), scans.clone());
+
+        let mut sim = VDIFSim::new(64, 4, 2);
+        sim.set_time(40, 0);
+        let start = sim.generate_frame().get_header().epoch_seconds_to_unix();
+        let scan = DateTime::from_timestamp(start + 2, 0).unwrap();
+        let triggers = TriggerSet::new()
+            .on_second(move |t| s.lock().unwrap().push(t.timestamp() - start))
+            .at(scan, move |t| c.lock().unwrap().push(t));
+        let mut reader = Triggered::new(sim, triggers);
+
+        // 8 frames per second across the two threads, starting one frame into the first second
+        for _ in 0..23 {
+            reader.read_frame().unwrap();
+        }
+        assert_eq!(*seconds.lock().unwrap(), vec![1, 2]);
+        assert_eq!(*scans.lock().unwrap(), vec![scan]);
+        reader.read_frame().unwrap();
+        assert_eq!(*seconds.lock().unwrap(), vec![1, 2, 3]);
+        assert_eq!(reader.get_mut_triggers().latest(), Some(start + 3));
+    }
+}
@@ -0,0 +1,168 @@
+//! Implements reading of VDIF recordings stored in the FlexBuff/vbs layout used by jive5ab.
+//!
+//! A vbs recording of a scan is split into chunk files, numbered by sequence, that are scattered across the
+//! directories `<root>/<scan>/` of several disks:
+//!
+//! ```text
+//! /mnt/disk0/scan/scan.00000000
+//! /mnt/disk1/scan/scan.00000001
+//! /mnt/disk0/scan/scan.00000002
+//! ...
+//! ```
+//!
+//! [`VBSStream`] reassembles the chunks into one continuous byte stream, which can then be read by a
+//! [`VDIFReader`].
+
+use std::fs::{self, File};
+use std::io::{Error, ErrorKind, Read, Result};
+use std::path::{Path, PathBuf};
+
+use crate::io::VDIFReader;
+
+/// A continuous byte stream over the chunk files of a vbs recording.
+pub struct VBSStream {
+    chunks: Vec<(u32, PathBuf)>,
+    chunk_header_size: usize,
+    next_chunk: usize,
+    current: Option<File>,
+}
+
+impl VBSStream {
+    /// Find the chunks of `scan` under each of `roots` and open them as one stream.
+    ///
+    /// Returns an error of kind [`NotFound`](ErrorKind::NotFound) if no chunks were found.
+    pub fn open<P: AsRef<Path>>(roots: &[P], scan: &str) -> Result<Self> {
+        let mut chunks = Vec::new();
+        for root in roots {
+            let dir = root.as_ref().join(scan);
+            if !dir.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if let Some(seq) = chunk_sequence(&path, scan) {
+                    chunks.push((seq, path));
+                }
+            }
+        }
+        if chunks.is_empty() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                "No vbs chunks found for scan",
+            ));
+        }
+        chunks.sort();
+
+        return Ok(Self {
+            chunks: chunks,
+            chunk_header_size: 0,
+            next_chunk: 0,
+            current: None,
+        });
+    }
+
+    /// Skip `size` bytes at the start of every chunk, for layouts that prefix each chunk with a header. Must be
+    /// called before reading.
+    pub fn set_chunk_header_size(&mut self, size: usize) {
+        self.chunk_header_size = size;
+    }
+
+    /// Get the paths of the chunks in stream order.
+    pub fn chunks(&self) -> Vec<&Path> {
+        return self.chunks.iter().map(|(_, p)| p.as_path()).collect();
+    }
+
+    /// Get the sequence numbers missing between the first and last chunk found, e.g. from a failed disk.
+    pub fn missing_chunks(&self) -> Vec<u32> {
+        let mut missing = Vec::new();
+        for pair in self.chunks.windows(2) {
+            missing.extend(pair[0].0 + 1..pair[1].0);
+        }
+        return missing;
+    }
+
+    fn open_next(&mut self) -> Result<bool> {
+        if self.next_chunk >= self.chunks.len() {
+            return Ok(false);
+        }
+        let mut file = File::open(&self.chunks[self.next_chunk].1)?;
+        let mut header = vec![0u8; self.chunk_header_size];
+        file.read_exact(&mut header)?;
+        self.current = Some(file);
+        self.next_chunk += 1;
+        return Ok(true);
+    }
+}
+
+impl Read for VBSStream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        loop {
+            if let Some(file) = self.current.as_mut() {
+                let n = file.read(buf)?;
+                if n > 0 || buf.is_empty() {
+                    return Ok(n);
+                }
+                self.current = None;
+            }
+            if !self.open_next()? {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+impl VDIFReader<VBSStream> {
+    /// Open the vbs recording of `scan` spread across `roots` as a single stream of VDIF frames.
+    pub fn open_vbs<P: AsRef<Path>>(roots: &[P], scan: &str, frame_size: usize) -> Result<Self> {
+        return Ok(Self::new(VBSStream::open(roots, scan)?, frame_size));
+    }
+}
+
+// The sequence number of a chunk file named `<scan>.<sequence>`, if `path` is one.
+fn chunk_sequence(path: &Path, scan: &str) -> Option<u32> {
+    let name = path.file_name()?.to_str()?;
+    let seq = name.strip_prefix(scan)?.strip_prefix('.')?;
+    if seq.is_empty() || !seq.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    return seq.parse().ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::VDIFRead;
+    use crate::testing::temp_path;
+    use crate::utils::sim::VDIFSim;
+
+    #[test]
+    fn test_vbs_stream() {
+        let base = temp_path("vbs");
+        let roots = [base.join("disk0"), base.join("disk1")];
+        let mut sim = VDIFSim::new(64, 10, 1);
+        for seq in [0u32, 1, 2, 4] {
+            let dir = roots[seq as usize % 2].join("scan");
+            fs::create_dir_all(&dir).unwrap();
+            let mut bytes = vec![0xAA; 8];
+            for _ in 0..2 {
+                bytes.extend_from_slice(sim.generate_frame().as_bytes());
+            }
+            fs::write(dir.join(format!("scan.{:08}", seq)), bytes).unwrap();
+        }
+        fs::write(roots[0].join("scan").join("scan.index"), b"ignored").unwrap();
+
+        let mut stream = VBSStream::open(&roots, "scan").unwrap();
+        stream.set_chunk_header_size(8);
+        assert_eq!(stream.missing_chunks(), vec![3]);
+        let mut reader = VDIFReader::new(stream, 64);
+        for frameno in 0..8 {
+            assert_eq!(reader.read_frame().unwrap().get_header().frameno, frameno);
+        }
+        assert_eq!(
+            reader.read_frame().unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+
+        fs::remove_dir_all(base).unwrap();
+    }
+}
@@ -0,0 +1,254 @@
+//! Implements [`Watchdog`], which detects stalled [`Pipeline`] stages and can restart the pipeline.
+
+use std::io::{Error, ErrorKind, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::utils::pipeline::{Pipeline, PipelineStats};
+
+/// Configures a [`Watchdog`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchdogOptions {
+    /// How long a stage can go without processing a frame before it is considered stalled.
+    pub stall_timeout: Duration,
+    /// How often [`supervise`](Watchdog::supervise) checks the pipeline.
+    pub poll_interval: Duration,
+    /// Whether [`supervise`](Watchdog::supervise) tears down and rebuilds the pipeline when a stage stalls.
+    pub restart: bool,
+    /// How long [`supervise`](Watchdog::supervise) waits for a stopped pipeline to finish before abandoning its
+    /// threads.
+    pub stop_timeout: Duration,
+}
+
+impl Default for WatchdogOptions {
+    fn default() -> Self {
+        return Self {
+            stall_timeout: Duration::from_secs(10),
+            poll_interval: Duration::from_secs(1),
+            restart: false,
+            stop_timeout: Duration::from_secs(10),
+        };
+    }
+}
+
+/// A stalled pipeline stage, found by a [`Watchdog`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stall {
+    /// The name of the stalled stage.
+    pub stage: String,
+    /// The index of the stalled stage within [`PipelineStats::stages`].
+    pub index: usize,
+    /// How long it has been since the stage last processed a frame.
+    pub since: Duration,
+    /// The number of frames the stage had processed when it stalled.
+    pub processed: u64,
+}
+
+/// Detects pipeline stages that have stopped making progress.
+///
+/// Since a stall starves every stage downstream of it, only the furthest upstream stalled stage is reported.
+pub struct Watchdog {
+    options: WatchdogOptions,
+    progress: Vec<(u64, Instant)>,
+}
+
+impl Watchdog {
+    /// Construct a new [`Watchdog`].
+    pub fn new(options: WatchdogOptions) -> Self {
+        return Self {
+            options: options,
+            progress: Vec::new(),
+        };
+    }
+
+    /// Forget the progress seen so far, for example after the pipeline has been rebuilt.
+    pub fn reset(&mut self) {
+        self.progress.clear();
+    }
+
+    /// Update the watchdog with the latest counters of a pipeline, returning the furthest upstream stage that has
+    /// not processed a frame within the stall timeout, if any.
+    pub fn check(&mut self, stats: &PipelineStats) -> Option<Stall> {
+        let now = Instant::now();
+        if self.progress.len() != stats.stages.len() {
+            self.progress = stats.stages.iter().map(|s| (s.processed, now)).collect();
+            return None;
+        }
+
+        let mut stall = None;
+        for (i, (stage, progress)) in stats
+            .stages
+            .iter()
+            .zip(self.progress.iter_mut())
+            .enumerate()
+        {
+            if stage.processed != progress.0 {
+                *progress = (stage.processed, now);
+            } else if stall.is_none() && now - progress.1 >= self.options.stall_timeout {
+                stall = Some(Stall {
+                    stage: stage.name.clone(),
+                    index: i,
+                    since: now - progress.1,
+                    processed: stage.processed,
+                });
+            }
+        }
+        return stall;
+    }
+
+    /// Run a pipeline built by `build` under the watchdog until it finishes or `running` is cleared, returning its
+    /// result.
+    ///
+    /// `on_stall` is called once for each stall with the stalled stage and the counters of the whole pipeline, for
+    /// logging diagnostics. If [`restart`](WatchdogOptions::restart) is set, the stalled pipeline is then stopped and
+    /// `build` is called again to rebuild its sockets and threads.
+    ///
+    /// A stage stuck in a call that never returns can't be stopped, so a pipeline that hasn't finished within the
+    /// [`stop_timeout`](WatchdogOptions::stop_timeout) of being stopped has its threads abandoned. Once `running` is
+    /// cleared, this returns an error of kind [`TimedOut`](ErrorKind::TimedOut) if that happens.
+    pub fn supervise<B, S>(
+        &mut self,
+        mut build: B,
+        mut on_stall: S,
+        running: &AtomicBool,
+    ) -> Result<PipelineStats>
+    where
+        B: FnMut() -> Result<Pipeline>,
+        S: FnMut(&Stall, &PipelineStats),
+    {
+        let mut pipeline = build()?;
+        let mut reported = false;
+        self.reset();
+
+        while running.load(Ordering::Relaxed) && !pipeline.is_finished() {
+            std::thread::sleep(self.options.poll_interval);
+            let stats = pipeline.stats();
+            match self.check(&stats) {
+                Some(stall) => {
+                    if !reported {
+                        on_stall(&stall, &stats);
+                        reported = true;
+                    }
+                    if self.options.restart {
+                        match self.shut_down(pipeline) {
+                            Err(e) if e.kind() != ErrorKind::TimedOut => return Err(e),
+                            _ => {}
+                        }
+                        pipeline = build()?;
+                        reported = false;
+                        self.reset();
+                    }
+                }
+                None => reported = false,
+            }
+        }
+
+        return self.shut_down(pipeline);
+    }
+
+    // Stop `pipeline` and wait for it to finish, abandoning its threads if they don't within the stop timeout
+    fn shut_down(&self, pipeline: Pipeline) -> Result<PipelineStats> {
+        pipeline.stop();
+        let deadline = Instant::now() + self.options.stop_timeout;
+        while !pipeline.is_finished() {
+            if Instant::now() >= deadline {
+                return Err(Error::new(
+                    ErrorKind::TimedOut,
+                    "The pipeline did not finish after being stopped",
+                ));
+            }
+            std::thread::sleep(self.options.poll_interval.min(Duration::from_millis(10)));
+        }
+        return pipeline.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::VDIFFrame;
+    use crate::io::VDIFWrite;
+    use crate::utils::pipeline::StageStats;
+    use crate::utils::sim::VDIFSim;
+
+    // A sink that takes one frame, then hangs forever
+    struct Hang(bool);
+
+    impl VDIFWrite for Hang {
+        fn write_frame(&mut self, _frame: VDIFFrame) -> Result<()> {
+            if self.0 {
+                loop {
+                    std::thread::park();
+                }
+            }
+            self.0 = true;
+            return Ok(());
+        }
+    }
+
+    #[test]
+    fn test_watchdog_check() {
+        let mut watchdog = Watchdog::new(WatchdogOptions {
+            stall_timeout: Duration::ZERO,
+            ..Default::default()
+        });
+        let mut stats = PipelineStats {
+            stages: vec![
+                StageStats {
+                    name: "source".to_owned(),
+                    ..Default::default()
+                },
+                StageStats {
+                    name: "sink".to_owned(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        assert!(watchdog.check(&stats).is_none());
+
+        stats.stages[0].processed = 5;
+        let stall = watchdog.check(&stats).unwrap();
+        assert_eq!(stall.stage, "sink");
+        assert_eq!(stall.index, 1);
+        assert_eq!(watchdog.check(&stats).unwrap().stage, "source");
+    }
+
+    #[test]
+    fn test_supervise_hung_pipeline() {
+        let mut options = WatchdogOptions {
+            stall_timeout: Duration::ZERO,
+            poll_interval: Duration::from_millis(5),
+            restart: true,
+            stop_timeout: Duration::from_millis(20),
+        };
+        let hung = || {
+            Pipeline::builder(VDIFSim::new(64, 10, 1))
+                .sink(Hang(false))
+                .spawn()
+        };
+
+        // The hung pipeline is abandoned and replaced by a working one
+        let running = AtomicBool::new(true);
+        let mut builds = 0;
+        let build = || {
+            builds += 1;
+            if builds == 1 {
+                return Ok(hung());
+            }
+            return Ok(Pipeline::builder(VDIFSim::new(64, 10, 1))
+                .sink(Vec::new())
+                .spawn());
+        };
+        let stop = |_: &Stall, _: &PipelineStats| running.store(false, Ordering::Relaxed);
+        assert!(Watchdog::new(options)
+            .supervise(build, stop, &running)
+            .is_ok());
+        assert_eq!(builds, 2);
+
+        options.restart = false;
+        running.store(true, Ordering::Relaxed);
+        let result = Watchdog::new(options).supervise(|| Ok(hung()), stop, &running);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::TimedOut);
+    }
+}
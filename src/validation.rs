@@ -0,0 +1,423 @@
+//! Implements [`ValidationLevel`], which sets how much checking readers, receivers and writers do on each frame.
+//!
+//! Checking costs time on every frame, so performance-critical paths can opt out explicitly with
+//! [`ValidationLevel::None`], which is the default everywhere.
+//!
+//! Where every problem with a frame is wanted rather than just the first, such as when deciding whether to ingest a
+//! recording at all, use [`VDIFFrame::validate`] with a [`ValidationProfile`].
+
+#[cfg(feature = "std")]
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::ControlFlow;
+#[cfg(feature = "std")]
+use std::io::{ErrorKind, Read};
+
+#[cfg(feature = "std")]
+use chrono::Utc;
+
+use crate::consts::HEADER_SIZE;
+use crate::frame::{FrameStorage, VDIFFrame};
+#[cfg(feature = "std")]
+use crate::header::vdiftime_from_date;
+use crate::header::VDIFHeader;
+use crate::header_encoding::decode_header_bytes;
+
+/// The highest data rate in bits/second a single thread is considered able to carry, around 69 Gbit/s. A frame number
+/// reaching the frame rate this implies is reported as implausible by a [`ValidationProfile`].
+pub const MAX_THREAD_DATA_RATE: u64 = 1 << 36;
+
+/// How thoroughly each frame is checked.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ValidationLevel {
+    /// No checks at all.
+    #[default]
+    None,
+    /// Check the header is consistent with the frame: its size field matches the number of bytes in the frame, and
+    /// the reference epoch is in range.
+    Header,
+    /// Everything checked by [`Header`](ValidationLevel::Header), and additionally reject legacy frames, frames
+    /// flagged as invalid, and frames whose [extended data](crate::edv::ExtendedData) has the wrong sync word.
+    Strict,
+}
+
+/// Why a frame failed validation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationError {
+    /// The header size field differs from the size of the frame.
+    SizeMismatch,
+    /// The reference epoch is beyond the range the crate can convert to a date.
+    EpochOutOfRange,
+    /// The frame uses the legacy header format.
+    Legacy,
+    /// The frame is flagged as invalid.
+    FlaggedInvalid,
+    /// The extended data sync word does not match its EDV.
+    BadSyncWord,
+    /// The reference epoch is later than the current one.
+    EpochInFuture,
+    /// The frame number is higher than any thread could reach within a second, given the frame size.
+    ImplausibleFrameNumber(u32),
+    /// The VDIF version is not one this crate understands.
+    UnsupportedVersion(u8),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Self::ImplausibleFrameNumber(frameno) => {
+                return write!(f, "Frame number {} is implausibly high", frameno);
+            }
+            Self::UnsupportedVersion(version) => {
+                return write!(f, "VDIF version {} is not supported", version);
+            }
+            Self::SizeMismatch => "Header frame size does not match the frame",
+            Self::EpochOutOfRange => "Header reference epoch is out of range",
+            Self::Legacy => "Legacy VDIF frames are not accepted",
+            Self::FlaggedInvalid => "Frame is flagged as invalid",
+            Self::BadSyncWord => "Header extended data has the wrong sync word",
+            Self::EpochInFuture => "Header reference epoch is in the future",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+impl core::error::Error for ValidationError {}
+
+#[cfg(feature = "std")]
+impl From<ValidationError> for std::io::Error {
+    fn from(error: ValidationError) -> Self {
+        return std::io::Error::new(std::io::ErrorKind::InvalidData, error);
+    }
+}
+
+// The checks shared by `ValidationLevel` and `ValidationProfile`, passing each problem found to `report` in the order
+// they are listed in `ValidationError`, and stopping early if it breaks
+fn check_header<B>(
+    header: &VDIFHeader,
+    frame_size: usize,
+    strict: bool,
+    mut report: impl FnMut(ValidationError) -> ControlFlow<B>,
+) -> ControlFlow<B> {
+    if header.bytesize() as usize != frame_size {
+        report(ValidationError::SizeMismatch)?;
+    }
+    if header.epoch >= 64 {
+        report(ValidationError::EpochOutOfRange)?;
+    }
+    if strict {
+        if header.is_legacy {
+            report(ValidationError::Legacy)?;
+        }
+        if !header.is_valid {
+            report(ValidationError::FlaggedInvalid)?;
+        }
+        if !header.extended_data().is_synced() {
+            report(ValidationError::BadSyncWord)?;
+        }
+    }
+    return ControlFlow::Continue(());
+}
+
+impl ValidationLevel {
+    /// Check `frame` at this level.
+    pub fn check<S: FrameStorage>(&self, frame: &VDIFFrame<S>) -> Result<(), ValidationError> {
+        if *self == ValidationLevel::None {
+            return Ok(());
+        }
+        let strict = *self == ValidationLevel::Strict;
+        return match check_header(
+            &frame.get_header(),
+            frame.bytesize(),
+            strict,
+            ControlFlow::Break,
+        ) {
+            ControlFlow::Break(error) => Err(error),
+            ControlFlow::Continue(()) => Ok(()),
+        };
+    }
+}
+
+/// The set of checks run by [`VDIFFrame::validate`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValidationProfile {
+    /// Report problems that make a frame unusable: a size field that disagrees with the frame, a reference epoch out of
+    /// range or in the future (which needs the `std` feature to read the clock), an unsupported VDIF version, or a
+    /// frame number beyond what a thread of [`MAX_THREAD_DATA_RATE`] could reach.
+    #[default]
+    Lenient,
+    /// Everything reported by [`Lenient`](ValidationProfile::Lenient), and additionally legacy frames, frames flagged
+    /// as invalid, and frames whose [extended data](crate::edv::ExtendedData) has the wrong sync word.
+    Strict,
+}
+
+impl ValidationProfile {
+    /// Get every problem with a frame of `frame_size` bytes carrying `header`, in the order they are listed in
+    /// [`ValidationError`].
+    pub fn problems(&self, header: &VDIFHeader, frame_size: usize) -> Vec<ValidationError> {
+        #[cfg(feature = "std")]
+        let current_epoch = Some(vdiftime_from_date(Utc::now().naive_utc()).0);
+        #[cfg(not(feature = "std"))]
+        let current_epoch = None;
+        return self.collect_problems(header, frame_size, current_epoch);
+    }
+
+    /// Get every problem with a frame as [`problems`](ValidationProfile::problems) does, but judging whether its
+    /// reference epoch is in the future against `current_epoch` rather than the clock.
+    pub fn problems_at(
+        &self,
+        header: &VDIFHeader,
+        frame_size: usize,
+        current_epoch: u8,
+    ) -> Vec<ValidationError> {
+        return self.collect_problems(header, frame_size, Some(current_epoch));
+    }
+
+    fn collect_problems(
+        &self,
+        header: &VDIFHeader,
+        frame_size: usize,
+        current_epoch: Option<u8>,
+    ) -> Vec<ValidationError> {
+        let mut problems = Vec::new();
+        let strict = *self == ValidationProfile::Strict;
+        let _ = check_header::<()>(header, frame_size, strict, |problem| {
+            problems.push(problem);
+            ControlFlow::Continue(())
+        });
+        if current_epoch.is_some_and(|epoch| header.epoch > epoch) {
+            problems.push(ValidationError::EpochInFuture);
+        }
+        let payload_bits = header.bytesize().saturating_sub(header.header_bytesize()) as u64 * 8;
+        if payload_bits > 0 && header.frameno as u64 >= MAX_THREAD_DATA_RATE / payload_bits {
+            problems.push(ValidationError::ImplausibleFrameNumber(header.frameno));
+        }
+        if header.version != 0 {
+            problems.push(ValidationError::UnsupportedVersion(header.version));
+        }
+        return problems;
+    }
+}
+
+/// A frame whose header size field disagrees with the framing of the stream carrying it, found by a
+/// [`FramingChecker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FramingMismatch {
+    /// The index of the frame or datagram within the stream.
+    pub index: u64,
+    /// The byte offset of the start of the frame within the stream.
+    pub offset: u64,
+    /// The size in bytes of the frame according to the stream: the datagram size, or the file stride.
+    pub actual: usize,
+    /// The size in bytes of the frame according to its header.
+    pub header_size: usize,
+}
+
+impl fmt::Display for FramingMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Frame {} at byte {} is {} bytes, but its header says {} bytes",
+            self.index, self.offset, self.actual, self.header_size
+        )
+    }
+}
+
+/// Checks the header size field of every frame against the size of the datagram or file stride carrying it.
+///
+/// A packetiser configured with the wrong frame size produces frames that look fine one at a time, but whose headers
+/// disagree with how they are actually framed, silently corrupting a whole recording. Feed each datagram, or each
+/// frame read at a fixed stride, through a checker to catch this as it happens, or use [`scan_framing`] on a file.
+#[derive(Debug, Default, Clone)]
+pub struct FramingChecker {
+    index: u64,
+    offset: u64,
+    mismatches: u64,
+}
+
+impl FramingChecker {
+    /// Construct a new [`FramingChecker`].
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Check a received datagram holding a single frame.
+    pub fn check_datagram(&mut self, datagram: &[u8]) -> Result<(), FramingMismatch> {
+        let header_size = if datagram.len() >= HEADER_SIZE {
+            decode_header_bytes(datagram[0..HEADER_SIZE].try_into().unwrap()).bytesize() as usize
+        } else {
+            0
+        };
+        return self.advance(datagram.len(), header_size);
+    }
+
+    /// Check a frame read from a stream of frames at a fixed stride, such as by a
+    /// [`VDIFReader`](crate::io::VDIFReader).
+    pub fn check_frame(&mut self, frame: &VDIFFrame) -> Result<(), FramingMismatch> {
+        return self.advance(frame.bytesize(), frame.get_header().bytesize() as usize);
+    }
+
+    /// Get the number of frames checked.
+    pub fn checked(&self) -> u64 {
+        return self.index;
+    }
+
+    /// Get the number of mismatches found.
+    pub fn mismatches(&self) -> u64 {
+        return self.mismatches;
+    }
+
+    fn advance(&mut self, actual: usize, header_size: usize) -> Result<(), FramingMismatch> {
+        let mismatch = FramingMismatch {
+            index: self.index,
+            offset: self.offset,
+            actual: actual,
+            header_size: header_size,
+        };
+        self.index += 1;
+        self.offset += actual as u64;
+        if actual != header_size {
+            self.mismatches += 1;
+            return Err(mismatch);
+        }
+        return Ok(());
+    }
+}
+
+/// Read `reader` as frames of `frame_size` bytes, returning the first `max_reports` frames whose header size field
+/// differs from `frame_size`. Any partial frame at the end is ignored.
+#[cfg(feature = "std")]
+pub fn scan_framing<R: Read>(
+    mut reader: R,
+    frame_size: usize,
+    max_reports: usize,
+) -> std::io::Result<Vec<FramingMismatch>> {
+    let mut checker = FramingChecker::new();
+    let mut buf = vec![0u8; frame_size];
+    let mut reports = Vec::new();
+    while reports.len() < max_reports {
+        match reader.read_exact(&mut buf) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+        if let Err(mismatch) = checker.check_datagram(&buf) {
+            reports.push(mismatch);
+        }
+    }
+    return Ok(reports);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::VDIFHeader;
+
+    #[test]
+    fn test_validation_levels() {
+        let mut frame = VDIFFrame::from_header(VDIFHeader {
+            size: 8,
+            bits_per_sample: 2,
+            ..Default::default()
+        });
+        assert_eq!(ValidationLevel::Header.check(&frame), Ok(()));
+        assert_eq!(
+            ValidationLevel::Strict.check(&frame),
+            Err(ValidationError::FlaggedInvalid)
+        );
+
+        let mut header = frame.get_header();
+        header.size = 4;
+        frame.set_header(header);
+        assert_eq!(ValidationLevel::None.check(&frame), Ok(()));
+        assert_eq!(
+            ValidationLevel::Header.check(&frame),
+            Err(ValidationError::SizeMismatch)
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut frame = VDIFFrame::from_header(VDIFHeader {
+            size: 8,
+            bits_per_sample: 2,
+            ..Default::default()
+        });
+        assert!(frame.validate(ValidationProfile::Lenient).is_empty());
+        assert_eq!(
+            frame.validate(ValidationProfile::Strict),
+            vec![ValidationError::FlaggedInvalid]
+        );
+
+        let frame8032 = VDIFFrame::from_header(VDIFHeader {
+            size: 8032 / 8,
+            bits_per_sample: 2,
+            frameno: 2_000_000,
+            version: 3,
+            epoch: 41,
+            ..Default::default()
+        });
+        // Judge the epoch against one fixed in 2020, rather than the clock
+        assert_eq!(
+            ValidationProfile::Lenient.problems_at(&frame8032.get_header(), 8032, 40),
+            vec![
+                ValidationError::EpochInFuture,
+                ValidationError::ImplausibleFrameNumber(2_000_000),
+                ValidationError::UnsupportedVersion(3),
+            ]
+        );
+        assert_eq!(
+            ValidationProfile::Lenient.problems_at(&frame8032.get_header(), 8032, 41),
+            vec![
+                ValidationError::ImplausibleFrameNumber(2_000_000),
+                ValidationError::UnsupportedVersion(3),
+            ]
+        );
+
+        // A raw bits/sample of zero is a 1-bit stream, not a fault
+        let mut header = frame.get_header();
+        header.bits_per_sample = 0;
+        header.size = 4;
+        frame.set_header(header);
+        assert_eq!(
+            frame.validate(ValidationProfile::Lenient),
+            vec![ValidationError::SizeMismatch]
+        );
+        assert_eq!(
+            ValidationLevel::Header.check(&frame),
+            Err(ValidationError::SizeMismatch)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_framing() {
+        // A file written at a 64 byte stride, by a packetiser configured for 40 byte frames from the third frame
+        let mut file = Vec::new();
+        for size in [8, 8, 5, 5] {
+            let frame = VDIFFrame::from_header(VDIFHeader {
+                size: size,
+                ..Default::default()
+            });
+            file.extend_from_slice(frame.as_bytes());
+            file.resize(file.len().next_multiple_of(64), 0);
+        }
+        let reports = scan_framing(&file[..], 64, 10).unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(
+            reports[0],
+            FramingMismatch {
+                index: 2,
+                offset: 128,
+                actual: 64,
+                header_size: 40
+            }
+        );
+
+        let mut checker = FramingChecker::new();
+        assert!(checker.check_datagram(&file[0..64]).is_ok());
+        assert_eq!(checker.check_datagram(&file[0..56]).unwrap_err().offset, 64);
+        assert_eq!((checker.checked(), checker.mismatches()), (2, 1));
+    }
+}